@@ -0,0 +1,359 @@
+//! A tiny language server for testing orinfar's LSP client without installing a real one.
+//!
+//! It keeps track of every open document by applying the changes it's sent, and reports what it
+//! has with `window/showMessage` whenever a document is saved, so any drift between the editor's
+//! buffer and the server's copy is visible. Every occurrence of the words `error` and `warning`
+//! is published as a diagnostic of that severity.
+//!
+//! Navigation works on whole words across the open documents: the definition of a word is its
+//! first occurrence, its references are every occurrence, and renaming replaces them all. Completion
+//! offers every word in the open documents starting with the one being typed, and formatting trims
+//! trailing whitespace.
+//!
+//! To use it, build it with `cargo build --example fake_lsp` and point a language at it in
+//! `$XDG_CONFIG_HOME/orinfar/lsp.toml`:
+//! ```toml
+//! [servers]
+//! c = ["/path/to/orinfar/target/debug/examples/fake_lsp"]
+//! ```
+
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write, stdin, stdout},
+};
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut content_length = 0;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(length) = line.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse().ok()?;
+        }
+    }
+
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+fn write_message(message: &Value) {
+    let content = message.to_string();
+    let mut stdout = stdout().lock();
+
+    let _ = write!(stdout, "Content-Length: {}\r\n\r\n{content}", content.len());
+    let _ = stdout.flush();
+}
+
+/// Converts a UTF-32 `Position` into a char index into `text`
+fn char_idx(text: &[char], position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0);
+    let character = position["character"].as_u64().unwrap_or(0);
+
+    let mut idx = 0;
+    for _ in 0..line {
+        idx += text[idx..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+    }
+
+    idx + usize::try_from(character).unwrap_or(0)
+}
+
+/// Converts a char index into `text` into a UTF-32 `Position`
+fn position(text: &[char], idx: usize) -> Value {
+    let line = text[..idx].iter().filter(|c| **c == '\n').count();
+    let line_start = text[..idx]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+
+    json!({ "line": line, "character": idx - line_start })
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The word at `position`, if there is one
+fn word_at(text: &[char], position: &Value) -> Option<String> {
+    let idx = char_idx(text, position);
+    if !text.get(idx).copied().is_some_and(is_word_char) {
+        return None;
+    }
+
+    let start = text[..idx]
+        .iter()
+        .rposition(|c| !is_word_char(*c))
+        .map_or(0, |i| i + 1);
+    let end = text[idx..]
+        .iter()
+        .position(|c| !is_word_char(*c))
+        .map_or(text.len(), |i| idx + i);
+
+    Some(text[start..end].iter().collect())
+}
+
+/// Every whole-word occurrence of `word` in the open documents, as `Location`s
+fn occurrences(documents: &HashMap<String, Vec<char>>, word: &str) -> Vec<Value> {
+    let word: Vec<char> = word.chars().collect();
+    let mut uris: Vec<&String> = documents.keys().collect();
+    uris.sort();
+
+    let mut locations = Vec::new();
+    for uri in uris {
+        let text = &documents[uri];
+        for start in 0..text.len() {
+            let end = start + word.len();
+            let bounded = (start == 0 || !is_word_char(text[start - 1]))
+                && text.get(end).is_none_or(|c| !is_word_char(*c));
+
+            if text[start..].starts_with(&word) && bounded {
+                locations.push(json!({
+                    "uri": uri,
+                    "range": { "start": position(text, start), "end": position(text, end) },
+                }));
+            }
+        }
+    }
+
+    locations
+}
+
+/// Every word in the open documents which starts with the word before `position`
+fn completions(
+    documents: &HashMap<String, Vec<char>>,
+    text: &[char],
+    position: &Value,
+) -> Vec<Value> {
+    let end = char_idx(text, position);
+    let start = text[..end]
+        .iter()
+        .rposition(|c| !is_word_char(*c))
+        .map_or(0, |i| i + 1);
+    let prefix: String = text[start..end].iter().collect();
+
+    let mut words: Vec<String> = documents
+        .values()
+        .flat_map(|text| text.split(|c| !is_word_char(*c)))
+        .map(|word| word.iter().collect::<String>())
+        .filter(|word| word.starts_with(&prefix) && *word != prefix)
+        .collect();
+    words.sort();
+    words.dedup();
+
+    words
+        .iter()
+        .map(|word| json!({ "label": word, "kind": 6 }))
+        .collect()
+}
+
+/// Edits removing the whitespace at the end of every line
+fn trailing_whitespace(text: &[char]) -> Vec<Value> {
+    let mut edits = Vec::new();
+    let mut line_start = 0;
+
+    for line in text.split(|c| *c == '\n') {
+        let trimmed = line.len() - line.iter().rev().take_while(|c| c.is_whitespace()).count();
+        if trimmed < line.len() {
+            edits.push(json!({
+                "range": {
+                    "start": position(text, line_start + trimmed),
+                    "end": position(text, line_start + line.len()),
+                },
+                "newText": "",
+            }));
+        }
+        line_start += line.len() + 1;
+    }
+
+    edits
+}
+
+fn apply_change(text: &mut Vec<char>, change: &Value) {
+    let new: Vec<char> = change["text"].as_str().unwrap_or("").chars().collect();
+
+    let Some(range) = change.get("range") else {
+        *text = new;
+        return;
+    };
+
+    let start = char_idx(text, &range["start"]);
+    let end = char_idx(text, &range["end"]);
+    text.splice(start..end, new);
+}
+
+/// Publishes a diagnostic for every `error` and `warning` in the document
+fn publish_diagnostics(uri: &str, text: &[char]) {
+    let text: String = text.iter().collect();
+    let mut diagnostics = Vec::new();
+
+    for (line_idx, line) in text.lines().enumerate() {
+        for (word, severity) in [("error", 1), ("warning", 2)] {
+            for (byte_idx, _) in line.match_indices(word) {
+                let character = line[..byte_idx].chars().count();
+                diagnostics.push(json!({
+                    "range": {
+                        "start": { "line": line_idx, "character": character },
+                        "end": { "line": line_idx, "character": character + word.len() },
+                    },
+                    "severity": severity,
+                    "source": "fake_lsp",
+                    "message": format!("found the word {word}"),
+                }));
+            }
+        }
+    }
+
+    write_message(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    }));
+}
+
+/// The capabilities of the server, which are everything it can fake
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "positionEncoding": "utf-32",
+            "textDocumentSync": { "openClose": true, "change": 2, "save": {} },
+            "definitionProvider": true,
+            "referencesProvider": true,
+            "hoverProvider": true,
+            "renameProvider": true,
+            "completionProvider": {},
+            "documentFormattingProvider": true,
+        },
+        "serverInfo": { "name": "fake_lsp" },
+    })
+}
+
+/// Answers a definition, references, hover or rename request about the word at the position in
+/// `params`
+fn navigate(
+    documents: &HashMap<String, Vec<char>>,
+    method: &str,
+    uri: &str,
+    params: &Value,
+) -> Value {
+    let word = documents
+        .get(uri)
+        .and_then(|text| word_at(text, &params["position"]));
+    let locations = word
+        .as_deref()
+        .map(|word| occurrences(documents, word))
+        .unwrap_or_default();
+
+    match (method, word) {
+        (_, None) => Value::Null,
+        ("textDocument/definition", _) => locations.first().cloned().unwrap_or(Value::Null),
+        ("textDocument/references", _) => Value::Array(locations),
+        ("textDocument/hover", Some(word)) => json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("```\n{word}\n```\nUsed {} times", locations.len()),
+            },
+        }),
+        (_, Some(_)) => {
+            let mut changes: HashMap<&str, Vec<Value>> = HashMap::new();
+            for location in &locations {
+                changes
+                    .entry(location["uri"].as_str().unwrap_or(""))
+                    .or_default()
+                    .push(json!({
+                        "range": location["range"],
+                        "newText": params["newName"],
+                    }));
+            }
+            json!({ "changes": changes })
+        }
+    }
+}
+
+fn main() {
+    let mut reader = BufReader::new(stdin().lock());
+    let mut documents: HashMap<String, Vec<char>> = HashMap::new();
+
+    while let Some(message) = read_message(&mut reader) {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+
+        match method {
+            "initialize" => write_message(&json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "result": initialize_result(),
+            })),
+            "shutdown" => {
+                write_message(&json!({ "jsonrpc": "2.0", "id": message["id"], "result": null }));
+            }
+            "exit" => return,
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                let text: Vec<char> = text.chars().collect();
+                publish_diagnostics(&uri, &text);
+                documents.insert(uri, text);
+            }
+            "textDocument/didChange" => {
+                if let Some(text) = documents.get_mut(&uri) {
+                    for change in params["contentChanges"].as_array().into_iter().flatten() {
+                        apply_change(text, change);
+                    }
+                    publish_diagnostics(&uri, text);
+                }
+            }
+            "textDocument/definition"
+            | "textDocument/references"
+            | "textDocument/hover"
+            | "textDocument/rename" => {
+                let result = navigate(&documents, method, &uri, params);
+                write_message(&json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }));
+            }
+            "textDocument/completion" => {
+                let items = documents
+                    .get(&uri)
+                    .map(|text| completions(&documents, text, &params["position"]))
+                    .unwrap_or_default();
+                write_message(&json!({ "jsonrpc": "2.0", "id": message["id"], "result": items }));
+            }
+            "textDocument/formatting" => {
+                let edits = documents
+                    .get(&uri)
+                    .map(|text| trailing_whitespace(text))
+                    .unwrap_or_default();
+                write_message(&json!({ "jsonrpc": "2.0", "id": message["id"], "result": edits }));
+            }
+            "textDocument/didClose" => {
+                documents.remove(&uri);
+            }
+            "textDocument/didSave" => {
+                let text = documents.get(&uri).map_or(0, Vec::len);
+                let lines = documents
+                    .get(&uri)
+                    .map_or(0, |text| text.iter().filter(|c| **c == '\n').count());
+
+                write_message(&json!({
+                    "jsonrpc": "2.0",
+                    "method": "window/showMessage",
+                    "params": { "type": 3, "message": format!("{uri}: {lines} lines, {text} chars") },
+                }));
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,54 +1,149 @@
 use crate::{
-    COMMANDS, MOTIONS, OPERATORS, TEXT_OBJECTS, VIEW_COMMANDS, buffer::Buffer,
-    global_state::GlobalState, operator::Operator, text_object::TextObjectType, utility::last_char,
-    view::View,
+    COMMANDS, LSP_COMMANDS, MOTIONS, OPERATORS, QUICKFIX_COMMANDS, SYNTAX_MOTIONS,
+    SYNTAX_TEXT_OBJECTS, TEXT_OBJECTS, VIEW_COMMANDS, buffer::Buffer, global_state::GlobalState,
+    mode::Mode, operator::Operator, text_object::TextObjectType, utility::last_char, view::View,
 };
+use tree_sitter::Tree;
 
+/// Runs the action chained so far, if it's complete. Actions which change the buffer's text
+/// (other than by entering Insert mode) are remembered in `last_chained` and `last_count`, so
+/// they can be repeated with `.`
 pub fn match_action(
     global_state: &mut GlobalState,
     last_chained: &mut Vec<char>,
     last_count: &mut u32,
     view: &mut View,
 ) {
-    let Some(last) = global_state.chained.last() else {
+    let chained = global_state.chained.clone();
+    let count = global_state.count;
+    // NOTE
+    // Cloning a `Rope` is cheap, it shares its chunks with the original
+    let text = view.get_buffer().rope.clone();
+
+    run_action(global_state, view);
+
+    // NOTE
+    // Undoing isn't a change of its own, so `.` after `u` repeats what was undone
+    let finished = global_state.chained.is_empty() && !chained.is_empty() && chained != ['u'];
+    if !finished {
+        return;
+    }
+    // NOTE
+    // A register prefix (eg. `"b`) is finished on its own, but it's kept for the action after it
+    // so that repeating that action uses the same register
+    if chained.first() == Some(&'"') {
+        global_state.register_prefix = chained;
+        return;
+    }
+
+    let prefix = std::mem::take(&mut global_state.register_prefix);
+    if !matches!(global_state.mode, Mode::Insert) && view.get_buffer().rope != text {
+        *last_chained = [prefix, chained].concat();
+        *last_count = count;
+    }
+}
+
+/// Runs the last action which changed the buffer again, with the count typed before `.` if
+/// there was one
+pub fn repeat(
+    global_state: &mut GlobalState,
+    last_chained: &[char],
+    last_count: u32,
+    view: &mut View,
+) {
+    if !global_state.chained.is_empty() || last_chained.is_empty() {
+        return;
+    }
+    if global_state.count == 1 {
+        global_state.count = last_count;
+    }
+    // The repeated action brings its own register prefix, if it had one
+    global_state.register_prefix.clear();
+
+    for c in last_chained {
+        global_state.chained.push(*c);
+        run_action(global_state, view);
+    }
+}
+
+fn run_action(global_state: &mut GlobalState, view: &mut View) {
+    // NOTE
+    // The argument is chained after the command or motion which takes it, so the action is
+    // matched on what came before it
+    let (chained, argument) = match global_state.chained.split_last() {
+        Some((argument, chained)) if global_state.awaiting_argument => (chained, Some(*argument)),
+        _ => (&global_state.chained[..], None),
+    };
+    let Some(last) = chained.last().copied() else {
         return;
     };
 
-    let cmd: String = global_state.chained.iter().collect();
+    let cmd: String = chained.iter().collect();
 
-    let buffer = view.get_buffer_mut();
+    let view_box = view.get_view_box();
+    // NOTE
+    // Cloning a `Tree` is cheap, it's reference counted
+    let tree = view_box.parse_tree.clone();
+    let buffer = &mut view_box.buffer;
 
     if let Some(operation) = global_state.next_operation {
         handle_pending_operation(
             operation,
             buffer,
+            tree.as_ref(),
             global_state,
-            last_chained,
-            last_count,
-            *last,
+            last,
+            argument,
         );
     } else if let Some(command) = COMMANDS.iter().find(|motion| motion.name == cmd) {
+        if command.takes_argument() && argument.is_none() {
+            global_state.awaiting_argument = true;
+            return;
+        }
+
         (0..global_state.count).for_each(|_| {
             command.execute(
                 buffer,
                 &mut global_state.register_handler,
                 &mut global_state.mode,
                 &mut global_state.undo_tree,
+                argument,
             );
         });
 
-        reset(global_state, last_chained, last_count);
+        reset(global_state);
     } else if let Some(view_command) = VIEW_COMMANDS.iter().find(|command| command.name == cmd) {
         (0..global_state.count).for_each(|_| view_command.execute(view));
 
-        reset(global_state, last_chained, last_count);
-    } else if global_state.chained.len() == 1
-        && let Some(motion) = MOTIONS.iter().find(|motion| motion.name == *last)
+        reset(global_state);
+    } else if let Some(lsp_command) = LSP_COMMANDS.iter().find(|command| command.name == cmd) {
+        lsp_command.execute(&mut global_state.lsp, view, &mut global_state.messages);
+
+        reset(global_state);
+    } else if let Some(quickfix_command) =
+        QUICKFIX_COMMANDS.iter().find(|command| command.name == cmd)
     {
-        (0..global_state.count).for_each(|_| motion.apply(buffer));
+        quickfix_command.execute(&mut global_state.quickfix, view, &mut global_state.messages);
+
+        reset(global_state);
+    } else if let Some(motion) = SYNTAX_MOTIONS.iter().find(|motion| motion.name == cmd) {
+        if let Some(tree) = &tree {
+            (0..global_state.count).for_each(|_| motion.apply(buffer, tree));
+        }
+
+        reset(global_state);
+    } else if chained.len() == 1
+        && let Some(motion) = MOTIONS.iter().find(|motion| motion.name == last)
+    {
+        if motion.takes_argument() && argument.is_none() {
+            global_state.awaiting_argument = true;
+            return;
+        }
+
+        (0..global_state.count).for_each(|_| motion.apply(buffer, argument));
 
-        reset(global_state, last_chained, last_count);
-    } else if let Some(operator) = OPERATORS.iter().find(|operator| operator.name == *last) {
+        reset(global_state);
+    } else if let Some(operator) = OPERATORS.iter().find(|operator| operator.name == last) {
         global_state.next_operation = Some(operator);
     }
 }
@@ -56,71 +151,122 @@ pub fn match_action(
 fn handle_pending_operation(
     operation: &Operator,
     buffer: &mut Buffer,
+    tree: Option<&Tree>,
     global_state: &mut GlobalState,
-    last_chained: &mut Vec<char>,
-    last_count: &mut u32,
     last: char,
+    argument: Option<char>,
 ) {
-    if last == 'i' {
-        global_state.text_object_type = Some(TextObjectType::Inside);
-    } else if last == 'a' {
-        global_state.text_object_type = Some(TextObjectType::Around);
-    } else if operation.name == last {
-        (0..global_state.count).for_each(|_| {
-            operation.entire_line(
+    let chained =
+        &global_state.chained[..global_state.chained.len() - usize::from(argument.is_some())];
+    let chained: String = chained.iter().collect();
+
+    // NOTE
+    // The text object type has to be checked first, since `a` is both the around key and the
+    // argument text object
+    if let Some(to_type) = &global_state.text_object_type {
+        // NOTE
+        // This is fine because for the text object, we only care about the last key pressed
+        if let Some(text_object) = SYNTAX_TEXT_OBJECTS
+            .iter()
+            .find(|to| last_char(to.name) == last)
+        {
+            if let Some(tree) = tree {
+                let level = usize::try_from(global_state.count).unwrap_or(1);
+                let bounds = text_object.bounds(buffer, tree, to_type, level);
+
+                operation.execute_bounds(
+                    bounds,
+                    buffer,
+                    &mut global_state.register_handler,
+                    &mut global_state.mode,
+                    &mut global_state.undo_tree,
+                    &mut global_state.status_bar,
+                );
+            }
+        } else if let Some(text_object) = TEXT_OBJECTS.iter().find(|to| last_char(to.name) == last)
+        {
+            let level = usize::try_from(global_state.count).unwrap_or(1);
+            let bounds = text_object.bounds(buffer, to_type, level);
+
+            operation.execute_bounds(
+                bounds,
                 buffer,
                 &mut global_state.register_handler,
                 &mut global_state.mode,
                 &mut global_state.undo_tree,
+                &mut global_state.status_bar,
             );
-        });
-
-        reset(global_state, last_chained, last_count);
-    } else if let Some(to_type) = &global_state.text_object_type {
-        // NOTE
-        // This is fine because for the text object, we only care about the last key pressed
-        let Some(text_object) = TEXT_OBJECTS.iter().find(|to| last_char(to.name) == last) else {
+        } else {
             // TODO Decide whether we should log things triggered easily by users?
             // log!("Could not find text object {}", last);
             return;
-        };
+        }
+
+        global_state.text_object_type = None;
 
+        reset(global_state);
+    } else if last == 'i' {
+        global_state.text_object_type = Some(TextObjectType::Inside);
+    } else if last == 'a' {
+        global_state.text_object_type = Some(TextObjectType::Around);
+    } else if operation.name == last {
         (0..global_state.count).for_each(|_| {
-            operation.execute_text_object(
-                text_object,
-                to_type,
+            operation.entire_line(
                 buffer,
                 &mut global_state.register_handler,
                 &mut global_state.mode,
                 &mut global_state.undo_tree,
+                &mut global_state.status_bar,
             );
         });
 
-        global_state.text_object_type = None;
+        reset(global_state);
+    } else if let Some(motion) = SYNTAX_MOTIONS
+        .iter()
+        .find(|motion| chained.ends_with(motion.name))
+    {
+        if let Some(tree) = tree {
+            (0..global_state.count).for_each(|_| {
+                operation.execute_syntax_motion(
+                    motion,
+                    tree,
+                    buffer,
+                    &mut global_state.register_handler,
+                    &mut global_state.mode,
+                    &mut global_state.undo_tree,
+                    &mut global_state.status_bar,
+                );
+            });
+        }
 
-        reset(global_state, last_chained, last_count);
+        reset(global_state);
     } else if let Some(motion) = MOTIONS.iter().find(|motion| motion.name == last) {
+        if motion.takes_argument() && argument.is_none() {
+            global_state.awaiting_argument = true;
+            return;
+        }
+
         (0..global_state.count).for_each(|_| {
             operation.execute_motion(
                 motion,
+                argument,
                 buffer,
                 &mut global_state.register_handler,
                 &mut global_state.mode,
                 &mut global_state.undo_tree,
+                &mut global_state.status_bar,
             );
         });
 
-        reset(global_state, last_chained, last_count);
+        reset(global_state);
     }
 }
 
-pub fn reset(global_state: &mut GlobalState, last_chained: &mut Vec<char>, last_count: &mut u32) {
-    last_chained.clone_from(&global_state.chained);
-    *last_count = global_state.count;
-
+pub fn reset(global_state: &mut GlobalState) {
     global_state.chained.clear();
     global_state.count = 1;
     global_state.next_operation = None;
+    global_state.awaiting_argument = false;
 }
 
 pub fn enumerate_normal_chars() -> Vec<char> {
@@ -129,11 +275,19 @@ pub fn enumerate_normal_chars() -> Vec<char> {
     let motion_chars = MOTIONS.iter().map(|cmd| cmd.name);
     let text_object_chars = TEXT_OBJECTS.iter().flat_map(|cmd| cmd.name.chars());
     let view_command_chars = VIEW_COMMANDS.iter().flat_map(|cmd| cmd.name.chars());
+    let lsp_command_chars = LSP_COMMANDS.iter().flat_map(|cmd| cmd.name.chars());
+    let quickfix_command_chars = QUICKFIX_COMMANDS.iter().flat_map(|cmd| cmd.name.chars());
+    let syntax_motion_chars = SYNTAX_MOTIONS.iter().flat_map(|cmd| cmd.name.chars());
+    let syntax_text_object_chars = SYNTAX_TEXT_OBJECTS.iter().flat_map(|cmd| cmd.name.chars());
 
     command_chars
         .chain(operator_chars)
         .chain(motion_chars)
         .chain(text_object_chars)
         .chain(view_command_chars)
+        .chain(lsp_command_chars)
+        .chain(quickfix_command_chars)
+        .chain(syntax_motion_chars)
+        .chain(syntax_text_object_chars)
         .collect()
 }
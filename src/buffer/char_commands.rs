@@ -1,8 +1,14 @@
 use crate::{
     buffer::Buffer,
+    diff::diff,
     undo::{Action, UndoTree},
 };
-use std::iter::once;
+use std::{borrow::Cow, iter::once, ops::Range};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How many characters on either side of a character are looked at to find the grapheme it's
+/// part of
+const GRAPHEME_WINDOW: usize = 32;
 
 impl Buffer {
     pub fn set_cursor(&mut self, cursor: usize) {
@@ -45,8 +51,8 @@ impl Buffer {
         self.insert_char_at(c, self.cursor);
     }
 
-    /// Inserts a newline at the current position, then adds spaces to the new line until the last
-    /// non-whitespace column lines up
+    /// Inserts a newline at the current position, then copies the previous line's indent (tabs
+    /// and all) to the new line, so that the first non-whitespace columns line up
     ///
     /// Increments cursor accordingly
     ///
@@ -57,17 +63,17 @@ impl Buffer {
     /// It returns the contents inserted into the buffer
     pub fn insert_newline(&mut self) -> String {
         let first_col = self.get_first_non_whitespace_col().unwrap_or(0);
+        let indent: String = self.get_curr_line().chars().take(first_col).collect();
         self.update_list_add_current();
 
         self.rope.insert_char(self.cursor, '\n');
         self.set_cursor(self.cursor + 1);
 
-        self.insert_n_times(' ', first_col);
+        self.rope.insert(self.cursor, &indent);
+        self.update_list_use_current_line();
         self.set_cursor(self.cursor + first_col);
 
-        once('\n')
-            .chain((0..first_col).map(|_| ' '))
-            .collect::<String>()
+        once('\n').chain(indent.chars()).collect::<String>()
     }
 
     pub fn insert_n_times_at(&mut self, c: char, n: usize, cursor: usize) {
@@ -81,6 +87,18 @@ impl Buffer {
         self.insert_n_times_at(c, n, self.cursor);
     }
 
+    /// Inserts a single indent at `cursor`
+    ///
+    /// # Returns
+    /// The text inserted
+    pub fn insert_indent_at(&mut self, cursor: usize) -> String {
+        let indent = self.indent.text();
+        self.rope.insert(cursor, &indent);
+        self.update_list_use_current_line();
+
+        indent
+    }
+
     pub fn get_curr_char(&self) -> char {
         self.rope.char(self.cursor)
     }
@@ -102,8 +120,42 @@ impl Buffer {
         None
     }
 
+    /// Moves past the grapheme the cursor is on
     pub fn next_char(&mut self) {
-        self.set_cursor(self.cursor + 1);
+        self.set_cursor(self.grapheme_at(self.cursor).end);
+    }
+
+    /// The grapheme (eg. a letter along with its accents, or an emoji made of several characters)
+    /// the character at `idx` is part of
+    ///
+    /// # Returns
+    /// The range of characters making up the grapheme
+    pub fn grapheme_at(&self, idx: usize) -> Range<usize> {
+        if idx >= self.rope.len_chars() {
+            return idx..idx + 1;
+        }
+
+        let row = self.rope.char_to_line(idx);
+        let line_start = self.rope.line_to_char(row);
+        let line_end = line_start + self.rope.line(row).len_chars();
+
+        // NOTE
+        // This runs on every move, so only the characters around `idx` are segmented rather than
+        // the whole line. Graphemes longer than the window only come from piling up accents, and
+        // are split up.
+        let mut start = usize::max(line_start, idx.saturating_sub(GRAPHEME_WINDOW));
+        let end = usize::min(line_end, idx + GRAPHEME_WINDOW + 1);
+        let window: Cow<str> = self.rope.slice(start..end).into();
+
+        for grapheme in window.graphemes(true) {
+            let end = start + grapheme.chars().count();
+            if idx < end {
+                return start..end;
+            }
+            start = end;
+        }
+
+        idx..idx + 1
     }
 
     pub fn get_prev_char(&self) -> Option<char> {
@@ -114,11 +166,12 @@ impl Buffer {
         }
     }
 
-    pub const fn prev_char(&mut self) {
+    /// Moves to the start of the grapheme before the one the cursor is on
+    pub fn prev_char(&mut self) {
         // We can't rely on `self.set_cursor` to clamp
         // because we would encounter an underflow first
         if self.cursor > 0 {
-            self.cursor -= 1;
+            self.cursor = self.grapheme_at(self.cursor - 1).start;
         }
     }
 
@@ -149,6 +202,8 @@ impl Buffer {
 
         let new_position = usize::min(start_of_next_row + col, end_next_row);
         self.set_cursor(new_position);
+        // The same column can be in the middle of a grapheme on another line
+        self.cursor = self.grapheme_at(self.cursor).start;
     }
 
     pub fn count_spaces_backwards(&self) -> usize {
@@ -168,11 +223,12 @@ impl Buffer {
     }
 
     // Returns the deleted string
-    pub fn delete_to_4_spaces_alignment(&mut self, space_count: usize) -> String {
-        let mut deleted = String::with_capacity(4);
-        let mut leftover = space_count % 4;
+    pub fn delete_to_indent_alignment(&mut self, space_count: usize) -> String {
+        let width = self.indent.width;
+        let mut deleted = String::with_capacity(width);
+        let mut leftover = space_count % width;
         if leftover == 0 {
-            leftover = 4;
+            leftover = width;
         }
         assert!(space_count >= leftover);
 
@@ -194,19 +250,22 @@ impl Buffer {
     /// For example for the string 'hello world' and the substring 'world',
     /// the index `10` will be put in the list.
     pub fn find_occurences(&self, text: &[char]) -> Vec<usize> {
-        let mut count = 0;
         let mut idxs_of_substitution: Vec<usize> = Vec::with_capacity(4);
+        if text.is_empty() {
+            return idxs_of_substitution;
+        }
 
-        for (i, char) in self.rope.chars().enumerate() {
-            if char == text[count] {
-                count += 1;
+        // NOTE
+        // Every start is checked, so that a partial match (eg. `aa` when searching `aab` in `aaab`)
+        // can't hide a full one which overlaps it
+        let chars: Vec<char> = self.rope.chars().collect();
+        let mut i = 0;
+        while i + text.len() <= chars.len() {
+            if chars[i..i + text.len()] == *text {
+                i += text.len();
+                idxs_of_substitution.push(i);
             } else {
-                count = 0;
-            }
-
-            if count == text.len() {
-                idxs_of_substitution.push(i + 1);
-                count = 0;
+                i += 1;
             }
         }
 
@@ -252,6 +311,84 @@ impl Buffer {
         self.clamp_cursor();
     }
 
+    /// Replaces the chars in `range` with `text`, keeping the cursor on the same character if it
+    /// was after the range
+    ///
+    /// # Returns
+    /// The action which undoes the replacement
+    pub fn replace_range(&mut self, range: Range<usize>, text: &str) -> Action {
+        let original = self.rope.slice(range.clone()).to_string();
+        let text_len = text.chars().count();
+
+        self.rope.remove(range.clone());
+        self.rope.insert(range.start, text);
+
+        if self.cursor >= range.end {
+            self.cursor = self.cursor - range.len() + text_len;
+        } else if self.cursor > range.start {
+            self.cursor = range.start;
+        }
+        self.clamp_cursor();
+
+        self.lines_for_updating = (0..self.len()).map(|_| true).collect();
+        self.has_changed = true;
+
+        Action::Group(vec![
+            Action::delete(range.start, &original),
+            Action::insert(range.start, &text),
+        ])
+    }
+
+    /// Changes the contents of the buffer to `new`, only replacing the lines which differ. A
+    /// cursor inside a replaced run of lines stays on the same line of its replacement (or the
+    /// last one, if it's shorter).
+    ///
+    /// # Returns
+    /// The action which undoes every change at once, or `None` if nothing changed
+    pub fn apply_diff(&mut self, new: &str) -> Option<Action> {
+        let old_lines: Vec<String> = self.rope.lines().map(|line| line.to_string()).collect();
+        let new_lines: Vec<String> = new.split_inclusive('\n').map(String::from).collect();
+        // NOTE
+        // A rope ending in a newline has an empty last line, which `split_inclusive` leaves out
+        let new_lines = if new.is_empty() || new.ends_with('\n') {
+            [new_lines, vec![String::new()]].concat()
+        } else {
+            new_lines
+        };
+
+        let hunks = diff(&old_lines, &new_lines);
+        if hunks.is_empty() {
+            return None;
+        }
+
+        let cursor_line = self.rope.char_to_line(self.cursor);
+        let cursor_col = self.cursor - self.rope.line_to_char(cursor_line);
+
+        // Later hunks are replaced first, so the line numbers of earlier ones stay the same
+        let mut group = Vec::new();
+        for hunk in hunks.iter().rev() {
+            let start = self.rope.line_to_char(hunk.old.start);
+            let end = self.rope.line_to_char(hunk.old.end);
+            let text = new_lines[hunk.new.clone()].concat();
+
+            group.push(self.replace_range(start..end, &text));
+
+            if hunk.old.contains(&cursor_line) && !hunk.new.is_empty() {
+                let offset = usize::min(cursor_line - hunk.old.start, hunk.new.len() - 1);
+                let line = hunk.old.start + offset;
+                let line_len = new_lines[hunk.new.start + offset]
+                    .trim_end_matches('\n')
+                    .chars()
+                    .count();
+
+                self.cursor = self.rope.line_to_char(line)
+                    + usize::min(cursor_col, line_len.saturating_sub(1));
+            }
+        }
+
+        Some(Action::Group(group))
+    }
+
     pub fn backspace(&mut self, undo_tree: &mut UndoTree) {
         if self.cursor == 0 {
             return;
@@ -260,11 +397,12 @@ impl Buffer {
         self.cursor -= 1;
         let char = self.get_curr_char();
 
-        // Handles deleting 4 spaces or up to the alignment of 4 spaces if possible
+        // Handles deleting an indent's worth of spaces or up to the alignment of an indent if
+        // possible
         let space_count = self.count_spaces_backwards();
 
-        if space_count > 1 {
-            let deleted = self.delete_to_4_spaces_alignment(space_count);
+        if space_count > 1 && self.indent.width > 0 {
+            let deleted = self.delete_to_indent_alignment(space_count);
 
             self.update_list_use_current_line();
             let action = Action::delete(self.cursor, &deleted);
@@ -402,3 +540,61 @@ impl Buffer {
         self.find_prev_gen(target, self.cursor, 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    fn buffer(text: &str) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.rope = Rope::from(text);
+        buffer
+    }
+
+    #[test]
+    fn grapheme_with_combining_accent() {
+        let buffer = buffer("ae\u{301}b\n");
+
+        assert_eq!(buffer.grapheme_at(0), 0..1);
+        assert_eq!(buffer.grapheme_at(1), 1..3);
+        assert_eq!(buffer.grapheme_at(2), 1..3);
+        assert_eq!(buffer.grapheme_at(3), 3..4);
+    }
+
+    #[test]
+    fn grapheme_with_zero_width_joiners() {
+        // A family, made of three people joined by two zero width joiners
+        let buffer = buffer("a\u{1f469}\u{200d}\u{1f469}\u{200d}\u{1f467}b\n");
+
+        assert_eq!(buffer.grapheme_at(1), 1..6);
+        assert_eq!(buffer.grapheme_at(4), 1..6);
+        assert_eq!(buffer.grapheme_at(6), 6..7);
+    }
+
+    #[test]
+    fn grapheme_far_along_a_line() {
+        let text = format!("{}e\u{301}{}\n", "a".repeat(100), "b".repeat(100));
+        let buffer = buffer(&text);
+
+        assert_eq!(buffer.grapheme_at(100), 100..102);
+        assert_eq!(buffer.grapheme_at(101), 100..102);
+        assert_eq!(buffer.grapheme_at(102), 102..103);
+    }
+
+    #[test]
+    fn move_over_graphemes() {
+        let mut buffer = buffer("ae\u{301}\u{1f469}\u{200d}\u{1f467}b\n");
+
+        buffer.next_char();
+        assert_eq!(buffer.cursor, 1);
+        buffer.next_char();
+        assert_eq!(buffer.cursor, 3);
+        buffer.next_char();
+        assert_eq!(buffer.cursor, 6);
+        buffer.prev_char();
+        assert_eq!(buffer.cursor, 3);
+        buffer.prev_char();
+        assert_eq!(buffer.cursor, 1);
+    }
+}
@@ -17,10 +17,6 @@ impl Buffer {
     }
 
     /// Returns the first index (absolute) of the line represented by the given `line_idx`
-    // NOTE
-    // We need to allow this because it's used only by another unused function
-    // In the future we may need to open an issue on `cargo-clippy` about this
-    #[allow(dead_code)]
     pub fn get_start_of_n_line(&self, line_idx: usize) -> usize {
         self.rope.line_to_char(line_idx)
     }
@@ -130,8 +126,7 @@ impl Buffer {
 
         // NOTE
         // Make sure to correctly add the trailing newline
-        if !contents.is_empty() && contents.chars().nth(contents.len() - 1).unwrap_or('\0') == '\n'
-        {
+        if contents.ends_with('\n') {
             contents
                 .lines()
                 .chain(once(""))
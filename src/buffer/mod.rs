@@ -2,6 +2,7 @@ mod char_commands;
 mod line_commands;
 mod update_list;
 
+use crate::options::Indent;
 use ropey::Rope;
 use std::fmt::Display;
 
@@ -19,6 +20,11 @@ pub struct Buffer {
     pub cursor: usize,
     /// The largest column since moving sideways
     pub intended_column: usize,
+    /// How indents are inserted, from the options of the view box holding it
+    pub indent: Indent,
+    /// Where the cursor was and went to each time it was moved out to a parent syntax node, so
+    /// that moving back in returns to where it came from
+    pub node_history: Vec<(usize, usize)>,
 }
 
 impl Buffer {
@@ -29,6 +35,8 @@ impl Buffer {
             rope: Rope::from(""),
             intended_column: 0,
             cursor: 0,
+            indent: Indent::default(),
+            node_history: Vec::new(),
         }
     }
 
@@ -1,5 +1,4 @@
-use crate::{highlight::ORANGE, utility::is_symbol};
-use crossterm::style::Color;
+use crate::{theme::HLGroup, utility::is_symbol};
 
 const KEYWORDS: &[&str] = &[
     "alignas",
@@ -75,37 +74,38 @@ fn is_operator(str: &str) -> bool {
     OPERATORS.contains(&str)
 }
 
-pub fn c_node_to_color(
+pub fn c_node_to_group(
     node_type: &str,
     parent_type: &str,
     _last_sibiling_type: &str,
-) -> Option<Color> {
-    let color = match node_type {
-        "#include" | "#define" | "#ifdef" | "#ifndef" | "#endif" => Color::DarkRed,
+) -> Option<HLGroup> {
+    let group = match node_type {
+        "#include" | "#define" | "#ifdef" | "#ifndef" | "#endif" => HLGroup::Preproc,
 
-        "string_content" | "character" | "\"" | "\'" | "system_lib_string" => Color::Green,
+        "string_content" | "character" | "\"" | "\'" | "system_lib_string" => HLGroup::String,
         "identifier"
             if parent_type == "function_declarator" || parent_type == "call_expression" =>
         {
-            Color::Green
+            HLGroup::Function
         }
 
-        "identifier" | "preproc_arg" => Color::Grey,
-        "field_identifier" => Color::Blue,
+        "identifier" | "preproc_arg" => HLGroup::Text,
+        "field_identifier" => HLGroup::Field,
 
-        "primitive_type" => Color::Yellow,
-        "type_identifier" => Color::DarkMagenta,
+        "primitive_type" => HLGroup::Type,
+        "type_identifier" => HLGroup::CustomType,
 
-        "comment" | ";" | "." | "," => Color::DarkGrey,
+        "comment" => HLGroup::Comment,
+        ";" | "." | "," => HLGroup::Delimiter,
         // Common macros
         // In the future a way to automatically determine
         // which strings are macros would be really cool
-        "number_literal" | "true" | "false" | "NULL" => Color::Magenta,
-        str if is_operator(str) => ORANGE,
-        str if str.chars().all(is_symbol) => Color::Grey,
-        str if is_c_keyword(str) => Color::Red,
+        "number_literal" | "true" | "false" | "NULL" => HLGroup::Constant,
+        str if is_operator(str) => HLGroup::Operator,
+        str if str.chars().all(is_symbol) => HLGroup::Text,
+        str if is_c_keyword(str) => HLGroup::Keyword,
         _ => return None,
     };
 
-    Some(color)
+    Some(group)
 }
@@ -1,5 +1,5 @@
-use crate::mode::Mode;
-use anyhow::{Result, bail};
+use crate::{mode::Mode, theme::DEFAULT_THEME};
+use anyhow::Result;
 use clap::Parser;
 use std::path::PathBuf;
 
@@ -10,8 +10,16 @@ pub struct Cli {
 
     #[arg(short, long, value_enum, default_value_t = Mode::Normal)]
     pub mode: Mode,
-    #[arg(short, long, default_value_t = true)]
+    /// Writes a debug log to `$XDG_STATE_HOME/orinfar/`
+    #[arg(short, long)]
     pub debug: bool,
+    /// The name of a built-in theme, or of a theme file in `$XDG_CONFIG_HOME/orinfar/themes/`
+    #[arg(short, long, default_value_t = DEFAULT_THEME.to_string())]
+    pub theme: String,
+    /// Presses these keys (eg. `dw:s/a/b<CR>`) without a terminal, then prints what the screen
+    /// shows
+    #[arg(short, long)]
+    pub keys: Option<String>,
 }
 
 impl Cli {
@@ -22,16 +30,12 @@ impl Cli {
             Some(ref path) => {
                 let path = PathBuf::from(path);
 
-                if path.is_dir() {
-                    // TODO netrw
-                    bail!("Orinfar does not support directory navigation");
-                } else if path.is_file() {
-                    Ok((cli, Some(path)))
-                } else {
+                // Directories are opened in the explorer
+                if !path.exists() {
                     std::fs::write(&path, "")?;
-
-                    Ok((cli, Some(path)))
                 }
+
+                Ok((cli, Some(path)))
             }
             None => Ok((cli, None)),
         }
@@ -0,0 +1,222 @@
+// NOTE
+// There are three ways of counting along a line:
+// - bytes, which tree-sitter (and so highlighting) uses
+// - characters, which the buffer's cursor and the rope use
+// - display columns, which is where things are drawn on the screen, since tabs and wide characters
+//   (eg. CJK and emoji) take up more than one column, and combining characters take up none
+//
+// The functions here convert from the first two to the last, and back
+use unicode_width::UnicodeWidthChar;
+
+/// How many columns `c` takes up, when it's shown starting at display column `col`
+pub fn char_width(c: char, col: usize, tabstop: usize) -> usize {
+    if c == '\t' {
+        tabstop - col % tabstop
+    } else {
+        // Control characters don't have a width, but are drawn as something
+        c.width().unwrap_or(1)
+    }
+}
+
+/// The display column after `c`, when it's shown starting at display column `col`
+pub fn next_col(c: char, col: usize, tabstop: usize) -> usize {
+    col + char_width(c, col, tabstop)
+}
+
+/// How many columns `chars` take up, shown from the start of a line
+pub fn width(chars: impl IntoIterator<Item = char>, tabstop: usize) -> usize {
+    chars
+        .into_iter()
+        .fold(0, |col, c| next_col(c, col, tabstop))
+}
+
+/// The display column each character of `line` starts at, followed by the width of the whole
+/// line
+pub fn columns(line: &str, tabstop: usize) -> Vec<usize> {
+    let mut columns = Vec::with_capacity(line.len() + 1);
+    let mut col = 0;
+
+    for c in line.chars() {
+        columns.push(col);
+        col = next_col(c, col, tabstop);
+    }
+    columns.push(col);
+
+    columns
+}
+
+/// The display column each byte of `line` is in, followed by the width of the whole line
+pub fn byte_columns(line: &str, tabstop: usize) -> Vec<usize> {
+    let mut columns = Vec::with_capacity(line.len() + 1);
+    let mut col = 0;
+
+    for c in line.chars() {
+        (0..c.len_utf8()).for_each(|_| columns.push(col));
+        col = next_col(c, col, tabstop);
+    }
+    columns.push(col);
+
+    columns
+}
+
+/// The index of the character of `line` drawn at display column `col`, or of the last character
+/// if the line isn't that wide
+pub fn char_at_col(line: &str, col: usize, tabstop: usize) -> usize {
+    let mut next = 0;
+
+    for (idx, c) in line.chars().enumerate() {
+        next = next_col(c, next, tabstop);
+        if next > col {
+            return idx;
+        }
+    }
+
+    line.chars().count().saturating_sub(1)
+}
+
+/// The display column each row of `line` starts at, when it's wrapped onto rows `width` columns
+/// wide
+///
+/// A wide character which would be cut off by the end of a row starts the next one instead, while
+/// tabs are split across rows, since they're drawn as spaces anyway
+pub fn wrap_starts(line: &str, width: usize, tabstop: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut col = 0;
+
+    for c in line.chars() {
+        let next = next_col(c, col, tabstop);
+        let start = starts.last().copied().unwrap_or(0);
+
+        if c != '\t' && next > start + width && col > start {
+            starts.push(col);
+        }
+        while let Some(&start) = starts.last()
+            && next > start + width
+        {
+            starts.push(start + width);
+        }
+        col = next;
+    }
+
+    starts
+}
+
+/// The text drawn in display columns `[from, to)` of `line`, with tabs as spaces
+///
+/// Wide characters cut off by either end are drawn as spaces, and combining characters are kept
+/// with the character they combine with
+pub fn slice(line: &str, from: usize, to: usize, tabstop: usize) -> String {
+    let mut text = String::with_capacity(to.saturating_sub(from));
+    let mut col = 0;
+    let mut shown = false;
+
+    for c in line.chars() {
+        let next = next_col(c, col, tabstop);
+
+        if next == col {
+            if shown {
+                text.push(c);
+            }
+            continue;
+        }
+        if col >= to {
+            break;
+        }
+
+        if from <= col && next <= to && c != '\t' {
+            text.push(c);
+            shown = true;
+        } else {
+            // Whatever part of it is between `from` and `to`
+            let (start, end) = (usize::max(col, from), usize::min(next, to));
+            (start..end).for_each(|_| text.push(' '));
+            shown = false;
+        }
+        col = next;
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tab_width_depends_on_column() {
+        assert_eq!(char_width('\t', 0, 4), 4);
+        assert_eq!(char_width('\t', 1, 4), 3);
+        assert_eq!(char_width('\t', 3, 4), 1);
+        assert_eq!(char_width('\t', 4, 4), 4);
+        assert_eq!(char_width('\t', 6, 8), 2);
+    }
+
+    #[test]
+    fn columns_after_tabs() {
+        assert_eq!(columns("a\tb\t", 4), [0, 1, 4, 5, 8]);
+        assert_eq!(byte_columns("é\tb", 4), [0, 0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn slice_tabs_after_text() {
+        assert_eq!(slice("a\tb", 0, 8, 4), "a   b");
+        assert_eq!(slice("ab\tc", 0, 8, 4), "ab  c");
+    }
+
+    #[test]
+    fn slice_through_a_tab() {
+        // The tab takes up columns 1 to 3, so only the part of it after `from` is shown
+        assert_eq!(slice("a\tb", 2, 8, 4), "  b");
+        assert_eq!(slice("a\tb", 0, 2, 4), "a ");
+        assert_eq!(slice("a\tb\tc", 5, 9, 4), "   c");
+    }
+
+    #[test]
+    fn slice_keeps_combining_accents() {
+        assert_eq!(slice("ae\u{301}b", 1, 3, 4), "e\u{301}b");
+        // The accent goes with the `e`, which is cut off
+        assert_eq!(slice("ae\u{301}b", 0, 1, 4), "a");
+        assert_eq!(slice("ae\u{301}b", 2, 3, 4), "b");
+    }
+
+    #[test]
+    fn slice_zero_width_joiners() {
+        let family = "\u{1f469}\u{200d}\u{1f467}";
+
+        assert_eq!(slice(family, 0, 4, 4), family);
+        // The joiner goes with the first person, which is cut off
+        assert_eq!(slice(family, 1, 4, 4), " \u{1f467}");
+    }
+
+    #[test]
+    fn slice_cuts_wide_characters_at_either_edge() {
+        // `中` takes up columns 1 and 2
+        assert_eq!(slice("a中b", 0, 4, 4), "a中b");
+        assert_eq!(slice("a中b", 2, 4, 4), " b");
+        assert_eq!(slice("a中b", 0, 2, 4), "a ");
+        assert_eq!(slice("a中b", 2, 2, 4), "");
+    }
+
+    #[test]
+    fn wrap_before_wide_characters() {
+        assert_eq!(wrap_starts("", 4, 4), [0]);
+        assert_eq!(wrap_starts("abcd", 4, 4), [0]);
+        assert_eq!(wrap_starts("abcde", 4, 4), [0, 4]);
+        // `中` would take up columns 3 and 4, so it starts the second row
+        assert_eq!(wrap_starts("abc中d", 4, 4), [0, 3]);
+        assert_eq!(wrap_starts("ab中中d", 4, 4), [0, 4]);
+        // The tab takes up columns 2 to 7, and is split across the rows
+        assert_eq!(wrap_starts("ab\tc", 3, 8), [0, 3, 6]);
+    }
+
+    #[test]
+    fn char_at_wide_and_combining_characters() {
+        assert_eq!(char_at_col("a中b", 1, 4), 1);
+        assert_eq!(char_at_col("a中b", 2, 4), 1);
+        assert_eq!(char_at_col("a中b", 3, 4), 2);
+        // The accent takes up no columns, so the character after it is next
+        assert_eq!(char_at_col("e\u{301}x", 0, 4), 0);
+        assert_eq!(char_at_col("e\u{301}x", 1, 4), 2);
+        assert_eq!(char_at_col("a中b", 10, 4), 2);
+    }
+}
@@ -4,21 +4,31 @@ use crate::{
     register::RegisterHandler,
     undo::{Action, UndoTree},
 };
-use crossterm::{
-    cursor::SetCursorStyle,
-    event::{Event, KeyCode, read},
-    execute,
-};
-use std::io::stdout;
+
+enum CommandFn {
+    Plain(
+        fn(
+            buffer: &mut Buffer,
+            register_handler: &mut RegisterHandler,
+            mode: &mut Mode,
+            undo_tree: &mut UndoTree,
+        ),
+    ),
+    /// Takes the character typed after the command's name, eg. the one `r` replaces with
+    Argument(
+        fn(
+            buffer: &mut Buffer,
+            register_handler: &mut RegisterHandler,
+            mode: &mut Mode,
+            undo_tree: &mut UndoTree,
+            argument: char,
+        ),
+    ),
+}
 
 pub struct Command<'a> {
     pub name: &'a str,
-    command: fn(
-        buffer: &mut Buffer,
-        register_handler: &mut RegisterHandler,
-        mode: &mut Mode,
-        undo_tree: &mut UndoTree,
-    ),
+    command: CommandFn,
 }
 
 impl<'a> Command<'a> {
@@ -31,7 +41,31 @@ impl<'a> Command<'a> {
             undo_tree: &mut UndoTree,
         ),
     ) -> Self {
-        Command { name, command }
+        Command {
+            name,
+            command: CommandFn::Plain(command),
+        }
+    }
+
+    /// A command which takes the character typed after it as an argument
+    pub const fn with_argument(
+        name: &'a str,
+        command: fn(
+            buffer: &mut Buffer,
+            register_handler: &mut RegisterHandler,
+            mode: &mut Mode,
+            undo_tree: &mut UndoTree,
+            argument: char,
+        ),
+    ) -> Self {
+        Command {
+            name,
+            command: CommandFn::Argument(command),
+        }
+    }
+
+    pub const fn takes_argument(&self) -> bool {
+        matches!(self.command, CommandFn::Argument(_))
     }
 
     pub fn execute(
@@ -40,12 +74,19 @@ impl<'a> Command<'a> {
         register_handler: &mut RegisterHandler,
         mode: &mut Mode,
         undo_tree: &mut UndoTree,
+        argument: Option<char>,
     ) {
-        (self.command)(buffer, register_handler, mode, undo_tree);
+        match (&self.command, argument) {
+            (CommandFn::Plain(command), _) => command(buffer, register_handler, mode, undo_tree),
+            (CommandFn::Argument(command), Some(argument)) => {
+                command(buffer, register_handler, mode, undo_tree, argument);
+            }
+            (CommandFn::Argument(_), None) => {}
+        }
     }
 }
 
-pub fn insert(
+pub const fn insert(
     _buffer: &mut Buffer,
     _register_handler: &mut RegisterHandler,
     mode: &mut Mode,
@@ -61,7 +102,7 @@ pub fn append(
     _undo_tree: &mut UndoTree,
 ) {
     if buffer.cursor != buffer.get_end_of_line() || buffer.cursor + 1 == buffer.rope.len_chars() {
-        buffer.cursor += 1;
+        buffer.cursor = buffer.grapheme_at(buffer.cursor).end;
     }
 
     mode.insert();
@@ -77,16 +118,20 @@ pub fn cut(
         if c == '\n' {
             return;
         }
-        register_handler.set_reg(c.to_string());
-        let anchor = buffer.cursor;
-
-        buffer.rope.remove(buffer.cursor..=buffer.cursor);
+        // The whole grapheme, so accents aren't left behind
+        let grapheme = buffer.grapheme_at(buffer.cursor);
+        let text = buffer.rope.slice(grapheme.clone()).to_string();
+        register_handler.set_reg(text.clone());
+        let anchor = grapheme.start;
+        buffer.cursor = anchor;
+
+        buffer.rope.remove(grapheme);
         if buffer.cursor != 0 && buffer.is_last_col() {
-            buffer.cursor -= 1;
+            buffer.prev_char();
         }
         buffer.update_list_use_current_line();
 
-        let action = Action::delete(anchor, &c);
+        let action = Action::delete(anchor, &text);
         undo_tree.new_action(action);
     }
 }
@@ -157,17 +202,14 @@ pub fn _crash(
     panic!("Intentionally Crashed")
 }
 
-pub fn set_curr_register(
+pub const fn set_curr_register(
     _buffer: &mut Buffer,
     register_handler: &mut RegisterHandler,
     _mode: &mut Mode,
     _undo_tree: &mut UndoTree,
+    reg_name: char,
 ) {
-    if let Event::Key(event) = read().expect("Could not read character from stdin")
-        && let KeyCode::Char(reg_name) = event.code
-    {
-        register_handler.current_register = reg_name;
-    }
+    register_handler.current_register = reg_name;
 }
 
 pub fn replace(
@@ -175,24 +217,18 @@ pub fn replace(
     _register_handler: &mut RegisterHandler,
     _mode: &mut Mode,
     undo_tree: &mut UndoTree,
+    c: char,
 ) {
     if buffer.cursor == buffer.rope.len_chars() {
         return;
     }
-    // NOTE
-    // We want to execute, because stdout won't get flushed before the effect gets reverted
-    execute!(stdout(), SetCursorStyle::SteadyUnderScore)
-        .expect("Crossterm steady underscore command failed");
-    if let Event::Key(event) = read().expect("Failed to read replacement character")
-        && let KeyCode::Char(c) = event.code
-    {
-        let original_char = buffer.get_curr_char();
-        buffer.replace_curr_char(c);
-
-        let action = Action::replace(vec![buffer.cursor + 1], &original_char, &c);
-        undo_tree.new_action_merge(action);
-    }
-    execute!(stdout(), SetCursorStyle::SteadyBlock).expect("Crossterm steady block command failed");
+
+    let original_char = buffer.get_curr_char();
+    buffer.replace_curr_char(c);
+
+    let action = Action::replace(vec![buffer.cursor + 1], &original_char, &c);
+    undo_tree.new_action_merge(action);
+
     buffer.has_changed = true;
 }
 
@@ -237,10 +273,10 @@ pub fn indent(
 
     let start_of_line = buffer.get_start_of_line();
 
-    buffer.insert_n_times_at(' ', 4, start_of_line);
-    buffer.cursor += 4;
+    let indent = buffer.insert_indent_at(start_of_line);
+    buffer.cursor += indent.chars().count();
 
-    let action = Action::insert(start_of_line, &"    ");
+    let action = Action::insert(start_of_line, &indent);
     undo_tree.new_action(action);
 }
 
@@ -250,20 +286,30 @@ pub fn unindent(
     _mode: &mut Mode,
     undo_tree: &mut UndoTree,
 ) {
-    if buffer.get_curr_line().len_chars() < 4 {
-        return;
-    }
-
     let start_of_line = buffer.get_start_of_line();
-    let maybe_tab = buffer.rope.slice(start_of_line..start_of_line + 4).as_str();
-
-    if maybe_tab == Some("    ") {
-        buffer.rope.remove(start_of_line..start_of_line + 4);
+    let line = buffer.get_curr_line();
+
+    // A tab, or up to an indent's worth of spaces
+    let len = if line.get_char(0) == Some('\t') {
+        1
+    } else {
+        line.chars()
+            .take(buffer.indent.width)
+            .take_while(|c| *c == ' ')
+            .count()
+    };
+
+    if len > 0 {
+        let deleted = buffer
+            .rope
+            .slice(start_of_line..start_of_line + len)
+            .to_string();
+        buffer.rope.remove(start_of_line..start_of_line + len);
         // Needed because we're using a raw rope method
         buffer.update_list_use_current_line();
-        buffer.cursor -= 4;
+        buffer.cursor = usize::max(buffer.cursor, start_of_line + len) - len;
 
-        let action = Action::delete(start_of_line, &"    ");
+        let action = Action::delete(start_of_line, &deleted);
         undo_tree.new_action(action);
 
         buffer.clamp_cursor();
@@ -0,0 +1,389 @@
+use crate::{
+    COMPLETION_SOURCES,
+    buffer::Buffer,
+    popup::Popup,
+    undo::{Action, UndoTree},
+    view::View,
+};
+use std::collections::HashSet;
+
+/// Text which can replace the chars from `start` up to the cursor
+#[derive(Debug, Clone)]
+pub struct CompletionItem {
+    pub start: usize,
+    pub text: String,
+    pub source: &'static str,
+}
+
+/// Somewhere candidates for completion come from, other than the language server
+pub struct CompletionSource<'a> {
+    pub name: &'a str,
+    complete: fn(view: &View) -> Vec<CompletionItem>,
+}
+
+impl<'a> CompletionSource<'a> {
+    pub const fn new(name: &'a str, complete: fn(view: &View) -> Vec<CompletionItem>) -> Self {
+        CompletionSource { name, complete }
+    }
+
+    pub fn complete(&self, view: &View) -> Vec<CompletionItem> {
+        (self.complete)(view)
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_path_char(c: char) -> bool {
+    is_word_char(c) || matches!(c, '/' | '.' | '-' | '~')
+}
+
+/// The index where the run of chars matching `predicate` before the cursor starts
+fn token_start(buffer: &Buffer, predicate: fn(char) -> bool) -> usize {
+    let before = buffer
+        .rope
+        .chars_at(buffer.cursor)
+        .reversed()
+        .take_while(|c| predicate(*c))
+        .count();
+
+    buffer.cursor - before
+}
+
+/// Where the word before the cursor starts
+pub fn word_start(buffer: &Buffer) -> usize {
+    token_start(buffer, is_word_char)
+}
+
+/// The word being typed, if there is one
+fn word_prefix(buffer: &Buffer) -> Option<(usize, String)> {
+    let start = word_start(buffer);
+    (start < buffer.cursor).then(|| (start, buffer.rope.slice(start..buffer.cursor).to_string()))
+}
+
+/// Every word in `buffer` which starts with `prefix`, closest to `near` first
+fn words(buffer: &Buffer, prefix: &str, near: usize) -> Vec<(usize, String)> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut word_start = 0;
+
+    for (i, c) in buffer.rope.chars().chain(std::iter::once(' ')).enumerate() {
+        if is_word_char(c) {
+            if word.is_empty() {
+                word_start = i;
+            }
+            word.push(c);
+        } else if !word.is_empty() {
+            if word.starts_with(prefix) && word != prefix {
+                words.push((word_start.abs_diff(near), std::mem::take(&mut word)));
+            }
+            word.clear();
+        }
+    }
+
+    words.sort_by_key(|(distance, _)| *distance);
+    words
+}
+
+pub fn buffer_words(view: &View) -> Vec<CompletionItem> {
+    let buffer = view.get_buffer();
+    let Some((start, prefix)) = word_prefix(buffer) else {
+        return vec![];
+    };
+
+    words(buffer, &prefix, buffer.cursor)
+        .into_iter()
+        .map(|(_, text)| CompletionItem {
+            start,
+            text,
+            source: "buffer",
+        })
+        .collect()
+}
+
+pub fn other_buffer_words(view: &View) -> Vec<CompletionItem> {
+    let current = view.get_buffer();
+    let Some((start, prefix)) = word_prefix(current) else {
+        return vec![];
+    };
+
+    view.view_boxes()
+        .filter(|view_box| !std::ptr::eq(&raw const view_box.buffer, current))
+        .flat_map(|view_box| words(&view_box.buffer, &prefix, 0))
+        .map(|(_, text)| CompletionItem {
+            start,
+            text,
+            source: "buffers",
+        })
+        .collect()
+}
+
+/// Completes the last component of a path containing a `/`, relative to where the editor was
+/// run from
+pub fn paths(view: &View) -> Vec<CompletionItem> {
+    let buffer = view.get_buffer();
+    let token_start = token_start(buffer, is_path_char);
+    let token = buffer.rope.slice(token_start..buffer.cursor).to_string();
+
+    let Some((dir, partial)) = token.rsplit_once('/') else {
+        return vec![];
+    };
+    let dir = match dir.strip_prefix('~') {
+        Some(rest) => std::env::var("HOME").unwrap_or_default() + rest,
+        None if dir.is_empty() && token.starts_with('/') => "/".to_string(),
+        None => dir.to_string(),
+    };
+    let dir = if dir.is_empty() { ".".to_string() } else { dir };
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut items: Vec<CompletionItem> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            // Hidden files are only completed once their `.` has been typed
+            if !name.starts_with(partial) || (name.starts_with('.') && !partial.starts_with('.')) {
+                return None;
+            }
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                name.push('/');
+            }
+
+            Some(CompletionItem {
+                start: buffer.cursor - partial.chars().count(),
+                text: name,
+                source: "path",
+            })
+        })
+        .collect();
+
+    items.sort_by(|a, b| a.text.cmp(&b.text));
+    items
+}
+
+/// The candidates for completing the text before the cursor in Insert mode, and which of them
+/// is selected
+pub struct Completion {
+    candidates: Vec<CompletionItem>,
+    /// The candidates which match what's been typed since completion started
+    matches: Vec<usize>,
+    selected: usize,
+    /// Where the text being completed started when completion started. Completion ends if the
+    /// cursor moves before it.
+    start: usize,
+}
+
+impl Completion {
+    /// Gathers candidates from every `CompletionSource`. The language server's candidates are
+    /// added with `Completion::add` once it responds.
+    pub fn start(view: &View) -> Self {
+        let buffer = view.get_buffer();
+        let mut completion = Self {
+            candidates: Vec::new(),
+            matches: Vec::new(),
+            selected: 0,
+            start: token_start(buffer, is_path_char),
+        };
+
+        let items = COMPLETION_SOURCES
+            .iter()
+            .flat_map(|source| source.complete(view))
+            .collect();
+        completion.add(items, buffer);
+
+        completion
+    }
+
+    /// Adds more candidates, leaving out duplicates of ones which are already there
+    pub fn add(&mut self, items: Vec<CompletionItem>, buffer: &Buffer) {
+        let mut seen: HashSet<String> = self
+            .candidates
+            .iter()
+            .map(|item| item.text.clone())
+            .collect();
+
+        self.candidates.extend(
+            items
+                .into_iter()
+                .filter(|item| item.start <= buffer.cursor && seen.insert(item.text.clone())),
+        );
+        self.refilter(buffer);
+    }
+
+    /// Narrows the candidates down to those matching the text typed before the cursor
+    ///
+    /// # Returns
+    /// Whether completion should continue
+    pub fn refilter(&mut self, buffer: &Buffer) -> bool {
+        if buffer.cursor < self.start {
+            return false;
+        }
+
+        let selected = self.matches.get(self.selected).copied();
+        self.matches = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                (self.start..=buffer.cursor).contains(&item.start)
+                    && item
+                        .text
+                        .starts_with(&buffer.rope.slice(item.start..buffer.cursor).to_string())
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        // Keep the same candidate selected if it still matches
+        self.selected = selected
+            .and_then(|selected| self.matches.iter().position(|i| *i == selected))
+            .unwrap_or(0);
+
+        true
+    }
+
+    pub const fn has_matches(&self) -> bool {
+        !self.matches.is_empty()
+    }
+
+    pub const fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    pub const fn prev(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    /// Replaces the text being completed with the selected candidate
+    ///
+    /// When the candidate just continues what was typed, the rest of it is inserted as part of
+    /// the same undo action as the typing. Otherwise the replacement is its own action.
+    pub fn accept(&self, buffer: &mut Buffer, undo_tree: &mut UndoTree) {
+        let Some(item) = self
+            .matches
+            .get(self.selected)
+            .map(|i| &self.candidates[*i])
+        else {
+            return;
+        };
+
+        let typed = buffer.rope.slice(item.start..buffer.cursor).to_string();
+        if let Some(rest) = item.text.strip_prefix(&typed) {
+            if rest.is_empty() {
+                return;
+            }
+            let position = buffer.cursor;
+            buffer.rope.insert(position, rest);
+            buffer.cursor += rest.chars().count();
+            buffer.lines_for_updating = (0..buffer.len()).map(|_| true).collect();
+            buffer.has_changed = true;
+
+            undo_tree.new_action_merge(Action::insert(position, &rest));
+        } else {
+            let action = buffer.replace_range(item.start..buffer.cursor, &item.text);
+            undo_tree.new_action(action);
+        }
+    }
+
+    /// The menu of matching candidates, or `None` if there aren't any
+    pub fn popup(&self) -> Option<Popup> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        let width = self
+            .matches
+            .iter()
+            .map(|i| self.candidates[*i].text.chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines = self
+            .matches
+            .iter()
+            .map(|i| {
+                let item = &self.candidates[*i];
+                format!("{:<width$}  {}", item.text, item.source)
+            })
+            .collect();
+
+        Some(Popup::menu(lines, self.selected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    /// `typed` as if it had just been typed at the end of `before` in Insert mode, so that it's
+    /// one undo action
+    fn typed(before: &str, typed: &str) -> (Buffer, UndoTree) {
+        let mut buffer = Buffer::new();
+        buffer.rope = Rope::from(before.to_string() + typed);
+        buffer.cursor = buffer.rope.len_chars();
+
+        let mut undo_tree = UndoTree::new();
+        let start = before.chars().count();
+        for (i, c) in typed.chars().enumerate() {
+            undo_tree.new_action_merge(Action::insert(start + i, &c));
+        }
+        (buffer, undo_tree)
+    }
+
+    /// A completion with the candidate `text`, replacing from `start`, selected whether or not it
+    /// matches what was typed
+    fn selecting(start: usize, text: &str) -> Completion {
+        Completion {
+            candidates: vec![CompletionItem {
+                start,
+                text: text.to_string(),
+                source: "test",
+            }],
+            matches: vec![0],
+            selected: 0,
+            start,
+        }
+    }
+
+    #[test]
+    fn continuing_what_was_typed_is_undone_with_the_typing() {
+        let (mut buffer, mut undo_tree) = typed("forest ", "fo");
+
+        selecting(7, "forest").accept(&mut buffer, &mut undo_tree);
+        assert_eq!(buffer.rope.to_string(), "forest forest");
+        assert_eq!(buffer.cursor, 13);
+
+        undo_tree.undo(&mut buffer);
+        assert_eq!(buffer.rope.to_string(), "forest ");
+    }
+
+    #[test]
+    fn replacing_what_was_typed_is_undone_on_its_own() {
+        let (mut buffer, mut undo_tree) = typed("x.", "Fo");
+
+        selecting(2, "foo").accept(&mut buffer, &mut undo_tree);
+        assert_eq!(buffer.rope.to_string(), "x.foo");
+
+        undo_tree.undo(&mut buffer);
+        assert_eq!(buffer.rope.to_string(), "x.Fo");
+        undo_tree.undo(&mut buffer);
+        assert_eq!(buffer.rope.to_string(), "x.");
+    }
+
+    #[test]
+    fn accepting_what_was_already_typed_changes_nothing() {
+        let (mut buffer, mut undo_tree) = typed("", "forest");
+
+        selecting(0, "forest").accept(&mut buffer, &mut undo_tree);
+        assert_eq!(buffer.rope.to_string(), "forest");
+
+        undo_tree.undo(&mut buffer);
+        assert_eq!(buffer.rope.to_string(), "");
+    }
+}
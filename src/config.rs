@@ -0,0 +1,62 @@
+use crate::message::Messages;
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+
+/// Reads the config file at `file` in `$XDG_CONFIG_HOME/orinfar/`, or the default config if there
+/// isn't one
+///
+/// # Errors
+/// - If the config file can't be read or is malformed
+pub fn load<T: DeserializeOwned + Default>(file: &str) -> Result<T> {
+    Ok(find(file)?.unwrap_or_default())
+}
+
+/// Reads the config file at `file` in `$XDG_CONFIG_HOME/orinfar/`, if there is one
+///
+/// # Errors
+/// - If the config file can't be read or is malformed
+pub fn find<T: DeserializeOwned>(file: &str) -> Result<Option<T>> {
+    let base = xdg::BaseDirectories::with_prefix("orinfar");
+    let Some(path) = base.find_config_file(file) else {
+        return Ok(None);
+    };
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Could not read config file {}", path.display()))?;
+    toml::from_str(&contents)
+        .map(Some)
+        .with_context(|| format!("Malformed config file {}", path.display()))
+}
+
+/// The loaded config, or the default if it couldn't be loaded, in which case the error is
+/// reported as a message
+// NOTE
+// Everything still works with the default config, so a broken config file shouldn't stop the
+// editor from starting
+pub fn or_default<T: Default>(loaded: Result<T>, messages: &mut Messages) -> T {
+    loaded.unwrap_or_else(|err| {
+        messages.error(format!("{err:#}, so the defaults are used"));
+        T::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn errors_are_reported_and_the_default_used() {
+        let mut messages = Messages::new();
+
+        assert_eq!(or_default(Ok(3), &mut messages), 3);
+        assert!(messages.current().is_none());
+
+        let loaded: Result<usize> = Err(anyhow!("Malformed config file make.toml"));
+        assert_eq!(or_default(loaded, &mut messages), 0);
+        assert_eq!(
+            messages.current().map(|message| message.text.as_str()),
+            Some("Malformed config file make.toml, so the defaults are used")
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::ops::Range;
+
+/// A run of items in the old sequence which were replaced by a run of items in the new one.
+/// Either run can be empty, for pure insertions and deletions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Finds the smallest set of hunks turning `old` into `new`, using Myers' algorithm
+///
+/// This is the linear space version, which finds the middle of the shortest path through the
+/// edits and then each half on either side of it, so that diffing on every change doesn't need
+/// memory in proportion to the size of the text times the number of edits.
+pub fn diff<T: PartialEq<U>, U>(old: &[T], new: &[U]) -> Vec<Hunk> {
+    let mut matches = Vec::new();
+    collect_matches(old, new, (0, 0), &mut matches);
+
+    let mut hunks = Vec::new();
+    let (mut old_idx, mut new_idx) = (0, 0);
+
+    for (x, y) in matches
+        .into_iter()
+        .chain(std::iter::once((old.len(), new.len())))
+    {
+        if x > old_idx || y > new_idx {
+            hunks.push(Hunk {
+                old: old_idx..x,
+                new: new_idx..y,
+            });
+        }
+        (old_idx, new_idx) = (x + 1, y + 1);
+    }
+
+    hunks
+}
+
+/// Pushes the pairs of indices of items which are kept, in order, offset by where `old` and `new`
+/// start in the sequences being diffed
+fn collect_matches<T: PartialEq<U>, U>(
+    old: &[T],
+    new: &[U],
+    offset: (usize, usize),
+    matches: &mut Vec<(usize, usize)>,
+) {
+    // NOTE
+    // The common prefix and suffix are skipped first, since most diffs are of mostly unchanged
+    // text, and this guarantees the middle differs at both ends
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    matches.extend((0..prefix).map(|i| (offset.0 + i, offset.1 + i)));
+
+    if !old_middle.is_empty() && !new_middle.is_empty() {
+        let snake = middle_snake(old_middle, new_middle);
+        let middle_offset = (offset.0 + prefix, offset.1 + prefix);
+
+        collect_matches(
+            &old_middle[..snake.start.0],
+            &new_middle[..snake.start.1],
+            middle_offset,
+            matches,
+        );
+        matches.extend((0..snake.end.0 - snake.start.0).map(|i| {
+            (
+                middle_offset.0 + snake.start.0 + i,
+                middle_offset.1 + snake.start.1 + i,
+            )
+        }));
+        collect_matches(
+            &old_middle[snake.end.0..],
+            &new_middle[snake.end.1..],
+            (middle_offset.0 + snake.end.0, middle_offset.1 + snake.end.1),
+            matches,
+        );
+    }
+
+    let suffix_start = (offset.0 + old.len() - suffix, offset.1 + new.len() - suffix);
+    matches.extend((0..suffix).map(|i| (suffix_start.0 + i, suffix_start.1 + i)));
+}
+
+/// A run of kept items, from `start` to `end` in the old and new sequences
+struct Snake {
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+/// The run of kept items in the middle of a shortest path through the edits, found by searching
+/// forwards from the start and backwards from the end at the same time until they overlap
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+fn middle_snake<T: PartialEq<U>, U>(old: &[T], new: &[U]) -> Snake {
+    let (old_len, new_len) = (old.len() as isize, new.len() as isize);
+    let max = (old_len + new_len + 1) / 2;
+    // NOTE
+    // `delta` is the diagonal the end is on, so forward diagonal `k` is backward diagonal
+    // `delta - k`. The paths can only overlap on the forward pass when it's odd, and on the
+    // backward pass when it's even.
+    let delta = old_len - new_len;
+    let odd = delta % 2 != 0;
+    let same = |x: isize, y: isize| old[x as usize] == new[y as usize];
+
+    // NOTE
+    // `forward[k]` is the furthest `x` reached from the start on diagonal `k` (where `k = x - y`),
+    // and `backward[k]` is how far back from the end, offset so that negative diagonals can be
+    // indexed
+    let idx = |k: isize| (k + max + 1) as usize;
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[idx(k - 1)] < forward[idx(k + 1)]) {
+                forward[idx(k + 1)]
+            } else {
+                forward[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let start = (x as usize, y as usize);
+
+            while x < old_len && y < new_len && same(x, y) {
+                x += 1;
+                y += 1;
+            }
+            forward[idx(k)] = x;
+
+            if odd && (delta - k).abs() < d && x + backward[idx(delta - k)] >= old_len {
+                return Snake {
+                    start,
+                    end: (x as usize, y as usize),
+                };
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[idx(k - 1)] < backward[idx(k + 1)]) {
+                backward[idx(k + 1)]
+            } else {
+                backward[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let end = ((old_len - x) as usize, (new_len - y) as usize);
+
+            while x < old_len && y < new_len && same(old_len - x - 1, new_len - y - 1) {
+                x += 1;
+                y += 1;
+            }
+            backward[idx(k)] = x;
+
+            if !odd && (delta - k).abs() <= d && x + forward[idx(delta - k)] >= old_len {
+                return Snake {
+                    start: ((old_len - x) as usize, (new_len - y) as usize),
+                    end,
+                };
+            }
+        }
+    }
+
+    unreachable!("The paths always overlap by the time they've made half the edits")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old: Range<usize>, new: Range<usize>) -> Hunk {
+        Hunk { old, new }
+    }
+
+    /// `old` with each hunk replaced by its lines from `new`
+    fn apply(old: &[char], new: &[char], hunks: &[Hunk]) -> Vec<char> {
+        let mut result = Vec::new();
+        let mut old_idx = 0;
+        for hunk in hunks {
+            result.extend_from_slice(&old[old_idx..hunk.old.start]);
+            result.extend_from_slice(&new[hunk.new.clone()]);
+            old_idx = hunk.old.end;
+        }
+        result.extend_from_slice(&old[old_idx..]);
+        result
+    }
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn empty_inputs() {
+        assert_eq!(diff::<char, char>(&[], &[]), []);
+        assert_eq!(diff::<char, char>(&[], &chars("ab")), [hunk(0..0, 0..2)]);
+        assert_eq!(diff::<char, char>(&chars("ab"), &[]), [hunk(0..2, 0..0)]);
+        assert_eq!(diff(&chars("ab"), &chars("ab")), []);
+    }
+
+    #[test]
+    fn only_inserts() {
+        assert_eq!(
+            diff(&chars("ace"), &chars("abcde")),
+            [hunk(1..1, 1..2), hunk(2..2, 3..4)]
+        );
+        assert_eq!(
+            diff(&chars("b"), &chars("abc")),
+            [hunk(0..0, 0..1), hunk(1..1, 2..3)]
+        );
+    }
+
+    #[test]
+    fn only_deletes() {
+        assert_eq!(
+            diff(&chars("abcde"), &chars("ace")),
+            [hunk(1..2, 1..1), hunk(3..4, 2..2)]
+        );
+        assert_eq!(
+            diff(&chars("abc"), &chars("b")),
+            [hunk(0..1, 0..0), hunk(2..3, 1..1)]
+        );
+    }
+
+    #[test]
+    fn interleaved_hunks() {
+        let (old, new) = (chars("abcabba"), chars("cbabac"));
+        let hunks = diff(&old, &new);
+
+        // The shortest edit script for Myers' example has 5 edits
+        let edits: usize = hunks.iter().map(|h| h.old.len() + h.new.len()).sum();
+        assert_eq!(edits, 5);
+        assert_eq!(apply(&old, &new, &hunks), new);
+
+        assert_eq!(
+            diff(&chars("a1b2c3"), &chars("a-b-c-")),
+            [hunk(1..2, 1..2), hunk(3..4, 3..4), hunk(5..6, 5..6)]
+        );
+    }
+
+    #[test]
+    fn long_changes_apply() {
+        let letters = "abcdefghijklmnopqrstuvwxyz".chars().cycle();
+        let old: Vec<char> = letters.clone().step_by(7).take(500).collect();
+        let new: Vec<char> = letters.step_by(11).take(600).collect();
+
+        assert_eq!(apply(&old, &new, &diff(&old, &new)), new);
+    }
+}
@@ -0,0 +1,807 @@
+use crate::{
+    ALL_NORMAL_CHARS, EXPLORER_COMMANDS,
+    action::{match_action, repeat, reset},
+    buffer::Buffer,
+    completion::Completion,
+    count::update_count,
+    explorer::ExplorerCommand,
+    finder::{self, Finder},
+    global_state::GlobalState,
+    keymap::Step,
+    meta_command::match_meta_command,
+    mode::Mode,
+    screen::Screen,
+    undo::Action,
+    view::View,
+};
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{io::Write, ops::ControlFlow};
+
+/// How many times recursive mappings can be expanded for a single key press, in case they map to
+/// themselves
+const MAX_MAPPING_EXPANSIONS: usize = 1000;
+
+/// Why the steps a key press expanded to stopped being run before the last of them
+enum Stop {
+    /// The editor was quit
+    Quit,
+    /// Recursive mappings were expanded more than `MAX_MAPPING_EXPANSIONS` times
+    TooRecursive,
+}
+
+/// `ControlFlow::Break` once the editor has been quit
+const fn quit_on_break(control: ControlFlow<()>) -> ControlFlow<Stop> {
+    match control {
+        ControlFlow::Break(()) => ControlFlow::Break(Stop::Quit),
+        ControlFlow::Continue(()) => ControlFlow::Continue(()),
+    }
+}
+
+/// The state of the editor, apart from the terminal. Keys can be fed to it from anywhere (the
+/// terminal, or a script of keys), and it can be rendered to anything which is written to like
+/// a terminal (stdout, or a `Screen`).
+pub struct Editor<'a> {
+    pub global_state: GlobalState<'a>,
+    pub view: View,
+    last_count: u32,
+    last_chained: Vec<char>,
+}
+
+impl<'a> Editor<'a> {
+    pub const fn new(global_state: GlobalState<'a>, view: View) -> Self {
+        Self {
+            global_state,
+            view,
+            last_count: 1,
+            last_chained: Vec::new(),
+        }
+    }
+
+    /// Handles any messages the language servers have sent since it was last called
+    ///
+    /// # Returns
+    /// Whether anything changed, in which case the editor should be rendered
+    pub fn handle_lsp_messages(&mut self) -> bool {
+        let Self {
+            global_state, view, ..
+        } = self;
+
+        if !global_state.lsp.handle_messages(
+            view,
+            &mut global_state.undo_tree,
+            &mut global_state.messages,
+        ) {
+            return false;
+        }
+
+        // NOTE
+        // Responses can open files and edit buffers other than the current one
+        view.view_boxes_mut().for_each(|view_box| {
+            let _ = view_box.parse();
+            view_box.diff_git();
+        });
+        global_state.lsp.sync(view, &mut global_state.messages);
+
+        if let Some(items) = global_state.lsp.take_completions()
+            && let Some(completion) = &mut global_state.completion
+        {
+            completion.add(items, view.get_buffer());
+            view.popup = completion.popup();
+        }
+
+        true
+    }
+
+    /// Updates the state of the editor in accordance with the key pressed, and the keys pressed
+    /// before it if they're mapped together
+    ///
+    /// # Returns
+    /// `ControlFlow::Break` once the editor has been quit
+    pub fn handle_key(&mut self, event: KeyEvent) -> ControlFlow<()> {
+        // NOTE
+        // The argument of a command (eg. the character `r` replaces with) is taken as typed, as is
+        // what's typed into the finder
+        if self.global_state.awaiting_argument || self.global_state.finder.is_some() {
+            return self.dispatch(event);
+        }
+
+        let mode = self.global_state.mode.clone();
+        let steps = self.global_state.keymap.resolve(&mode, event);
+        // NOTE
+        // The expansions are counted across every mapping the key press leads to, as a mapping
+        // which maps to itself more than once grows exponentially without getting any deeper
+        let mut expansions = 0;
+
+        match self.run_steps(steps, &mut expansions) {
+            ControlFlow::Break(Stop::Quit) => ControlFlow::Break(()),
+            ControlFlow::Break(Stop::TooRecursive) => {
+                self.global_state
+                    .messages
+                    .error("Mapping is too deeply recursive");
+                ControlFlow::Continue(())
+            }
+            ControlFlow::Continue(()) => ControlFlow::Continue(()),
+        }
+    }
+
+    fn run_steps(&mut self, steps: Vec<Step>, expansions: &mut usize) -> ControlFlow<Stop> {
+        for step in steps {
+            match step {
+                Step::Key(key) => quit_on_break(self.dispatch(key))?,
+                Step::Mapped {
+                    keys,
+                    recursive: false,
+                } => keys
+                    .into_iter()
+                    .try_for_each(|key| quit_on_break(self.dispatch(key)))?,
+                Step::Mapped {
+                    keys,
+                    recursive: true,
+                } => {
+                    *expansions += 1;
+                    if *expansions > MAX_MAPPING_EXPANSIONS {
+                        return ControlFlow::Break(Stop::TooRecursive);
+                    }
+
+                    self.run_recursive(&keys, expansions)?;
+                }
+                Step::Action(action) => {
+                    quit_on_break(action.execute(&mut self.global_state, &mut self.view))?;
+                    self.sync_changes();
+                }
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Runs the keys a recursive mapping is mapped to, mapping each of them in the mode the
+    /// editor is in by the time it's reached
+    fn run_recursive(&mut self, keys: &[KeyEvent], expansions: &mut usize) -> ControlFlow<Stop> {
+        let mut rest = keys;
+        while let Some(&key) = rest.first() {
+            if self.global_state.awaiting_argument || self.global_state.finder.is_some() {
+                quit_on_break(self.dispatch(key))?;
+                rest = &rest[1..];
+                continue;
+            }
+
+            let mode = self.global_state.mode.clone();
+            let Some((step, used)) = self.global_state.keymap.expand_first(&mode, rest) else {
+                break;
+            };
+            rest = &rest[used..];
+            self.run_steps(vec![step], expansions)?;
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Updates the state of the editor in accordance with the key, as it's bound without any
+    /// mappings
+    #[allow(clippy::too_many_lines)]
+    fn dispatch(&mut self, event: KeyEvent) -> ControlFlow<()> {
+        let Self {
+            global_state,
+            view,
+            last_count,
+            last_chained,
+        } = self;
+
+        global_state.messages.dismiss();
+        if view.popup.take().is_some() {
+            view.redraw_all();
+        }
+        let explorer_command = explorer_command(global_state, view, event.code);
+        let in_quickfix = view.get_view_box().quickfix;
+        let buffer = view.get_buffer_mut();
+
+        match (event.code, global_state.mode.clone()) {
+            _ if global_state.finder.is_some() => {
+                finder::handle_key(global_state, view, event);
+            }
+            (KeyCode::Char('p'), Mode::Normal)
+                if event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                global_state.finder = Some(Finder::new(""));
+            }
+
+            // NOTE
+            // Any character can be the argument, even one which means something else on its own
+            (KeyCode::Char(c), Mode::Normal) if global_state.awaiting_argument => {
+                global_state.chained.push(c);
+                match_action(global_state, last_chained, last_count, view);
+            }
+
+            (_, Mode::Normal) if explorer_command.is_some() => {
+                if let Some(command) = explorer_command {
+                    command.execute(global_state, view);
+                }
+            }
+
+            (KeyCode::Enter, Mode::Normal) if in_quickfix => {
+                match global_state.quickfix.open_at_cursor(view) {
+                    Ok(message) => global_state.messages.info(message),
+                    Err(err) => global_state.messages.warn(format!("{err:#}")),
+                }
+            }
+
+            (KeyCode::Char(c), Mode::Normal) if c.is_numeric() => {
+                update_count(c, &mut global_state.count);
+            }
+
+            (KeyCode::Char(':'), Mode::Normal) => {
+                global_state.mode = Mode::Meta;
+                global_state.status_bar.push(':');
+            }
+
+            (KeyCode::Char('/'), Mode::Normal) => {
+                global_state.mode.search();
+                global_state.status_bar.push('/');
+            }
+
+            (KeyCode::Char('n'), Mode::Normal) => buffer.goto_next_string(&global_state.search_str),
+            (KeyCode::Char('N'), Mode::Normal) => buffer.goto_prev_string(&global_state.search_str),
+
+            (KeyCode::Char('.'), Mode::Normal) => {
+                repeat(global_state, last_chained, *last_count, view);
+            }
+
+            (KeyCode::Char(c), Mode::Normal) => {
+                if !ALL_NORMAL_CHARS.contains(&c) {
+                    return ControlFlow::Continue(());
+                }
+                global_state.chained.push(c);
+
+                match_action(global_state, last_chained, last_count, view);
+            }
+
+            (KeyCode::Esc, Mode::Normal) => reset(global_state),
+
+            // Completion
+            (KeyCode::Tab, Mode::Insert) if completing(global_state) => {
+                if let Some(completion) = &mut global_state.completion {
+                    completion.next();
+                }
+            }
+            (KeyCode::Char('n'), Mode::Insert)
+                if completing(global_state) && event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                if let Some(completion) = &mut global_state.completion {
+                    completion.next();
+                }
+            }
+            (KeyCode::BackTab, Mode::Insert) if completing(global_state) => {
+                if let Some(completion) = &mut global_state.completion {
+                    completion.prev();
+                }
+            }
+            (KeyCode::Char('p'), Mode::Insert)
+                if completing(global_state) && event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                if let Some(completion) = &mut global_state.completion {
+                    completion.prev();
+                }
+            }
+            (KeyCode::Enter, Mode::Insert) if completing(global_state) => {
+                if let Some(completion) = global_state.completion.take() {
+                    completion.accept(buffer, &mut global_state.undo_tree);
+                }
+            }
+            (KeyCode::Esc, Mode::Insert) if completing(global_state) => {
+                global_state.completion = None;
+            }
+            (KeyCode::Char('n'), Mode::Insert)
+                if event.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                if !can_complete(buffer) {
+                    global_state.messages.warn("No completions");
+                    return ControlFlow::Continue(());
+                }
+
+                // NOTE
+                // The language server's candidates are added when it responds, if there is one
+                let requested = global_state.lsp.complete(view).is_ok();
+                let completion = Completion::start(view);
+                if !requested && !completion.has_matches() {
+                    global_state.messages.warn("No completions");
+                }
+                global_state.completion = Some(completion);
+            }
+
+            (KeyCode::Esc, Mode::Insert) => {
+                if buffer.cursor != buffer.get_start_of_line() {
+                    buffer.cursor -= 1;
+                }
+                global_state.mode.normal();
+            }
+
+            (KeyCode::Backspace, Mode::Insert) => buffer.backspace(&mut global_state.undo_tree),
+
+            (KeyCode::Char(c), Mode::Insert) => {
+                buffer.insert_char(c);
+                buffer.cursor += 1;
+                buffer.update_list_use_current_line();
+
+                let action = Action::insert(buffer.cursor - 1, &c);
+                global_state.undo_tree.new_action_merge(action);
+            }
+
+            (KeyCode::Tab, Mode::Insert) => {
+                let indent = buffer.insert_indent_at(buffer.cursor);
+                buffer.cursor += indent.chars().count();
+
+                let action = Action::insert(buffer.cursor - indent.chars().count(), &indent);
+                global_state.undo_tree.new_action_merge(action);
+            }
+
+            (KeyCode::Enter, Mode::Insert) => {
+                let newline = buffer.insert_newline();
+
+                let action = Action::insert(buffer.cursor - newline.len(), &newline);
+                global_state.undo_tree.new_action(action);
+            }
+
+            (KeyCode::Enter, Mode::Meta) => match match_meta_command(global_state, view) {
+                Ok(ControlFlow::Break(())) => return ControlFlow::Break(()),
+                Ok(ControlFlow::Continue(())) => {}
+                Err(err) => {
+                    global_state.messages.error(format!("{err:#}"));
+                    global_state.mode.normal();
+                    global_state.status_bar.clear();
+                }
+            },
+
+            (KeyCode::Char(c), Mode::Meta | Mode::Search) => global_state.status_bar.push(c),
+
+            (KeyCode::Esc, Mode::Meta | Mode::Search) => {
+                global_state.mode.normal();
+                global_state.status_bar.clear();
+            }
+            (KeyCode::Backspace, Mode::Meta | Mode::Search) => global_state.status_bar.delete(),
+
+            (_, Mode::Meta) => {}
+
+            (KeyCode::Enter, Mode::Search) => {
+                global_state.search_str = global_state
+                    .status_bar
+                    .buffer()
+                    .split_at(1)
+                    .1
+                    .chars()
+                    .collect();
+
+                global_state.mode.normal();
+                global_state.status_bar.clear();
+            }
+
+            (KeyCode::Left, Mode::Normal | Mode::Insert) => buffer.prev_char(),
+            (KeyCode::Right, Mode::Normal | Mode::Insert) => buffer.next_char(),
+            (KeyCode::Up, Mode::Normal | Mode::Insert) => buffer.prev_row(),
+            (KeyCode::Down, Mode::Normal | Mode::Insert) => buffer.next_row(),
+
+            _ => return ControlFlow::Continue(()),
+        }
+
+        // Whatever was typed narrows down the candidates, which are shown in place of any other
+        // popup
+        if let Some(completion) = &mut global_state.completion {
+            if matches!(global_state.mode, Mode::Insert) && completion.refilter(view.get_buffer()) {
+                view.popup = completion.popup();
+            } else {
+                global_state.completion = None;
+            }
+        }
+
+        self.sync_changes();
+
+        ControlFlow::Continue(())
+    }
+
+    /// Brings everything which follows the buffers (the syntax tree, the git diff, and the
+    /// language servers) up to date with whatever was just done to them
+    fn sync_changes(&mut self) {
+        let Self {
+            global_state, view, ..
+        } = self;
+
+        let workspace_undo = global_state.undo_tree.take_workspace_undo();
+        if !workspace_undo.is_empty() {
+            view.undo_workspace(workspace_undo, &mut global_state.undo_tree);
+        }
+
+        // NOTE
+        // The explorer's commands act on the entries read back from its buffer, so any edit to
+        // the listing is undone rather than letting it get out of step with the directory
+        let mut edited = false;
+        for view_box in view.view_boxes_mut() {
+            edited |= view_box.restore_explorer();
+        }
+        if edited {
+            if matches!(global_state.mode, Mode::Insert) {
+                global_state.mode.normal();
+            }
+            global_state
+                .messages
+                .warn("The explorer can't be edited, use its commands instead");
+        }
+
+        let _ = view.get_view_box().parse();
+        view.get_view_box().diff_git();
+        global_state.lsp.sync(view, &mut global_state.messages);
+    }
+
+    /// Draws the editor (or the parts of it which have changed since it was last drawn)
+    ///
+    /// # Errors
+    /// - If writing to `out` fails
+    pub fn render(&mut self, out: &mut impl Write) -> Result<()> {
+        let adjusted = self.view.adjust();
+        self.view.render(&self.global_state, adjusted, out)?;
+        self.view.get_buffer_mut().update_list_reset();
+
+        Ok(())
+    }
+
+    /// Presses each of `keys` in turn, rendering to `screen` after each of them
+    ///
+    /// # Returns
+    /// `ControlFlow::Break` if the keys quit the editor
+    ///
+    /// # Errors
+    /// - If rendering fails
+    pub fn feed(
+        &mut self,
+        keys: impl IntoIterator<Item = KeyEvent>,
+        screen: &mut Screen,
+    ) -> Result<ControlFlow<()>> {
+        for key in keys {
+            let control = self.handle_key(key);
+            self.render(screen)?;
+
+            if control.is_break() {
+                return Ok(control);
+            }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+/// The explorer command bound to `key`, if the current view box is showing an explorer and
+/// nothing else has been typed
+fn explorer_command(
+    global_state: &GlobalState,
+    view: &mut View,
+    key: KeyCode,
+) -> Option<&'static ExplorerCommand> {
+    if !global_state.chained.is_empty() || view.get_view_box().explorer.is_none() {
+        return None;
+    }
+
+    EXPLORER_COMMANDS.iter().find(|command| command.key == key)
+}
+
+/// Whether there are completion candidates to choose from
+fn completing(global_state: &GlobalState) -> bool {
+    global_state
+        .completion
+        .as_ref()
+        .is_some_and(Completion::has_matches)
+}
+
+/// `ctrl-n` completes the text before the cursor, unless there isn't any
+fn can_complete(buffer: &Buffer) -> bool {
+    buffer.cursor > buffer.get_start_of_line()
+        && !buffer
+            .rope
+            .get_char(buffer.cursor - 1)
+            .is_some_and(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::parse_keys,
+        keymap::Keymap,
+        options::OptionsConfig,
+        sign::{Sign, SignSource},
+        theme::HLGroup,
+    };
+    use std::collections::HashMap;
+
+    const WIDTH: u16 = 30;
+    const HEIGHT: u16 = 6;
+
+    /// The rows on the screen and the cursor's position on it after typing `keys` into a buffer
+    /// holding `text`, starting at its first character
+    fn screen_after(text: &str, keys: &str) -> (String, (u16, u16)) {
+        screen_after_with_keymap(text, Keymap::default(), keys)
+    }
+
+    fn screen_after_with_keymap(text: &str, keymap: Keymap, keys: &str) -> (String, (u16, u16)) {
+        screen_after_setup(text, keymap, |_| {}, keys)
+    }
+
+    /// As `screen_after`, with `setup` changing the view before the keys are typed
+    fn screen_after_setup(
+        text: &str,
+        keymap: Keymap,
+        setup: impl FnOnce(&mut View),
+        keys: &str,
+    ) -> (String, (u16, u16)) {
+        let mut global_state = GlobalState {
+            keymap,
+            ..GlobalState::default()
+        };
+        let mut view = View::new(WIDTH, HEIGHT, OptionsConfig::default());
+        view.get_buffer_mut()
+            .replace_contents(text, &mut global_state.undo_tree);
+        view.get_buffer_mut().cursor = 0;
+        setup(&mut view);
+
+        let mut editor = Editor::new(global_state, view);
+        let mut screen = Screen::new(WIDTH, HEIGHT);
+        editor.render(&mut screen).expect("Rendered");
+        let _ = editor
+            .feed(parse_keys(keys).expect("Valid keys"), &mut screen)
+            .expect("Rendered");
+
+        (screen.to_string(), screen.cursor)
+    }
+
+    #[test]
+    fn delete_word() {
+        let (screen, cursor) = screen_after("one two three\n", "dw");
+
+        assert_eq!(screen, "0 two three\n1\n\n\n\n-- Unattached Buffer -- \"a");
+        assert_eq!(cursor, (2, 0));
+    }
+
+    #[test]
+    fn move_down_with_a_count() {
+        let (screen, cursor) = screen_after("a\nb\nc\nd\ne\n", "3j");
+
+        assert_eq!(
+            screen,
+            "0 a\n1 b\n2 c\n3 d\n4 e\n-- Unattached Buffer -- \"a"
+        );
+        assert_eq!(cursor, (2, 3));
+    }
+
+    #[test]
+    fn change_inside_parentheses() {
+        // NOTE
+        // The change operator is `t`, so this is Vi's `ci(`
+        let (screen, cursor) = screen_after("f(a, b) + g(c)\n", "fbti(x<esc>");
+
+        assert_eq!(
+            screen,
+            "0 f(x) + g(c)\n1\n\n\n\n-- Unattached Buffer -- \"a"
+        );
+        assert_eq!(cursor, (4, 0));
+    }
+
+    #[test]
+    fn change_inside_a_block_on_lines_of_its_own() {
+        let text = "void f() {\n    a();\n    b();\n}\n";
+        let (screen, cursor) = screen_after(text, "jti{x = 1;<Esc>");
+
+        assert_eq!(
+            screen,
+            "0 void f() {\n1 x = 1;\n2 }\n3\n\n-- Unattached Buffer -- \"a"
+        );
+        assert_eq!(cursor, (7, 1));
+
+        // An empty line inside is typed into rather than joined onto the closing line
+        let (screen, _) = screen_after("{\n\n}\n", "jti{x<Esc>");
+        assert_eq!(screen, "0 {\n1 x\n2 }\n3\n\n-- Unattached Buffer -- \"a");
+    }
+
+    #[test]
+    fn recursive_mapping_in_the_mode_each_key_is_reached_in() {
+        // `x` is only mapped in normal mode, so it's typed as is once `i` enters insert mode
+        let keymap =
+            Keymap::parse("[normal]\nQ = { keys = \"xix<esc>\", recursive = true }\nx = \"dd\"\n")
+                .expect("Valid keymap");
+        let (screen, _) = screen_after_with_keymap("a\nbc\n", keymap, "Q");
+
+        assert_eq!(screen, "0 xbc\n1\n\n\n\n-- Unattached Buffer -- \"a");
+    }
+
+    #[test]
+    fn self_doubling_mapping_is_stopped_once() {
+        // Each `Q` expands to two more, so it would take 2^1000 steps to run out on its own
+        let keymap = Keymap::parse(
+            "[normal]\nQ = { keys = \"QQ\", recursive = true }\nW = { keys = \"Qx\", recursive = true }\n",
+        )
+        .expect("Valid keymap");
+        let mut global_state = GlobalState {
+            keymap,
+            ..GlobalState::default()
+        };
+        let mut view = View::new(WIDTH, HEIGHT, OptionsConfig::default());
+        view.get_buffer_mut()
+            .replace_contents("abc\n", &mut global_state.undo_tree);
+        view.get_buffer_mut().cursor = 0;
+
+        let mut editor = Editor::new(global_state, view);
+        let mut screen = Screen::new(WIDTH, HEIGHT);
+        let _ = editor
+            .feed(parse_keys("W").expect("Valid keys"), &mut screen)
+            .expect("Rendered");
+
+        // The `x` after `Q` isn't run either (the message is cut off at the width of the screen)
+        assert_eq!(
+            screen.to_string(),
+            "0 abc\n1\n\n\n\nMapping is too deeply recursiv"
+        );
+        assert_eq!(
+            editor.global_state.messages.history(),
+            "ERROR: Mapping is too deeply recursive"
+        );
+    }
+
+    #[test]
+    fn repeat_with_the_register_it_was_done_with() {
+        // `.` deletes into `b` again, even though `c` was chosen since, so `b` holds `two`
+        let (screen, _) = screen_after("one\ntwo\nthree\n", "\"bdd\"cyy.\"bp");
+
+        assert_eq!(screen, "0 two\n1 three\n2\n\n\n-- Unattached Buffer -- \"b");
+    }
+
+    #[test]
+    fn substitute() {
+        let (screen, _) = screen_after("a b a\nba\n", ":s/a/b<CR>");
+
+        assert_eq!(screen, "0 b b b\n1 bb\n2\n\n\n3 substitutions");
+    }
+
+    #[test]
+    fn tab_after_text_inserts_an_indent() {
+        let (screen, _) = screen_after("int x;\n", ":set noet<CR>$i<Tab>// c<Esc>");
+
+        assert_eq!(
+            screen,
+            "0 int x;  // c\n1\n\n\n\n-- Unattached Buffer -- \"a"
+        );
+    }
+
+    #[test]
+    fn ctrl_n_completes_a_word() {
+        let (screen, _) = screen_after("forest\n", "$i fo<C-n><CR><Esc>");
+
+        assert_eq!(
+            screen,
+            "0 forest forest\n1\n\n\n\n-- Unattached Buffer -- \"a"
+        );
+    }
+
+    #[test]
+    fn wrap_a_line_wider_than_the_box() {
+        let (screen, cursor) = screen_after(&("a".repeat(40) + "\n"), ":set wrap<CR>");
+
+        assert_eq!(
+            screen,
+            format!(
+                "0 {}\n↪ {}\n1\n\n\n-- Unattached Buffer -- \"a",
+                "a".repeat(27),
+                "a".repeat(13)
+            )
+        );
+        assert_eq!(cursor, (2, 0));
+    }
+
+    #[test]
+    fn cursor_on_a_continuation_row() {
+        let (_, cursor) = screen_after(&("a".repeat(40) + "\n"), ":set wrap<CR>35l");
+
+        // Column 35 is the 9th column of the second row, after the 27 on the first
+        assert_eq!(cursor, (10, 1));
+    }
+
+    #[test]
+    fn line_taller_than_the_view_box() {
+        // 200 columns wrap onto 8 rows of 27, and the view box only has 5
+        let text = "abcdefghij".repeat(20) + "\nx\n";
+        let rows = [
+            "abcdefghijabcdefghijabcdefg",
+            "hijabcdefghijabcdefghijabcd",
+            "efghijabcdefghijabcdefghija",
+            "bcdefghijabcdefghijabcdefgh",
+            "ijabcdefghijabcdefghijabcde",
+            "fghijabcdefghijabcdefghijab",
+            "cdefghijabcdefghijabcdefghi",
+            "jabcdefghij",
+        ];
+        let screen = |first: usize| {
+            let mut screen = if first == 0 { "0 " } else { "↪ " }.to_string();
+            screen.push_str(&rows[first..first + 5].join("\n↪ "));
+            screen + "\n-- Unattached Buffer -- \"a"
+        };
+
+        // It's shown from its start
+        let (shown, cursor) = screen_after(&text, ":set wrap<CR>");
+        assert_eq!(shown, screen(0));
+        assert_eq!(cursor, (2, 0));
+
+        // Until the cursor moves past the bottom of the view box
+        let (shown, cursor) = screen_after(&text, ":set wrap<CR>99l99l");
+        assert_eq!(shown, screen(3));
+        assert_eq!(cursor, (11, 4));
+
+        // Moving back up doesn't scroll until the cursor moves past the top
+        let (shown, cursor) = screen_after(&text, ":set wrap<CR>99l99lgkgkgk");
+        assert_eq!(shown, screen(3));
+        assert_eq!(cursor, (11, 1));
+        let (shown, cursor) = screen_after(&text, ":set wrap<CR>99l99lgkgkgkgkgkgk");
+        assert_eq!(shown, screen(1));
+        assert_eq!(cursor, (11, 0));
+    }
+
+    #[test]
+    fn wide_character_at_a_wrap_boundary() {
+        // `中` takes up 2 columns, and only 1 is left on the first row
+        let (screen, cursor) = screen_after(&("a".repeat(26) + "中b\n"), ":set wrap<CR>26l");
+
+        assert_eq!(
+            screen,
+            format!(
+                "0 {}\n↪ 中b\n1\n\n\n-- Unattached Buffer -- \"a",
+                "a".repeat(26)
+            )
+        );
+        assert_eq!(cursor, (2, 1));
+
+        let (_, cursor) = screen_after(&("a".repeat(26) + "中b\n"), ":set wrap<CR>26lgk");
+        assert_eq!(cursor, (2, 0));
+    }
+
+    #[test]
+    fn relative_line_numbers() {
+        let (screen, cursor) = screen_after("a\nb\nc\n", ":set rnu nonu<CR>jj");
+
+        assert_eq!(screen, "2 a\n1 b\n0 c\n1\n\n-- Unattached Buffer -- \"a");
+        assert_eq!(cursor, (2, 2));
+    }
+
+    #[test]
+    fn hybrid_line_numbers() {
+        // With `number` on as well, the cursor's line shows its own number
+        let (screen, _) = screen_after("a\nb\nc\n", ":set rnu<CR>jj");
+
+        assert_eq!(screen, "2 a\n1 b\n2 c\n1\n\n-- Unattached Buffer -- \"a");
+    }
+
+    #[test]
+    fn hidden_gutter() {
+        let (screen, cursor) = screen_after("a\nb\nc\n", ":set nonu<CR>j");
+
+        assert_eq!(screen, "a\nb\nc\n\n\n-- Unattached Buffer -- \"a");
+        assert_eq!(cursor, (0, 1));
+    }
+
+    #[test]
+    fn gutter_widens_when_the_sign_column_appears() {
+        let (screen, cursor) = screen_after("a\nb\nc\n", ":set scl<CR>j");
+        assert_eq!(
+            screen,
+            " 0 a\n 1 b\n 2 c\n 3\n\n-- Unattached Buffer -- \"a"
+        );
+        assert_eq!(cursor, (3, 1));
+
+        // Without `signcolumn`, it's only there while there are signs
+        let signs = |view: &mut View| {
+            let signs = HashMap::from([(1, Sign::new('+', HLGroup::GitAdded))]);
+            view.get_view_box().set_signs(SignSource::Git, signs);
+        };
+        let (screen, cursor) = screen_after_setup("a\nb\nc\n", Keymap::default(), signs, "j");
+        assert_eq!(
+            screen,
+            " 0 a\n+1 b\n 2 c\n 3\n\n-- Unattached Buffer -- \"a"
+        );
+        assert_eq!(cursor, (3, 1));
+    }
+}
@@ -0,0 +1,711 @@
+use crate::{global_state::GlobalState, mode::Mode, view::View, view_box::ViewBox};
+use anyhow::{Context, Result, bail};
+use crossterm::event::KeyCode;
+use ropey::Rope;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The lines listed before the entries: the directory itself, then `../`
+const HEADER_LINES: usize = 2;
+
+/// How the entries of a directory are ordered. Directories are always listed before files.
+#[derive(Clone, Copy, Default)]
+enum Sort {
+    #[default]
+    Name,
+    Modified,
+    Size,
+}
+
+impl Sort {
+    const fn next(self) -> Self {
+        match self {
+            Self::Name => Self::Modified,
+            Self::Modified => Self::Size,
+            Self::Size => Self::Name,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Modified => "time",
+            Self::Size => "size",
+        }
+    }
+}
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// A directory listing shown in a view box in place of a file, which can be browsed and edited
+/// like netrw
+pub struct Explorer {
+    pub dir: PathBuf,
+    show_hidden: bool,
+    sort: Sort,
+    /// What was last listed, which the buffer is put back to if it's edited
+    listing: Rope,
+}
+
+impl Explorer {
+    /// The listing of `self.dir`, one entry per line with directories ending in `/`
+    fn contents(&self) -> Result<String> {
+        let mut entries = fs::read_dir(&self.dir)
+            .with_context(|| format!("Could not read {}", self.dir.display()))?
+            .filter_map(std::result::Result::ok)
+            .filter_map(|item| {
+                let name = item.file_name().to_string_lossy().to_string();
+                let metadata = item.metadata().ok()?;
+
+                Some(Entry {
+                    name,
+                    is_dir: metadata.is_dir(),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    size: metadata.len(),
+                })
+            })
+            .filter(|entry| self.show_hidden || !entry.name.starts_with('.'))
+            .collect::<Vec<Entry>>();
+
+        entries.sort_by(|a, b| {
+            let order = match self.sort {
+                Sort::Name => a.name.cmp(&b.name),
+                // Newest and largest first
+                Sort::Modified => b.modified.cmp(&a.modified),
+                Sort::Size => b.size.cmp(&a.size),
+            };
+            b.is_dir.cmp(&a.is_dir).then(order)
+        });
+
+        let hidden = if self.show_hidden {
+            ", showing hidden"
+        } else {
+            ""
+        };
+        let mut contents = format!(
+            "{}/ (sorted by {}{hidden})\n../",
+            self.dir.display(),
+            self.sort.name()
+        );
+        for entry in entries {
+            contents.push('\n');
+            contents.push_str(&entry.name);
+            if entry.is_dir {
+                contents.push('/');
+            }
+        }
+
+        Ok(contents)
+    }
+}
+
+/// Explorer
+impl ViewBox {
+    /// Shows the listing of `dir` in the view box, detaching it from its file
+    ///
+    /// # Errors
+    /// - If `dir` can't be read
+    pub fn explore(&mut self, dir: &Path) -> Result<()> {
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Could not open {}", dir.display()))?;
+        if !dir.is_dir() {
+            bail!("{} is not a directory", dir.display());
+        }
+
+        let (show_hidden, sort) = self
+            .explorer
+            .as_ref()
+            .map_or((false, Sort::Name), |explorer| {
+                (explorer.show_hidden, explorer.sort)
+            });
+        self.set_path(None);
+        self.quickfix = false;
+        self.explorer = Some(Explorer {
+            dir,
+            show_hidden,
+            sort,
+            listing: Rope::new(),
+        });
+
+        self.top = 0;
+        self.left = 0;
+        self.buffer.cursor = 0;
+        self.refresh_explorer()?;
+        self.buffer.set_row(HEADER_LINES.min(self.buffer.len() - 1));
+
+        Ok(())
+    }
+
+    /// Lists the explorer's directory again, keeping the cursor on the same line
+    ///
+    /// # Errors
+    /// - If the directory can't be read
+    pub fn refresh_explorer(&mut self) -> Result<()> {
+        let Some(explorer) = &mut self.explorer else {
+            return Ok(());
+        };
+        explorer.listing = Rope::from(explorer.contents()?);
+        let listing = explorer.listing.clone();
+        let row = self.buffer.get_row();
+
+        // NOTE
+        // The listing isn't an edit, so it isn't recorded in the undo tree
+        let buffer = &mut self.buffer;
+        buffer.rope = listing;
+        buffer.lines_for_updating = (0..buffer.len()).map(|_| true).collect();
+        buffer.cursor = 0;
+        buffer.set_row(row.min(buffer.len() - 1));
+        buffer.has_changed = true;
+
+        Ok(())
+    }
+
+    /// Lists the explorer's directory again if its buffer has been edited, since the entries are
+    /// read back from the buffer
+    ///
+    /// # Returns
+    /// Whether it had been edited
+    pub fn restore_explorer(&mut self) -> bool {
+        let Some(explorer) = &self.explorer else {
+            return false;
+        };
+        if self.buffer.rope == explorer.listing {
+            return false;
+        }
+
+        // NOTE
+        // If the directory can't be read anymore, what was last listed is put back instead
+        let listing = explorer.listing.clone();
+        if self.refresh_explorer().is_err() {
+            self.buffer.rope = listing;
+            self.buffer.lines_for_updating = (0..self.buffer.len()).map(|_| true).collect();
+            self.buffer.clamp_cursor();
+            self.buffer.has_changed = true;
+        }
+
+        true
+    }
+
+    /// The path of the entry on the cursor's line, if it's on one
+    ///
+    /// The name is read back from the buffer rather than remembered, so the listing can't get out
+    /// of step with what's shown.
+    fn explorer_entry(&self) -> Option<PathBuf> {
+        let explorer = self.explorer.as_ref()?;
+        let row = self.buffer.get_row();
+        if row == 0 {
+            return None;
+        }
+
+        let line = self.buffer.rope.line(row).to_string();
+        let name = line.trim_end_matches(['\n', '\r']).trim_end_matches('/');
+        if name.is_empty() {
+            return None;
+        }
+
+        Some(explorer.dir.join(name))
+    }
+
+    /// Moves the cursor onto the entry called `name`, if it's listed
+    fn select_entry(&mut self, name: &str) {
+        let row = self.buffer.rope.lines().skip(1).position(|line| {
+            let line = line.to_string();
+            line.trim_end_matches(['\n', '\r']).trim_end_matches('/') == name
+        });
+
+        if let Some(row) = row {
+            self.buffer.set_row(row + 1);
+        }
+    }
+}
+
+/// A key which acts on the entry under the cursor, in view boxes showing an explorer
+pub struct ExplorerCommand {
+    pub key: KeyCode,
+    command: fn(global_state: &mut GlobalState, view: &mut View) -> Result<()>,
+}
+
+impl ExplorerCommand {
+    pub const fn new(
+        key: KeyCode,
+        command: fn(global_state: &mut GlobalState, view: &mut View) -> Result<()>,
+    ) -> Self {
+        Self { key, command }
+    }
+
+    pub fn execute(&self, global_state: &mut GlobalState, view: &mut View) {
+        if let Err(err) = (self.command)(global_state, view) {
+            global_state.messages.warn(format!("{err:#}"));
+        }
+    }
+}
+
+/// Opens the file under the cursor in place of the explorer, or lists the directory under it
+pub fn open(global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let view_box = view.get_view_box();
+    let Some(path) = view_box.explorer_entry() else {
+        return Ok(());
+    };
+
+    if path.ends_with("..") {
+        return parent(global_state, view);
+    } else if path.is_dir() {
+        return view_box.explore(&path);
+    }
+
+    // Paths under the working directory are shown relative to it, like they would've been typed
+    let path = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or(path);
+
+    view.open(path)
+}
+
+/// Lists the parent directory, with the cursor on the directory we came from
+pub fn parent(_global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let view_box = view.get_view_box();
+    let Some(explorer) = &view_box.explorer else {
+        return Ok(());
+    };
+    let Some(parent) = explorer.dir.parent().map(Path::to_path_buf) else {
+        return Ok(());
+    };
+    let name = explorer
+        .dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string());
+
+    view_box.explore(&parent)?;
+    if let Some(name) = name {
+        view_box.select_entry(&name);
+    }
+
+    Ok(())
+}
+
+pub fn cycle_sort(_global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let view_box = view.get_view_box();
+    if let Some(explorer) = &mut view_box.explorer {
+        explorer.sort = explorer.sort.next();
+    }
+
+    view_box.refresh_explorer()
+}
+
+pub fn toggle_hidden(_global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let view_box = view.get_view_box();
+    if let Some(explorer) = &mut view_box.explorer {
+        explorer.show_hidden = !explorer.show_hidden;
+    }
+
+    view_box.refresh_explorer()
+}
+
+// NOTE
+// Creating, renaming and deleting entries are meta commands, so these just start typing them.
+// Having to press enter on the filled in `:rm` is what confirms a deletion.
+#[allow(clippy::unnecessary_wraps)]
+pub fn prompt_create_file(global_state: &mut GlobalState, _view: &mut View) -> Result<()> {
+    prompt(global_state, "touch ");
+    Ok(())
+}
+#[allow(clippy::unnecessary_wraps)]
+pub fn prompt_create_dir(global_state: &mut GlobalState, _view: &mut View) -> Result<()> {
+    prompt(global_state, "mkdir ");
+    Ok(())
+}
+pub fn prompt_rename(global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let name = entry_name(view)?;
+    prompt(global_state, &format!("mv {name} "));
+    Ok(())
+}
+pub fn prompt_delete(global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let name = entry_name(view)?;
+    prompt(global_state, &format!("rm {name}"));
+    Ok(())
+}
+
+fn entry_name(view: &mut View) -> Result<String> {
+    view.get_view_box()
+        .explorer_entry()
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().to_string()))
+        .context("No entry under the cursor")
+}
+
+fn prompt(global_state: &mut GlobalState, command: &str) {
+    global_state.mode = Mode::Meta;
+    global_state.status_bar.clear();
+    global_state.status_bar.push(':');
+    command
+        .chars()
+        .for_each(|c| global_state.status_bar.push(c));
+}
+
+/// The directory the explorer in the current view box is listing
+///
+/// # Errors
+/// - If the current view box isn't showing an explorer, since it's unclear what names would be
+///   relative to
+fn base_dir(view: &mut View) -> Result<PathBuf> {
+    view.get_view_box()
+        .explorer
+        .as_ref()
+        .map(|explorer| explorer.dir.clone())
+        .context("Files can only be changed from an explorer")
+}
+
+/// Lists every explorer's directory again after an entry changed, with the cursor on `name` in
+/// the current view box
+fn refresh_explorers(view: &mut View, name: Option<&str>) -> Result<()> {
+    for view_box in view.view_boxes_mut() {
+        view_box.refresh_explorer()?;
+    }
+
+    if let Some(name) = name {
+        view.get_view_box().select_entry(name);
+    }
+
+    Ok(())
+}
+
+fn entry_arg(arg: &str) -> Result<&str> {
+    let name = arg.trim();
+    if name.is_empty() {
+        bail!("No name given");
+    }
+    Ok(name)
+}
+
+/// Creates an empty file, relative to the explorer's directory
+///
+/// # Errors
+/// - If the current view box isn't showing an explorer, no name was given, or the file can't be
+///   created
+pub fn create_file(view: &mut View, arg: &str) -> Result<()> {
+    let name = entry_arg(arg)?;
+
+    fs::File::options()
+        .create(true)
+        .append(true)
+        .open(base_dir(view)?.join(name))
+        .with_context(|| format!("Could not create {name}"))?;
+
+    refresh_explorers(view, Some(name))
+}
+
+/// Creates a directory (and any missing parents), relative to the explorer's directory
+///
+/// # Errors
+/// - If the current view box isn't showing an explorer, no name was given, or the directory
+///   can't be created
+pub fn create_dir(view: &mut View, arg: &str) -> Result<()> {
+    let name = entry_arg(arg)?.trim_end_matches('/');
+
+    fs::create_dir_all(base_dir(view)?.join(name))
+        .with_context(|| format!("Could not create {name}"))?;
+
+    refresh_explorers(view, Some(name))
+}
+
+/// Renames the first entry to the second, relative to the explorer's directory
+///
+/// # Errors
+/// - If the current view box isn't showing an explorer, there aren't two names, or the entry
+///   can't be renamed
+// TODO
+// Names containing whitespace can't be given
+pub fn rename(view: &mut View, arg: &str) -> Result<()> {
+    let mut names = arg.split_whitespace();
+    let (Some(from), Some(to), None) = (names.next(), names.next(), names.next()) else {
+        bail!("Usage: mv <from> <to>");
+    };
+
+    let dir = base_dir(view)?;
+    fs::rename(dir.join(from), dir.join(to))
+        .with_context(|| format!("Could not rename {from} to {to}"))?;
+
+    refresh_explorers(view, Some(to.trim_end_matches('/')))
+}
+
+/// Deletes a file or an empty directory, relative to the explorer's directory
+///
+/// # Errors
+/// - If the current view box isn't showing an explorer, no name was given, or the entry can't
+///   be deleted
+pub fn remove(view: &mut View, arg: &str) -> Result<()> {
+    let name = entry_arg(arg)?;
+    let path = base_dir(view)?.join(name);
+
+    // NOTE
+    // Only empty directories are removed, there's no undoing this
+    let removed = if path.is_dir() {
+        fs::remove_dir(&path)
+    } else {
+        fs::remove_file(&path)
+    };
+    removed.with_context(|| format!("Could not delete {name}"))?;
+
+    refresh_explorers(view, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{editor::Editor, input::parse_keys, options::OptionsConfig, screen::Screen};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    /// A directory of files to explore, which is removed when it's dropped
+    struct TempDir {
+        dir: PathBuf,
+    }
+
+    impl TempDir {
+        fn new(files: &[&str]) -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "orinfar-explorer-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).expect("Could not create temporary directory");
+
+            // Names ending in `/` are directories
+            for file in files {
+                let path = dir.join(file);
+                if file.ends_with('/') {
+                    fs::create_dir_all(path).expect("Could not create directory");
+                } else {
+                    fs::write(path, file).expect("Could not write file");
+                }
+            }
+
+            Self { dir }
+        }
+
+        /// A view box exploring the directory
+        fn explore(&self) -> (GlobalState<'static>, View) {
+            let mut view = View::new(80, 24, OptionsConfig::default());
+            view.get_view_box().explore(&self.dir).expect("Explored");
+            (GlobalState::default(), view)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// The lines of the listing, after the directory's name
+    fn listing(view: &mut View) -> Vec<String> {
+        let rope = &view.get_view_box().buffer.rope;
+        rope.lines()
+            .skip(1)
+            .map(|line| line.to_string().trim_end().to_string())
+            .collect()
+    }
+
+    fn header(view: &mut View) -> String {
+        view.get_view_box().buffer.rope.line(0).to_string()
+    }
+
+    fn cursor_line(view: &mut View) -> String {
+        let buffer = &view.get_view_box().buffer;
+        buffer
+            .rope
+            .line(buffer.get_row())
+            .to_string()
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn lists_directories_first_then_by_name() {
+        let dir = TempDir::new(&["b.txt", "a.txt", "z/", "c/"]);
+        let (_, mut view) = dir.explore();
+
+        assert!(header(&mut view).ends_with("/ (sorted by name)\n"));
+        assert_eq!(listing(&mut view), ["../", "c/", "z/", "a.txt", "b.txt"]);
+        // The cursor starts on the first entry
+        assert_eq!(cursor_line(&mut view), "c/");
+    }
+
+    #[test]
+    fn sorts_by_time_and_size() {
+        let dir = TempDir::new(&["z/", "small", "medium", "large"]);
+        fs::write(dir.dir.join("medium"), "x".repeat(10)).expect("Written");
+        fs::write(dir.dir.join("large"), "x".repeat(100)).expect("Written");
+        let now = SystemTime::now();
+        for (name, age) in [("small", 1), ("medium", 3), ("large", 2)] {
+            fs::File::options()
+                .write(true)
+                .open(dir.dir.join(name))
+                .and_then(|file| file.set_modified(now - Duration::from_secs(age * 60)))
+                .expect("Modified");
+        }
+        let (mut global_state, mut view) = dir.explore();
+
+        cycle_sort(&mut global_state, &mut view).expect("Sorted");
+        assert!(header(&mut view).ends_with("(sorted by time)\n"));
+        assert_eq!(
+            listing(&mut view),
+            ["../", "z/", "small", "large", "medium"]
+        );
+
+        cycle_sort(&mut global_state, &mut view).expect("Sorted");
+        assert!(header(&mut view).ends_with("(sorted by size)\n"));
+        assert_eq!(
+            listing(&mut view),
+            ["../", "z/", "large", "medium", "small"]
+        );
+
+        cycle_sort(&mut global_state, &mut view).expect("Sorted");
+        assert_eq!(
+            listing(&mut view),
+            ["../", "z/", "large", "medium", "small"]
+        );
+        assert!(header(&mut view).ends_with("(sorted by name)\n"));
+    }
+
+    #[test]
+    fn toggles_hidden_files() {
+        let dir = TempDir::new(&["shown", ".hidden", ".config/"]);
+        let (mut global_state, mut view) = dir.explore();
+        assert_eq!(listing(&mut view), ["../", "shown"]);
+
+        toggle_hidden(&mut global_state, &mut view).expect("Toggled");
+        assert!(header(&mut view).ends_with("(sorted by name, showing hidden)\n"));
+        assert_eq!(listing(&mut view), ["../", ".config/", ".hidden", "shown"]);
+
+        toggle_hidden(&mut global_state, &mut view).expect("Toggled");
+        assert_eq!(listing(&mut view), ["../", "shown"]);
+    }
+
+    #[test]
+    fn creates_renames_and_deletes_entries() {
+        let dir = TempDir::new(&["a.txt"]);
+        let (_, mut view) = dir.explore();
+
+        create_file(&mut view, "new.txt").expect("Created");
+        assert!(dir.dir.join("new.txt").is_file());
+        assert_eq!(cursor_line(&mut view), "new.txt");
+
+        create_dir(&mut view, "sub/").expect("Created");
+        assert!(dir.dir.join("sub").is_dir());
+        assert_eq!(listing(&mut view), ["../", "sub/", "a.txt", "new.txt"]);
+        assert_eq!(cursor_line(&mut view), "sub/");
+
+        rename(&mut view, "new.txt b.txt").expect("Renamed");
+        assert!(!dir.dir.join("new.txt").exists());
+        assert_eq!(fs::read_to_string(dir.dir.join("b.txt")).expect("Read"), "");
+        assert_eq!(cursor_line(&mut view), "b.txt");
+        assert!(rename(&mut view, "b.txt").is_err());
+
+        fs::write(dir.dir.join("sub/inner"), "").expect("Written");
+        assert!(remove(&mut view, "sub").is_err());
+        remove(&mut view, "sub/inner").expect("Deleted");
+        remove(&mut view, "sub").expect("Deleted");
+        remove(&mut view, "b.txt").expect("Deleted");
+        assert_eq!(listing(&mut view), ["../", "a.txt"]);
+        assert!(remove(&mut view, "missing").is_err());
+    }
+
+    #[test]
+    fn entries_are_only_changed_from_an_explorer() {
+        let mut view = View::new(80, 24, OptionsConfig::default());
+
+        assert!(create_file(&mut view, "new.txt").is_err());
+    }
+
+    #[test]
+    fn enter_opens_entries_and_dash_goes_up() {
+        let dir = TempDir::new(&["sub/", "sub/inner.txt", "top.txt"]);
+        let (mut global_state, mut view) = dir.explore();
+        let root = view
+            .get_view_box()
+            .explorer
+            .as_ref()
+            .expect("Exploring")
+            .dir
+            .clone();
+
+        // Into `sub/`, which is the first entry
+        open(&mut global_state, &mut view).expect("Opened");
+        let explorer = view.get_view_box().explorer.as_ref().expect("Exploring");
+        assert_eq!(explorer.dir, root.join("sub"));
+        assert_eq!(listing(&mut view), ["../", "inner.txt"]);
+
+        // Back up, with the cursor on the directory we came from
+        parent(&mut global_state, &mut view).expect("Went up");
+        assert_eq!(
+            view.get_view_box()
+                .explorer
+                .as_ref()
+                .expect("Exploring")
+                .dir,
+            root
+        );
+        assert_eq!(cursor_line(&mut view), "sub/");
+
+        // `../` goes up as well
+        view.get_view_box().buffer.set_row(1);
+        open(&mut global_state, &mut view).expect("Opened");
+        let explorer = view.get_view_box().explorer.as_ref().expect("Exploring");
+        assert_eq!(Some(explorer.dir.as_path()), root.parent());
+
+        // Opening a file replaces the explorer
+        view.get_view_box().explore(&root).expect("Explored");
+        view.get_view_box().buffer.set_row(3);
+        open(&mut global_state, &mut view).expect("Opened");
+        assert!(view.get_view_box().explorer.is_none());
+        assert_eq!(view.get_path(), Some(&root.join("top.txt")));
+        assert_eq!(view.get_buffer().to_string(), "top.txt");
+    }
+
+    #[test]
+    fn edits_to_the_listing_are_undone() {
+        let dir = TempDir::new(&["a.txt", "b.txt"]);
+        let (global_state, view) = dir.explore();
+        let mut editor = Editor::new(global_state, view);
+        let mut screen = Screen::new(80, 24);
+        let listed = listing(&mut editor.view);
+
+        for keys in ["x", "ix", "o", "yyp", ":s/txt/doc<CR>"] {
+            let _ = editor
+                .feed(parse_keys(keys).expect("Valid keys"), &mut screen)
+                .expect("Rendered");
+
+            assert_eq!(listing(&mut editor.view), listed, "after {keys}");
+            assert!(matches!(editor.global_state.mode, Mode::Normal));
+            assert_eq!(
+                editor
+                    .global_state
+                    .messages
+                    .current()
+                    .map(|message| message.text.as_str()),
+                Some("The explorer can't be edited, use its commands instead"),
+                "after {keys}"
+            );
+        }
+
+        // The entries are still where their names are listed
+        let _ = editor
+            .feed(parse_keys("j<CR>").expect("Valid keys"), &mut screen)
+            .expect("Rendered");
+        let path = editor.view.get_path().expect("Opened").clone();
+        assert_eq!(path.file_name(), Some("b.txt".as_ref()));
+    }
+}
@@ -1,40 +1,127 @@
 use std::{
+    collections::hash_map::Entry,
     fs,
     path::{Path, PathBuf},
 };
 
-use crate::{log, view::View};
-use anyhow::Result;
+use crate::{buffer::Buffer, options::detect_indent, view::View};
+use anyhow::{Context, Result, bail};
 use ropey::Rope;
 
 impl View {
+    /// Attaches the current view box to the file at `path`, replacing whatever it was showing
+    ///
+    /// # Errors
+    /// - If the file can't be read
+    pub fn open(&mut self, path: PathBuf) -> Result<()> {
+        let view_box = self.get_view_box();
+
+        view_box.explorer = None;
+        view_box.quickfix = false;
+        view_box.buffer.rope = Rope::new();
+        view_box.buffer.cursor = 0;
+        view_box.top = 0;
+        view_box.left = 0;
+        self.set_path(Some(path));
+
+        self.load_file()
+    }
+
     pub fn load_file(&mut self) -> Result<()> {
         let Some(path) = self.get_path().cloned() else {
             return Ok(());
         };
+        let hidden = std::path::absolute(&path)
+            .ok()
+            .and_then(|path| self.hidden_buffers.remove(&path));
 
         let buffer = self.get_buffer_mut();
-        if !fs::exists(&path)? {
+        if let Some(hidden) = hidden {
+            buffer.rope = hidden.rope;
+        } else if !fs::exists(&path)? {
             fs::write(path, buffer.rope.to_string())?;
             return Ok(());
+        } else {
+            let contents = fs::read_to_string(path)?;
+            buffer.rope = Rope::from(contents);
         }
 
-        let contents = fs::read_to_string(path)?;
-        buffer.rope = Rope::from(contents);
-
         buffer.lines_for_updating = (0..buffer.len()).map(|_| true).collect::<Vec<bool>>();
         buffer.cursor = usize::min(buffer.cursor, buffer.rope.len_chars());
         buffer.has_changed = true;
 
+        let view_box = self.get_view_box();
+        let mut options = *view_box.options();
+        if options.detectindent {
+            detect_indent(&mut options, &view_box.buffer.rope);
+            view_box.set_options(options);
+        }
+
         Ok(())
     }
 
     pub fn write(&self) -> Result<()> {
-        if let Some(path) = self.get_path() {
-            let buffer = self.get_buffer().to_string();
-            fs::write(path, buffer)?;
-        } else {
-            log!("WARNING: Cannot Write Unattached Buffer");
+        let Some(path) = self.get_path() else {
+            bail!("Cannot write an unattached buffer");
+        };
+
+        let buffer = self.get_buffer().to_string();
+        fs::write(path, buffer)?;
+
+        Ok(())
+    }
+
+    /// The hidden buffer of the file at the absolute `path`, reading it from disk if there isn't
+    /// one yet
+    ///
+    /// # Errors
+    /// - If the file can't be read
+    pub fn hidden_buffer(&mut self, path: &Path) -> Result<&mut Buffer> {
+        match self.hidden_buffers.entry(path.to_path_buf()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let contents = fs::read_to_string(path)
+                    .with_context(|| format!("Could not read {}", path.display()))?;
+                let mut buffer = Buffer::new();
+                buffer.rope = Rope::from(contents);
+
+                Ok(entry.insert(buffer))
+            }
+        }
+    }
+
+    /// Writes every hidden buffer to its file, and drops it now that the file is up to date
+    ///
+    /// # Returns
+    /// The files which were written
+    ///
+    /// # Errors
+    /// - If any of the files can't be written, in which case it and the ones after it are kept
+    pub fn write_hidden(&mut self) -> Result<Vec<PathBuf>> {
+        let mut written = Vec::new();
+        let paths: Vec<PathBuf> = self.hidden_buffers.keys().cloned().collect();
+
+        for path in paths {
+            let buffer = &self.hidden_buffers[&path];
+            fs::write(&path, buffer.rope.to_string())
+                .with_context(|| format!("Could not write {}", path.display()))?;
+            self.hidden_buffers.remove(&path);
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
+    /// Checks that there aren't any hidden buffers whose changes would be lost by quitting
+    ///
+    /// # Errors
+    /// - If there are hidden buffers which haven't been written
+    pub fn check_hidden_written(&self) -> Result<()> {
+        if !self.hidden_buffers.is_empty() {
+            bail!(
+                "{} files which aren't open have unwritten changes (:wall writes them, :q! quits anyway)",
+                self.hidden_buffers.len()
+            );
         }
 
         Ok(())
@@ -45,56 +132,24 @@ impl View {
         view_box.adjust()
     }
 
+    /// Attaches the current view box to `path`, with the options for its filetype
     pub fn set_path(&mut self, path: Option<PathBuf>) {
-        let view_box = &mut self.boxes[self.cursor];
+        let options = self.options.for_path(path.as_deref());
+        let view_box = self.get_view_box();
 
         view_box.set_path(path);
+        view_box.set_options(options);
     }
 
     pub fn get_path(&self) -> Option<&PathBuf> {
-        let view_box = &self.boxes[self.cursor];
+        let view_box = self.view_box();
 
         view_box.path()
     }
 
-    pub fn get_git_hash(&self) -> Option<&str> {
-        let view_box = &self.boxes[self.cursor];
+    pub fn get_git_status(&self) -> Option<String> {
+        let view_box = self.view_box();
 
-        view_box.git_hash.as_deref()
+        view_box.git_status()
     }
 }
-
-pub fn try_get_git_hash(path: Option<&PathBuf>) -> Option<String> {
-    let mut git_hash: Option<String> = None;
-
-    if let Some(path) = path {
-        let path = if path.is_dir() {
-            path
-        } else {
-            // NOTE
-            // I believe the only time this will fail is in the root directory
-            path.parent().unwrap_or_else(|| Path::new("/"))
-        }
-        .to_str()
-        .unwrap_or("");
-
-        let git_stem = if path.is_empty() {
-            String::from(".git")
-        } else {
-            format!("{path}/.git")
-        };
-
-        let head_path = format!("{git_stem}/HEAD");
-
-        if let Ok(head_str) = std::fs::read_to_string(head_path).map(|s| s.trim().to_string())
-            && let Some(head) = head_str.split(' ').nth(1)
-        {
-            let ref_path = format!("{git_stem}/{head}");
-            git_hash = std::fs::read_to_string(ref_path)
-                .ok()
-                .map(|s| s.trim().chars().take(7).collect::<String>());
-        }
-    }
-
-    git_hash
-}
@@ -0,0 +1,426 @@
+use crate::{
+    columns,
+    global_state::GlobalState,
+    options::OptionsConfig,
+    theme::{HLGroup, Theme},
+    view::View,
+    walk::walk,
+};
+use anyhow::Result;
+use crossterm::{
+    cursor::MoveTo,
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    queue,
+    style::{Print, SetBackgroundColor, SetForegroundColor},
+};
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+// Scoring
+const MATCH: i64 = 16;
+/// Matching the first character of a path component or word
+const BOUNDARY: i64 = 10;
+/// Matching a capital in the middle of a camelCase word
+const CAMEL_CASE: i64 = 8;
+/// Matching in the file name rather than a directory
+const FILE_NAME: i64 = 4;
+const CONSECUTIVE: i64 = 6;
+const GAP_START: i64 = -3;
+const GAP_EXTEND: i64 = -1;
+/// The score of a position the query can't be matched at
+const NONE: i64 = i64::MIN / 2;
+
+/// The preview is only read from the start of the file
+const MAX_PREVIEW_BYTES: u64 = 64 * 1024;
+
+/// Scores how well `query` fuzzily matches `candidate`, which it does if every character of the
+/// query appears in the candidate in order. Matches at the start of words, in the file name, and
+/// in runs score higher, and gaps between them score lower.
+///
+/// # Returns
+/// The score, and the indices of the characters of `candidate` which were matched
+fn score(query: &[char], candidate: &[char]) -> Option<(i64, Vec<usize>)> {
+    let (len, candidate_len) = (query.len(), candidate.len());
+    if len == 0 {
+        return Some((0, Vec::new()));
+    }
+
+    // NOTE
+    // Smart case: the query only matches case sensitively if it has a capital in it
+    let case_sensitive = query.iter().any(|c| c.is_uppercase());
+    let same = |q: char, c: char| {
+        if case_sensitive {
+            q == c
+        } else {
+            q == c.to_ascii_lowercase()
+        }
+    };
+
+    let mut rest = candidate.iter();
+    if !query.iter().all(|q| rest.any(|c| same(*q, *c))) {
+        return None;
+    }
+
+    let file_name = candidate
+        .iter()
+        .rposition(|c| *c == '/')
+        .map_or(0, |i| i + 1);
+    let bonus = |j: usize| {
+        let boundary = match j.checked_sub(1).map(|prev| candidate[prev]) {
+            None | Some('/' | '_' | '-' | '.' | ' ') => BOUNDARY,
+            Some(prev) if prev.is_lowercase() && candidate[j].is_uppercase() => CAMEL_CASE,
+            Some(_) => 0,
+        };
+        boundary + if j >= file_name { FILE_NAME } else { 0 }
+    };
+
+    // NOTE
+    // `scores[i][j]` is the best score of the first `i + 1` characters of the query, with the last
+    // of them matched at `j`, and `from[i][j]` is where the one before it was matched
+    let mut scores = vec![vec![NONE; candidate_len]; len];
+    let mut from = vec![vec![0; candidate_len]; len];
+
+    for (j, c) in candidate.iter().enumerate() {
+        if same(query[0], *c) {
+            scores[0][j] = MATCH + bonus(j);
+        }
+    }
+
+    for i in 1..len {
+        // The best score of an earlier match which leaves a gap, with the gap's penalty so far
+        let (mut gap, mut gap_from) = (NONE, 0);
+
+        for j in i..candidate_len {
+            if j >= 2 {
+                gap += GAP_EXTEND;
+                if scores[i - 1][j - 2] > gap {
+                    (gap, gap_from) = (scores[i - 1][j - 2], j - 2);
+                }
+            }
+            if !same(query[i], candidate[j]) {
+                continue;
+            }
+
+            let consecutive = scores[i - 1][j - 1] + CONSECUTIVE;
+            let gapped = gap + GAP_START;
+            let (prev, prev_j) = if consecutive >= gapped {
+                (consecutive, j - 1)
+            } else {
+                (gapped, gap_from)
+            };
+
+            if prev > NONE / 2 {
+                scores[i][j] = prev + MATCH + bonus(j);
+                from[i][j] = prev_j;
+            }
+        }
+    }
+
+    let (mut j, best) = scores[len - 1]
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by_key(|(_, score)| *score)?;
+    if best <= NONE / 2 {
+        return None;
+    }
+
+    let mut positions = vec![0; len];
+    for i in (0..len).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    Some((best, positions))
+}
+
+struct Match {
+    /// The index of the file in `Finder::files`
+    file: usize,
+    score: i64,
+    /// The indices of the characters of the file's path which matched the query
+    positions: Vec<usize>,
+}
+
+/// An overlay for opening files in the project by typing part of their path, with the best
+/// matches listed first and the selected file previewed beside them
+pub struct Finder {
+    /// Relative to the working directory
+    files: Vec<Vec<char>>,
+    query: Vec<char>,
+    matches: Vec<Match>,
+    selected: usize,
+    /// The first lines of the selected file
+    preview: Vec<String>,
+}
+
+impl Finder {
+    /// Lists every file under the working directory which isn't ignored by git
+    pub fn new(query: &str) -> Self {
+        let files = walk(Path::new("."))
+            .into_iter()
+            .map(|path| path.to_string_lossy().chars().collect())
+            .collect();
+
+        let mut finder = Self {
+            files,
+            query: query.chars().collect(),
+            matches: Vec::new(),
+            selected: 0,
+            preview: Vec::new(),
+        };
+        finder.refilter();
+
+        finder
+    }
+
+    fn refilter(&mut self) {
+        self.matches = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(file, path)| {
+                let (score, positions) = score(&self.query, path)?;
+                Some(Match {
+                    file,
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+
+        // Ties go to the shorter path
+        let files = &self.files;
+        self.matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(files[a.file].len().cmp(&files[b.file].len()))
+                .then(files[a.file].cmp(&files[b.file]))
+        });
+
+        self.selected = 0;
+        self.load_preview();
+    }
+
+    fn load_preview(&mut self) {
+        self.preview = self.selected().map_or_else(Vec::new, |path| preview(&path));
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.refilter();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.refilter();
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.matches.len() {
+            self.selected += 1;
+            self.load_preview();
+        }
+    }
+
+    pub fn prev(&mut self) {
+        if self.selected > 0 {
+            self.selected -= 1;
+            self.load_preview();
+        }
+    }
+
+    /// The path of the selected file
+    pub fn selected(&self) -> Option<PathBuf> {
+        let file = self.matches.get(self.selected)?.file;
+        Some(PathBuf::from(self.files[file].iter().collect::<String>()))
+    }
+
+    /// Draws the finder over the middle of the screen, with the matches on the left and the
+    /// preview on the right if there's room for it
+    ///
+    /// # Returns
+    /// Where the cursor goes, at the end of the query
+    pub fn render(
+        &self,
+        screen: (u16, u16),
+        options: &OptionsConfig,
+        theme: &Theme,
+        stdout: &mut impl Write,
+    ) -> Result<(u16, u16)> {
+        let (screen_width, screen_height) = (usize::from(screen.0), usize::from(screen.1));
+        let width = (screen_width * 9 / 10).max(screen_width.min(20));
+        let height = (screen_height * 8 / 10).max(screen_height.min(5));
+        let (left, top) = ((screen_width - width) / 2, (screen_height - height) / 2);
+
+        let list_width = if width >= 60 { width / 2 } else { width };
+        let rows = height.saturating_sub(1);
+
+        let prompt: String = self.query.iter().collect();
+        let count = format!("{}/{}", self.matches.len(), self.files.len());
+        let prompt = format!(
+            " > {prompt:<inner$}{count} ",
+            inner = list_width.saturating_sub(count.chars().count() + 4)
+        );
+        draw(stdout, (left, top), &prompt, theme.style(HLGroup::Popup))?;
+
+        let scroll = (self.selected + 1).saturating_sub(rows);
+        for row in 0..rows {
+            let line = self.matches.get(scroll + row);
+            let group = if line.is_some() && scroll + row == self.selected {
+                HLGroup::Selection
+            } else {
+                HLGroup::Popup
+            };
+            let style = theme.style(group);
+            let highlight = theme.style(HLGroup::SearchMatch).fg;
+
+            queue!(
+                stdout,
+                MoveTo(to_u16(left), to_u16(top + 1 + row)),
+                SetBackgroundColor(style.bg),
+                Print(' ')
+            )?;
+
+            let path = line.map_or(&[][..], |line| &self.files[line.file][..]);
+            let positions = line.map_or(&[][..], |line| &line.positions[..]);
+            for i in 0..list_width.saturating_sub(2) {
+                let fg = if positions.contains(&i) {
+                    highlight
+                } else {
+                    style.fg
+                };
+                queue!(
+                    stdout,
+                    SetForegroundColor(fg),
+                    Print(path.get(i).copied().unwrap_or(' '))
+                )?;
+            }
+            queue!(stdout, Print(' '))?;
+        }
+
+        if list_width < width {
+            let preview_width = width - list_width;
+            let style = theme.style(HLGroup::Popup);
+            let tabstop = options.for_path(self.selected().as_deref()).tabstop;
+
+            for row in 0..height {
+                let text = self.preview.get(row).map_or("", String::as_str);
+                let inner = preview_width.saturating_sub(3);
+                let text = columns::slice(text, 0, inner, tabstop);
+                let padding = inner - columns::width(text.chars(), tabstop);
+                let line = format!("│ {text}{} ", " ".repeat(padding));
+                draw(stdout, (left + list_width, top + row), &line, style)?;
+            }
+        }
+
+        Ok((to_u16(left + 3 + self.query.len()), to_u16(top)))
+    }
+}
+
+fn to_u16(n: usize) -> u16 {
+    u16::try_from(n).unwrap_or(u16::MAX)
+}
+
+fn draw(
+    stdout: &mut impl Write,
+    (x, y): (usize, usize),
+    text: &str,
+    style: crate::theme::Style,
+) -> Result<()> {
+    queue!(
+        stdout,
+        MoveTo(to_u16(x), to_u16(y)),
+        SetForegroundColor(style.fg),
+        SetBackgroundColor(style.bg),
+        Print(text)
+    )?;
+    Ok(())
+}
+
+/// The first lines of the file at `path`, or a note saying why it can't be shown
+fn preview(path: &Path) -> Vec<String> {
+    let mut bytes = Vec::new();
+    let read =
+        fs::File::open(path).and_then(|file| file.take(MAX_PREVIEW_BYTES).read_to_end(&mut bytes));
+    if read.is_err() {
+        return vec!["Could not read file".to_string()];
+    }
+
+    if bytes.contains(&0) {
+        return vec!["Binary file".to_string()];
+    }
+
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Handles a keypress while the finder is open. Typing narrows down the matches, the arrow keys
+/// (or `ctrl-n` and `ctrl-p`) change the selection, `enter` opens it in the current view box,
+/// `ctrl-x` and `ctrl-v` open it in a new horizontal or vertical split, and `esc` closes the
+/// finder.
+pub fn handle_key(global_state: &mut GlobalState, view: &mut View, event: KeyEvent) {
+    let Some(finder) = &mut global_state.finder else {
+        return;
+    };
+    let ctrl = event.modifiers.contains(KeyModifiers::CONTROL);
+
+    let split: Option<fn(&mut View, usize) -> Option<usize>> = match (event.code, ctrl) {
+        (KeyCode::Enter, _) => None,
+        (KeyCode::Char('x'), true) => Some(View::split_view_box_horizontal),
+        (KeyCode::Char('v'), true) => Some(View::split_view_box_vertical),
+
+        (KeyCode::Down, _) | (KeyCode::Char('n'), true) => return finder.next(),
+        (KeyCode::Up, _) | (KeyCode::Char('p'), true) => return finder.prev(),
+        (KeyCode::Backspace, _) => return finder.pop(),
+        (KeyCode::Char(c), false) => return finder.push(c),
+        (KeyCode::Esc, _) => {
+            global_state.finder = None;
+            view.redraw_all();
+            return;
+        }
+        _ => return,
+    };
+
+    let Some(path) = finder.selected() else {
+        return;
+    };
+    global_state.finder = None;
+    view.redraw_all();
+
+    if let Some(split) = split
+        && let Some(new_view_box) = split(view, view.current_view_box())
+    {
+        view.set_current_view_box(new_view_box);
+    }
+
+    if let Err(err) = view.open(path) {
+        global_state.messages.error(format!("{err:#}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn previews_only_read_the_start_of_the_file() {
+        let path = std::env::temp_dir().join(format!("orinfar-preview-{}", std::process::id()));
+        let mut contents = "line\n".repeat(20_000).into_bytes();
+        contents.push(0);
+        fs::write(&path, &contents).expect("Written");
+
+        // The NUL past the preview doesn't make it a binary file
+        let lines = preview(&path);
+        assert_eq!(lines.first().map(String::as_str), Some("line"));
+        let read = lines.join("\n").len();
+        assert_eq!(u64::try_from(read).ok(), Some(MAX_PREVIEW_BYTES));
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{config, global_state::GlobalState, shell, view::View};
+use anyhow::Result;
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+
+#[derive(Deserialize, Default)]
+struct FormatConfig {
+    /// Whether buffers are formatted before they're written
+    #[serde(default)]
+    on_write: bool,
+    /// Commands which read text from stdin and write it formatted to stdout, by file extension.
+    /// `{path}` in an argument is replaced with the path of the file being formatted.
+    #[serde(default)]
+    formatters: HashMap<String, Vec<String>>,
+}
+
+/// The external formatters buffers are piped through. Buffers without one are formatted by their
+/// language server instead.
+#[derive(Default)]
+pub struct Formatter {
+    config: FormatConfig,
+}
+
+impl Formatter {
+    /// Reads the formatter config from `$XDG_CONFIG_HOME/orinfar/format.toml`, if there is one
+    ///
+    /// # Errors
+    /// - If the config file can't be read or is malformed
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            config: config::load("format.toml")?,
+        })
+    }
+
+    pub const fn on_write(&self) -> bool {
+        self.config.on_write
+    }
+
+    /// The command formatting the file at `path`, with `{path}` filled in
+    // NOTE
+    // `{path}` looks like a format argument, but is meant to be replaced literally
+    #[allow(clippy::literal_string_with_formatting_args)]
+    fn command(&self, path: &Path) -> Option<Vec<String>> {
+        let extension = path.extension()?.to_str()?;
+        let command = self.config.formatters.get(extension)?;
+        if command.is_empty() {
+            return None;
+        }
+
+        let path = path.to_string_lossy();
+        Some(
+            command
+                .iter()
+                .map(|arg| arg.replace("{path}", &path))
+                .collect(),
+        )
+    }
+}
+
+/// Formats the current view box's buffer with the formatter configured for its file, or
+/// failing that its language server. Only the lines which change are replaced, as a single
+/// action.
+///
+/// # Errors
+/// - If there's no formatter for the buffer, or formatting fails
+pub fn format_buffer(global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let command = view
+        .get_path()
+        .and_then(|path| global_state.formatter.command(path));
+
+    let Some(command) = command else {
+        return global_state.lsp.format(
+            view,
+            &mut global_state.undo_tree,
+            &mut global_state.messages,
+        );
+    };
+
+    let buffer = view.get_buffer_mut();
+    let formatted = shell::run(&command, &buffer.rope.to_string())?.stdout_or_error(&command[0])?;
+
+    if let Some(action) = buffer.apply_diff(&formatted) {
+        global_state.undo_tree.new_action(action);
+    }
+
+    Ok(())
+}
+
+/// Formats the current view box's buffer before it's written, if that's enabled and there's a
+/// formatter for it. Failing to format doesn't stop the buffer from being written.
+pub fn format_on_write(global_state: &mut GlobalState, view: &mut View) {
+    if !global_state.formatter.on_write() {
+        return;
+    }
+
+    let has_formatter = view
+        .get_path()
+        .is_some_and(|path| global_state.formatter.command(path).is_some())
+        || global_state.lsp.can_format(view);
+
+    if has_formatter && let Err(err) = format_buffer(global_state, view) {
+        global_state
+            .messages
+            .error(format!("Formatting failed: {err:#}"));
+    }
+}
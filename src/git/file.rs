@@ -0,0 +1,206 @@
+use super::{Repository, Status};
+use crate::{
+    diff::{Hunk, diff},
+    sign::Sign,
+    theme::HLGroup,
+};
+use anyhow::{Context, Result};
+use ropey::Rope;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// A file in a git repository, and how the buffer showing it differs from the version of it
+/// staged in the index
+pub struct GitFile {
+    repo: Repository,
+    path: PathBuf,
+    /// The mode the file is staged with
+    mode: u32,
+    /// The lines of the staged version, or `None` if the file isn't tracked
+    staged: Option<Vec<String>>,
+    /// The lines changed in the buffer, which is the new side of each hunk
+    hunks: Vec<Hunk>,
+    /// The repository's status when the file was last opened, written or staged
+    status: Option<Status>,
+}
+
+impl GitFile {
+    /// # Returns
+    /// `None` if the file isn't in a repository, or its staged version can't be read
+    pub fn open(path: &Path) -> Option<Self> {
+        let mut file = Self {
+            repo: Repository::discover(path)?,
+            path: path.to_path_buf(),
+            mode: 0o100_644,
+            staged: None,
+            hunks: Vec::new(),
+            status: None,
+        };
+        file.reload().ok()?;
+
+        Some(file)
+    }
+
+    /// Reads the staged version of the file and the repository's status again, after they might
+    /// have been changed
+    ///
+    /// # Errors
+    /// - If the index, the staged version, or the repository's status can't be read
+    pub fn reload(&mut self) -> Result<()> {
+        self.staged = match self.repo.index_entry(&self.path)? {
+            Some(entry) => {
+                self.mode = entry.mode;
+                let blob = self.repo.read_blob(entry.id)?;
+                Some(lines(&String::from_utf8_lossy(&blob)))
+            }
+            None => None,
+        };
+        self.status = Some(self.repo.status()?);
+
+        Ok(())
+    }
+
+    pub const fn status(&self) -> Option<&Status> {
+        self.status.as_ref()
+    }
+
+    /// Compares the buffer's text with the staged version
+    pub fn diff(&mut self, rope: &Rope) {
+        // NOTE
+        // The lines are compared as slices of the rope, rather than copying the whole buffer on
+        // every change. The rope has an empty last line after a trailing newline, which the
+        // staged lines don't.
+        let lines: Vec<_> = rope.lines().filter(|line| line.len_chars() > 0).collect();
+        self.hunks = self
+            .staged
+            .as_ref()
+            .map(|staged| diff(staged, &lines))
+            .unwrap_or_default();
+    }
+
+    /// A sign next to each changed line, and next to where lines were deleted
+    pub fn signs(&self) -> HashMap<usize, Sign> {
+        let mut signs = HashMap::new();
+
+        for hunk in &self.hunks {
+            if hunk.new.is_empty() {
+                // Deleted lines are marked below the line before them, or above the first line
+                let sign = if hunk.new.start == 0 { '‾' } else { '_' };
+                signs.insert(sign_line(hunk), Sign::new(sign, HLGroup::GitDeleted));
+                continue;
+            }
+
+            let sign = if hunk.old.is_empty() {
+                Sign::new('+', HLGroup::GitAdded)
+            } else {
+                Sign::new('~', HLGroup::GitChanged)
+            };
+            hunk.new.clone().for_each(|line_num| {
+                signs.insert(line_num, sign);
+            });
+        }
+
+        signs
+    }
+
+    /// The hunk changing the line, or with its deleted lines marked next to it
+    pub fn hunk_at(&self, line_num: usize) -> Option<&Hunk> {
+        self.hunks
+            .iter()
+            .find(|hunk| hunk.new.contains(&line_num) || sign_line(hunk) == line_num)
+    }
+
+    /// The line the first hunk after `line_num` starts on
+    pub fn next_hunk(&self, line_num: usize) -> Option<usize> {
+        self.hunks
+            .iter()
+            .map(sign_line)
+            .find(|start| *start > line_num)
+    }
+
+    /// The line the last hunk before `line_num` starts on
+    pub fn prev_hunk(&self, line_num: usize) -> Option<usize> {
+        self.hunks
+            .iter()
+            .rev()
+            .map(sign_line)
+            .find(|start| *start < line_num)
+    }
+
+    /// The staged text the hunk replaces
+    pub fn staged_text(&self, hunk: &Hunk) -> String {
+        self.staged
+            .as_ref()
+            .map(|staged| staged[hunk.old.clone()].concat())
+            .unwrap_or_default()
+    }
+
+    /// The hunk as a unified diff, with the staged lines it removes and the lines it adds
+    pub fn preview(&self, hunk: &Hunk, rope: &Rope) -> String {
+        let removed = self
+            .staged_text(hunk)
+            .lines()
+            .map(|line| format!("-{line}"))
+            .collect::<Vec<String>>();
+        let added = hunk.new.clone().map(|line_num| {
+            let line = rope.line(line_num).to_string();
+            format!("+{}", line.trim_end_matches(['\n', '\r']))
+        });
+
+        let header = format!(
+            "@@ -{},{} +{},{} @@",
+            hunk.old.start + 1,
+            hunk.old.len(),
+            hunk.new.start + 1,
+            hunk.new.len()
+        );
+
+        std::iter::once(header)
+            .chain(removed)
+            .chain(added)
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Stages the hunk, leaving the rest of the staged file as it was
+    ///
+    /// # Errors
+    /// - If the file isn't tracked, or can't be staged
+    pub fn stage(&mut self, hunk: &Hunk, rope: &Rope) -> Result<()> {
+        let staged = self.staged.as_ref().context("File isn't tracked by git")?;
+        let buffer = lines(&rope.to_string());
+
+        let contents = [
+            &staged[..hunk.old.start],
+            &buffer[hunk.new.clone()],
+            &staged[hunk.old.end..],
+        ]
+        .concat()
+        .concat();
+
+        self.repo.stage(&self.path, self.mode, &contents)?;
+        self.reload()?;
+        self.diff(rope);
+
+        Ok(())
+    }
+}
+
+/// The line a hunk's sign is on, which is the line before deleted lines, since they aren't in the
+/// buffer anymore
+const fn sign_line(hunk: &Hunk) -> usize {
+    if hunk.new.start == hunk.new.end {
+        hunk.new.start.saturating_sub(1)
+    } else {
+        hunk.new.start
+    }
+}
+
+/// Splits text into lines, keeping their newlines so that a missing one at the end is a change
+fn lines(text: &str) -> Vec<String> {
+    text.split_inclusive('\n')
+        .map(ToString::to_string)
+        .collect()
+}
@@ -0,0 +1,224 @@
+// NOTE
+// The index (`.git/index`) is a header, a sorted list of entries, and then extensions, of which
+// only the cached tree is read.
+// See https://git-scm.com/docs/index-format
+use super::ObjectId;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+/// The size of an entry before its path: the file's stat data, object id and flags
+const ENTRY_HEADER_LEN: usize = 62;
+/// Set in an entry's flags when it has a second set of flags (from version 3)
+const EXTENDED_FLAG: u16 = 0x4000;
+/// Set in an entry's second set of flags when it's left out of a sparse checkout
+const SKIP_WORKTREE_FLAG: u16 = 0x4000;
+
+/// A file staged in the index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// From the top of the repository, separated by `/`
+    pub path: String,
+    pub mode: u32,
+    pub id: ObjectId,
+    /// When the file was last modified when it was staged, in seconds and nanoseconds
+    pub modified: (u32, u32),
+    /// The file's size when it was staged, truncated to 32 bits
+    pub size: u32,
+    /// Non-zero for the versions of a file with a merge conflict
+    pub stage: u16,
+    /// Set when the file is left out of the work tree by a sparse checkout
+    pub skip_worktree: bool,
+}
+
+/// The parts of the index needed to compare it with the work tree and `HEAD`
+pub struct Index {
+    /// Sorted by path
+    pub entries: Vec<IndexEntry>,
+    /// The tree the index would be committed as, if the index has it cached and it's still valid
+    pub tree: Option<ObjectId>,
+}
+
+impl Index {
+    /// Reads the index file at `path`
+    ///
+    /// # Errors
+    /// - If the index can't be read, or is malformed
+    pub fn read(path: &Path) -> Result<Self> {
+        // A repository without any staged files doesn't have an index
+        if !path.exists() {
+            return Ok(Self {
+                entries: Vec::new(),
+                tree: None,
+            });
+        }
+
+        let data =
+            std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let mut reader = Reader {
+            data: &data,
+            pos: 0,
+        };
+
+        if reader.bytes(4)? != b"DIRC" {
+            bail!("{} isn't a git index", path.display());
+        }
+        let version = reader.u32()?;
+        if !(2..=4).contains(&version) {
+            bail!("Unsupported index version {version}");
+        }
+        let count = reader.u32()?;
+
+        let mut entries = Vec::with_capacity(count as usize);
+        let mut prev_path: Vec<u8> = Vec::new();
+        for _ in 0..count {
+            entries.push(reader.entry(version, &mut prev_path)?);
+        }
+
+        Ok(Self {
+            entries,
+            tree: reader.cached_tree()?,
+        })
+    }
+
+    /// The entry for the file at `path` (from the top of the repository, separated by `/`)
+    ///
+    /// # Returns
+    /// `None` if the file isn't in the index, or is only there as a merge conflict
+    pub fn find(&self, path: &str) -> Option<&IndexEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.stage == 0 && entry.path == path)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn entry(&mut self, version: u32, prev_path: &mut Vec<u8>) -> Result<IndexEntry> {
+        let start = self.pos;
+        let header = self.bytes(ENTRY_HEADER_LEN)?;
+        let u32_at = |pos: usize| {
+            u32::from_be_bytes([
+                header[pos],
+                header[pos + 1],
+                header[pos + 2],
+                header[pos + 3],
+            ])
+        };
+
+        let mut id = [0; 20];
+        id.copy_from_slice(&header[40..60]);
+        let flags = u16::from_be_bytes([header[60], header[61]]);
+        let extended_flags = if version >= 3 && flags & EXTENDED_FLAG != 0 {
+            let bytes = self.bytes(2)?;
+            u16::from_be_bytes([bytes[0], bytes[1]])
+        } else {
+            0
+        };
+
+        let path = if version == 4 {
+            // Paths are stored as how much of the end of the previous one to drop, and what to
+            // add in its place
+            let strip = self.offset()?;
+            let kept = prev_path
+                .len()
+                .checked_sub(strip)
+                .context("Malformed index path")?;
+            prev_path.truncate(kept);
+            prev_path.extend_from_slice(self.until_nul()?);
+            prev_path.clone()
+        } else {
+            let path = self.until_nul()?.to_vec();
+            // Entries are padded with nul bytes to a multiple of 8 bytes
+            let len = self.pos - start;
+            self.bytes(len.next_multiple_of(8) - len)?;
+            path
+        };
+
+        Ok(IndexEntry {
+            path: String::from_utf8_lossy(&path).into_owned(),
+            mode: u32_at(24),
+            id: ObjectId(id),
+            modified: (u32_at(8), u32_at(12)),
+            size: u32_at(36),
+            stage: (flags >> 12) & 0b11,
+            skip_worktree: extended_flags & SKIP_WORKTREE_FLAG != 0,
+        })
+    }
+
+    /// The root tree from the cached tree extension, which follows the entries
+    fn cached_tree(&mut self) -> Result<Option<ObjectId>> {
+        // The index ends with a checksum of everything before it
+        while self.pos + 8 + 20 <= self.data.len() {
+            let signature = self.bytes(4)?;
+            let len = self.u32()? as usize;
+            let data = self.bytes(len)?;
+            if signature != b"TREE" {
+                continue;
+            }
+
+            // NOTE
+            // The first entry is the root, as `<path>\0<entry count> <subtree count>\n<id>`, where
+            // the path is empty and the entry count is `-1` (without an id) if it's out of date
+            let mut tree = Reader { data, pos: 0 };
+            tree.until_nul()?;
+            let counts = tree.until_byte(b'\n')?;
+            if counts.starts_with(b"-") {
+                return Ok(None);
+            }
+
+            let mut id = [0; 20];
+            id.copy_from_slice(tree.bytes(20)?);
+            return Ok(Some(ObjectId(id)));
+        }
+
+        Ok(None)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .context("Index ended early")?;
+        self.pos += len;
+
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// The bytes up to the next nul byte, skipping over it
+    fn until_nul(&mut self) -> Result<&'a [u8]> {
+        self.until_byte(0)
+    }
+
+    /// The bytes up to the next `end`, skipping over it
+    fn until_byte(&mut self, end: u8) -> Result<&'a [u8]> {
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .iter()
+            .position(|byte| *byte == end)
+            .context("Index ended early")?;
+        self.pos += len + 1;
+
+        Ok(&rest[..len])
+    }
+
+    /// A number in git's variable length offset encoding
+    fn offset(&mut self) -> Result<usize> {
+        let mut byte = self.bytes(1)?[0];
+        let mut offset = usize::from(byte & 0x7f);
+        while byte & 0x80 != 0 {
+            byte = self.bytes(1)?[0];
+            offset = ((offset + 1) << 7) | usize::from(byte & 0x7f);
+        }
+
+        Ok(offset)
+    }
+}
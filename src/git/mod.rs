@@ -0,0 +1,204 @@
+mod file;
+mod index;
+mod object;
+mod refs;
+mod status;
+#[cfg(test)]
+mod tests;
+
+pub use file::GitFile;
+pub use status::Status;
+
+use crate::shell;
+use anyhow::{Context, Result, bail};
+use index::{Index, IndexEntry};
+use object::{Kind, ObjectStore};
+use std::{
+    cell::OnceCell,
+    fmt::Display,
+    path::{Component, Path, PathBuf},
+};
+
+/// The SHA-1 hash an object is stored under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ObjectId([u8; 20]);
+
+impl ObjectId {
+    /// # Errors
+    /// - If `hex` isn't 40 hexadecimal digits
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != 40 || !hex.is_ascii() {
+            bail!("Invalid object id: {hex}");
+        }
+
+        let mut id = [0; 20];
+        for (i, byte) in id.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .with_context(|| format!("Invalid object id: {hex}"))?;
+        }
+
+        Ok(Self(id))
+    }
+
+    /// The id `data` would be stored under as a blob
+    fn for_blob(data: &[u8]) -> Self {
+        let mut hash = sha1_smol::Sha1::new();
+        hash.update(format!("blob {}\0", data.len()).as_bytes());
+        hash.update(data);
+
+        Self(hash.digest().bytes())
+    }
+
+    /// The first 7 digits, which is how git abbreviates ids
+    pub fn short(&self) -> String {
+        let mut hex = self.to_string();
+        hex.truncate(7);
+        hex
+    }
+}
+
+impl Display for ObjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// A git repository, read straight from its `.git` directory
+// NOTE
+// The fields are named after what git calls them (eg. `$GIT_COMMON_DIR`)
+#[allow(clippy::struct_field_names)]
+pub struct Repository {
+    /// The directory with the work tree's `HEAD` and index, which is usually `.git`
+    git_dir: PathBuf,
+    /// The directory with the objects and refs shared between all of the repository's work
+    /// trees, which is the same as `git_dir` unless this is a linked work tree
+    common_dir: PathBuf,
+    /// The directory the repository's files are checked out in
+    work_dir: PathBuf,
+    /// The objects, which are opened the first time they're read rather than for every object
+    objects: OnceCell<ObjectStore>,
+}
+
+impl Repository {
+    /// Finds the repository the file or directory at `path` is in, which is the closest one in
+    /// any of the directories above it
+    pub fn discover(path: &Path) -> Option<Self> {
+        let path = std::path::absolute(path).ok()?;
+
+        path.ancestors().find_map(Self::at)
+    }
+
+    /// The repository checked out in `work_dir`, if there is one
+    fn at(work_dir: &Path) -> Option<Self> {
+        let dot_git = work_dir.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else if dot_git.is_file() {
+            // Linked work trees and submodules have a file pointing to their git directory
+            // instead, as `gitdir: <path>`, which can be relative to the work tree
+            let contents = std::fs::read_to_string(&dot_git).ok()?;
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            work_dir.join(target)
+        } else {
+            return None;
+        };
+
+        // Linked work trees point to the main repository's git directory in `commondir`
+        let common_dir = std::fs::read_to_string(git_dir.join("commondir")).map_or_else(
+            |_| git_dir.clone(),
+            |common_dir| git_dir.join(common_dir.trim()),
+        );
+
+        Some(Self {
+            git_dir,
+            common_dir,
+            work_dir: work_dir.to_path_buf(),
+            objects: OnceCell::new(),
+        })
+    }
+
+    /// The path of the file at `path` from the top of the repository, as the index stores it
+    fn relative_path(&self, path: &Path) -> Result<String> {
+        let path = std::path::absolute(path)?;
+        let relative = path
+            .strip_prefix(&self.work_dir)
+            .with_context(|| format!("{} isn't in the repository", path.display()))?;
+
+        let parts: Vec<&str> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect();
+
+        Ok(parts.join("/"))
+    }
+
+    /// The version of the file at `path` staged in the index, or `None` if it isn't tracked
+    ///
+    /// # Errors
+    /// - If the index can't be read
+    fn index_entry(&self, path: &Path) -> Result<Option<IndexEntry>> {
+        let index = self.index()?;
+        Ok(index.find(&self.relative_path(path)?).cloned())
+    }
+
+    /// # Errors
+    /// - If the index can't be read
+    fn index(&self) -> Result<Index> {
+        Index::read(&self.git_dir.join("index"))
+    }
+
+    /// # Errors
+    /// - If a pack's index can't be read
+    fn objects(&self) -> Result<&ObjectStore> {
+        if let Some(objects) = self.objects.get() {
+            return Ok(objects);
+        }
+
+        let objects = ObjectStore::open(&self.common_dir.join("objects"))?;
+        Ok(self.objects.get_or_init(|| objects))
+    }
+
+    /// The contents of the blob `id`
+    ///
+    /// # Errors
+    /// - If the object can't be found or read, or isn't a blob
+    fn read_blob(&self, id: ObjectId) -> Result<Vec<u8>> {
+        self.objects()?.read_kind(id, Kind::Blob)
+    }
+
+    /// Stages `contents` as the file at `path`
+    ///
+    /// # Errors
+    /// - If `git` can't be run or fails
+    // NOTE
+    // Objects are only ever read directly. Writing them means keeping the index and its
+    // extensions consistent, which `git` already does.
+    fn stage(&self, path: &Path, mode: u32, contents: &str) -> Result<()> {
+        let id = self.git(&["hash-object", "-w", "--no-filters", "--stdin"], contents)?;
+        let id = ObjectId::from_hex(&id)?;
+
+        let cache_info = format!("{mode:o},{id},{}", self.relative_path(path)?);
+        self.git(&["update-index", "--cacheinfo", &cache_info], "")?;
+
+        Ok(())
+    }
+
+    /// Runs `git` in the repository, writing `input` to its stdin
+    ///
+    /// # Returns
+    /// What it wrote to stdout
+    fn git(&self, args: &[&str], input: &str) -> Result<String> {
+        let command: Vec<String> = ["git", "-C", &self.work_dir.to_string_lossy()]
+            .iter()
+            .chain(args)
+            .map(ToString::to_string)
+            .collect();
+        let name = format!("git {}", args.first().unwrap_or(&""));
+
+        shell::run(&command, input)?.stdout_or_error(&name)
+    }
+}
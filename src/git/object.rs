@@ -0,0 +1,437 @@
+// NOTE
+// Objects are either loose, each zlib compressed in its own file under `objects/`, or packed
+// together in `objects/pack/`, where they can also be stored as the changes from another object.
+// See https://git-scm.com/docs/gitformat-pack
+use super::ObjectId;
+use anyhow::{Context, Result, bail};
+use flate2::read::ZlibDecoder;
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// The types objects are stored as in a pack
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+
+/// Packs with more objects than this store offsets past 2GiB in a second table
+const LARGE_OFFSET: u32 = 0x8000_0000;
+
+/// The most deltas an object can be stored as a chain of, which is as deep as git makes them
+const MAX_DELTA_CHAIN: usize = 4095;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn from_name(name: &[u8]) -> Result<Self> {
+        match name {
+            b"commit" => Ok(Self::Commit),
+            b"tree" => Ok(Self::Tree),
+            b"blob" => Ok(Self::Blob),
+            b"tag" => Ok(Self::Tag),
+            _ => bail!("Unknown object type {}", String::from_utf8_lossy(name)),
+        }
+    }
+
+    fn from_packed(kind: u8) -> Result<Self> {
+        match kind {
+            1 => Ok(Self::Commit),
+            2 => Ok(Self::Tree),
+            3 => Ok(Self::Blob),
+            4 => Ok(Self::Tag),
+            _ => bail!("Unknown packed object type {kind}"),
+        }
+    }
+}
+
+/// The objects in a repository's `objects` directory
+pub struct ObjectStore {
+    objects: PathBuf,
+    /// The path of each pack and its index, which is read once rather than for every object, and
+    /// again if an object can't be found (eg. after `git gc` has repacked it)
+    packs: RefCell<Vec<(PathBuf, Vec<u8>)>>,
+}
+
+/// Where an object is stored
+enum Location {
+    Loose(PathBuf),
+    Packed { pack: PathBuf, offset: u64 },
+}
+
+/// An object as it's stored in a pack
+enum Packed {
+    Object(Kind, Vec<u8>),
+    /// The changes from the object stored at `base` in the same pack
+    OfsDelta {
+        base: u64,
+        delta: Vec<u8>,
+    },
+    /// The changes from the object `base`
+    RefDelta {
+        base: ObjectId,
+        delta: Vec<u8>,
+    },
+}
+
+impl ObjectStore {
+    /// # Errors
+    /// - If a pack's index can't be read
+    pub fn open(objects: &Path) -> Result<Self> {
+        Ok(Self {
+            objects: objects.to_path_buf(),
+            packs: RefCell::new(read_pack_indexes(objects)?),
+        })
+    }
+
+    /// Reads the object `id`
+    ///
+    /// # Errors
+    /// - If the object doesn't exist, or can't be read
+    pub fn read(&self, id: ObjectId) -> Result<(Kind, Vec<u8>)> {
+        self.read_stored(id).or_else(|err| {
+            // NOTE
+            // The packs might have changed since they were found (eg. by `git gc` or `git fetch`),
+            // so they're looked for again before giving up
+            let packs = read_pack_indexes(&self.objects)?;
+            let pack_paths = |packs: &[(PathBuf, Vec<u8>)]| -> Vec<PathBuf> {
+                packs.iter().map(|(pack, _)| pack.clone()).collect()
+            };
+            if pack_paths(&packs) == pack_paths(&self.packs.borrow()) {
+                return Err(err);
+            }
+
+            *self.packs.borrow_mut() = packs;
+            self.read_stored(id)
+        })
+    }
+
+    /// Reads the object `id`, checking that it's a `kind`
+    ///
+    /// # Errors
+    /// - If the object doesn't exist, can't be read, or is a different kind of object
+    pub fn read_kind(&self, id: ObjectId, kind: Kind) -> Result<Vec<u8>> {
+        let (found, data) = self.read(id)?;
+        if found != kind {
+            bail!("Object {id} is a {found:?}, not a {kind:?}");
+        }
+
+        Ok(data)
+    }
+
+    /// Reads the object `id` from the packs found so far, following the chain of deltas it's
+    /// stored as back to the object they're the changes from
+    fn read_stored(&self, id: ObjectId) -> Result<(Kind, Vec<u8>)> {
+        let mut location = self.find(id)?;
+        // NOTE
+        // The chain is followed in a loop rather than recursively, so a malformed pack whose
+        // deltas go round in a circle is an error rather than a stack overflow
+        let mut deltas = Vec::new();
+        let (kind, mut data) = loop {
+            let (pack, offset) = match location {
+                Location::Loose(path) => break read_loose(&path)?,
+                Location::Packed { pack, offset } => (pack, offset),
+            };
+
+            location = match read_packed(&pack, offset)? {
+                Packed::Object(kind, data) => break (kind, data),
+                Packed::OfsDelta { base, delta } => {
+                    deltas.push(delta);
+                    Location::Packed { pack, offset: base }
+                }
+                Packed::RefDelta { base, delta } => {
+                    deltas.push(delta);
+                    self.find(base)?
+                }
+            };
+            if deltas.len() > MAX_DELTA_CHAIN {
+                bail!("Object {id} is stored as too many deltas");
+            }
+        };
+
+        for delta in deltas.iter().rev() {
+            data = apply_delta(&data, delta)?;
+        }
+
+        Ok((kind, data))
+    }
+
+    /// Where the object `id` is stored, out of the loose objects and the packs found so far
+    fn find(&self, id: ObjectId) -> Result<Location> {
+        let hex = id.to_string();
+        let loose = self.objects.join(&hex[..2]).join(&hex[2..]);
+        if loose.exists() {
+            return Ok(Location::Loose(loose));
+        }
+
+        for (pack, idx) in self.packs.borrow().iter() {
+            if let Some(offset) = find_in_pack_index(idx, id)
+                .with_context(|| format!("Malformed index for {}", pack.display()))?
+            {
+                return Ok(Location::Packed {
+                    pack: pack.clone(),
+                    offset,
+                });
+            }
+        }
+
+        bail!("Object {id} not found")
+    }
+}
+
+/// The path of each pack in `objects/pack/`, and its index
+///
+/// # Errors
+/// - If a pack's index can't be read
+fn read_pack_indexes(objects: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut packs = Vec::new();
+    let entries = std::fs::read_dir(objects.join("pack"))
+        .into_iter()
+        .flatten();
+    for entry in entries.flatten() {
+        let idx = entry.path();
+        if idx.extension().is_none_or(|ext| ext != "idx") {
+            continue;
+        }
+
+        let data =
+            std::fs::read(&idx).with_context(|| format!("Could not read {}", idx.display()))?;
+        packs.push((idx.with_extension("pack"), data));
+    }
+    packs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(packs)
+}
+
+/// Reads the object starting at `offset` in the pack at `path`
+fn read_packed(path: &Path, offset: u64) -> Result<Packed> {
+    let file = File::open(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    reader.seek(SeekFrom::Start(offset))?;
+
+    // The type and size are packed into a variable length number, with the type in bits 4-6 of
+    // the first byte
+    let mut byte = read_byte(&mut reader)?;
+    let kind = (byte >> 4) & 0b111;
+    let mut size = usize::from(byte & 0x0f);
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        byte = read_byte(&mut reader)?;
+        size |= usize::from(byte & 0x7f)
+            .checked_shl(shift)
+            .context("Malformed packed object")?;
+        shift += 7;
+    }
+
+    match kind {
+        OFS_DELTA => {
+            // The base is always somewhere before the delta
+            let distance = read_offset(&mut reader)?;
+            if distance == 0 || distance > offset {
+                bail!("Malformed packed delta");
+            }
+
+            Ok(Packed::OfsDelta {
+                base: offset - distance,
+                delta: inflate(&mut reader, size)?,
+            })
+        }
+        REF_DELTA => {
+            let mut base = [0; 20];
+            reader.read_exact(&mut base)?;
+
+            Ok(Packed::RefDelta {
+                base: ObjectId(base),
+                delta: inflate(&mut reader, size)?,
+            })
+        }
+        kind => Ok(Packed::Object(
+            Kind::from_packed(kind)?,
+            inflate(&mut reader, size)?,
+        )),
+    }
+}
+
+fn read_loose(path: &Path) -> Result<(Kind, Vec<u8>)> {
+    let file = File::open(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let mut data = Vec::new();
+    ZlibDecoder::new(file).read_to_end(&mut data)?;
+
+    // Loose objects start with `<type> <size>\0`
+    let header_len = data
+        .iter()
+        .position(|byte| *byte == 0)
+        .context("Malformed loose object")?;
+    let kind = data[..header_len]
+        .split(|byte| *byte == b' ')
+        .next()
+        .context("Malformed loose object")?;
+    let kind = Kind::from_name(kind)?;
+
+    Ok((kind, data.split_off(header_len + 1)))
+}
+
+/// Looks `id` up in a (version 2) pack index
+///
+/// # Returns
+/// Where the object starts in the pack, if it's there
+fn find_in_pack_index(data: &[u8], id: ObjectId) -> Result<Option<u64>> {
+    let u32_at = |pos: usize| -> Result<u32> {
+        let bytes = data.get(pos..pos + 4).context("Pack index ended early")?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    if data.get(..4) != Some(b"\xfftOc".as_slice()) || u32_at(4)? != 2 {
+        bail!("Unsupported pack index version");
+    }
+
+    // NOTE
+    // The fanout table holds how many ids start with each byte or less, so the ids starting with
+    // the same byte as `id` are `fanout[first - 1]..fanout[first]`
+    let fanout = |byte: usize| -> Result<usize> { Ok(u32_at(8 + byte * 4)? as usize) };
+    let first = usize::from(id.0[0]);
+    let count = fanout(255)?;
+    let (mut low, mut high) = (
+        if first == 0 { 0 } else { fanout(first - 1)? },
+        fanout(first)?,
+    );
+
+    let ids = 8 + 256 * 4;
+    let id_at = |i: usize| data.get(ids + i * 20..ids + (i + 1) * 20);
+    while low < high {
+        let mid = usize::midpoint(low, high);
+        let mid_id = id_at(mid).context("Pack index ended early")?;
+        match mid_id.cmp(id.0.as_slice()) {
+            std::cmp::Ordering::Less => low = mid + 1,
+            std::cmp::Ordering::Greater => high = mid,
+            std::cmp::Ordering::Equal => {
+                // The ids are followed by a CRC for each object, and then their offsets
+                let offsets = ids + count * 24;
+                let offset = u32_at(offsets + mid * 4)?;
+                if offset < LARGE_OFFSET {
+                    return Ok(Some(u64::from(offset)));
+                }
+
+                let large = offsets + count * 4 + (offset - LARGE_OFFSET) as usize * 8;
+                let bytes = data
+                    .get(large..large + 8)
+                    .context("Pack index ended early")?;
+                let mut offset = [0; 8];
+                offset.copy_from_slice(bytes);
+                return Ok(Some(u64::from_be_bytes(offset)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn read_byte(reader: &mut impl Read) -> Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// A number in git's variable length offset encoding, which is how far back in the pack the
+/// base of a delta is
+fn read_offset(reader: &mut impl Read) -> Result<u64> {
+    let mut byte = read_byte(reader)?;
+    let mut offset = u64::from(byte & 0x7f);
+    while byte & 0x80 != 0 {
+        byte = read_byte(reader)?;
+        offset = offset
+            .checked_add(1)
+            .and_then(|offset| offset.checked_mul(1 << 7))
+            .context("Malformed packed delta")?
+            | u64::from(byte & 0x7f);
+    }
+
+    Ok(offset)
+}
+
+/// Decompresses the `size` bytes of zlib compressed data at the start of `reader`
+fn inflate(reader: &mut impl Read, size: usize) -> Result<Vec<u8>> {
+    // NOTE
+    // The size comes from the pack, so it isn't trusted with allocating space up front
+    let mut data = Vec::new();
+    ZlibDecoder::new(reader)
+        .take(size as u64)
+        .read_to_end(&mut data)?;
+    if data.len() != size {
+        bail!("Packed object is smaller than its header says");
+    }
+
+    Ok(data)
+}
+
+/// Builds an object from the object its delta is from
+///
+/// A delta is the size of the base and of the result, followed by instructions to either copy a
+/// range of the base, or insert the bytes which follow the instruction
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut delta = delta.iter().copied();
+    let mut size = || -> Result<usize> {
+        let mut size = 0;
+        let mut shift = 0;
+        loop {
+            let byte = delta.next().context("Malformed delta")?;
+            size |= usize::from(byte & 0x7f)
+                .checked_shl(shift)
+                .context("Malformed delta")?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(size);
+            }
+        }
+    };
+
+    if size()? != base.len() {
+        bail!("Delta is for a different base object");
+    }
+    let result_size = size()?;
+    let mut result = Vec::new();
+
+    while let Some(instruction) = delta.next() {
+        if instruction & 0x80 == 0 {
+            // Insert the next `instruction` bytes
+            let len = usize::from(instruction);
+            result.extend(delta.by_ref().take(len));
+            continue;
+        }
+
+        // Copy, with the offset and size's bytes only there when their bit is set
+        let mut number = |bits: std::ops::Range<u8>| -> Result<usize> {
+            let mut number = 0;
+            for (i, bit) in bits.enumerate() {
+                if instruction & (1 << bit) != 0 {
+                    let byte = delta.next().context("Malformed delta")?;
+                    number |= usize::from(byte) << (8 * i);
+                }
+            }
+            Ok(number)
+        };
+        let start = number(0..4)?;
+        let len = match number(4..7)? {
+            0 => 0x10000,
+            len => len,
+        };
+
+        let copied = base
+            .get(start..start + len)
+            .context("Delta copies past the end of its base")?;
+        result.extend_from_slice(copied);
+    }
+
+    if result.len() != result_size {
+        bail!("Delta builds a different size of object than it says");
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,84 @@
+// NOTE
+// Refs are stored either loose, as a file under the git directory with the id (or `ref: <name>`
+// for a symbolic ref) in it, or together in `packed-refs`, one `<id> <name>` per line.
+// See https://git-scm.com/docs/git-pack-refs
+use super::{ObjectId, Repository};
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+/// How many symbolic refs are followed before giving up, in case they point to each other
+const MAX_SYMBOLIC_DEPTH: usize = 5;
+
+/// What's checked out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Head {
+    /// The checked out branch, or `None` if `HEAD` is detached
+    pub branch: Option<String>,
+    /// The checked out commit, or `None` if the branch doesn't have any commits yet
+    pub id: Option<ObjectId>,
+}
+
+impl Repository {
+    /// # Errors
+    /// - If `HEAD` can't be read, or points to a malformed ref
+    pub fn head(&self) -> Result<Head> {
+        let head = read_to_string(&self.git_dir.join("HEAD"))?;
+
+        match head.strip_prefix("ref:") {
+            Some(name) => {
+                let name = name.trim();
+                Ok(Head {
+                    branch: Some(name.strip_prefix("refs/heads/").unwrap_or(name).to_string()),
+                    id: self.resolve_ref(name, 0)?,
+                })
+            }
+            None => Ok(Head {
+                branch: None,
+                id: Some(ObjectId::from_hex(&head)?),
+            }),
+        }
+    }
+
+    /// The commit the ref `name` (eg. `refs/heads/main`) points to, or `None` if it doesn't
+    /// exist
+    fn resolve_ref(&self, name: &str, depth: usize) -> Result<Option<ObjectId>> {
+        if depth > MAX_SYMBOLIC_DEPTH {
+            bail!("Too many levels of symbolic refs at {name}");
+        }
+
+        // Refs like `refs/bisect` are only for the work tree, the rest are shared
+        for dir in [&self.git_dir, &self.common_dir] {
+            let path = dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+
+            let contents = read_to_string(&path)?;
+            return contents.strip_prefix("ref:").map_or_else(
+                || ObjectId::from_hex(&contents).map(Some),
+                |target| self.resolve_ref(target.trim(), depth + 1),
+            );
+        }
+
+        let packed_refs = self.common_dir.join("packed-refs");
+        if !packed_refs.exists() {
+            return Ok(None);
+        }
+
+        // Lines starting with `#` are comments, and with `^` are the commit the tag on the
+        // line above points to
+        read_to_string(&packed_refs)?
+            .lines()
+            .filter(|line| !line.starts_with(['#', '^']))
+            .filter_map(|line| line.split_once(' '))
+            .find(|(_, ref_name)| *ref_name == name)
+            .map(|(id, _)| ObjectId::from_hex(id))
+            .transpose()
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map(|contents| contents.trim().to_string())
+        .with_context(|| format!("Could not read {}", path.display()))
+}
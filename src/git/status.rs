@@ -0,0 +1,182 @@
+use super::{
+    ObjectId, Repository,
+    index::Index,
+    object::{Kind, ObjectStore},
+    refs::Head,
+};
+use anyhow::{Context, Result};
+use std::{fmt::Display, fs::Metadata, path::Path, time::UNIX_EPOCH};
+
+/// The mode of a directory in a tree
+const TREE_MODE: u32 = 0o040_000;
+/// The mode of a submodule, which is a commit rather than a file
+const GITLINK_MODE: u32 = 0o160_000;
+/// The mode of a symlink, which is stored as the path it points to
+const SYMLINK_MODE: u32 = 0o120_000;
+
+/// What's checked out in a repository, and whether it has changes which haven't been committed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub head: Head,
+    pub dirty: bool,
+}
+
+impl Display for Status {
+    /// Shown as the branch, the commit's short hash, and `*` when there are changes, eg.
+    /// `main 1a2b3c4*`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let branch = self.head.branch.as_deref().unwrap_or("(detached)");
+        let dirty = if self.dirty { "*" } else { "" };
+
+        match self.head.id {
+            Some(id) => write!(f, "{branch} {}{dirty}", id.short()),
+            None => write!(f, "{branch}{dirty}"),
+        }
+    }
+}
+
+impl Repository {
+    /// # Errors
+    /// - If `HEAD`, the index, or the objects they point to can't be read
+    // NOTE
+    // Like `git describe --dirty`, untracked files don't make the repository dirty, so only the
+    // files in the index need to be looked at
+    pub fn status(&self) -> Result<Status> {
+        let head = self.head()?;
+        let index = self.index()?;
+        let dirty = self.has_staged_changes(&head, &index)? || self.has_unstaged_changes(&index)?;
+
+        Ok(Status { head, dirty })
+    }
+
+    /// Whether the index differs from the checked out commit
+    fn has_staged_changes(&self, head: &Head, index: &Index) -> Result<bool> {
+        let Some(commit) = head.id else {
+            return Ok(!index.entries.is_empty());
+        };
+        if index.entries.iter().any(|entry| entry.stage != 0) {
+            return Ok(true);
+        }
+
+        let objects = self.objects()?;
+        let tree = commit_tree(objects, commit)?;
+        // The index caches the tree it would be committed as, which saves flattening the commit's
+        // tree when nothing's been staged
+        if index.tree == Some(tree) {
+            return Ok(false);
+        }
+
+        let mut committed = Vec::new();
+        flatten_tree(objects, tree, "", &mut committed)?;
+        committed.sort();
+
+        let mut staged: Vec<(String, u32, ObjectId)> = index
+            .entries
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.mode, entry.id))
+            .collect();
+        staged.sort();
+
+        Ok(committed != staged)
+    }
+
+    /// Whether any of the files in the index have been changed in the work tree
+    fn has_unstaged_changes(&self, index: &Index) -> Result<bool> {
+        for entry in &index.entries {
+            if entry.skip_worktree || [TREE_MODE, GITLINK_MODE].contains(&entry.mode) {
+                continue;
+            }
+
+            let path = self.work_dir.join(&entry.path);
+            let Ok(metadata) = path.symlink_metadata() else {
+                return Ok(true);
+            };
+
+            // The index only keeps the lower 32 bits of the size and modification time
+            #[allow(clippy::cast_possible_truncation)]
+            if metadata.len() as u32 != entry.size {
+                return Ok(true);
+            }
+            if modified(&metadata) == Some(entry.modified) {
+                continue;
+            }
+
+            // The file's been touched since it was staged, but might still be the same
+            let contents = if entry.mode == SYMLINK_MODE {
+                std::fs::read_link(&path)?
+                    .to_string_lossy()
+                    .into_owned()
+                    .into_bytes()
+            } else {
+                read(&path)?
+            };
+            if ObjectId::for_blob(&contents) != entry.id {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// The tree a commit is of, from its first line (`tree <id>`)
+fn commit_tree(objects: &ObjectStore, id: ObjectId) -> Result<ObjectId> {
+    let commit = objects.read_kind(id, Kind::Commit)?;
+    let tree = commit
+        .strip_prefix(b"tree ")
+        .and_then(|rest| rest.get(..40))
+        .with_context(|| format!("Malformed commit {id}"))?;
+
+    ObjectId::from_hex(&String::from_utf8_lossy(tree))
+}
+
+/// Adds every file in the tree `id`, and the trees in it, to `files` as `(path, mode, id)`
+fn flatten_tree(
+    objects: &ObjectStore,
+    id: ObjectId,
+    prefix: &str,
+    files: &mut Vec<(String, u32, ObjectId)>,
+) -> Result<()> {
+    let tree = objects.read_kind(id, Kind::Tree)?;
+
+    // Each entry is `<mode in octal> <name>\0<20 byte id>`
+    let mut rest = tree.as_slice();
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|byte| *byte == b' ')
+            .context("Malformed tree")?;
+        let nul = rest
+            .iter()
+            .position(|byte| *byte == 0)
+            .context("Malformed tree")?;
+        let entry_id = rest.get(nul + 1..nul + 21).context("Malformed tree")?;
+
+        let mode = u32::from_str_radix(&String::from_utf8_lossy(&rest[..space]), 8)?;
+        let name = String::from_utf8_lossy(&rest[space + 1..nul]);
+        let path = format!("{prefix}{name}");
+        let mut entry = [0; 20];
+        entry.copy_from_slice(entry_id);
+
+        if mode == TREE_MODE {
+            flatten_tree(objects, ObjectId(entry), &format!("{path}/"), files)?;
+        } else {
+            files.push((path, mode, ObjectId(entry)));
+        }
+        rest = &rest[nul + 21..];
+    }
+
+    Ok(())
+}
+
+/// When the file was last modified, as the index stores it
+fn modified(metadata: &Metadata) -> Option<(u32, u32)> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    #[allow(clippy::cast_possible_truncation)]
+    Some((since_epoch.as_secs() as u32, since_epoch.subsec_nanos()))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))
+}
@@ -0,0 +1,493 @@
+use super::{GitFile, ObjectId, Repository, Status, object::ObjectStore};
+use crate::theme::HLGroup;
+use flate2::{Compression, write::ZlibEncoder};
+use ropey::Rope;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A repository in a temporary directory, which is removed when it's dropped
+struct TempRepo {
+    dir: PathBuf,
+}
+
+impl TempRepo {
+    fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "orinfar-git-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).expect("Could not create temporary directory");
+
+        let repo = Self { dir };
+        repo.git(&["init", "-q"]);
+        repo
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    fn write(&self, name: &str, contents: &str) {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Could not create directory");
+        }
+        std::fs::write(path, contents).expect("Could not write file");
+    }
+
+    fn git(&self, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(&self.dir)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .output()
+            .expect("Could not run git");
+        assert!(
+            output.status.success(),
+            "git {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    fn commit(&self, name: &str, contents: &str) {
+        self.write(name, contents);
+        self.git(&["add", name]);
+        self.git(&["commit", "-q", "-m", name]);
+    }
+
+    fn repository(&self) -> Repository {
+        Repository::discover(&self.dir).expect("Repository not found")
+    }
+
+    fn rev_parse(&self, rev: &str) -> ObjectId {
+        ObjectId::from_hex(&self.git(&["rev-parse", rev])).expect("Invalid object id")
+    }
+
+    fn status(&self, path: &str) -> Status {
+        Repository::discover(&self.path(path))
+            .expect("Repository not found")
+            .status()
+            .expect("Status is readable")
+    }
+
+    /// The contents of the blob at `rev` (eg. `HEAD:file`), read from the object store
+    fn read(&self, rev: &str) -> String {
+        let blob = self
+            .repository()
+            .read_blob(self.rev_parse(rev))
+            .expect("Could not read blob");
+        String::from_utf8(blob).expect("Blob isn't UTF-8")
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn numbered_lines(count: usize) -> String {
+    (0..count)
+        .map(|i| format!("line {i}\n"))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+#[test]
+fn object_id_round_trips_through_hex() {
+    let hex = "0123456789abcdef0123456789abcdef01234567";
+    let id = ObjectId::from_hex(hex).expect("Valid object id");
+
+    assert_eq!(id.to_string(), hex);
+    assert!(ObjectId::from_hex("0123").is_err());
+    assert!(ObjectId::from_hex(&"g".repeat(40)).is_err());
+}
+
+#[test]
+fn discovers_repository_from_subdirectory() {
+    let repo = TempRepo::new();
+    repo.write("a/b/c.txt", "text\n");
+
+    let found = Repository::discover(&repo.path("a/b/c.txt")).expect("Repository not found");
+    assert_eq!(found.work_dir, repo.dir);
+    assert_eq!(
+        found
+            .relative_path(&repo.path("a/b/c.txt"))
+            .expect("Path is in the repository"),
+        "a/b/c.txt"
+    );
+}
+
+#[test]
+fn reads_staged_loose_blob() {
+    let repo = TempRepo::new();
+    repo.write("file.txt", "staged\n");
+    repo.git(&["add", "file.txt"]);
+    repo.write("file.txt", "changed after staging\n");
+
+    let entry = repo
+        .repository()
+        .index_entry(&repo.path("file.txt"))
+        .expect("Index is readable")
+        .expect("File is staged");
+    assert_eq!(entry.mode, 0o100_644);
+
+    let blob = repo
+        .repository()
+        .read_blob(entry.id)
+        .expect("Blob is readable");
+    assert_eq!(blob, b"staged\n");
+}
+
+#[test]
+fn untracked_files_have_no_index_entry() {
+    let repo = TempRepo::new();
+    repo.commit("tracked.txt", "tracked\n");
+    repo.write("untracked.txt", "untracked\n");
+
+    let entry = repo
+        .repository()
+        .index_entry(&repo.path("untracked.txt"))
+        .expect("Index is readable");
+    assert!(entry.is_none());
+}
+
+#[test]
+fn reads_entries_from_index_version_4() {
+    let repo = TempRepo::new();
+    repo.write("dir/first.txt", "first\n");
+    repo.write("dir/second.txt", "second\n");
+    repo.write("other.txt", "other\n");
+    repo.git(&["add", "."]);
+    repo.git(&["update-index", "--index-version", "4"]);
+
+    for (name, contents) in [
+        ("dir/first.txt", "first\n"),
+        ("dir/second.txt", "second\n"),
+        ("other.txt", "other\n"),
+    ] {
+        let entry = repo
+            .repository()
+            .index_entry(&repo.path(name))
+            .expect("Index is readable")
+            .expect("File is staged");
+        let blob = repo
+            .repository()
+            .read_blob(entry.id)
+            .expect("Blob is readable");
+        assert_eq!(blob, contents.as_bytes());
+    }
+}
+
+#[test]
+fn reads_packed_and_deltified_blobs() {
+    let repo = TempRepo::new();
+    let mut versions = Vec::new();
+    for i in 0..5 {
+        let contents = numbered_lines(200) + &format!("version {i}\n");
+        repo.commit("file.txt", &contents);
+        versions.push(contents);
+    }
+    repo.git(&["gc", "-q", "--aggressive"]);
+
+    let loose = repo.path(".git/objects").read_dir().expect("Objects exist");
+    assert!(
+        loose
+            .flatten()
+            .all(|entry| ["pack", "info"].contains(&&*entry.file_name().to_string_lossy())),
+        "Objects should all be packed"
+    );
+
+    for (i, contents) in versions.iter().rev().enumerate() {
+        assert_eq!(&repo.read(&format!("HEAD~{i}:file.txt")), contents);
+    }
+}
+
+#[test]
+fn finds_objects_packed_after_the_first_read() {
+    let repo = TempRepo::new();
+    repo.commit("a.txt", "a\n");
+    let repository = repo.repository();
+    let a = repo.rev_parse("HEAD:a.txt");
+    assert_eq!(repository.read_blob(a).expect("Blob is readable"), b"a\n");
+
+    // The objects were only loose when they were first read
+    repo.commit("b.txt", "b\n");
+    repo.git(&["gc", "-q"]);
+    let b = repo.rev_parse("HEAD:b.txt");
+    assert_eq!(repository.read_blob(b).expect("Blob is readable"), b"b\n");
+    assert_eq!(repository.read_blob(a).expect("Blob is readable"), b"a\n");
+}
+
+fn compressed(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).expect("Compressed");
+    encoder.finish().expect("Compressed")
+}
+
+/// Writes a pack of `entries`, which are the raw bytes of each object stored in it, with an index
+/// giving each of them the id `[i + 1; 20]`
+fn write_pack(objects: &Path, entries: &[Vec<u8>]) {
+    let count = |n: usize| u32::try_from(n).expect("Small pack").to_be_bytes();
+
+    let mut pack = b"PACK\0\0\0\x02".to_vec();
+    pack.extend(count(entries.len()));
+    let mut offsets = Vec::new();
+    for entry in entries {
+        offsets.extend(count(pack.len()));
+        pack.extend(entry);
+    }
+
+    let mut idx = b"\xfftOc\0\0\0\x02".to_vec();
+    for byte in 0..256 {
+        idx.extend(count(usize::min(byte, entries.len())));
+    }
+    for i in 1..=entries.len() {
+        idx.extend([u8::try_from(i).expect("Small pack"); 20]);
+    }
+    idx.extend(vec![0; entries.len() * 4]);
+    idx.extend(offsets);
+
+    let dir = objects.join("pack");
+    std::fs::create_dir_all(&dir).expect("Could not create directory");
+    std::fs::write(dir.join("pack-test.pack"), pack).expect("Could not write pack");
+    std::fs::write(dir.join("pack-test.idx"), idx).expect("Could not write index");
+}
+
+#[test]
+fn malformed_packs_are_errors() {
+    let repo = TempRepo::new();
+    let objects = repo.path("objects");
+    let delta = compressed(b"\x03\x03\x03abc");
+
+    // A delta whose base is itself
+    let mut same_offset = vec![0x60 | 6, 0];
+    same_offset.extend(&delta);
+    // A delta whose base would be before the start of the pack
+    let mut before_pack = vec![0x60 | 6, 0x7f];
+    before_pack.extend(&delta);
+    // A delta whose base is its own id
+    let mut same_id = vec![0x70 | 6];
+    same_id.extend([3; 20]);
+    same_id.extend(&delta);
+    // A blob which says it's far bigger than it is
+    let mut huge = vec![0xb0 | 0x0f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f];
+    huge.extend(compressed(b"abc"));
+    // A blob which is fine
+    let mut blob = vec![0x30 | 3];
+    blob.extend(compressed(b"abc"));
+    write_pack(&objects, &[same_offset, before_pack, same_id, huge, blob]);
+
+    let store = ObjectStore::open(&objects).expect("Indexes are readable");
+    for i in 1..=4 {
+        assert!(store.read(ObjectId([i; 20])).is_err());
+    }
+    let (_, data) = store.read(ObjectId([5; 20])).expect("Blob is readable");
+    assert_eq!(data, b"abc");
+}
+
+#[test]
+fn signs_added_changed_and_deleted_lines() {
+    let repo = TempRepo::new();
+    repo.commit("file.txt", &numbered_lines(10));
+
+    let mut file = GitFile::open(&repo.path("file.txt")).expect("File is in a repository");
+    let mut lines: Vec<String> = numbered_lines(10).lines().map(String::from).collect();
+    lines[2] = "changed".to_string();
+    lines.remove(5);
+    lines.insert(7, "added".to_string());
+    file.diff(&Rope::from(lines.join("\n") + "\n"));
+
+    let signs = file.signs();
+    let group = |line_num: usize| signs.get(&line_num).map(|sign| sign.group);
+    assert_eq!(group(2), Some(HLGroup::GitChanged));
+    assert_eq!(group(4), Some(HLGroup::GitDeleted));
+    assert_eq!(group(7), Some(HLGroup::GitAdded));
+    assert_eq!(signs.len(), 3);
+
+    assert_eq!(file.next_hunk(0), Some(2));
+    assert_eq!(file.next_hunk(2), Some(4));
+    assert_eq!(file.next_hunk(7), None);
+    assert_eq!(file.prev_hunk(7), Some(4));
+    assert_eq!(file.prev_hunk(2), None);
+}
+
+#[test]
+fn previews_hunk_as_diff() {
+    let repo = TempRepo::new();
+    repo.commit("file.txt", "one\ntwo\nthree\n");
+
+    let mut file = GitFile::open(&repo.path("file.txt")).expect("File is in a repository");
+    let rope = Rope::from("one\n2\nthree\n");
+    file.diff(&rope);
+
+    let hunk = file.hunk_at(1).expect("Line is changed").clone();
+    assert_eq!(file.preview(&hunk, &rope), "@@ -2,1 +2,1 @@\n-two\n+2");
+    assert!(file.hunk_at(0).is_none());
+}
+
+#[test]
+fn stages_only_the_hunk() {
+    let repo = TempRepo::new();
+    repo.commit("file.txt", &numbered_lines(20));
+
+    let contents = numbered_lines(20)
+        .replace("line 2\n", "first change\n")
+        .replace("line 15\n", "second change\n");
+    repo.write("file.txt", &contents);
+    let rope = Rope::from(contents.as_str());
+
+    let mut file = GitFile::open(&repo.path("file.txt")).expect("File is in a repository");
+    file.diff(&rope);
+    let hunk = file.hunk_at(15).expect("Line is changed").clone();
+    file.stage(&hunk, &rope).expect("Hunk can be staged");
+
+    assert_eq!(
+        repo.read(":file.txt"),
+        numbered_lines(20).replace("line 15\n", "second change\n")
+    );
+    // Only the unstaged hunk is left
+    assert!(file.hunk_at(2).is_some());
+    assert!(file.hunk_at(15).is_none());
+    assert_eq!(file.signs().len(), 1);
+}
+
+#[test]
+fn files_outside_a_repository_have_no_git_file() {
+    let dir = std::env::temp_dir().join(format!("orinfar-no-git-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("Could not create temporary directory");
+
+    // NOTE
+    // The temporary directory could itself be in a repository, in which case there's nothing to
+    // check
+    if Repository::discover(&dir).is_none() {
+        assert!(GitFile::open(&dir.join("file.txt")).is_none());
+    }
+    let _ = std::fs::remove_dir_all(Path::new(&dir));
+}
+
+#[test]
+fn shows_branch_short_hash_and_dirty_state() {
+    let repo = TempRepo::new();
+    repo.git(&["checkout", "-q", "-b", "main"]);
+    repo.commit("dir/file.txt", "committed\n");
+    let short = repo.rev_parse("HEAD").short();
+
+    let status = repo.status("dir/file.txt");
+    assert_eq!(status.head.branch.as_deref(), Some("main"));
+    assert_eq!(status.head.id, Some(repo.rev_parse("HEAD")));
+    assert_eq!(status.to_string(), format!("main {short}"));
+
+    // Untracked files don't make the repository dirty
+    repo.write("untracked.txt", "untracked\n");
+    assert!(!repo.status("dir/file.txt").dirty);
+
+    repo.write("dir/file.txt", "changed\n");
+    assert_eq!(
+        repo.status("dir/file.txt").to_string(),
+        format!("main {short}*")
+    );
+
+    // Rewriting a file with what was committed leaves it clean, even though it's been touched
+    repo.write("dir/file.txt", "committed\n");
+    assert!(!repo.status("dir/file.txt").dirty);
+
+    repo.write("dir/file.txt", "staged\n");
+    repo.git(&["add", "dir/file.txt"]);
+    assert!(repo.status("dir/file.txt").dirty);
+    repo.write("dir/file.txt", "committed\n");
+    assert!(
+        repo.status("dir/file.txt").dirty,
+        "Staged changes are dirty"
+    );
+}
+
+#[test]
+fn shows_unborn_branch() {
+    let repo = TempRepo::new();
+    let branch = repo.git(&["symbolic-ref", "--short", "HEAD"]);
+
+    let status = repo.status("file.txt");
+    assert_eq!(status.head.branch.as_deref(), Some(branch.trim()));
+    assert_eq!(status.head.id, None);
+    assert!(!status.dirty);
+
+    repo.write("file.txt", "text\n");
+    repo.git(&["add", "file.txt"]);
+    assert_eq!(
+        repo.status("file.txt").to_string(),
+        format!("{}*", branch.trim())
+    );
+}
+
+#[test]
+fn resolves_packed_refs_and_detached_head() {
+    let repo = TempRepo::new();
+    repo.git(&["checkout", "-q", "-b", "main"]);
+    repo.commit("file.txt", "first\n");
+    repo.commit("file.txt", "second\n");
+    repo.git(&["pack-refs", "--all"]);
+    assert!(!repo.path(".git/refs/heads/main").exists());
+
+    let head = repo.repository().head().expect("HEAD is readable");
+    assert_eq!(head.branch.as_deref(), Some("main"));
+    assert_eq!(head.id, Some(repo.rev_parse("HEAD")));
+
+    repo.git(&["checkout", "-q", "--detach", "HEAD~1"]);
+    let status = repo.status("file.txt");
+    assert_eq!(status.head.branch, None);
+    assert_eq!(status.head.id, Some(repo.rev_parse("HEAD")));
+    assert_eq!(
+        status.to_string(),
+        format!("(detached) {}", repo.rev_parse("HEAD").short())
+    );
+    assert!(!status.dirty);
+}
+
+#[test]
+fn discovers_linked_work_tree() {
+    let repo = TempRepo::new();
+    repo.commit("file.txt", &numbered_lines(10));
+    repo.git(&["worktree", "add", "-q", "-b", "feature", "tree"]);
+    assert!(repo.path("tree/.git").is_file());
+
+    let found = Repository::discover(&repo.path("tree/file.txt")).expect("Repository not found");
+    assert_eq!(found.work_dir, repo.path("tree"));
+
+    let status = found.status().expect("Status is readable");
+    assert_eq!(status.head.branch.as_deref(), Some("feature"));
+    assert_eq!(status.head.id, Some(repo.rev_parse("HEAD")));
+    assert!(!status.dirty);
+
+    // The objects are shared with the main work tree
+    let mut file = GitFile::open(&repo.path("tree/file.txt")).expect("File is in a repository");
+    file.diff(&Rope::from(numbered_lines(10).replace("line 3", "changed")));
+    assert!(file.hunk_at(3).is_some());
+}
+
+#[test]
+fn discovers_git_directory_from_file() {
+    let repo = TempRepo::new();
+    repo.git(&["checkout", "-q", "-b", "main"]);
+    repo.commit("file.txt", "text\n");
+
+    // Submodules are checked out with a `.git` file pointing to a directory in their parent's
+    std::fs::rename(repo.path(".git"), repo.path("modules")).expect("Could not move .git");
+    repo.write(".git", "gitdir: modules\n");
+
+    let status = repo.status("file.txt");
+    assert_eq!(status.head.branch.as_deref(), Some("main"));
+    assert!(!status.dirty);
+    assert_eq!(repo.read("HEAD:file.txt"), "text\n");
+}
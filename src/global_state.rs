@@ -1,36 +1,78 @@
 use crate::{
-    mode::Mode, operator::Operator, register::RegisterHandler, status_bar::StatusBar,
-    text_object::TextObjectType, undo::UndoTree,
+    completion::Completion, finder::Finder, format::Formatter, keymap::Keymap, lsp::Lsp,
+    make::Make, message::Messages, mode::Mode, operator::Operator, quickfix::Quickfix,
+    register::RegisterHandler, status_bar::StatusBar, text_object::TextObjectType, theme::Theme,
+    undo::UndoTree,
 };
 
 pub struct GlobalState<'a> {
     pub next_operation: Option<&'a Operator>,
     pub text_object_type: Option<TextObjectType>,
+    /// Whether the last key chained was a command or motion which takes the next key as its
+    /// argument (eg. `r` or `f`), in which case the next key is chained whatever it is
+    pub awaiting_argument: bool,
+    /// The register prefix (eg. `"b`) chained before the current action, which is repeated along
+    /// with it by `.`
+    pub register_prefix: Vec<char>,
 
     pub mode: Mode,
     pub count: u32,
     pub chained: Vec<char>,
     pub search_str: Vec<char>,
+    pub completion: Option<Completion>,
+    pub finder: Option<Finder>,
 
     pub undo_tree: UndoTree,
     pub register_handler: RegisterHandler,
     pub status_bar: StatusBar,
+    pub messages: Messages,
+    pub theme: Theme,
+    pub lsp: Lsp,
+    pub formatter: Formatter,
+    pub make: Make,
+    pub keymap: Keymap,
+    pub quickfix: Quickfix,
+}
+
+/// The state without any config, so that it can be made without reading `$XDG_CONFIG_HOME`
+/// (eg. in tests)
+impl Default for GlobalState<'_> {
+    fn default() -> Self {
+        Self::new(
+            Theme::default(),
+            Lsp::disabled(),
+            Formatter::default(),
+            Make::default(),
+            Keymap::default(),
+        )
+    }
 }
 
 impl GlobalState<'_> {
-    pub fn new() -> Self {
+    pub fn new(theme: Theme, lsp: Lsp, formatter: Formatter, make: Make, keymap: Keymap) -> Self {
         Self {
             next_operation: None,
             text_object_type: None,
+            awaiting_argument: false,
+            register_prefix: Vec::new(),
 
             mode: Mode::Normal,
             count: 1,
             chained: Vec::new(),
             search_str: Vec::new(),
+            completion: None,
+            finder: None,
 
             undo_tree: UndoTree::new(),
             register_handler: RegisterHandler::new(),
             status_bar: StatusBar::new(),
+            messages: Messages::new(),
+            theme,
+            lsp,
+            formatter,
+            make,
+            keymap,
+            quickfix: Quickfix::default(),
         }
     }
 }
@@ -0,0 +1,99 @@
+use crate::{buffer::Buffer, quickfix::Entry, view::View, walk::walk};
+use ropey::Rope;
+use std::path::Path;
+
+/// Searches every file under the working directory which isn't ignored by git for `pattern`,
+/// with the same search as the substitute meta-command
+///
+/// Files open in a view box are searched as they are in the buffer, so that the results line up
+/// with what's shown. Binary files are skipped.
+pub fn grep(pattern: &str, view: &View) -> Vec<Entry> {
+    grep_in(Path::new("."), pattern, view)
+}
+
+/// As `grep`, under `root`. The entries' paths are relative to it.
+fn grep_in(root: &Path, pattern: &str, view: &View) -> Vec<Entry> {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let mut entries = Vec::new();
+
+    for path in walk(root) {
+        let absolute = std::path::absolute(root.join(&path)).ok();
+        let open = view.view_boxes().find(|view_box| {
+            view_box
+                .path()
+                .is_some_and(|open| std::path::absolute(open).ok() == absolute)
+        });
+        let rope = match open {
+            Some(view_box) => view_box.buffer.rope.clone(),
+            None => match std::fs::read_to_string(root.join(&path)) {
+                Ok(contents) if !contents.contains('\0') => Rope::from(contents),
+                _ => continue,
+            },
+        };
+
+        let mut buffer = Buffer::new();
+        buffer.rope = rope;
+
+        // NOTE
+        // Occurences are found by the index just past their end
+        for end in buffer.find_occurences(&pattern) {
+            let start = end - pattern.len();
+            let line = buffer.rope.char_to_line(start);
+
+            entries.push(Entry {
+                path: path.clone(),
+                line,
+                col: start - buffer.rope.line_to_char(line),
+                text: buffer.rope.line(line).to_string().trim_end().to_string(),
+            });
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OptionsConfig;
+    use std::fs;
+
+    #[test]
+    fn finds_every_occurence_in_open_and_unopened_files() {
+        let dir = std::env::temp_dir().join(format!("orinfar-grep-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("Created");
+        fs::write(dir.join("a.txt"), "one two\n  two, two\n").expect("Written");
+        fs::write(dir.join("b.bin"), "two\0").expect("Written");
+        fs::write(dir.join("c.txt"), "three\n").expect("Written");
+        fs::write(dir.join("ignored.txt"), "two\n").expect("Written");
+        fs::write(dir.join(".gitignore"), "ignored.txt\n").expect("Written");
+
+        // The open file is searched as it is in the buffer, not on disk
+        let mut view = View::new(80, 24, OptionsConfig::default());
+        view.set_path(Some(dir.join("c.txt")));
+        view.load_file().expect("Loaded");
+        view.get_buffer_mut().rope = Rope::from("three\ntwo\n");
+
+        let entries: Vec<(String, usize, usize, String)> = grep_in(&dir, "two", &view)
+            .into_iter()
+            .map(|entry| {
+                let path = entry.path.to_string_lossy().to_string();
+                (path, entry.line, entry.col, entry.text)
+            })
+            .collect();
+        let entry =
+            |path: &str, line, col, text: &str| (path.to_string(), line, col, text.to_string());
+
+        assert_eq!(
+            entries,
+            [
+                entry("a.txt", 0, 4, "one two"),
+                entry("a.txt", 1, 2, "  two, two"),
+                entry("a.txt", 1, 7, "  two, two"),
+                entry("c.txt", 1, 0, "two"),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
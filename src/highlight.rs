@@ -1,4 +1,9 @@
-use crate::{utility::traverse_tree, view_box::ViewBox};
+use crate::{
+    columns::slice,
+    theme::{HLGroup, Theme},
+    utility::traverse_tree,
+    view_box::ViewBox,
+};
 use crossterm::style::Color;
 use tree_sitter::{Node, Point, Tree, TreeCursor};
 
@@ -16,29 +21,29 @@ impl ViewBox {
     }
 
     /// Adds hl blocks for empty lines at the end of the document
-    pub fn append_empty_lines(&self, tree_hl_blocks: &mut Vec<Vec<HLBlock>>) {
+    pub fn append_empty_lines(&self, tree_hl_blocks: &mut Vec<Vec<HLBlock>>, theme: &Theme) {
         let buffer_lines: usize = self.buffer.rope.len_lines();
         let hl_lines: usize = tree_hl_blocks.len();
         if hl_lines < buffer_lines {
             for _ in hl_lines..buffer_lines {
-                tree_hl_blocks.push(vec![HLBlock::empty()]);
+                tree_hl_blocks.push(vec![HLBlock::empty(theme)]);
             }
         }
     }
 
     /// Adds blank hl blocks for empty lines in the middle of the document
-    pub fn fill_in_empty_lines(tree_hl_blocks: &mut [Vec<HLBlock>]) {
+    pub fn fill_in_empty_lines(tree_hl_blocks: &mut [Vec<HLBlock>], theme: &Theme) {
         tree_hl_blocks
             .iter_mut()
             .filter(|l| l.is_empty())
             .for_each(|hl_blocks| {
-                hl_blocks.push(HLBlock::empty());
+                hl_blocks.push(HLBlock::empty(theme));
             });
     }
 
     /// Returns a list of lines, each containing highlight blocks
     /// Returns an empty list if `self.parse_tree.is_none()`
-    pub fn highlight(&self) -> Vec<Vec<HLBlock>> {
+    pub fn highlight(&self, theme: &Theme) -> Vec<Vec<HLBlock>> {
         let Some(tree) = &self.parse_tree else {
             return vec![];
         };
@@ -47,10 +52,10 @@ impl ViewBox {
             return vec![];
         };
 
-        let mut tree_hl_blocks = highlight_tree(tree, language.highlight);
+        let mut tree_hl_blocks = highlight_tree(tree, language.highlight, theme);
 
-        self.append_empty_lines(&mut tree_hl_blocks);
-        Self::fill_in_empty_lines(&mut tree_hl_blocks);
+        self.append_empty_lines(&mut tree_hl_blocks, theme);
+        Self::fill_in_empty_lines(&mut tree_hl_blocks, theme);
 
         tree_hl_blocks
     }
@@ -62,6 +67,10 @@ pub enum HLEnd {
     EndOfLine,
 }
 
+/// The part of a line drawn in a color
+///
+/// `start` and `end` are the byte columns tree-sitter gives, until `ViewBox` moves them to the
+/// display columns they're drawn at
 #[derive(Debug, Clone)]
 pub struct HLBlock {
     pub start: usize,
@@ -70,13 +79,15 @@ pub struct HLBlock {
     pub bg_color: Color,
 }
 
-impl<'a> HLBlock {
-    pub const fn empty() -> Self {
+impl HLBlock {
+    pub fn empty(theme: &Theme) -> Self {
+        let style = theme.style(HLGroup::EmptyLine);
+
         Self {
             start: 0,
             end: HLEnd::EndOfLine,
-            fg_color: Color::DarkGrey,
-            bg_color: Color::Reset,
+            fg_color: style.fg,
+            bg_color: style.bg,
         }
     }
 
@@ -87,27 +98,28 @@ impl<'a> HLBlock {
         }
     }
 
-    pub fn slice_text(&self, line: &'a str) -> &'a str {
-        match self.end {
-            HLEnd::Bounded(end) => &line[self.start..end],
-            HLEnd::EndOfLine => &line[self.start..],
-        }
+    /// The text the block covers in `line`, once it's been moved to display columns
+    pub fn slice_text(&self, line: &str, tabstop: usize) -> String {
+        let end = self.get_end().unwrap_or(usize::MAX);
+        slice(line, self.start, end, tabstop)
     }
 }
 
 fn hl_group_from_node(
     node: Node,
     hl_blocks: &mut Vec<Vec<HLBlock>>,
-    node_type_to_color: fn(&str, &str, &str) -> Option<Color>,
+    node_type_to_group: fn(&str, &str, &str) -> Option<HLGroup>,
+    theme: &Theme,
 ) {
     let Some(parent) = node.parent() else { return };
     let last_sibiling_type = node.prev_sibling().map_or("", |node| node.kind());
 
     // Not every node needs a HLBlock, some are non-lexical nodes
     // Will return if on a non-lexical node
-    let Some(color) = node_type_to_color(node.kind(), parent.kind(), last_sibiling_type) else {
+    let Some(group) = node_type_to_group(node.kind(), parent.kind(), last_sibiling_type) else {
         return;
     };
+    let color = theme.fg(group);
 
     let start: Point = node.start_position();
     let end: Point = node.end_position();
@@ -185,7 +197,7 @@ fn try_expand_hl_block_back(
                 *start_column = end;
             } else {
                 panic!(
-                    "Attempting to append to a line where the last highlight block already goes to the end of the line\nThis likely means that the `node_type_to_color` function being used returns `Some(color)` for nodes with overlapping range (e.g. both a parent and one of its descendants)."
+                    "Attempting to append to a line where the last highlight block already goes to the end of the line\nThis likely means that the `node_type_to_group` function being used returns `Some(group)` for nodes with overlapping range (e.g. both a parent and one of its descendants)."
                 );
             }
         }
@@ -198,10 +210,11 @@ fn try_expand_hl_block_back(
 
 fn highlight_tree(
     tree: &Tree,
-    node_type_to_color: fn(&str, &str, &str) -> Option<Color>,
+    node_type_to_group: fn(&str, &str, &str) -> Option<HLGroup>,
+    theme: &Theme,
 ) -> Vec<Vec<HLBlock>> {
     let node_action = |cursor: &TreeCursor, hl_blocks: &mut Vec<Vec<HLBlock>>| {
-        hl_group_from_node(cursor.node(), hl_blocks, node_type_to_color);
+        hl_group_from_node(cursor.node(), hl_blocks, node_type_to_group, theme);
     };
 
     // NOTE
@@ -211,11 +224,3 @@ fn highlight_tree(
 
     traverse_tree(tree, node_action, move_up_hook, move_down_hook)
 }
-
-// NOTE
-// My special orange since my colorscheme (everforest) isn't actually base16 compliant
-pub const ORANGE: Color = Color::Rgb {
-    r: 230,
-    g: 152,
-    b: 117,
-};
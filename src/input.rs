@@ -0,0 +1,90 @@
+use anyhow::{Result, bail};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Reads keys written in the same notation as Vim's mappings, where most characters stand for
+/// themselves and special keys are written in angle brackets:
+/// - `<Esc>`, `<CR>` (or `<Enter>`), `<BS>`, `<Tab>`, `<S-Tab>`, `<Space>`
+/// - `<Up>`, `<Down>`, `<Left>` and `<Right>`
+/// - `<C-x>` for `ctrl-x`
+/// - `<lt>` for a literal `<`
+///
+/// # Errors
+/// - If a name in angle brackets isn't a key, or the brackets aren't closed
+pub fn parse_keys(keys: &str) -> Result<Vec<KeyEvent>> {
+    let mut events = Vec::new();
+    let mut chars = keys.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            events.push(KeyEvent::from(KeyCode::Char(c)));
+            continue;
+        }
+
+        let mut name = String::new();
+        let mut closed = false;
+        for c in chars.by_ref() {
+            if c == '>' {
+                closed = true;
+                break;
+            }
+            name.push(c);
+        }
+        if !closed {
+            bail!("Unclosed <{name}");
+        }
+
+        let event = match name.to_lowercase().as_str() {
+            "esc" => KeyCode::Esc.into(),
+            "cr" | "enter" | "return" => KeyCode::Enter.into(),
+            "bs" | "backspace" => KeyCode::Backspace.into(),
+            "tab" => KeyCode::Tab.into(),
+            "s-tab" => KeyCode::BackTab.into(),
+            "space" => KeyCode::Char(' ').into(),
+            "lt" => KeyCode::Char('<').into(),
+            "up" => KeyCode::Up.into(),
+            "down" => KeyCode::Down.into(),
+            "left" => KeyCode::Left.into(),
+            "right" => KeyCode::Right.into(),
+            lower => match lower
+                .strip_prefix("c-")
+                .map(|rest| rest.chars().collect::<Vec<_>>())
+            {
+                Some(rest) if rest.len() == 1 => {
+                    KeyEvent::new(KeyCode::Char(rest[0]), KeyModifiers::CONTROL)
+                }
+                _ => bail!("Unknown key <{name}>"),
+            },
+        };
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_named_keys() {
+        let keys = parse_keys("a<Esc><c-w><lt><CR>").expect("Valid keys");
+
+        assert_eq!(
+            keys,
+            [
+                KeyCode::Char('a').into(),
+                KeyCode::Esc.into(),
+                KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL),
+                KeyCode::Char('<').into(),
+                KeyCode::Enter.into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_unclosed_keys() {
+        assert!(parse_keys("<nope>").is_err());
+        assert!(parse_keys("<esc").is_err());
+        assert!(parse_keys("a<").is_err());
+    }
+}
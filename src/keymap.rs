@@ -0,0 +1,248 @@
+use crate::{NAMED_ACTIONS, config, input::parse_keys, mode::Mode, named_action::NamedAction};
+use anyhow::{Context, Result, bail};
+use crossterm::event::KeyEvent;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+struct KeymapConfig {
+    /// The keys `<leader>` stands for in mappings
+    #[serde(default = "default_leader")]
+    leader: String,
+    #[serde(default)]
+    normal: HashMap<String, MappingConfig>,
+    #[serde(default)]
+    insert: HashMap<String, MappingConfig>,
+    /// Used while typing meta-commands and searches
+    #[serde(default)]
+    meta: HashMap<String, MappingConfig>,
+}
+
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        Self {
+            leader: default_leader(),
+            normal: HashMap::new(),
+            insert: HashMap::new(),
+            meta: HashMap::new(),
+        }
+    }
+}
+
+fn default_leader() -> String {
+    "\\".to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MappingConfig {
+    Keys(String),
+    Full {
+        keys: String,
+        #[serde(default)]
+        recursive: bool,
+    },
+    /// Mapped to one of `NAMED_ACTIONS` by name, eg. `{ action = "write" }`
+    Action {
+        action: String,
+    },
+}
+
+struct Mapping {
+    lhs: Vec<KeyEvent>,
+    rhs: Step,
+}
+
+/// What a key (along with the keys before it) turned out to mean
+#[derive(Clone)]
+pub enum Step {
+    /// A key which isn't part of a mapping, which means what it's bound to
+    Key(KeyEvent),
+    Mapped {
+        keys: Vec<KeyEvent>,
+        /// Whether the keys it's mapped to are mapped themselves, rather than being the built-in
+        /// bindings
+        recursive: bool,
+    },
+    Action(&'static NamedAction<'static>),
+}
+
+/// Mappings from sequences of keys to other sequences of keys, per mode
+#[derive(Default)]
+pub struct Keymap {
+    normal: Vec<Mapping>,
+    insert: Vec<Mapping>,
+    meta: Vec<Mapping>,
+    /// The keys typed so far which could still be the start of a mapping
+    pending: Vec<KeyEvent>,
+}
+
+impl Keymap {
+    /// Reads the keymap from `$XDG_CONFIG_HOME/orinfar/keymap.toml`, if there is one
+    ///
+    /// # Errors
+    /// - If the config file can't be read or is malformed, or a mapping has a key which doesn't
+    ///   exist
+    pub fn load() -> Result<Self> {
+        Self::from_config(&config::load("keymap.toml")?).context("Invalid keymap config")
+    }
+
+    /// # Errors
+    /// - If `contents` isn't a valid keymap config, or a mapping has a key or action which
+    ///   doesn't exist
+    pub fn parse(contents: &str) -> Result<Self> {
+        Self::from_config(&toml::from_str(contents)?)
+    }
+
+    fn from_config(config: &KeymapConfig) -> Result<Self> {
+        let mappings = |maps: &HashMap<String, MappingConfig>| -> Result<Vec<Mapping>> {
+            maps.iter()
+                .map(|(lhs, rhs)| {
+                    let leader = |keys: &str| {
+                        keys.replace("<leader>", &config.leader)
+                            .replace("<Leader>", &config.leader)
+                    };
+                    let context = || format!("Invalid mapping {lhs}");
+
+                    let rhs = match rhs {
+                        MappingConfig::Keys(keys) => Step::Mapped {
+                            keys: parse_keys(&leader(keys)).with_context(context)?,
+                            recursive: false,
+                        },
+                        MappingConfig::Full { keys, recursive } => Step::Mapped {
+                            keys: parse_keys(&leader(keys)).with_context(context)?,
+                            recursive: *recursive,
+                        },
+                        MappingConfig::Action { action } => Step::Action(
+                            NAMED_ACTIONS
+                                .iter()
+                                .find(|named| named.name == action)
+                                .with_context(|| format!("Unknown action {action}"))
+                                .with_context(context)?,
+                        ),
+                    };
+                    let mapping = Mapping {
+                        lhs: parse_keys(&leader(lhs)).with_context(context)?,
+                        rhs,
+                    };
+                    if mapping.lhs.is_empty() {
+                        bail!("Mapping with no keys");
+                    }
+
+                    Ok(mapping)
+                })
+                .collect()
+        };
+
+        Ok(Self {
+            normal: mappings(&config.normal)?,
+            insert: mappings(&config.insert)?,
+            meta: mappings(&config.meta)?,
+            pending: Vec::new(),
+        })
+    }
+
+    fn mappings(&self, mode: &Mode) -> &[Mapping] {
+        match mode {
+            Mode::Normal => &self.normal,
+            Mode::Insert => &self.insert,
+            Mode::Meta | Mode::Search => &self.meta,
+            Mode::Visual => &[],
+        }
+    }
+
+    /// Adds `key` to the keys typed so far
+    ///
+    /// # Returns
+    /// What the keys typed so far mean, which is nothing yet if they could still be the start of
+    /// a longer mapping
+    // NOTE
+    // There's no timeout, so a key which is both mapped and the start of a longer mapping waits
+    // for the next key to decide which it is
+    pub fn resolve(&mut self, mode: &Mode, key: KeyEvent) -> Vec<Step> {
+        self.pending.push(key);
+
+        let (steps, used) = split(self.mappings(mode), &self.pending);
+        self.pending.drain(..used);
+
+        steps
+    }
+
+    /// What the first of `keys` means in `mode`, when there aren't any more keys coming after
+    /// them (eg. the keys a recursive mapping is mapped to)
+    ///
+    /// # Returns
+    /// The step, and how many of the keys it used up, or `None` if there aren't any keys
+    // NOTE
+    // Only the first step is worked out, since running it can change the mode the rest of the
+    // keys are mapped in (eg. `i` in normal mode)
+    pub fn expand_first(&self, mode: &Mode, keys: &[KeyEvent]) -> Option<(Step, usize)> {
+        next_step(self.mappings(mode), keys)
+    }
+}
+
+/// Splits `keys` into the mappings (and unmapped keys) they're made up of, preferring the
+/// longest mapping at each point
+///
+/// # Returns
+/// The steps, and how many of the keys they used up. Keys at the end which could be the start of
+/// a mapping are left over.
+fn split(mappings: &[Mapping], keys: &[KeyEvent]) -> (Vec<Step>, usize) {
+    let mut steps = Vec::new();
+    let mut i = 0;
+
+    while i < keys.len() {
+        let rest = &keys[i..];
+        if mappings
+            .iter()
+            .any(|mapping| mapping.lhs.len() > rest.len() && mapping.lhs.starts_with(rest))
+        {
+            break;
+        }
+
+        let Some((step, used)) = next_step(mappings, rest) else {
+            break;
+        };
+        steps.push(step);
+        i += used;
+    }
+
+    (steps, i)
+}
+
+/// The longest mapping `keys` start with, or the first key if they don't start with any
+///
+/// # Returns
+/// The step, and how many of the keys it used up, or `None` if there aren't any keys
+fn next_step(mappings: &[Mapping], keys: &[KeyEvent]) -> Option<(Step, usize)> {
+    let longest = mappings
+        .iter()
+        .filter(|mapping| keys.starts_with(&mapping.lhs))
+        .max_by_key(|mapping| mapping.lhs.len());
+
+    longest.map_or_else(
+        || keys.first().map(|key| (Step::Key(*key), 1)),
+        |mapping| Some((mapping.rhs.clone(), mapping.lhs.len())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_to_named_action() {
+        let mut keymap = Keymap::parse("[normal]\n\"<leader>w\" = { action = \"write\" }\n")
+            .expect("Valid keymap");
+        let keys = parse_keys("\\w").expect("Valid keys");
+
+        assert!(keymap.resolve(&Mode::Normal, keys[0]).is_empty());
+        let steps = keymap.resolve(&Mode::Normal, keys[1]);
+        assert!(matches!(steps.as_slice(), [Step::Action(action)] if action.name == "write"));
+    }
+
+    #[test]
+    fn reject_unknown_action() {
+        assert!(Keymap::parse("[normal]\nQ = { action = \"nothing\" }\n").is_err());
+    }
+}
@@ -1,19 +1,26 @@
-use crate::{c::c_node_to_color, markdown::md_node_to_color};
-use crossterm::style::Color;
+use crate::{c::c_node_to_group, markdown::md_node_to_group, theme::HLGroup};
 
 pub struct OrinLanguage {
+    /// The identifier language servers know the language by
+    pub language_id: &'static str,
+    /// The command to start the language server with, unless it's configured otherwise
+    pub server: &'static [&'static str],
     pub extensions: Vec<String>,
-    pub highlight: fn(&str, &str, &str) -> Option<Color>,
+    pub highlight: fn(&str, &str, &str) -> Option<HLGroup>,
     pub lang: tree_sitter::Language,
 }
 
 impl OrinLanguage {
     pub fn new<'a>(
+        language_id: &'static str,
+        server: &'static [&'static str],
         extensions: &'a [&'a str],
         lang: tree_sitter::Language,
-        highlight: fn(&str, &str, &str) -> Option<Color>,
+        highlight: fn(&str, &str, &str) -> Option<HLGroup>,
     ) -> Self {
         Self {
+            language_id,
+            server,
             extensions: extensions.iter().map(|s| (*s).to_string()).collect(),
             lang,
             highlight,
@@ -22,8 +29,20 @@ impl OrinLanguage {
 
     pub fn from_ext(extension: &str) -> Option<Self> {
         let lang = match extension {
-            "c" | "h" => Self::new(&["c", "h"], tree_sitter_c::LANGUAGE.into(), c_node_to_color),
-            "md" => Self::new(&["md"], tree_sitter_md::LANGUAGE.into(), md_node_to_color),
+            "c" | "h" => Self::new(
+                "c",
+                &["clangd"],
+                &["c", "h"],
+                tree_sitter_c::LANGUAGE.into(),
+                c_node_to_group,
+            ),
+            "md" => Self::new(
+                "markdown",
+                &["marksman", "server"],
+                &["md"],
+                tree_sitter_md::LANGUAGE.into(),
+                md_node_to_group,
+            ),
             _ => {
                 return None;
             }
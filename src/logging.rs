@@ -11,12 +11,7 @@ pub struct OrinfarData {
 /// # Returns
 /// - On a success, a `OrinfarData` structure containing data about the user's Orinfar use
 pub fn setup_logging_and_data() -> Result<OrinfarData> {
-    // This could fail if the dir already exists, so we don't care if this fails
-    if let Err(err) = std::fs::create_dir(log_dir())
-        && err.to_string() != "File exists (os error 17)"
-    {
-        return Err(err.into());
-    }
+    std::fs::create_dir_all(log_dir())?;
     std::fs::File::create(log_file())?;
     let data_path = data_file();
     if !data_path.exists() {
@@ -83,11 +78,14 @@ pub fn write_data(key: &impl ToString, value: &impl ToString) {
         .expect("unable to append data");
 }
 
+// NOTE
+// Nothing's logged until `DEBUG` is set, so that code which logs can run without `main` (eg. in
+// tests)
 #[macro_export]
 macro_rules! log {
     ($($arg:tt)*) => {{
         use $crate::DEBUG;
-        if *DEBUG.get().unwrap() {
+        if DEBUG.get().copied().unwrap_or(false) {
             $crate::logging::log(&format!($($arg)*))
         }
     }};
@@ -97,7 +95,7 @@ macro_rules! log {
 macro_rules! logn {
     ($($arg:tt)*) => {{
         use $crate::DEBUG;
-        if *DEBUG.get().unwrap() {
+        if DEBUG.get().copied().unwrap_or(false) {
             $crate::logging::log_no_newline(&format!($($arg)*))
         }
     }};
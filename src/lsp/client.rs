@@ -0,0 +1,338 @@
+use crate::{
+    completion::CompletionItem,
+    lsp::{
+        document::{Document, PositionEncoding, SyncKind},
+        path_to_uri,
+        transport::{read_message, write_message},
+    },
+};
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for a server to shut down before killing it
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Something the server sent that the editor might care about.
+/// Requests from the server are answered by the client itself.
+pub enum Incoming {
+    Response {
+        method: String,
+        result: Result<Value, String>,
+    },
+    Notification {
+        method: String,
+        params: Value,
+    },
+}
+
+/// A connection to a single language server process
+pub struct LspClient {
+    pub name: String,
+    process: Child,
+    stdin: ChildStdin,
+    /// Messages read from the server's stdout by the reader thread
+    receiver: Receiver<Value>,
+
+    next_id: i64,
+    /// The methods of requests we're waiting on responses for, by id
+    pending_requests: HashMap<i64, String>,
+    /// Messages that can't be sent until the server responds to `initialize`
+    queued: Vec<Value>,
+    initialized: bool,
+
+    pub sync_kind: SyncKind,
+    pub encoding: PositionEncoding,
+    pub capabilities: Value,
+    /// Open documents, by absolute path
+    pub documents: HashMap<PathBuf, Document>,
+    /// Candidates from the last completion response, until they're taken by the editor
+    pub completions: Option<Vec<CompletionItem>>,
+}
+
+impl LspClient {
+    /// Starts the server, then sends it the `initialize` request. The server isn't usable until it
+    /// responds, but anything sent before then is queued rather than blocking the editor.
+    ///
+    /// # Errors
+    /// - If the server can't be started
+    pub fn start(command: &[String], root: &Path) -> Result<Self> {
+        let (program, args) = command
+            .split_first()
+            .context("Language server command is empty")?;
+
+        let mut process = Command::new(program)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start language server {program}"))?;
+
+        let stdin = process
+            .stdin
+            .take()
+            .context("Language server has no stdin")?;
+        let stdout = process
+            .stdout
+            .take()
+            .context("Language server has no stdout")?;
+
+        // NOTE
+        // The main loop blocks on reading terminal events, so the server is read from on its own
+        // thread, which stops once the server closes its stdout
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            name: Path::new(program).file_name().map_or_else(
+                || program.clone(),
+                |name| name.to_string_lossy().to_string(),
+            ),
+            process,
+            stdin,
+            receiver,
+
+            next_id: 0,
+            pending_requests: HashMap::new(),
+            queued: Vec::new(),
+            initialized: false,
+
+            sync_kind: SyncKind::None,
+            encoding: PositionEncoding::Utf16,
+            capabilities: Value::Null,
+            documents: HashMap::new(),
+            completions: None,
+        };
+
+        let params = json!({
+            "processId": std::process::id(),
+            "clientInfo": { "name": "orinfar", "version": env!("CARGO_PKG_VERSION") },
+            "rootUri": path_to_uri(root),
+            "workspaceFolders": [{ "uri": path_to_uri(root), "name": root.to_string_lossy() }],
+            "capabilities": {
+                "general": { "positionEncodings": ["utf-32", "utf-16"] },
+                "textDocument": {
+                    "synchronization": { "didSave": true, "dynamicRegistration": false },
+                    "completion": { "completionItem": { "snippetSupport": false } },
+                },
+                "window": { "showMessage": {} },
+            },
+        });
+        let id = client.next_id();
+        client.pending_requests.insert(id, "initialize".into());
+        write_message(
+            &mut client.stdin,
+            &json!({ "jsonrpc": "2.0", "id": id, "method": "initialize", "params": params }),
+        )?;
+
+        Ok(client)
+    }
+
+    const fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn send(&mut self, message: Value) -> Result<()> {
+        if !self.initialized {
+            self.queued.push(message);
+            return Ok(());
+        }
+
+        write_message(&mut self.stdin, &message)
+    }
+
+    /// Sends a request, whose response will be returned by `poll` once it arrives
+    ///
+    /// # Returns
+    /// The id of the request
+    pub fn request(&mut self, method: &str, params: &Value) -> Result<i64> {
+        let id = self.next_id();
+        self.pending_requests.insert(id, method.to_string());
+
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+        Ok(id)
+    }
+
+    /// Whether a request for `method` has been sent and not yet responded to
+    pub fn is_pending(&self, method: &str) -> bool {
+        self.pending_requests
+            .values()
+            .any(|pending| pending == method)
+    }
+
+    /// Stops waiting for the response to the request `id`, so that it's dropped if it comes
+    pub fn forget(&mut self, id: i64) {
+        self.pending_requests.remove(&id);
+    }
+
+    pub fn notify(&mut self, method: &str, params: &Value) -> Result<()> {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn respond(&mut self, id: &Value, result: Result<Value, (i64, &str)>) -> Result<()> {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+
+        // NOTE
+        // Responses can't be queued, since the server could be waiting on them to initialize
+        write_message(&mut self.stdin, &message)
+    }
+
+    /// Handles every message received since the last call, without blocking
+    ///
+    /// # Returns
+    /// The responses and notifications the editor should handle
+    pub fn poll(&mut self) -> Result<Vec<Incoming>> {
+        let mut incoming = Vec::new();
+
+        while let Ok(message) = self.receiver.try_recv() {
+            let method = message.get("method").and_then(Value::as_str);
+
+            match (message.get("id"), method) {
+                // Requests from the server
+                (Some(id), Some(method)) => {
+                    let result = Self::answer(method, message.get("params"));
+                    self.respond(id, result)?;
+                }
+                // Responses to our requests
+                (Some(id), None) => {
+                    let Some(method) = id.as_i64().and_then(|id| self.pending_requests.remove(&id))
+                    else {
+                        continue;
+                    };
+
+                    let result = message.get("error").map_or_else(
+                        || Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                        |error| {
+                            Err(error
+                                .get("message")
+                                .and_then(Value::as_str)
+                                .unwrap_or("Unknown error")
+                                .to_string())
+                        },
+                    );
+
+                    if method == "initialize" {
+                        self.initialize(result.as_ref().unwrap_or(&Value::Null))?;
+                    }
+
+                    incoming.push(Incoming::Response { method, result });
+                }
+                (None, Some(method)) => incoming.push(Incoming::Notification {
+                    method: method.to_string(),
+                    params: message.get("params").cloned().unwrap_or(Value::Null),
+                }),
+                (None, None) => {}
+            }
+        }
+
+        Ok(incoming)
+    }
+
+    /// The answers to requests from the server.
+    /// We don't support any of them in a meaningful way, but servers can get stuck if they're
+    /// not answered.
+    fn answer(method: &str, params: Option<&Value>) -> Result<Value, (i64, &'static str)> {
+        match method {
+            "workspace/configuration" => {
+                let items = params
+                    .and_then(|params| params.get("items"))
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len);
+
+                Ok(Value::Array(vec![Value::Null; items]))
+            }
+            "client/registerCapability"
+            | "client/unregisterCapability"
+            | "window/workDoneProgress/create"
+            | "window/showMessageRequest" => Ok(Value::Null),
+            _ => Err((-32601, "Method not supported by orinfar")),
+        }
+    }
+
+    fn initialize(&mut self, result: &Value) -> Result<()> {
+        let capabilities = result.get("capabilities").cloned().unwrap_or(Value::Null);
+
+        self.encoding = PositionEncoding::from_name(
+            capabilities.get("positionEncoding").and_then(Value::as_str),
+        );
+        self.sync_kind = SyncKind::from_capability(capabilities.get("textDocumentSync"));
+        self.capabilities = capabilities;
+        self.initialized = true;
+
+        write_message(
+            &mut self.stdin,
+            &json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        )?;
+
+        for message in std::mem::take(&mut self.queued) {
+            write_message(&mut self.stdin, &message)?;
+        }
+
+        Ok(())
+    }
+
+    /// Asks the server to shut down and exit, killing it if it takes too long
+    pub fn shutdown(&mut self) {
+        if self.initialized && self.request("shutdown", &Value::Null).is_ok() {
+            let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+
+            // NOTE
+            // We have to wait for the response before sending `exit`
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                match self.receiver.recv_timeout(timeout) {
+                    Ok(message) if message.get("method").is_none() => {
+                        let is_shutdown = message
+                            .get("id")
+                            .and_then(Value::as_i64)
+                            .and_then(|id| self.pending_requests.get(&id))
+                            .is_some_and(|method| method == "shutdown");
+                        if is_shutdown {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            let _ = self.notify("exit", &Value::Null);
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.process.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
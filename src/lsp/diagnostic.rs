@@ -0,0 +1,102 @@
+use crate::{lsp::document::PositionEncoding, theme::HLGroup};
+use ropey::Rope;
+use serde_json::Value;
+
+/// How serious a `Diagnostic` is, ordered from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl DiagnosticSeverity {
+    /// Diagnostics without a severity are treated as errors
+    // https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#diagnosticSeverity
+    const fn from_lsp(severity: Option<u64>) -> Self {
+        match severity {
+            Some(2) => Self::Warning,
+            Some(3) => Self::Information,
+            Some(4) => Self::Hint,
+            _ => Self::Error,
+        }
+    }
+
+    pub const fn group(self) -> HLGroup {
+        match self {
+            Self::Error => HLGroup::DiagnosticError,
+            Self::Warning => HLGroup::DiagnosticWarning,
+            Self::Information => HLGroup::DiagnosticInfo,
+            Self::Hint => HLGroup::DiagnosticHint,
+        }
+    }
+
+    /// The character drawn in the gutter next to lines with a diagnostic
+    pub const fn sign(self) -> char {
+        match self {
+            Self::Error => 'E',
+            Self::Warning => 'W',
+            Self::Information => 'I',
+            Self::Hint => 'H',
+        }
+    }
+}
+
+/// A problem reported by a language server, covering the chars `start..end` of the buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub start: usize,
+    pub end: usize,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Converts an LSP `Diagnostic`, whose range refers to `rope`
+    pub fn from_lsp(diagnostic: &Value, rope: &Rope, encoding: PositionEncoding) -> Option<Self> {
+        let start = encoding.char_idx(rope, diagnostic.pointer("/range/start")?)?;
+        let end = encoding.char_idx(rope, diagnostic.pointer("/range/end")?)?;
+
+        let message = diagnostic.get("message")?.as_str()?;
+        let message = diagnostic
+            .get("source")
+            .and_then(Value::as_str)
+            .map_or_else(
+                || message.to_string(),
+                |source| format!("{source}: {message}"),
+            );
+
+        Some(Self {
+            start,
+            end: usize::max(start, end),
+            severity: DiagnosticSeverity::from_lsp(
+                diagnostic.get("severity").and_then(Value::as_u64),
+            ),
+            message,
+        })
+    }
+
+    /// Keeps the diagnostic over the same text after the chars `prefix..old_end` are replaced
+    /// by the chars `prefix..new_end`
+    pub fn shift(&mut self, prefix: usize, old_end: usize, new_end: usize) {
+        let shift = |idx: usize| {
+            if idx >= old_end {
+                idx - old_end + new_end
+            } else if idx > prefix {
+                usize::min(idx, new_end)
+            } else {
+                idx
+            }
+        };
+
+        self.start = shift(self.start);
+        self.end = shift(self.end);
+    }
+
+    /// Whether the diagnostic should be shown on the `line_start..line_end` chars of a line.
+    /// Empty diagnostics are shown on the character they're at.
+    pub const fn on_line(&self, line_start: usize, line_end: usize) -> bool {
+        self.start <= line_end && (self.end > line_start || self.start >= line_start)
+    }
+}
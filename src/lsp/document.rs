@@ -0,0 +1,231 @@
+use crate::lsp::diagnostic::Diagnostic;
+use ropey::Rope;
+use serde_json::{Value, json};
+
+/// How the `character` of an LSP `Position` is counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Servers that don't say which encoding they use are using UTF-16
+    pub fn from_name(name: Option<&str>) -> Self {
+        match name {
+            Some("utf-8") => Self::Utf8,
+            Some("utf-32") => Self::Utf32,
+            _ => Self::Utf16,
+        }
+    }
+
+    /// Converts a char index into `rope` into an LSP `Position`
+    pub fn position(self, rope: &Rope, char_idx: usize) -> Value {
+        let line = rope.char_to_line(char_idx);
+        let line_start = rope.line_to_char(line);
+
+        let character = match self {
+            Self::Utf8 => rope.char_to_byte(char_idx) - rope.char_to_byte(line_start),
+            Self::Utf16 => rope.char_to_utf16_cu(char_idx) - rope.char_to_utf16_cu(line_start),
+            Self::Utf32 => char_idx - line_start,
+        };
+
+        json!({ "line": line, "character": character })
+    }
+
+    /// Converts an LSP `Position` into a char index into `rope`. Positions past the end of a
+    /// line or of the rope are clamped to the end.
+    pub fn char_idx(self, rope: &Rope, position: &Value) -> Option<usize> {
+        let line = usize::try_from(position.get("line")?.as_u64()?).ok()?;
+        let character = usize::try_from(position.get("character")?.as_u64()?).ok()?;
+
+        if line >= rope.len_lines() {
+            return Some(rope.len_chars());
+        }
+
+        let line_start = rope.line_to_char(line);
+        let line_slice = rope.line(line);
+
+        let offset = match self {
+            Self::Utf8 => line_slice.byte_to_char(usize::min(character, line_slice.len_bytes())),
+            Self::Utf16 => {
+                line_slice.utf16_cu_to_char(usize::min(character, line_slice.len_utf16_cu()))
+            }
+            Self::Utf32 => usize::min(character, line_slice.len_chars()),
+        };
+
+        Some(line_start + offset)
+    }
+}
+
+/// How the server wants documents to be synced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncKind {
+    None,
+    Full,
+    Incremental,
+}
+
+impl SyncKind {
+    /// Reads the `textDocumentSync` server capability, which is either a number or an object
+    pub fn from_capability(capability: Option<&Value>) -> Self {
+        let kind = capability
+            .and_then(|sync| sync.get("change").or(Some(sync)))
+            .and_then(Value::as_u64);
+
+        match kind {
+            Some(1) => Self::Full,
+            Some(2) => Self::Incremental,
+            _ => Self::None,
+        }
+    }
+}
+
+/// A document opened with a language server, along with the text it was last sent
+pub struct Document {
+    pub uri: String,
+    pub version: i32,
+    text: Rope,
+    /// The diagnostics last published for the document, kept over the same text as it's edited
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    pub const fn new(uri: String, text: Rope) -> Self {
+        Self {
+            uri,
+            version: 0,
+            text,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub const fn text(&self) -> &Rope {
+        &self.text
+    }
+
+    /// Updates the document to `new`, returning the `TextDocumentContentChangeEvent` describing the
+    /// change, or `None` if nothing changed.
+    ///
+    /// Incremental changes are a single range covering everything between the common prefix and
+    /// common suffix of the old and new text, which is exact for the single edits a keypress makes.
+    pub fn change(
+        &mut self,
+        new: &Rope,
+        sync_kind: SyncKind,
+        encoding: PositionEncoding,
+    ) -> Option<Value> {
+        let old = &self.text;
+        let (old_len, new_len) = (old.len_chars(), new.len_chars());
+
+        let prefix = old
+            .chars()
+            .zip(new.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if prefix == old_len && prefix == new_len {
+            return None;
+        }
+
+        let suffix = old
+            .chars_at(old_len)
+            .reversed()
+            .zip(new.chars_at(new_len).reversed())
+            .take(usize::min(old_len, new_len) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let change = match sync_kind {
+            SyncKind::Incremental => json!({
+                "range": {
+                    "start": encoding.position(old, prefix),
+                    "end": encoding.position(old, old_len - suffix),
+                },
+                "text": new.slice(prefix..new_len - suffix).to_string(),
+            }),
+            SyncKind::Full | SyncKind::None => json!({ "text": new.to_string() }),
+        };
+
+        for diagnostic in &mut self.diagnostics {
+            diagnostic.shift(prefix, old_len - suffix, new_len - suffix);
+        }
+
+        self.text = new.clone();
+        self.version += 1;
+
+        Some(change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The change from `old` to `new` synced incrementally with UTF-32 positions
+    fn change(old: &str, new: &str) -> Option<Value> {
+        let mut document = Document::new("file:///a".into(), Rope::from_str(old));
+        document.change(
+            &Rope::from_str(new),
+            SyncKind::Incremental,
+            PositionEncoding::Utf32,
+        )
+    }
+
+    fn range(start: (usize, usize), end: (usize, usize), text: &str) -> Value {
+        json!({
+            "range": {
+                "start": { "line": start.0, "character": start.1 },
+                "end": { "line": end.0, "character": end.1 },
+            },
+            "text": text,
+        })
+    }
+
+    #[test]
+    fn changes_cover_what_is_between_the_common_prefix_and_suffix() {
+        assert_eq!(change("abc", "abc"), None);
+        assert_eq!(change("abc", "aXbc"), Some(range((0, 1), (0, 1), "X")));
+        assert_eq!(change("abc", "ac"), Some(range((0, 1), (0, 2), "")));
+        assert_eq!(change("abc", "aXYc"), Some(range((0, 1), (0, 2), "XY")));
+        assert_eq!(change("", "a\n"), Some(range((0, 0), (0, 0), "a\n")));
+        // The `t` of `two` is kept, since `three` starts with it
+        assert_eq!(
+            change("one\ntwo\nthree\n", "one\nthree\n"),
+            Some(range((1, 1), (2, 1), ""))
+        );
+    }
+
+    #[test]
+    fn prefix_and_suffix_do_not_overlap() {
+        // The prefix takes all of "aaa", so the suffix can't take any of it as well
+        assert_eq!(change("aaa", "aaaa"), Some(range((0, 3), (0, 3), "a")));
+        assert_eq!(change("abab", "ab"), Some(range((0, 2), (0, 4), "")));
+    }
+
+    #[test]
+    fn changes_are_positioned_in_the_servers_encoding() {
+        let mut document = Document::new("file:///a".into(), Rope::from_str("😀a"));
+        let change = document.change(
+            &Rope::from_str("😀ba"),
+            SyncKind::Incremental,
+            PositionEncoding::Utf16,
+        );
+
+        assert_eq!(change, Some(range((0, 2), (0, 2), "b")));
+    }
+
+    #[test]
+    fn full_sync_sends_the_whole_text_and_bumps_the_version() {
+        let mut document = Document::new("file:///a".into(), Rope::from_str("a"));
+        let change = document.change(
+            &Rope::from_str("ab"),
+            SyncKind::Full,
+            PositionEncoding::Utf32,
+        );
+
+        assert_eq!(change, Some(json!({ "text": "ab" })));
+        assert_eq!(document.version, 1);
+        assert_eq!(document.text(), &Rope::from_str("ab"));
+    }
+}
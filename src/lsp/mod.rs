@@ -0,0 +1,603 @@
+use crate::{
+    config,
+    lsp::{
+        client::{Incoming, LspClient},
+        document::{Document, SyncKind},
+    },
+    message::Messages,
+    undo::UndoTree,
+    view::View,
+};
+use anyhow::Result;
+use ropey::Rope;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+mod client;
+mod diagnostic;
+mod document;
+mod navigation;
+mod transport;
+
+pub use diagnostic::{Diagnostic, DiagnosticSeverity};
+
+#[derive(Deserialize, Default)]
+struct LspConfig {
+    /// Commands to start language servers with, by language id, overriding the defaults in
+    /// `OrinLanguage`. An empty command disables the server for that language.
+    #[serde(default)]
+    servers: HashMap<String, Vec<String>>,
+}
+
+/// Every running language server, at most one per language
+pub struct Lsp {
+    config: LspConfig,
+    root: PathBuf,
+    clients: HashMap<&'static str, LspClient>,
+    /// Languages whose servers couldn't be started or have died, so we don't keep retrying
+    failed: HashSet<&'static str>,
+    /// Whether servers are started at all, which they aren't if the config couldn't be loaded
+    enabled: bool,
+}
+
+impl Lsp {
+    /// Reads the language server config from `$XDG_CONFIG_HOME/orinfar/lsp.toml`, if there is
+    /// one. Servers aren't started until a buffer in their language is opened.
+    ///
+    /// # Errors
+    /// - If the config file can't be read or is malformed
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            config: config::load("lsp.toml")?,
+            root: std::env::current_dir()?,
+            clients: HashMap::new(),
+            failed: HashSet::new(),
+            enabled: true,
+        })
+    }
+
+    /// Never starts any language servers
+    pub fn disabled() -> Self {
+        Self {
+            config: LspConfig::default(),
+            root: PathBuf::new(),
+            clients: HashMap::new(),
+            failed: HashSet::new(),
+            enabled: false,
+        }
+    }
+
+    /// Returns the client for `language_id`, starting its server if it isn't running yet
+    fn client(
+        &mut self,
+        language_id: &'static str,
+        default_command: &[&str],
+        messages: &mut Messages,
+    ) -> Option<&mut LspClient> {
+        if !self.enabled || self.failed.contains(language_id) {
+            return None;
+        }
+
+        if !self.clients.contains_key(language_id) {
+            let command = self
+                .config
+                .servers
+                .get(language_id)
+                .cloned()
+                .unwrap_or_else(|| {
+                    default_command
+                        .iter()
+                        .map(|arg| (*arg).to_string())
+                        .collect()
+                });
+            if command.is_empty() {
+                self.failed.insert(language_id);
+                return None;
+            }
+
+            match LspClient::start(&command, &self.root) {
+                Ok(client) => {
+                    self.clients.insert(language_id, client);
+                }
+                Err(err) => {
+                    messages.warn(format!("{err:#}"));
+                    self.failed.insert(language_id);
+                    return None;
+                }
+            }
+        }
+
+        self.clients.get_mut(language_id)
+    }
+
+    /// Stops using the server for `language_id` after it fails
+    fn fail(&mut self, language_id: &'static str, err: &anyhow::Error, messages: &mut Messages) {
+        if let Some(mut client) = self.clients.remove(language_id) {
+            messages.error(format!("Language server {} failed: {err:#}", client.name));
+            client.shutdown();
+        }
+        self.failed.insert(language_id);
+    }
+
+    /// Brings each language server up to date with the buffers in `view`, opening documents for
+    /// newly attached buffers, sending the changes to edited ones, and closing ones which are no
+    /// longer shown in any view box. The view boxes are then given the diagnostics of their
+    /// documents.
+    pub fn sync(&mut self, view: &mut View, messages: &mut Messages) {
+        let mut shown: HashSet<(&'static str, PathBuf)> = HashSet::new();
+
+        for view_box in view.view_boxes() {
+            let (Some(path), Some((_, language))) = (view_box.path(), &view_box.parser) else {
+                continue;
+            };
+            let Ok(path) = std::path::absolute(path) else {
+                continue;
+            };
+
+            // NOTE
+            // A file can be open in multiple view boxes, but only the first one is synced
+            if !shown.insert((language.language_id, path.clone())) {
+                continue;
+            }
+
+            let rope = &view_box.buffer.rope;
+            if let Some(client) = self.client(language.language_id, language.server, messages)
+                && let Err(err) = sync_document(client, language.language_id, &path, rope)
+            {
+                self.fail(language.language_id, &err, messages);
+            }
+        }
+
+        let mut failures = Vec::new();
+        for (language_id, client) in &mut self.clients {
+            let closed: Vec<PathBuf> = client
+                .documents
+                .keys()
+                .filter(|path| !shown.contains(&(*language_id, (*path).clone())))
+                .cloned()
+                .collect();
+
+            for path in closed {
+                if let Some(document) = client.documents.remove(&path) {
+                    let params = json!({ "textDocument": { "uri": document.uri } });
+                    if let Err(err) = client.notify("textDocument/didClose", &params) {
+                        failures.push((*language_id, err));
+                        break;
+                    }
+                }
+            }
+        }
+
+        for (language_id, err) in failures {
+            self.fail(language_id, &err, messages);
+        }
+
+        self.apply_diagnostics(view);
+    }
+
+    /// Gives each view box the diagnostics published for its file
+    fn apply_diagnostics(&self, view: &mut View) {
+        for view_box in view.view_boxes_mut() {
+            let diagnostics = view_box
+                .path()
+                .and_then(|path| std::path::absolute(path).ok())
+                .and_then(|path| {
+                    self.clients
+                        .values()
+                        .find_map(|client| client.documents.get(&path))
+                })
+                .map(|document| document.diagnostics.clone())
+                .unwrap_or_default();
+
+            view_box.set_diagnostics(diagnostics);
+        }
+    }
+
+    /// Tells the server that `path` was written
+    pub fn did_save(&mut self, path: &Path, messages: &mut Messages) {
+        let Ok(path) = std::path::absolute(path) else {
+            return;
+        };
+
+        let mut failure = None;
+        for (language_id, client) in &mut self.clients {
+            let Some(document) = client.documents.get(&path) else {
+                continue;
+            };
+
+            let include_text = client
+                .capabilities
+                .pointer("/textDocumentSync/save/includeText")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+
+            let mut params = json!({ "textDocument": { "uri": document.uri } });
+            if include_text {
+                params["text"] = Value::String(document.text().to_string());
+            }
+            if let Err(err) = client.notify("textDocument/didSave", &params) {
+                failure = Some((*language_id, err));
+            }
+        }
+
+        if let Some((language_id, err)) = failure {
+            self.fail(language_id, &err, messages);
+        }
+    }
+
+    /// Handles everything the servers have sent since the last call, without blocking
+    ///
+    /// # Returns
+    /// Whether anything changed which needs to be redrawn
+    pub fn handle_messages(
+        &mut self,
+        view: &mut View,
+        undo_tree: &mut UndoTree,
+        messages: &mut Messages,
+    ) -> bool {
+        let mut redraw = false;
+        let mut failures = Vec::new();
+
+        for (language_id, client) in &mut self.clients {
+            let incoming = match client.poll() {
+                Ok(incoming) => incoming,
+                Err(err) => {
+                    failures.push((*language_id, err));
+                    continue;
+                }
+            };
+
+            for incoming in incoming {
+                redraw |= handle_incoming(client, incoming, view, undo_tree, messages);
+            }
+        }
+
+        for (language_id, err) in failures {
+            self.fail(language_id, &err, messages);
+        }
+
+        if redraw {
+            self.apply_diagnostics(view);
+        }
+
+        redraw
+    }
+
+    pub fn shutdown(&mut self) {
+        self.clients.values_mut().for_each(LspClient::shutdown);
+        self.clients.clear();
+    }
+}
+
+fn sync_document(
+    client: &mut LspClient,
+    language_id: &str,
+    path: &Path,
+    rope: &Rope,
+) -> Result<()> {
+    let Some(document) = client.documents.get_mut(path) else {
+        let document = Document::new(path_to_uri(path), rope.clone());
+        let params = json!({
+            "textDocument": {
+                "uri": document.uri,
+                "languageId": language_id,
+                "version": document.version,
+                "text": rope.to_string(),
+            }
+        });
+
+        client.documents.insert(path.to_path_buf(), document);
+        return client.notify("textDocument/didOpen", &params);
+    };
+
+    if client.sync_kind == SyncKind::None {
+        return Ok(());
+    }
+
+    let Some(change) = document.change(rope, client.sync_kind, client.encoding) else {
+        return Ok(());
+    };
+
+    let params = json!({
+        "textDocument": { "uri": document.uri, "version": document.version },
+        "contentChanges": [change],
+    });
+    client.notify("textDocument/didChange", &params)
+}
+
+/// # Returns
+/// Whether anything changed which needs to be redrawn
+fn handle_incoming(
+    client: &mut LspClient,
+    incoming: Incoming,
+    view: &mut View,
+    undo_tree: &mut UndoTree,
+    messages: &mut Messages,
+) -> bool {
+    let server = &client.name;
+
+    match incoming {
+        Incoming::Response {
+            method,
+            result: Err(err),
+        } => {
+            messages.error(format!("{server}: {method} failed: {err}"));
+            true
+        }
+        Incoming::Response {
+            method,
+            result: Ok(result),
+        } => navigation::handle_response(client, &method, &result, view, undo_tree, messages),
+        Incoming::Notification { method, params } if method == "window/showMessage" => {
+            let text = params.get("message").and_then(Value::as_str).unwrap_or("");
+            let text = format!("{server}: {text}");
+
+            // https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#messageType
+            match params.get("type").and_then(Value::as_u64) {
+                Some(1) => messages.error(text),
+                Some(2) => messages.warn(text),
+                _ => messages.info(text),
+            }
+            true
+        }
+        Incoming::Notification { method, params } if method == "window/logMessage" => {
+            log!(
+                "{server}: {}",
+                params.get("message").unwrap_or(&Value::Null)
+            );
+            false
+        }
+        Incoming::Notification { method, params }
+            if method == "textDocument/publishDiagnostics" =>
+        {
+            publish_diagnostics(client, &params)
+        }
+        Incoming::Notification { .. } => false,
+    }
+}
+
+/// Replaces the diagnostics of the document they were published for
+///
+/// # Returns
+/// Whether the document is open
+fn publish_diagnostics(client: &mut LspClient, params: &Value) -> bool {
+    let Some(uri) = params.get("uri").and_then(Value::as_str) else {
+        return false;
+    };
+    let encoding = client.encoding;
+    let Some(document) = client
+        .documents
+        .values_mut()
+        .find(|document| document.uri == uri)
+    else {
+        return false;
+    };
+
+    // NOTE
+    // The ranges are converted using the text the server was last sent, which is what they refer
+    // to unless the server is lagging behind. From then on they follow the edits made to it.
+    let diagnostics = params
+        .get("diagnostics")
+        .and_then(Value::as_array)
+        .map(|diagnostics| {
+            diagnostics
+                .iter()
+                .filter_map(|diagnostic| {
+                    Diagnostic::from_lsp(diagnostic, document.text(), encoding)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    document.diagnostics = diagnostics;
+    true
+}
+
+/// Converts an absolute path into a `file://` URI, percent-encoding anything that isn't allowed
+/// in one
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let mut uri = String::from("file://");
+
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+
+    uri
+}
+
+/// Converts a `file://` URI back into a path, or `None` for any other kind of URI
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut path = Vec::with_capacity(encoded.len());
+
+    let mut i = 0;
+    while i < encoded.len() {
+        let decoded = (encoded[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        if let Some(byte) = decoded {
+            path.push(byte);
+            i += 3;
+        } else {
+            path.push(encoded[i]);
+            i += 1;
+        }
+    }
+
+    Some(PathBuf::from(String::from_utf8(path).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::document::PositionEncoding;
+    use std::{
+        process::Command,
+        sync::OnceLock,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    /// How long to wait for the fake server to send something
+    const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Builds `examples/fake_lsp.rs`, returning the path to its executable
+    fn fake_lsp() -> &'static str {
+        static EXECUTABLE: OnceLock<String> = OnceLock::new();
+
+        EXECUTABLE.get_or_init(|| {
+            let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+            let output = Command::new(cargo)
+                .args(["build", "--example", "fake_lsp", "--message-format=json"])
+                .current_dir(env!("CARGO_MANIFEST_DIR"))
+                .output()
+                .expect("Cargo runs");
+
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+                .filter(|message| message.pointer("/target/name") == Some(&json!("fake_lsp")))
+                .find_map(|message| message["executable"].as_str().map(String::from))
+                .expect("fake_lsp builds")
+        })
+    }
+
+    fn start() -> LspClient {
+        LspClient::start(&[fake_lsp().to_string()], &std::env::temp_dir()).expect("Started")
+    }
+
+    /// Polls `client` until it receives something `f` picks out
+    fn wait_for<T>(client: &mut LspClient, mut f: impl FnMut(Incoming) -> Option<T>) -> T {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+
+        while Instant::now() < deadline {
+            for incoming in client.poll().expect("Polled") {
+                if let Some(found) = f(incoming) {
+                    return found;
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        panic!("The server didn't send what was waited for");
+    }
+
+    fn diagnostics(client: &mut LspClient) -> Value {
+        wait_for(client, |incoming| match incoming {
+            Incoming::Notification { method, params }
+                if method == "textDocument/publishDiagnostics" =>
+            {
+                Some(params["diagnostics"].clone())
+            }
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn initializes_with_the_servers_capabilities() {
+        let mut client = start();
+
+        wait_for(&mut client, |incoming| match incoming {
+            Incoming::Response { method, result } if method == "initialize" => Some(result),
+            _ => None,
+        })
+        .expect("Initialized");
+
+        assert_eq!(client.sync_kind, SyncKind::Incremental);
+        assert_eq!(client.encoding, PositionEncoding::Utf32);
+        client.shutdown();
+    }
+
+    #[test]
+    fn syncs_changes_incrementally_and_gets_diagnostics() {
+        let mut client = start();
+        let path = std::env::temp_dir().join("orinfar-fake-lsp.c");
+
+        sync_document(&mut client, "c", &path, &Rope::from_str("int a;\n")).expect("Opened");
+        assert_eq!(diagnostics(&mut client), json!([]));
+
+        sync_document(
+            &mut client,
+            "c",
+            &path,
+            &Rope::from_str("int error;\nint a;\n"),
+        )
+        .expect("Changed");
+        let diagnostics = diagnostics(&mut client);
+        assert_eq!(diagnostics.as_array().map(Vec::len), Some(1));
+        assert_eq!(diagnostics[0]["severity"], 1);
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({
+                "start": { "line": 0, "character": 4 },
+                "end": { "line": 0, "character": 9 },
+            })
+        );
+
+        // The server reports the size of its copy when it's saved, which only matches if the
+        // changes were applied where they were meant to be
+        let uri = path_to_uri(&path);
+        client
+            .notify(
+                "textDocument/didSave",
+                &json!({ "textDocument": { "uri": uri } }),
+            )
+            .expect("Saved");
+        let message = wait_for(&mut client, |incoming| match incoming {
+            Incoming::Notification { method, params } if method == "window/showMessage" => {
+                params["message"].as_str().map(String::from)
+            }
+            _ => None,
+        });
+        assert_eq!(message, format!("{uri}: 2 lines, 18 chars"));
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn responses_to_forgotten_requests_are_dropped() {
+        let mut client = start();
+        let path = std::env::temp_dir().join("orinfar-fake-lsp-forget.c");
+        sync_document(&mut client, "c", &path, &Rope::from_str("a \n")).expect("Opened");
+        diagnostics(&mut client);
+
+        let uri = path_to_uri(&path);
+        let params = json!({
+            "textDocument": { "uri": uri },
+            "options": { "tabSize": 4, "insertSpaces": true },
+        });
+        let id = client
+            .request("textDocument/formatting", &params)
+            .expect("Requested");
+        client.forget(id);
+
+        // The server answers in order, so the response would come before the message
+        client
+            .notify(
+                "textDocument/didSave",
+                &json!({ "textDocument": { "uri": uri } }),
+            )
+            .expect("Saved");
+        let response = wait_for(&mut client, |incoming| match incoming {
+            Incoming::Response { method, .. } => Some(Some(method)),
+            Incoming::Notification { method, .. } if method == "window/showMessage" => Some(None),
+            Incoming::Notification { .. } => None,
+        });
+        assert_eq!(response, None);
+
+        client.shutdown();
+    }
+}
@@ -0,0 +1,742 @@
+use crate::{
+    buffer::Buffer,
+    completion::{CompletionItem, word_start},
+    lsp::{Lsp, client::LspClient, document::PositionEncoding, uri_to_path},
+    message::Messages,
+    meta_command::{attach_buffer, show_in_split},
+    popup::Popup,
+    undo::{Action, UndoTree},
+    view::View,
+};
+use anyhow::{Context, Result, bail};
+use ropey::Rope;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// How long to wait for a language server to format a buffer before giving up
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(2);
+
+impl Lsp {
+    /// The client whose server has opened the current view box's buffer, along with the path
+    /// it knows the buffer by
+    ///
+    /// # Arguments
+    /// - `capability`: The server capability saying whether `method` is supported
+    fn buffer_client(
+        &mut self,
+        view: &mut View,
+        method: &str,
+        capability: &str,
+    ) -> Result<(&mut LspClient, PathBuf)> {
+        let view_box = view.get_view_box();
+        let (Some(path), Some((_, language))) = (view_box.path(), &view_box.parser) else {
+            bail!("No language server for this buffer");
+        };
+        let path = std::path::absolute(path)?;
+
+        let Some(client) = self.clients.get_mut(language.language_id) else {
+            bail!("No language server for this buffer");
+        };
+        if !client.documents.contains_key(&path) {
+            bail!("{} hasn't opened this buffer", client.name);
+        }
+
+        match client.capabilities.get(capability) {
+            None | Some(Value::Null | Value::Bool(false)) => {
+                bail!("{} doesn't support {method}", client.name);
+            }
+            Some(_) => {}
+        }
+
+        Ok((client, path))
+    }
+
+    /// Sends a request about the position of the cursor in the current view box, whose response
+    /// is handled by `handle_response`
+    ///
+    /// # Arguments
+    /// - `capability`: The server capability saying whether the request is supported
+    /// - `params`: Any params other than the text document and position
+    fn request_at_cursor(
+        &mut self,
+        view: &mut View,
+        method: &str,
+        capability: &str,
+        mut params: Value,
+    ) -> Result<()> {
+        let (client, path) = self.buffer_client(view, method, capability)?;
+
+        let buffer = view.get_buffer();
+        params["textDocument"] = json!({ "uri": client.documents[&path].uri });
+        params["position"] = client.encoding.position(&buffer.rope, buffer.cursor);
+
+        client.request(method, &params)?;
+        Ok(())
+    }
+
+    /// Jumps to the definition of the symbol under the cursor, opening its file in the current
+    /// view box if it's in another one
+    ///
+    /// # Errors
+    /// - If there's no language server for the buffer, or it can't find definitions
+    pub fn goto_definition(&mut self, view: &mut View) -> Result<()> {
+        self.request_at_cursor(
+            view,
+            "textDocument/definition",
+            "definitionProvider",
+            json!({}),
+        )
+    }
+
+    /// Lists the references to the symbol under the cursor in a split
+    ///
+    /// # Errors
+    /// - If there's no language server for the buffer, or it can't find references
+    pub fn references(&mut self, view: &mut View) -> Result<()> {
+        self.request_at_cursor(
+            view,
+            "textDocument/references",
+            "referencesProvider",
+            json!({ "context": { "includeDeclaration": true } }),
+        )
+    }
+
+    /// Shows the documentation of the symbol under the cursor in a popup
+    ///
+    /// # Errors
+    /// - If there's no language server for the buffer, or it can't hover
+    pub fn hover(&mut self, view: &mut View) -> Result<()> {
+        self.request_at_cursor(view, "textDocument/hover", "hoverProvider", json!({}))
+    }
+
+    /// Asks for completion candidates for the word before the cursor, which are collected with
+    /// `Lsp::take_completions`
+    ///
+    /// # Errors
+    /// - If there's no language server for the buffer, or it can't complete
+    pub fn complete(&mut self, view: &mut View) -> Result<()> {
+        self.request_at_cursor(
+            view,
+            "textDocument/completion",
+            "completionProvider",
+            json!({}),
+        )
+    }
+
+    /// Takes the candidates from any completion responses since the last call
+    pub fn take_completions(&mut self) -> Option<Vec<CompletionItem>> {
+        self.clients
+            .values_mut()
+            .filter_map(|client| client.completions.take())
+            .reduce(|mut all, items| {
+                all.extend(items);
+                all
+            })
+    }
+
+    /// Whether the language server for the current view box's buffer can format it
+    pub fn can_format(&mut self, view: &mut View) -> bool {
+        self.buffer_client(
+            view,
+            "textDocument/formatting",
+            "documentFormattingProvider",
+        )
+        .is_ok()
+    }
+
+    /// Formats the current view box's buffer, waiting for the language server to respond so that
+    /// the buffer can be written straight afterwards
+    ///
+    /// # Errors
+    /// - If there's no language server for the buffer, it can't format, or it doesn't respond in
+    ///   time
+    pub fn format(
+        &mut self,
+        view: &mut View,
+        undo_tree: &mut UndoTree,
+        messages: &mut Messages,
+    ) -> Result<()> {
+        let method = "textDocument/formatting";
+        let indent = view.get_buffer().indent;
+        let (client, path) = self.buffer_client(view, method, "documentFormattingProvider")?;
+
+        let params = json!({
+            "textDocument": { "uri": client.documents[&path].uri },
+            "options": { "tabSize": indent.width, "insertSpaces": !indent.tabs },
+        });
+        let id = client.request(method, &params)?;
+
+        let name = client.name.clone();
+        let language_id = view
+            .view_box()
+            .parser
+            .as_ref()
+            .map(|(_, language)| language.language_id);
+        let deadline = Instant::now() + FORMAT_TIMEOUT;
+
+        while let Some(client) = language_id
+            .and_then(|language_id| self.clients.get_mut(language_id))
+            .filter(|client| client.is_pending(method))
+        {
+            if Instant::now() > deadline {
+                // NOTE
+                // A late response would be applied to whichever buffer is current by then, so
+                // it's dropped instead
+                client.forget(id);
+                bail!("{name} took too long to format the buffer");
+            }
+
+            self.handle_messages(view, undo_tree, messages);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        Ok(())
+    }
+
+    /// Renames the symbol under the cursor everywhere it's used
+    ///
+    /// # Errors
+    /// - If there's no language server for the buffer, or it can't rename
+    pub fn rename(&mut self, view: &mut View, new_name: &str) -> Result<()> {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            bail!("Usage: rename <new name>");
+        }
+
+        self.request_at_cursor(
+            view,
+            "textDocument/rename",
+            "renameProvider",
+            json!({ "newName": new_name }),
+        )
+    }
+}
+
+/// Handles the successful response to a request made by `Lsp::request_at_cursor`
+///
+/// # Returns
+/// Whether anything changed which needs to be redrawn
+pub fn handle_response(
+    client: &mut LspClient,
+    method: &str,
+    result: &Value,
+    view: &mut View,
+    undo_tree: &mut UndoTree,
+    messages: &mut Messages,
+) -> bool {
+    let handled = match method {
+        "textDocument/definition" => goto_location(client.encoding, result, view, messages),
+        "textDocument/references" => {
+            show_references(client.encoding, result, view, undo_tree, messages);
+            Ok(())
+        }
+        "textDocument/hover" => {
+            show_hover(result, view, messages);
+            Ok(())
+        }
+        "textDocument/rename" => {
+            apply_workspace_edit(client.encoding, result, view, undo_tree, messages)
+        }
+        "textDocument/completion" => {
+            client.completions = Some(completion_items(client.encoding, result, view));
+            Ok(())
+        }
+        "textDocument/formatting" => apply_formatting(client.encoding, result, view, undo_tree),
+        _ => return false,
+    };
+
+    if let Err(err) = handled {
+        messages.error(format!("{}: {err:#}", client.name));
+    }
+    true
+}
+
+/// Shortens `path` to be relative to the current directory, if it's inside it
+fn display_path(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .map_or_else(|| path.to_path_buf(), Path::to_path_buf)
+}
+
+/// Whether the view box's file is `path`, which is absolute
+fn shows_file(path: Option<&PathBuf>, file: &Path) -> bool {
+    path.and_then(|path| std::path::absolute(path).ok())
+        .is_some_and(|path| path == file)
+}
+
+/// Reads the `Location` or `LocationLink` (or the first of a list of them) in `result`
+fn location(result: &Value) -> Option<(PathBuf, &Value)> {
+    let location = match result {
+        Value::Array(locations) => locations.first()?,
+        location => location,
+    };
+
+    let uri = location
+        .get("uri")
+        .or_else(|| location.get("targetUri"))?
+        .as_str()?;
+    let start = location
+        .pointer("/range/start")
+        .or_else(|| location.pointer("/targetSelectionRange/start"))?;
+
+    Some((uri_to_path(uri)?, start))
+}
+
+fn goto_location(
+    encoding: PositionEncoding,
+    result: &Value,
+    view: &mut View,
+    messages: &mut Messages,
+) -> Result<()> {
+    let Some((path, start)) = location(result) else {
+        messages.warn("No definition found");
+        return Ok(());
+    };
+
+    if !shows_file(view.get_path(), &path) {
+        attach_buffer(&display_path(&path).to_string_lossy(), view);
+        view.load_file()?;
+        let _ = view.get_view_box().parse();
+    }
+
+    let buffer = view.get_buffer_mut();
+    let cursor = encoding
+        .char_idx(&buffer.rope, start)
+        .context("Malformed definition position")?;
+    buffer.set_cursor(cursor);
+
+    Ok(())
+}
+
+fn show_references(
+    encoding: PositionEncoding,
+    result: &Value,
+    view: &mut View,
+    undo_tree: &mut UndoTree,
+    messages: &mut Messages,
+) {
+    let references: Vec<(PathBuf, &Value)> = result
+        .as_array()
+        .map(|locations| locations.iter().filter_map(location).collect())
+        .unwrap_or_default();
+
+    if references.is_empty() {
+        messages.warn("No references found");
+        return;
+    }
+
+    // NOTE
+    // Files which aren't open are read from disk, but only once
+    let mut files: HashMap<PathBuf, Rope> = HashMap::new();
+    let mut contents = String::new();
+
+    for (path, start) in &references {
+        if !files.contains_key(path) {
+            let rope = view
+                .view_boxes()
+                .find(|view_box| shows_file(view_box.path(), path))
+                .map_or_else(
+                    || Rope::from(std::fs::read_to_string(path).unwrap_or_default()),
+                    |view_box| view_box.buffer.rope.clone(),
+                );
+            files.insert(path.clone(), rope);
+        }
+        let rope = &files[path];
+
+        let Some(idx) = encoding.char_idx(rope, start) else {
+            continue;
+        };
+        let line = rope.char_to_line(idx);
+        let col = idx - rope.line_to_char(line);
+        let text = rope.line(line).to_string();
+
+        let _ = writeln!(
+            contents,
+            "{}:{}:{}: {}",
+            display_path(path).to_string_lossy(),
+            line + 1,
+            col + 1,
+            text.trim_end()
+        );
+    }
+
+    show_in_split(view, &contents, undo_tree);
+    messages.info(format!("{} references", references.len()));
+}
+
+/// Reads the `contents` of a `Hover`, which can be a `MarkupContent`, a `MarkedString`, or a
+/// list of `MarkedString`s
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(hover_text)
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+        contents => contents
+            .get("value")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+fn show_hover(result: &Value, view: &mut View, messages: &mut Messages) {
+    let popup = Popup::new(&hover_text(result.get("contents").unwrap_or(&Value::Null)));
+
+    if popup.is_empty() {
+        messages.warn("No hover information");
+    } else {
+        view.popup = Some(popup);
+    }
+}
+
+/// Reads the `CompletionItem`s out of a `CompletionList` or a list of them
+fn completion_items(
+    encoding: PositionEncoding,
+    result: &Value,
+    view: &View,
+) -> Vec<CompletionItem> {
+    let items = match result {
+        Value::Array(items) => items,
+        result => match result.get("items").and_then(Value::as_array) {
+            Some(items) => items,
+            None => return vec![],
+        },
+    };
+
+    let buffer = view.get_buffer();
+    let default_start = word_start(buffer);
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let label = item.get("label").and_then(Value::as_str)?;
+            // NOTE
+            // Snippets would need their placeholders removed, so only their label is inserted
+            let is_snippet = item.get("insertTextFormat").and_then(Value::as_u64) == Some(2);
+
+            // The edit is either a `TextEdit` or an `InsertReplaceEdit`
+            let edit = item.get("textEdit");
+            let text = edit
+                .and_then(|edit| edit.get("newText"))
+                .or_else(|| item.get("insertText"))
+                .and_then(Value::as_str)
+                .filter(|_| !is_snippet)
+                .unwrap_or(label);
+            let start = edit
+                .and_then(|edit| {
+                    edit.pointer("/range/start")
+                        .or_else(|| edit.pointer("/insert/start"))
+                })
+                .and_then(|start| encoding.char_idx(&buffer.rope, start))
+                .unwrap_or(default_start);
+
+            Some(CompletionItem {
+                start,
+                text: text.to_string(),
+                source: "lsp",
+            })
+        })
+        .collect()
+}
+
+/// Reads the text edits for each file out of a `WorkspaceEdit`
+fn workspace_text_edits(
+    edit: &Value,
+    messages: &mut Messages,
+) -> Result<Vec<(PathBuf, Vec<Value>)>> {
+    let mut files = Vec::new();
+    let mut add = |uri: &str, edits: &Value| -> Result<()> {
+        let path = uri_to_path(uri).with_context(|| format!("Unsupported URI {uri}"))?;
+        let edits = edits.as_array().cloned().unwrap_or_default();
+        files.push((path, edits));
+        Ok(())
+    };
+
+    if let Some(document_changes) = edit.get("documentChanges").and_then(Value::as_array) {
+        for change in document_changes {
+            let uri = change.pointer("/textDocument/uri").and_then(Value::as_str);
+            match (uri, change.get("edits")) {
+                (Some(uri), Some(edits)) => add(uri, edits)?,
+                // Creating, renaming and deleting files
+                _ => {
+                    messages.warn("Changes to files themselves aren't supported, and were skipped");
+                }
+            }
+        }
+    } else if let Some(changes) = edit.get("changes").and_then(Value::as_object) {
+        for (uri, edits) in changes {
+            add(uri, edits)?;
+        }
+    }
+
+    Ok(files)
+}
+
+/// Converts `edits` into char ranges of `rope` and their new text, from last to first so they
+/// can be applied in order
+fn text_edits(
+    encoding: PositionEncoding,
+    rope: &Rope,
+    edits: &[Value],
+) -> Result<Vec<(usize, usize, String)>> {
+    let mut edits = edits
+        .iter()
+        .map(|edit| {
+            let start = edit
+                .pointer("/range/start")
+                .and_then(|start| encoding.char_idx(rope, start));
+            let end = edit
+                .pointer("/range/end")
+                .and_then(|end| encoding.char_idx(rope, end));
+            let text = edit.get("newText").and_then(Value::as_str);
+
+            match (start, end, text) {
+                (Some(start), Some(end), Some(text)) => {
+                    Ok((start, usize::max(start, end), text.to_string()))
+                }
+                _ => bail!("Malformed text edit {edit}"),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+    Ok(edits)
+}
+
+/// Applies `edits` to `rope`
+fn apply_text_edits(encoding: PositionEncoding, rope: &mut Rope, edits: &[Value]) -> Result<()> {
+    for (start, end, text) in text_edits(encoding, rope, edits)? {
+        rope.remove(start..end);
+        rope.insert(start, &text);
+    }
+
+    Ok(())
+}
+
+/// Applies the `TextEdit`s formatting the current view box's buffer as a minimal diff, so that
+/// only the lines which changed are touched
+fn apply_formatting(
+    encoding: PositionEncoding,
+    result: &Value,
+    view: &mut View,
+    undo_tree: &mut UndoTree,
+) -> Result<()> {
+    // NOTE
+    // `Lsp::format` waits for the response, so the buffer it's for is still the current one
+    let buffer = view.get_buffer_mut();
+    let mut rope = buffer.rope.clone();
+    apply_text_edits(
+        encoding,
+        &mut rope,
+        result.as_array().map_or(&[], Vec::as_slice),
+    )?;
+
+    if let Some(action) = buffer.apply_diff(&rope.to_string()) {
+        undo_tree.new_action(action);
+    }
+
+    Ok(())
+}
+
+/// Applies a `WorkspaceEdit` to every view box showing the files it changes, as a single
+/// `Action::Workspace`. Files which aren't open are changed in hidden buffers, so that nothing's
+/// written until `:wall`.
+fn apply_workspace_edit(
+    encoding: PositionEncoding,
+    edit: &Value,
+    view: &mut View,
+    undo_tree: &mut UndoTree,
+    messages: &mut Messages,
+) -> Result<()> {
+    let files = workspace_text_edits(edit, messages)?;
+    if files.is_empty() {
+        messages.warn("Nothing to rename");
+        return Ok(());
+    }
+
+    let mut actions = Vec::new();
+    let mut hidden = 0;
+    let mut total = 0;
+
+    for (path, edits) in &files {
+        total += edits.len();
+        let mut shown = false;
+
+        for view_box in view.view_boxes_mut() {
+            if !shows_file(view_box.path(), path) {
+                continue;
+            }
+            shown = true;
+
+            let action = edit_buffer(encoding, &mut view_box.buffer, edits)?;
+            actions.push((path.clone(), action));
+            let _ = view_box.parse();
+        }
+
+        if !shown {
+            let buffer = view.hidden_buffer(path)?;
+            let action = edit_buffer(encoding, buffer, edits)?;
+            actions.push((path.clone(), action));
+            hidden += 1;
+        }
+    }
+
+    undo_tree.new_action(Action::Workspace(actions));
+
+    messages.info(format!(
+        "Renamed {total} occurrences in {} files",
+        files.len()
+    ));
+    if hidden > 0 {
+        messages.info(format!(
+            "{hidden} files which aren't open were changed, write them with :wall"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Applies `edits` to `buffer`
+///
+/// # Returns
+/// The action which undoes them
+fn edit_buffer(encoding: PositionEncoding, buffer: &mut Buffer, edits: &[Value]) -> Result<Action> {
+    let group = text_edits(encoding, &buffer.rope, edits)?
+        .into_iter()
+        .map(|(start, end, text)| buffer.replace_range(start..end, &text))
+        .collect();
+
+    Ok(Action::Group(group))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lsp::path_to_uri, options::OptionsConfig};
+
+    /// Renames `foo` to `bar` in both files, from their positions in `int foo;\nfoo = 1;\n` and
+    /// `foo b;\n`
+    fn rename_edit(shown: &Path, hidden: &Path) -> Value {
+        let edit = |line: usize, character: usize| {
+            json!({
+                "range": {
+                    "start": { "line": line, "character": character },
+                    "end": { "line": line, "character": character + 3 },
+                },
+                "newText": "bar",
+            })
+        };
+
+        json!({
+            "changes": {
+                path_to_uri(shown): [edit(0, 4), edit(1, 0)],
+                path_to_uri(hidden): [edit(0, 0)],
+            },
+        })
+    }
+
+    #[test]
+    fn renames_are_undone_once_in_every_buffer_without_writing() {
+        let dir = std::env::temp_dir().join(format!("orinfar-rename-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Created");
+        let (shown, hidden) = (dir.join("a.c"), dir.join("b.c"));
+        std::fs::write(&shown, "int foo;\nfoo = 1;\n").expect("Written");
+        std::fs::write(&hidden, "foo b;\n").expect("Written");
+
+        // The same file is shown in two view boxes, each with its own buffer
+        let mut view = View::new(80, 24, OptionsConfig::default());
+        view.set_path(Some(shown.clone()));
+        view.load_file().expect("Loaded");
+        let split = view.split_view_box_vertical(0).expect("Split");
+        view.set_current_view_box(split);
+        view.set_path(Some(shown.clone()));
+        view.load_file().expect("Loaded");
+
+        let mut undo_tree = UndoTree::new();
+        let mut messages = Messages::new();
+        let edit = rename_edit(&shown, &hidden);
+        apply_workspace_edit(
+            PositionEncoding::Utf32,
+            &edit,
+            &mut view,
+            &mut undo_tree,
+            &mut messages,
+        )
+        .expect("Renamed");
+
+        for view_box in view.view_boxes() {
+            assert_eq!(view_box.buffer.rope.to_string(), "int bar;\nbar = 1;\n");
+        }
+        assert_eq!(view.hidden_buffers[&hidden].rope.to_string(), "bar b;\n");
+        assert_eq!(std::fs::read_to_string(&hidden).expect("Read"), "foo b;\n");
+
+        undo_tree.undo(view.get_buffer_mut());
+        let actions = undo_tree.take_workspace_undo();
+        view.undo_workspace(actions, &mut undo_tree);
+
+        for view_box in view.view_boxes() {
+            assert_eq!(view_box.buffer.rope.to_string(), "int foo;\nfoo = 1;\n");
+        }
+        assert_eq!(view.hidden_buffers[&hidden].rope.to_string(), "foo b;\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hidden_buffers_are_written_once_and_block_quitting_until_then() {
+        let dir = std::env::temp_dir().join(format!("orinfar-rename-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Created");
+        let (shown, hidden) = (dir.join("a.c"), dir.join("b.c"));
+        std::fs::write(&shown, "int foo;\nfoo = 1;\n").expect("Written");
+        std::fs::write(&hidden, "foo b;\n").expect("Written");
+
+        let mut view = View::new(80, 24, OptionsConfig::default());
+        view.set_path(Some(shown.clone()));
+        view.load_file().expect("Loaded");
+
+        let mut undo_tree = UndoTree::new();
+        let mut messages = Messages::new();
+        let edit = rename_edit(&shown, &hidden);
+        apply_workspace_edit(
+            PositionEncoding::Utf32,
+            &edit,
+            &mut view,
+            &mut undo_tree,
+            &mut messages,
+        )
+        .expect("Renamed");
+        assert!(view.check_hidden_written().is_err());
+
+        assert_eq!(
+            view.write_hidden().expect("Written"),
+            std::slice::from_ref(&hidden)
+        );
+        assert_eq!(std::fs::read_to_string(&hidden).expect("Read"), "bar b;\n");
+        assert!(view.hidden_buffers.is_empty());
+        assert!(view.check_hidden_written().is_ok());
+
+        // Writing again doesn't overwrite changes made to the file since
+        std::fs::write(&hidden, "baz b;\n").expect("Written");
+        assert!(view.write_hidden().expect("Written").is_empty());
+        assert_eq!(std::fs::read_to_string(&hidden).expect("Read"), "baz b;\n");
+
+        // Undoing reads the file back in to undo the rename in it
+        std::fs::write(&hidden, "bar b;\n").expect("Written");
+        undo_tree.undo(view.get_buffer_mut());
+        let actions = undo_tree.take_workspace_undo();
+        view.undo_workspace(actions, &mut undo_tree);
+        assert_eq!(view.hidden_buffers[&hidden].rope.to_string(), "foo b;\n");
+        assert!(view.check_hidden_written().is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+// NOTE
+// The base protocol is just a `Content-Length` header, a blank line, then that many bytes of JSON
+// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#baseProtocol
+
+/// Reads one message from a language server
+///
+/// # Returns
+/// - `None` if the server closed its end of the stream
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut content_length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = Some(value.trim().parse::<usize>()?);
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        bail!("Language server sent a message without a Content-Length");
+    };
+
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+
+    let message = serde_json::from_slice(&content).context("Language server sent invalid JSON")?;
+    Ok(Some(message))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let content = message.to_string();
+
+    write!(writer, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn read_all(stream: &str) -> Vec<Value> {
+        let mut reader = stream.as_bytes();
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut reader).expect("Valid message") {
+            messages.push(message);
+        }
+
+        messages
+    }
+
+    #[test]
+    fn reads_back_to_back_messages() {
+        let stream = "Content-Length: 7\r\n\r\n{\"a\":1}Content-Length: 2\r\n\r\n[]";
+
+        assert_eq!(read_all(stream), [json!({ "a": 1 }), json!([])]);
+    }
+
+    #[test]
+    fn reads_any_headers_in_any_case() {
+        let stream = "content-length: 2\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}";
+
+        assert_eq!(read_all(stream), [json!({})]);
+    }
+
+    #[test]
+    fn content_length_is_in_bytes() {
+        let mut written = Vec::new();
+        write_message(&mut written, &json!("é😀")).expect("Written");
+
+        assert!(written.starts_with(b"Content-Length: 8\r\n\r\n"));
+        assert_eq!(read_all(&String::from_utf8_lossy(&written)), [json!("é😀")]);
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        assert!(read_message(&mut &b"Content-Type: x\r\n\r\n{}"[..]).is_err());
+        assert!(read_message(&mut &b"Content-Length: x\r\n\r\n{}"[..]).is_err());
+        assert!(read_message(&mut &b"Content-Length: 2\r\n\r\n{"[..]).is_err());
+        assert!(read_message(&mut &b"Content-Length: 1\r\n\r\n}"[..]).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+use crate::{lsp::Lsp, message::Messages, view::View};
+use anyhow::Result;
+
+/// A command which asks the language server about the current buffer. Its result is handled
+/// when the server responds, so the command only reports failing to ask.
+pub struct LspCommand<'a> {
+    pub name: &'a str,
+    command: fn(lsp: &mut Lsp, view: &mut View) -> Result<()>,
+}
+
+impl<'a> LspCommand<'a> {
+    pub const fn new(
+        name: &'a str,
+        command: fn(lsp: &mut Lsp, view: &mut View) -> Result<()>,
+    ) -> Self {
+        LspCommand { name, command }
+    }
+
+    pub fn execute(&self, lsp: &mut Lsp, view: &mut View, messages: &mut Messages) {
+        if let Err(err) = (self.command)(lsp, view) {
+            messages.warn(format!("{err:#}"));
+        }
+    }
+}
@@ -6,24 +6,52 @@ use crate::{
         Command as Cmd, append, cut, first_row, indent, insert, insert_new_line,
         insert_new_line_above, last_row, paste, replace, set_curr_register, undo, unindent,
     },
+    completion::{CompletionSource, buffer_words, other_buffer_words, paths},
+    editor::Editor,
+    explorer::{
+        ExplorerCommand, cycle_sort, open, parent, prompt_create_dir, prompt_create_file,
+        prompt_delete, prompt_rename, toggle_hidden,
+    },
+    format::Formatter,
     global_state::GlobalState,
+    input::parse_keys,
+    keymap::Keymap,
     logging::{setup_logging_and_data, write_data},
+    lsp::Lsp,
+    lsp_command::LspCommand,
+    make::Make,
+    message::Messages,
     motion::Motion,
-    operator::{Operator, change, delete, yank},
+    named_action::{
+        NamedAction, find_file, format, quit, reset_hunk, run_make, show_hunk, show_messages,
+        stage_hunk, write_all_buffers, write_buffer,
+    },
+    operator::{Operator, change, delete, filter, yank},
+    options::OptionsConfig,
     program_loop::program_loop,
+    quickfix::{Quickfix, QuickfixCommand},
+    screen::Screen,
+    syntax_motion::SyntaxMotion,
+    syntax_text_object::{SyntaxTextObject, argument, block, comment, function, node, structure},
     text_object::{
-        TextObject, curly_braces, grav, parentheses, quotations, single_quotations, square_braces,
+        TextObject, big_word, curly_braces, grav, paragraph, parentheses, quotations, sentence,
+        single_quotations, square_braces, tag, word,
     },
+    theme::Theme,
     view::{View, cleanup, terminal_setup},
     view_command::{
         ViewCommand, center_viewbox_on_cursor, move_down_one_view_box, move_left_one_view_box,
-        move_right_one_view_box, move_up_one_view_box, split_curr_view_box_horizontal,
+        move_right_one_view_box, move_up_one_view_box, next_diagnostic, next_display_row,
+        next_hunk, prev_diagnostic, prev_display_row, prev_hunk, split_curr_view_box_horizontal,
         split_curr_view_box_vertical,
     },
 };
 use anyhow::{Result, bail};
-use crossterm::terminal::size;
-use std::sync::{LazyLock, OnceLock};
+use crossterm::{event::KeyCode, terminal::size};
+use std::{
+    io::stdout,
+    sync::{LazyLock, OnceLock},
+};
 
 #[macro_use]
 mod utility;
@@ -31,34 +59,61 @@ mod action;
 mod buffer;
 mod c;
 mod cli;
+mod columns;
 mod commands;
+mod completion;
+mod config;
 mod count;
+mod diff;
+mod editor;
+mod explorer;
 mod file_io;
+mod finder;
+mod format;
+mod git;
 mod global_state;
+mod grep;
 mod highlight;
+mod input;
+mod keymap;
 #[macro_use]
 mod logging;
 mod language;
+mod lsp;
+mod lsp_command;
+mod make;
 mod markdown;
+mod message;
 mod meta_command;
 mod mode;
 mod motion;
+mod named_action;
 mod operator;
+mod options;
 mod panic_hook;
+mod popup;
 mod program_loop;
+mod quickfix;
 mod register;
+mod screen;
+mod shell;
+mod sign;
 mod status_bar;
+mod syntax_motion;
+mod syntax_text_object;
 mod text_object;
+mod theme;
 mod tutorial;
 mod undo;
 mod view;
 mod view_box;
 mod view_command;
 mod view_node;
+mod walk;
 
 pub static DEBUG: OnceLock<bool> = OnceLock::new();
 
-pub static VIEW_COMMANDS: [ViewCommand; 8] = [
+pub static VIEW_COMMANDS: [ViewCommand; 14] = [
     ViewCommand::new("zz", center_viewbox_on_cursor),
     // View Box related
     ViewCommand::new("zd", move_down_one_view_box),
@@ -68,6 +123,61 @@ pub static VIEW_COMMANDS: [ViewCommand; 8] = [
     ViewCommand::new("zx", View::delete_curr_view_box),
     ViewCommand::new("zv", split_curr_view_box_vertical),
     ViewCommand::new("zh", split_curr_view_box_horizontal),
+    // Diagnostics
+    ViewCommand::new("]d", next_diagnostic),
+    ViewCommand::new("[d", prev_diagnostic),
+    // Git
+    ViewCommand::new("]c", next_hunk),
+    ViewCommand::new("[c", prev_hunk),
+    // Wrapped lines
+    ViewCommand::new("gj", next_display_row),
+    ViewCommand::new("gk", prev_display_row),
+];
+
+pub static LSP_COMMANDS: [LspCommand; 3] = [
+    LspCommand::new("gd", Lsp::goto_definition),
+    LspCommand::new("gr", Lsp::references),
+    LspCommand::new("K", Lsp::hover),
+];
+
+pub static NAMED_ACTIONS: [NamedAction; 10] = [
+    // Files
+    NamedAction::new("write", write_buffer),
+    NamedAction::new("write_all", write_all_buffers),
+    NamedAction::new("quit", quit),
+    NamedAction::new("format", format),
+    NamedAction::new("find_file", find_file),
+    NamedAction::new("make", run_make),
+    NamedAction::new("messages", show_messages),
+    // Git
+    NamedAction::new("show_hunk", show_hunk),
+    NamedAction::new("stage_hunk", stage_hunk),
+    NamedAction::new("reset_hunk", reset_hunk),
+];
+
+pub static QUICKFIX_COMMANDS: [QuickfixCommand; 2] = [
+    QuickfixCommand::new("]q", Quickfix::next),
+    QuickfixCommand::new("[q", Quickfix::prev),
+];
+
+pub static COMPLETION_SOURCES: [CompletionSource; 3] = [
+    CompletionSource::new("buffer", buffer_words),
+    CompletionSource::new("buffers", other_buffer_words),
+    CompletionSource::new("path", paths),
+];
+
+pub static EXPLORER_COMMANDS: [ExplorerCommand; 8] = [
+    // Navigation
+    ExplorerCommand::new(KeyCode::Enter, open),
+    ExplorerCommand::new(KeyCode::Char('-'), parent),
+    // Listing
+    ExplorerCommand::new(KeyCode::Char('s'), cycle_sort),
+    ExplorerCommand::new(KeyCode::Char('a'), toggle_hidden),
+    // Entries
+    ExplorerCommand::new(KeyCode::Char('%'), prompt_create_file),
+    ExplorerCommand::new(KeyCode::Char('d'), prompt_create_dir),
+    ExplorerCommand::new(KeyCode::Char('R'), prompt_rename),
+    ExplorerCommand::new(KeyCode::Char('D'), prompt_delete),
 ];
 
 pub static COMMANDS: [Cmd; 13] = [
@@ -78,22 +188,23 @@ pub static COMMANDS: [Cmd; 13] = [
     Cmd::new("O", insert_new_line_above),
     // Single character edit
     Cmd::new("x", cut),
-    Cmd::new("r", replace),
+    Cmd::with_argument("r", replace),
     // File Traversal
     Cmd::new("G", last_row),
     Cmd::new("gg", first_row),
     // Misc
     Cmd::new("u", undo),
     Cmd::new("p", paste),
-    Cmd::new("\"", set_curr_register),
+    Cmd::with_argument("\"", set_curr_register),
     Cmd::new(">", indent),
     Cmd::new("<", unindent),
 ];
 
-pub static OPERATORS: [Operator; 3] = [
+pub static OPERATORS: [Operator; 4] = [
     Operator::new('d', delete),
     Operator::new('y', yank),
     Operator::new('t', change),
+    Operator::new('!', filter),
 ];
 
 pub static MOTIONS: [Motion; 15] = [
@@ -110,20 +221,19 @@ pub static MOTIONS: [Motion; 15] = [
     Motion::inclusive('$', Buffer::end_of_line),
     Motion::inclusive('_', Buffer::beginning_of_line),
     // Finding operators
-    Motion::inclusive('f', Buffer::find),
-    Motion::inclusive('F', Buffer::find_back),
-    Motion::inclusive('c', Buffer::find_until),
+    Motion::with_argument('f', Buffer::find),
+    Motion::with_argument('F', Buffer::find_back),
+    Motion::with_argument('c', Buffer::find_until),
     // Paragraph operators
     Motion::inclusive('%', Buffer::next_corresponding_bracket),
     Motion::inclusive('}', Buffer::next_empty_line),
     Motion::inclusive('{', Buffer::prev_empty_line),
 ];
 
-pub static TEXT_OBJECTS: [TextObject; 12] = [
+pub static TEXT_OBJECTS: [TextObject; 15] = [
     // Parentheses
     TextObject::new("(", parentheses),
     TextObject::new(")", parentheses),
-    TextObject::new("p", parentheses),
     // Curly Braces
     TextObject::new("{", curly_braces),
     TextObject::new("}", curly_braces),
@@ -131,46 +241,126 @@ pub static TEXT_OBJECTS: [TextObject; 12] = [
     // Square Braces
     TextObject::new("[", square_braces),
     TextObject::new("]", square_braces),
-    TextObject::new("s", square_braces),
     // Quatations
     TextObject::new("\"", quotations),
     TextObject::new("\'", single_quotations),
     TextObject::new("`", grav),
+    // Prose
+    TextObject::new("w", word),
+    TextObject::new("W", big_word),
+    TextObject::new("s", sentence),
+    TextObject::new("p", paragraph),
+    // Markup
+    TextObject::new("t", tag),
+];
+
+pub static SYNTAX_MOTIONS: [SyntaxMotion; 6] = [
+    // Functions
+    SyntaxMotion::exclusive("]f", Buffer::next_function),
+    SyntaxMotion::exclusive("[f", Buffer::prev_function),
+    // Siblings
+    SyntaxMotion::exclusive("]s", Buffer::next_sibling),
+    SyntaxMotion::exclusive("[s", Buffer::prev_sibling),
+    // Parents and children
+    SyntaxMotion::exclusive("[n", Buffer::parent_node),
+    SyntaxMotion::exclusive("]n", Buffer::child_node),
+];
+
+pub static SYNTAX_TEXT_OBJECTS: [SyntaxTextObject; 6] = [
+    SyntaxTextObject::new("f", function),
+    SyntaxTextObject::new("S", structure),
+    SyntaxTextObject::new("a", argument),
+    SyntaxTextObject::new("*", comment),
+    SyntaxTextObject::new("b", block),
+    SyntaxTextObject::new("n", node),
 ];
 
 pub static ALL_NORMAL_CHARS: LazyLock<Vec<char>> = LazyLock::new(enumerate_normal_chars);
 
+/// The size of the screen (columns and rows) when keys are pressed without a terminal
+const HEADLESS_SIZE: (u16, u16) = (80, 24);
+
+/// # Errors
+/// - If the terminal can't be set up
 pub fn main() -> Result<()> {
     let (cli, path) = Cli::parse_path()?;
     if let Err(_b) = DEBUG.set(cli.debug) {
         bail!("Failed to set DEBUG variable");
     }
+    // NOTE
+    // Config errors are reported once the editor is running, using the defaults in the meantime
+    let mut messages = Messages::new();
+    let theme = config::or_default(Theme::load(&cli.theme), &mut messages);
+    let lsp = Lsp::load();
+    let formatter = config::or_default(Formatter::load(), &mut messages);
+    let make = config::or_default(Make::load(), &mut messages);
+    let keymap = config::or_default(Keymap::load(), &mut messages);
+    let options = config::or_default(OptionsConfig::load(), &mut messages);
 
-    let (cols, rows) = size()?;
-    terminal_setup(rows, cols)?;
+    // NOTE
+    // Keys given on the command line are pressed without touching the terminal, so that what
+    // they do can be checked (eg. in tests)
+    let headless = cli.keys.as_deref().map(parse_keys).transpose()?;
 
-    panic_hook::add_panic_hook(&cleanup);
+    let (cols, rows) = if headless.is_some() {
+        HEADLESS_SIZE
+    } else {
+        size()?
+    };
+    if headless.is_none() {
+        terminal_setup(rows, cols)?;
+        panic_hook::add_panic_hook(&cleanup);
+    }
 
     let data = setup_logging_and_data()?;
     // Used for not putting excluded chars in the chain
 
-    let mut view = View::new(cols, rows);
-    let mut global_state = GlobalState::new();
+    let mut view = View::new(cols, rows, options);
+    let mut global_state = GlobalState::new(theme, Lsp::disabled(), formatter, make, keymap);
+    global_state.messages = messages;
+    // NOTE
+    // Everything but the language servers still works without the LSP config, so not being able
+    // to load it is only reported
+    match lsp {
+        Ok(lsp) => global_state.lsp = lsp,
+        Err(err) => global_state
+            .messages
+            .error(format!("{err:#}, so language servers are off")),
+    }
 
-    if !data.has_opened && path.is_none() {
+    if !data.has_opened && path.is_none() && headless.is_none() {
         view.get_view_box()
             .write_welcome_screen(&mut global_state.undo_tree);
         write_data(&"has_opened", &"true");
     }
 
-    view.set_path(path);
-    view.load_file()?;
+    match path {
+        Some(path) if path.is_dir() => view.get_view_box().explore(&path)?,
+        path => {
+            view.set_path(path);
+            view.load_file()?;
+        }
+    }
 
     let _ = view.get_view_box().parse();
+    view.get_view_box().diff_git();
+    global_state.lsp.sync(&mut view, &mut global_state.messages);
+
+    let mut editor = Editor::new(global_state, view);
+
+    if let Some(keys) = headless {
+        let mut screen = Screen::new(cols, rows);
+        editor.render(&mut screen)?;
+        let _ = editor.feed(keys, &mut screen)?;
+        editor.global_state.lsp.shutdown();
+
+        println!("{screen}");
+        return Ok(());
+    }
 
-    view.render(&global_state, false)?;
+    editor.render(&mut stdout().lock())?;
 
-    program_loop(global_state, view)?;
+    program_loop(editor)?;
 
     cleanup()
 }
@@ -0,0 +1,221 @@
+use crate::{config, global_state::GlobalState, quickfix::Entry, shell, view::View};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+struct MakeConfig {
+    /// The shell command `:make` runs, which any arguments are appended to
+    #[serde(default = "default_command")]
+    command: String,
+}
+
+impl Default for MakeConfig {
+    fn default() -> Self {
+        Self {
+            command: default_command(),
+        }
+    }
+}
+
+fn default_command() -> String {
+    "make".to_string()
+}
+
+/// The build command run by `:make`, whose errors are loaded into the quickfix list
+#[derive(Default)]
+pub struct Make {
+    config: MakeConfig,
+}
+
+impl Make {
+    /// Reads the make config from `$XDG_CONFIG_HOME/orinfar/make.toml`, if there is one
+    ///
+    /// # Errors
+    /// - If the config file can't be read or is malformed
+    pub fn load() -> Result<Self> {
+        Ok(Self {
+            config: config::load("make.toml")?,
+        })
+    }
+}
+
+/// Reads the locations out of compiler output, in either of the forms
+/// - `file:line:col: message` or `file:line: message`, like gcc (and rustc with
+///   `--message-format short`)
+/// - `  --> file:line:col` on the line after an `error: message` (or `warning: message`) header,
+///   like rustc
+///
+/// Lines which don't point at a file which exists are skipped.
+pub fn parse_errors(output: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut header: Option<&str> = None;
+
+    for line in output.lines() {
+        if let Some(location) = line.trim_start().strip_prefix("--> ") {
+            if let Some((path, line, col, _)) = parse_location(location) {
+                entries.push(entry(path, line, col, header.unwrap_or_default()));
+            }
+        } else if let Some((path, line, col, message)) = parse_location(line) {
+            entries.push(entry(path, line, col, message));
+        } else if ["error", "warning", "note", "help"]
+            .iter()
+            .any(|kind| line.starts_with(kind))
+        {
+            header = Some(line);
+        }
+    }
+
+    entries
+}
+
+fn entry(path: PathBuf, line: usize, col: usize, message: &str) -> Entry {
+    Entry {
+        path,
+        line: line.saturating_sub(1),
+        col: col.saturating_sub(1),
+        text: message.trim().to_string(),
+    }
+}
+
+/// Splits `file:line:col: message` (where the column and message are optional) into its parts,
+/// with the line and column as written (one-indexed)
+fn parse_location(text: &str) -> Option<(PathBuf, usize, usize, &str)> {
+    let mut parts = text.splitn(4, ':');
+    let path = parts.next()?;
+    let line = parts.next()?.trim().parse().ok()?;
+
+    if path.is_empty() || path.starts_with(char::is_whitespace) || !Path::new(path).is_file() {
+        return None;
+    }
+
+    let (col, message) = match (parts.next(), parts.next()) {
+        (Some(col), message) if col.trim().parse::<usize>().is_ok() => {
+            (col.trim().parse().ok()?, message.unwrap_or_default())
+        }
+        // NOTE
+        // Without a column, the message was split up at its colons
+        (Some(_), _) => (1, text.splitn(3, ':').nth(2).unwrap_or_default()),
+        (None, _) => (1, ""),
+    };
+
+    Some((PathBuf::from(path), line, col, message))
+}
+
+/// Runs the make command (with `args` appended) and loads the locations in what it writes into
+/// the quickfix list
+///
+/// # Errors
+/// - If the command can't be run, or it fails without writing any locations
+pub fn make(global_state: &mut GlobalState, view: &mut View, args: &str) -> Result<()> {
+    let command = format!("{} {}", global_state.make.config.command, args.trim());
+    let command = command.trim();
+
+    let output = shell::run_shell(command, "")?;
+    let entries = parse_errors(&format!("{}\n{}", output.stdout, output.stderr));
+
+    if !entries.is_empty() {
+        let message = global_state.quickfix.set(entries, view)?;
+        global_state.messages.info(message);
+    } else if output.success {
+        global_state.messages.info(format!("{command} finished"));
+    } else {
+        output.stdout_or_error(command)?;
+    }
+
+    Ok(())
+}
+
+/// Loads the locations in the file at `path` (eg. saved compiler output) into the quickfix list
+///
+/// # Errors
+/// - If the file can't be read, or has no locations in it
+pub fn load_error_file(global_state: &mut GlobalState, view: &mut View, path: &str) -> Result<()> {
+    let path = path.trim();
+    if path.is_empty() {
+        bail!("No file given");
+    }
+
+    let output = std::fs::read_to_string(path).with_context(|| format!("Could not read {path}"))?;
+    let entries = parse_errors(&output);
+    if entries.is_empty() {
+        bail!("No locations in {path}");
+    }
+
+    let message = global_state.quickfix.set(entries, view)?;
+    global_state.messages.info(message);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE
+    // Locations are only read if their file exists, and tests run in the root of the repository
+    fn parsed(output: &str) -> Vec<(String, usize, usize, String)> {
+        parse_errors(output)
+            .into_iter()
+            .map(|entry| {
+                let path = entry.path.to_string_lossy().to_string();
+                (path, entry.line, entry.col, entry.text)
+            })
+            .collect()
+    }
+
+    fn expected(line: usize, col: usize, text: &str) -> (String, usize, usize, String) {
+        ("src/make.rs".to_string(), line, col, text.to_string())
+    }
+
+    #[test]
+    fn gcc_locations() {
+        assert_eq!(
+            parsed("src/make.rs:12:5: error: expected ';' before '}' token"),
+            [expected(11, 4, "error: expected ';' before '}' token")]
+        );
+    }
+
+    #[test]
+    fn locations_without_a_column() {
+        assert_eq!(
+            parsed("src/make.rs:3: warning: unused: x\nsrc/make.rs:4"),
+            [expected(2, 0, "warning: unused: x"), expected(3, 0, "")]
+        );
+    }
+
+    #[test]
+    fn rustc_locations_under_their_header() {
+        let output = "\
+error[E0308]: mismatched types
+  --> src/make.rs:10:3
+   |
+10 |     x
+   |     ^ expected `u8`
+
+warning: unused variable: `y`
+ --> src/make.rs:20:9
+";
+
+        assert_eq!(
+            parsed(output),
+            [
+                expected(9, 2, "error[E0308]: mismatched types"),
+                expected(19, 8, "warning: unused variable: `y`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_what_is_not_a_location() {
+        let output = "\
+missing.rs:1:1: error: no such file
+Compiling orinfar v0.0.7
+   |
+src/make.rs: error: no line
+  --> missing.rs:2:2
+";
+
+        assert_eq!(parsed(output), []);
+    }
+}
@@ -1,33 +1,35 @@
-use crate::highlight::ORANGE;
-use crossterm::style::Color;
+use crate::theme::HLGroup;
 
-pub fn md_node_to_color(
+pub fn md_node_to_group(
     node_type: &str,
     _parent_type: &str,
     last_sibiling_type: &str,
-) -> Option<Color> {
-    let color = match node_type {
+) -> Option<HLGroup> {
+    let group = match node_type {
         "inline" => match last_sibiling_type {
-            "atx_h1_marker" => Color::Red,
-            "atx_h2_marker" => ORANGE,
-            "atx_h3_marker" => Color::Yellow,
-            "atx_h4_marker" => Color::Green,
-            "atx_h5_marker" => Color::Blue,
-            "atx_h6_marker" => Color::Magenta,
+            "atx_h1_marker" => HLGroup::Heading1,
+            "atx_h2_marker" => HLGroup::Heading2,
+            "atx_h3_marker" => HLGroup::Heading3,
+            "atx_h4_marker" => HLGroup::Heading4,
+            "atx_h5_marker" => HLGroup::Heading5,
+            "atx_h6_marker" => HLGroup::Heading6,
 
             _ => return None,
         },
-        "paragraph" => Color::Grey,
+        "paragraph" => HLGroup::Text,
 
-        "atx_h1_marker" => Color::Red,
-        "atx_h2_marker" => ORANGE,
-        "atx_h3_marker" => Color::Yellow,
-        "atx_h4_marker" | "link_text" => Color::Green,
-        "atx_h5_marker" | "link_destination" | "list_marker_minus" => Color::Blue,
-        "atx_h6_marker" => Color::Magenta,
+        "atx_h1_marker" => HLGroup::Heading1,
+        "atx_h2_marker" => HLGroup::Heading2,
+        "atx_h3_marker" => HLGroup::Heading3,
+        "atx_h4_marker" => HLGroup::Heading4,
+        "atx_h5_marker" => HLGroup::Heading5,
+        "atx_h6_marker" => HLGroup::Heading6,
+        "link_text" => HLGroup::LinkText,
+        "link_destination" => HLGroup::LinkDestination,
+        "list_marker_minus" => HLGroup::ListMarker,
 
         _ => return None,
     };
 
-    Some(color)
+    Some(group)
 }
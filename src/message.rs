@@ -0,0 +1,139 @@
+use crate::theme::HLGroup;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// The highlight group the status bar uses for messages of this severity
+    pub const fn group(self) -> HLGroup {
+        match self {
+            Self::Info => HLGroup::StatusBar,
+            Self::Warning => HLGroup::WarningMessage,
+            Self::Error => HLGroup::ErrorMessage,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Info => "INFO",
+            Self::Warning => "WARNING",
+            Self::Error => "ERROR",
+        };
+        write!(f, "{name}")
+    }
+}
+
+pub struct Message {
+    pub severity: Severity,
+    pub text: String,
+}
+
+/// Messages for the user, such as errors from meta-commands.
+/// The latest message is shown in the status bar until the next keypress, and every message is
+/// kept so that `:messages` can show them later.
+pub struct Messages {
+    history: Vec<Message>,
+    showing: bool,
+}
+
+impl Messages {
+    pub const fn new() -> Self {
+        Self {
+            history: Vec::new(),
+            showing: false,
+        }
+    }
+
+    pub fn push(&mut self, severity: Severity, text: impl Into<String>) {
+        let text = text.into();
+        log!("{severity}: {text}");
+
+        self.history.push(Message { severity, text });
+        self.showing = true;
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Severity::Info, text);
+    }
+
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.push(Severity::Warning, text);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Severity::Error, text);
+    }
+
+    /// The message to show in the status bar, if any
+    pub fn current(&self) -> Option<&Message> {
+        if self.showing {
+            self.history.last()
+        } else {
+            None
+        }
+    }
+
+    /// Stops showing the current message in the status bar (it stays in the history)
+    pub const fn dismiss(&mut self) {
+        self.showing = false;
+    }
+
+    /// Every message so far, oldest first, one per line
+    pub fn history(&self) -> String {
+        self.history
+            .iter()
+            .map(|message| format!("{}: {}", message.severity, message.text))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_until_dismissed() {
+        let mut messages = Messages::new();
+        assert!(messages.current().is_none());
+
+        messages.warn("No completions");
+        let current = messages.current().expect("Showing");
+        assert_eq!(current.severity, Severity::Warning);
+        assert_eq!(current.severity.group(), HLGroup::WarningMessage);
+        assert_eq!(current.text, "No completions");
+
+        messages.dismiss();
+        assert!(messages.current().is_none());
+
+        // A new message is shown again, over any older one
+        messages.info("Written");
+        messages.error("Not an editor command: wq!!");
+        let current = messages.current().expect("Showing");
+        assert_eq!(current.severity, Severity::Error);
+        assert_eq!(current.text, "Not an editor command: wq!!");
+    }
+
+    #[test]
+    fn history_keeps_every_message_in_order() {
+        let mut messages = Messages::new();
+        assert_eq!(messages.history(), "");
+
+        messages.info("Written");
+        messages.dismiss();
+        messages.warn("No completions");
+        messages.error("Not an editor command: x");
+
+        assert_eq!(
+            messages.history(),
+            "INFO: Written\nWARNING: No completions\nERROR: Not an editor command: x"
+        );
+    }
+}
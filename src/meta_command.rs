@@ -1,10 +1,26 @@
 use crate::{
-    buffer::Buffer, global_state::GlobalState, mode::Mode, undo::UndoTree, utility::SplitOnce,
-    view::View, view_box::ViewBox, view_command::split_curr_view_box_horizontal,
+    buffer::Buffer,
+    explorer,
+    finder::Finder,
+    format::{format_buffer, format_on_write},
+    global_state::GlobalState,
+    grep::grep,
+    make::{load_error_file, make},
+    mode::Mode,
+    options,
+    popup::Popup,
+    shell,
+    theme::Theme,
+    undo::{Action, UndoTree},
+    utility::SplitOnce,
+    view::View,
 };
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use ropey::Rope;
-use std::{ops::ControlFlow, path::PathBuf};
+use std::{
+    ops::{ControlFlow, Range},
+    path::PathBuf,
+};
 
 // TODO
 // Eventually match from a list of `MatchCommand`s to make them easier to manage
@@ -16,58 +32,73 @@ pub fn match_meta_command(
     global_state: &mut GlobalState,
     view: &mut View,
 ) -> Result<ControlFlow<()>> {
-    let (command, arg): (&[char], &[char]) = global_state.status_bar[1..]
-        .split_once_a(|c| *c == ' ' || *c == '/')
-        .unwrap_or_else(|| (&global_state.status_bar[1..], &[]));
-    let (command, arg): (String, String) = (command.iter().collect(), arg.iter().collect());
+    let (range, command, arg) = split_meta_command(&global_state.status_bar[1..], view)?;
 
     let mut control = ControlFlow::Continue(());
 
     match command.as_str() {
-        "write" | "w" => view.write()?,
+        "write" | "w" => write(global_state, view)?,
+        "wall" | "wa" => write_all(global_state, view)?,
         "quit" | "q" => {
+            view.check_hidden_written()?;
+            control = ControlFlow::Break(());
+        }
+        "quit!" | "q!" => {
             control = ControlFlow::Break(());
         }
         "wq" => {
-            view.write()?;
+            write(global_state, view)?;
+            view.check_hidden_written()?;
             control = ControlFlow::Break(());
         }
 
+        "fmt" => format_buffer(global_state, view)?,
+
         "unattach" | "u" => view.set_path(None),
 
-        "load" | "l" => {
-            view.load_file()?;
-            let view_box = view.get_view_box();
-            view_box.render(false)?;
-        }
+        "load" | "l" => view.load_file()?,
 
+        "open" | "o" if std::path::Path::new(arg.trim()).is_dir() => {
+            view.get_view_box().explore(arg.trim().as_ref())?;
+        }
         "open" | "o" => {
-            attach_buffer(&arg, view.get_view_box());
+            attach_buffer(&arg, view);
             view.load_file()?;
+        }
+
+        "set" | "se" => options::set(view, &mut global_state.messages, &arg)?,
 
-            let view_box = view.get_view_box();
-            view_box.render(false)?;
+        "theme" => {
+            global_state.theme = Theme::load(arg.trim())?;
+            view.redraw_all();
         }
 
         "sub" | "s" => {
             let buffer = view.get_buffer_mut();
-            substitute_cmd(buffer, &arg, &mut global_state.undo_tree);
-        }
+            let substitutions = substitute_cmd(buffer, &arg, &mut global_state.undo_tree)?;
 
-        "dir" | "d" => 'block: {
-            if view.get_buffer().rope.len_chars() == 0 {
-                print_directories(view, &mut global_state.undo_tree)?;
-                break 'block;
+            if substitutions == 0 {
+                global_state
+                    .messages
+                    .warn(format!("Pattern not found: {arg}"));
+            } else {
+                global_state
+                    .messages
+                    .info(format!("{substitutions} substitutions"));
             }
+        }
 
-            split_curr_view_box_horizontal(view);
+        "grep" => grep_command(global_state, view, &arg)?,
+        "make" => make(global_state, view, &arg)?,
+        "cfile" | "cf" => load_error_file(global_state, view, &arg)?,
 
-            let anchor = view.cursor;
-            view.cursor = view.boxes.len() - 1;
+        "find" => global_state.finder = Some(Finder::new(arg.trim())),
 
-            print_directories(view, &mut global_state.undo_tree)?;
-            view.cursor = anchor;
-        }
+        "dir" | "d" => explore(view, &arg)?,
+        "touch" => explorer::create_file(view, &arg)?,
+        "mkdir" => explorer::create_dir(view, &arg)?,
+        "mv" => explorer::rename(view, &arg)?,
+        "rm" => explorer::remove(view, &arg)?,
 
         "reg" => {
             let registers = &mut global_state.register_handler.to_string();
@@ -77,15 +108,37 @@ pub fn match_meta_command(
                     .replace_contents(registers, &mut global_state.undo_tree);
             }
 
-            split_curr_view_box_horizontal(view);
+            if let Some(split) = view.split_view_box_horizontal(view.current_view_box()) {
+                view.get_view_box_at(split)
+                    .buffer
+                    .replace_contents(registers, &mut global_state.undo_tree);
+            }
+        }
+
+        "messages" | "mes" => {
+            let messages = global_state.messages.history();
+            show_in_split(view, &messages, &mut global_state.undo_tree);
+        }
 
-            let anchor = view.cursor;
-            view.cursor = view.boxes.len() - 1;
+        "rename" => global_state.lsp.rename(view, &arg)?,
 
-            view.get_buffer_mut()
-                .replace_contents(registers, &mut global_state.undo_tree);
+        "hunk" => view.popup = Some(Popup::new(&view.get_view_box().hunk_preview()?)),
+        "stage" => view.get_view_box().stage_hunk()?,
+        "reset" => view
+            .get_view_box()
+            .reset_hunk(&mut global_state.undo_tree)?,
 
-            view.cursor = anchor;
+        "!" => {
+            if let Some(lines) = range {
+                let buffer = view.get_buffer_mut();
+                filter_lines(buffer, lines, &arg, &mut global_state.undo_tree)?;
+            } else {
+                run_command(global_state, view, &arg)?;
+            }
+        }
+        "read" | "r" => {
+            let buffer = view.get_buffer_mut();
+            read_command(buffer, range, &arg, &mut global_state.undo_tree)?;
         }
 
         n => {
@@ -93,7 +146,7 @@ pub fn match_meta_command(
                 let buffer = view.get_buffer_mut();
                 buffer.set_row(num + 1);
             } else {
-                log!("Unknown Meta-Command: {}", n);
+                bail!("Unknown meta-command: {n}");
             }
         }
     }
@@ -104,26 +157,241 @@ pub fn match_meta_command(
     Ok(control)
 }
 
-pub fn substitute_cmd(buffer: &mut Buffer, arg: &str, undo_tree: &mut UndoTree) {
-    if arg.len() < 3 {
-        return;
+/// Splits a meta-command into its range, name and argument
+fn split_meta_command(
+    input: &[char],
+    view: &View,
+) -> Result<(Option<Range<usize>>, String, String)> {
+    let (range, rest) = match parse_range(input, view.get_buffer())? {
+        // NOTE
+        // A lone line number is the go to line meta-command, not a range
+        (_, []) => (None, input),
+        parsed => parsed,
+    };
+
+    let (command, arg): (&[char], &[char]) = if rest.first() == Some(&'!') {
+        rest.split_at(1)
+    } else {
+        rest.split_once_a(|c| *c == ' ' || *c == '/')
+            .unwrap_or((rest, &[]))
+    };
+    let (command, arg): (String, String) = (command.iter().collect(), arg.iter().collect());
+
+    if range.is_some() && !matches!(command.as_str(), "!" | "read" | "r") {
+        bail!("{command} doesn't take a range");
     }
 
-    let substitution: Vec<&str> = arg.split('/').collect();
+    Ok((range, command, arg))
+}
 
-    if substitution.len() != 2 {
-        log!(
-            "Malformed substitution meta-command: {:?}. Should be in the form: s/[orig]/[new]",
-            substitution
-        );
+/// Parses the range of lines at the start of a meta-command. Line numbers are the ones shown in
+/// the gutter.
+/// - `%`: Every line
+/// - `[address]` or `[address],[address]`, where an address is `.` (the cursor's line), `$` (the
+///   last line) or a line number, followed by any number of offsets like `+2` or `-1`
+///
+/// # Returns
+/// The lines, if there's a range, and the rest of the meta-command
+fn parse_range<'a>(
+    input: &'a [char],
+    buffer: &Buffer,
+) -> Result<(Option<Range<usize>>, &'a [char])> {
+    if input.first() == Some(&'%') {
+        return Ok((Some(0..buffer.len()), &input[1..]));
+    }
 
-        return;
+    let (Some(start), rest) = parse_address(input, buffer)? else {
+        return Ok((None, input));
+    };
+    let (end, rest) = match rest.split_first() {
+        Some((',', rest)) => match parse_address(rest, buffer)? {
+            (Some(end), rest) => (end, rest),
+            (None, _) => bail!("Missing the end of the range"),
+        },
+        _ => (start, rest),
+    };
+
+    let (start, end) = (usize::min(start, end), usize::max(start, end));
+    if end >= buffer.len() {
+        bail!("The range goes past the last line");
+    }
+
+    Ok((Some(start..end + 1), rest))
+}
+
+/// Parses a line of a range, see `parse_range`
+fn parse_address<'a>(input: &'a [char], buffer: &Buffer) -> Result<(Option<usize>, &'a [char])> {
+    let digits = |chars: &[char]| chars.iter().take_while(|c| c.is_ascii_digit()).count();
+    let number = |chars: &[char]| chars.iter().collect::<String>().parse::<usize>();
+
+    let (mut line, mut rest) = match input.first() {
+        Some('.') => (Some(buffer.get_row()), &input[1..]),
+        Some('$') => (Some(buffer.len() - 1), &input[1..]),
+        Some(c) if c.is_ascii_digit() => {
+            let (line, rest) = input.split_at(digits(input));
+            (Some(number(line)?), rest)
+        }
+        _ => (None, input),
+    };
+
+    while let Some((sign @ ('+' | '-'), after)) = rest.split_first() {
+        let (amount, after) = after.split_at(digits(after));
+        let amount = if amount.is_empty() {
+            1
+        } else {
+            number(amount)?
+        };
+        let base = line.unwrap_or_else(|| buffer.get_row());
+
+        line = Some(if *sign == '+' {
+            base + amount
+        } else {
+            base.checked_sub(amount)
+                .context("The range goes before the first line")?
+        });
+        rest = after;
+    }
+
+    Ok((line, rest))
+}
+
+/// Replaces `lines` with the output of running them through the shell command `command`, as a
+/// single action
+fn filter_lines(
+    buffer: &mut Buffer,
+    lines: Range<usize>,
+    command: &str,
+    undo_tree: &mut UndoTree,
+) -> Result<()> {
+    let start = buffer.rope.line_to_char(lines.start);
+    let end = buffer.rope.line_to_char(lines.end);
+    let input = buffer.rope.slice(start..end).to_string();
+
+    let mut output = shell::run_shell(command, &input)?.stdout_or_error(command)?;
+    // The last line of the buffer doesn't end in a newline, so its replacement shouldn't either
+    if !input.ends_with('\n') && output.ends_with('\n') {
+        output.pop();
+    }
+
+    let action = buffer.replace_range(start..end, &output);
+    buffer.set_cursor(start);
+    undo_tree.new_action(action);
+
+    Ok(())
+}
+
+/// Shows the output of the shell command `command` in a split
+fn run_command(global_state: &mut GlobalState, view: &mut View, command: &str) -> Result<()> {
+    let output = shell::run_shell(command, "")?;
+    let contents = output.stdout + &output.stderr;
+
+    if contents.trim().is_empty() {
+        global_state.messages.info(format!("{command}: no output"));
+    } else {
+        show_in_split(view, contents.trim_end(), &mut global_state.undo_tree);
+    }
+    if !output.success {
+        global_state.messages.warn(format!("{command} failed"));
+    }
+
+    Ok(())
+}
+
+/// Inserts the output of the shell command in `arg` (`!<command>`) below the last line of
+/// `range`, or failing that the cursor's line, as a single action
+fn read_command(
+    buffer: &mut Buffer,
+    range: Option<Range<usize>>,
+    arg: &str,
+    undo_tree: &mut UndoTree,
+) -> Result<()> {
+    let Some(command) = arg.trim_start().strip_prefix('!') else {
+        bail!("Usage: r !<command>");
+    };
+    let line = range.map_or_else(|| buffer.get_row(), |lines| lines.end - 1);
+
+    let output = shell::run_shell(command, "")?.stdout_or_error(command)?;
+    if output.is_empty() {
+        return Ok(());
+    }
+
+    let len = buffer.rope.len_chars();
+    let (position, text) = if line + 1 < buffer.len() {
+        (buffer.rope.line_to_char(line + 1), output)
+    } else if len > 0 && buffer.rope.char(len - 1) != '\n' {
+        // Below the last line, which doesn't end in a newline
+        (len, format!("\n{}", output.trim_end_matches('\n')))
+    } else {
+        (len, output)
+    };
+
+    buffer.rope.insert(position, &text);
+    buffer.lines_for_updating = (0..buffer.len()).map(|_| true).collect();
+    buffer.has_changed = true;
+    buffer.set_cursor(position + usize::from(text.starts_with('\n')));
+
+    undo_tree.new_action(Action::insert(position, &text));
+
+    Ok(())
+}
+
+/// Writes the current view box's buffer to its file, formatting it first if that's enabled
+pub fn write(global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    format_on_write(global_state, view);
+    view.write()?;
+    view.get_view_box().reload_git()?;
+
+    if let Some(path) = view.get_path() {
+        let message = format!("\"{}\" written", path.to_string_lossy());
+        global_state.messages.info(message);
+        global_state.lsp.did_save(path, &mut global_state.messages);
+    }
+
+    Ok(())
+}
+
+/// Writes every view box which is attached to a file, then every hidden buffer
+pub fn write_all(global_state: &mut GlobalState, view: &mut View) -> Result<()> {
+    let current = view.current_view_box();
+    let written = (0..view.view_box_count()).try_for_each(|idx| {
+        if view.get_view_box_at(idx).path().is_none() {
+            return Ok(());
+        }
+        view.set_current_view_box(idx);
+        write(global_state, view)
+    });
+    view.set_current_view_box(current);
+    written?;
+
+    let hidden = view.write_hidden()?;
+    for path in &hidden {
+        global_state.lsp.did_save(path, &mut global_state.messages);
+    }
+    if !hidden.is_empty() {
+        let message = format!("{} files which aren't open written", hidden.len());
+        global_state.messages.info(message);
+    }
+
+    Ok(())
+}
+
+/// # Returns
+/// The number of substitutions made
+pub fn substitute_cmd(buffer: &mut Buffer, arg: &str, undo_tree: &mut UndoTree) -> Result<usize> {
+    let substitution: Vec<&str> = arg.split('/').collect();
+
+    if substitution.len() != 2 || substitution[0].is_empty() {
+        bail!("Malformed substitution: s/{arg}. Should be in the form: s/[orig]/[new]");
     }
 
     let original: Vec<char> = substitution[0].chars().collect();
     let new: String = substitution[1].chars().collect();
 
     let idxs_of_substitution = buffer.find_occurences(&original);
+    if idxs_of_substitution.is_empty() {
+        return Ok(0);
+    }
+
     buffer.replace_text(
         &new,
         &original.iter().collect::<String>(),
@@ -134,30 +402,75 @@ pub fn substitute_cmd(buffer: &mut Buffer, arg: &str, undo_tree: &mut UndoTree)
 
     buffer.update_list_set(.., true);
     buffer.has_changed = true;
-}
 
-pub fn print_directories(view: &mut View, undo_tree: &mut UndoTree) -> Result<()> {
-    let path = PathBuf::from("./");
+    Ok(idxs_of_substitution.len())
+}
 
-    let dir = std::fs::read_dir(path)?;
-    let contents = dir
-        .filter_map(std::result::Result::ok)
-        .map(|item| {
-            let mut path = item.file_name().to_string_lossy().to_string();
-            path.push('\n');
-            path
-        })
-        .collect::<String>();
+/// Lists every occurence of `arg` in the project in the quickfix list
+fn grep_command(global_state: &mut GlobalState, view: &mut View, arg: &str) -> Result<()> {
+    let pattern = arg.trim();
+    if pattern.is_empty() {
+        bail!("No pattern given");
+    }
 
-    // Some issue with replacing contents that has a trailing newline
-    view.get_buffer_mut()
-        .replace_contents(&contents[0..contents.len() - 1], undo_tree);
+    let entries = grep(pattern, view);
+    if entries.is_empty() {
+        global_state
+            .messages
+            .warn(format!("Pattern not found: {pattern}"));
+    } else {
+        let message = global_state.quickfix.set(entries, view)?;
+        global_state.messages.info(message);
+    }
 
     Ok(())
 }
 
-pub fn attach_buffer(arg: &str, view_box: &mut ViewBox) {
+/// Opens an explorer on `arg`, or failing that the directory of the current file. It's shown in a
+/// new split, or in the current view box if its buffer is empty, and the cursor moves into it.
+fn explore(view: &mut View, arg: &str) -> Result<()> {
+    let dir = if arg.trim().is_empty() {
+        let view_box = view.get_view_box();
+        match (&view_box.explorer, view_box.path()) {
+            (Some(explorer), _) => explorer.dir.clone(),
+            (None, Some(path)) => path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .map_or_else(|| PathBuf::from("."), std::path::Path::to_path_buf),
+            (None, None) => PathBuf::from("."),
+        }
+    } else {
+        PathBuf::from(arg.trim())
+    };
+
+    if view.get_buffer().rope.len_chars() != 0
+        && let Some(split) = view.split_view_box_horizontal(view.current_view_box())
+    {
+        view.set_current_view_box(split);
+    }
+
+    view.get_view_box().explore(&dir)
+}
+
+/// Shows `contents` in a new split, or in the current view box if its buffer is empty
+pub fn show_in_split(view: &mut View, contents: &str, undo_tree: &mut UndoTree) {
+    if view.get_buffer().rope.len_chars() == 0 {
+        view.get_buffer_mut().replace_contents(contents, undo_tree);
+        return;
+    }
+
+    if let Some(split) = view.split_view_box_horizontal(view.current_view_box()) {
+        view.get_view_box_at(split)
+            .buffer
+            .replace_contents(contents, undo_tree);
+    }
+}
+
+pub fn attach_buffer(arg: &str, view: &mut View) {
     let path_buf = PathBuf::from(arg.trim());
+    let view_box = view.get_view_box();
+    view_box.explorer = None;
+    view_box.quickfix = false;
 
     // If we already have a file, we don't want to write the contents
     // to a new empty file
@@ -168,5 +481,120 @@ pub fn attach_buffer(arg: &str, view_box: &mut ViewBox) {
         view_box.buffer.has_changed = true;
     }
 
-    view_box.set_path(Some(path_buf));
+    view.set_path(Some(path_buf));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OptionsConfig;
+
+    fn chars(input: &str) -> Vec<char> {
+        input.chars().collect()
+    }
+
+    /// A buffer of five lines with the cursor on the third
+    fn buffer() -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.rope = Rope::from("one\ntwo\nthree\nfour\nfive");
+        buffer.set_cursor(buffer.rope.line_to_char(2));
+        buffer
+    }
+
+    fn range(input: &str) -> Result<(Option<Range<usize>>, String)> {
+        let input = chars(input);
+        parse_range(&input, &buffer()).map(|(range, rest)| (range, rest.iter().collect()))
+    }
+
+    #[test]
+    fn addresses() {
+        let buffer = buffer();
+        let address = |input: &str| {
+            let input = chars(input);
+            parse_address(&input, &buffer)
+                .map(|(line, rest)| (line, rest.iter().collect::<String>()))
+                .ok()
+        };
+
+        assert_eq!(address("."), Some((Some(2), String::new())));
+        assert_eq!(address("$!"), Some((Some(4), "!".to_string())));
+        assert_eq!(address("12r"), Some((Some(12), "r".to_string())));
+        assert_eq!(address(".+2,"), Some((Some(4), ",".to_string())));
+        assert_eq!(address("-"), Some((Some(1), String::new())));
+        assert_eq!(address("$-1-1"), Some((Some(2), String::new())));
+        assert_eq!(address("w"), Some((None, "w".to_string())));
+        assert_eq!(address("-3"), None);
+    }
+
+    #[test]
+    fn ranges() {
+        let parsed = |input: &str| range(input).ok();
+
+        assert_eq!(parsed("%!sort"), Some((Some(0..5), "!sort".to_string())));
+        assert_eq!(parsed(".!"), Some((Some(2..3), "!".to_string())));
+        assert_eq!(parsed("$r"), Some((Some(4..5), "r".to_string())));
+        assert_eq!(parsed("1,3!"), Some((Some(1..4), "!".to_string())));
+        assert_eq!(parsed(".,$!"), Some((Some(2..5), "!".to_string())));
+        assert_eq!(parsed("w"), Some((None, "w".to_string())));
+
+        // Reversed ranges are swapped around
+        assert_eq!(parsed("3,1!"), Some((Some(1..4), "!".to_string())));
+
+        assert!(range("2,5!").is_err());
+        assert!(range("9!").is_err());
+        assert!(range("1,!").is_err());
+    }
+
+    #[test]
+    fn split_into_range_name_and_argument() {
+        let mut view = View::new(80, 24, OptionsConfig::default());
+        view.get_buffer_mut().rope = Rope::from("one\ntwo\nthree");
+        let split = |input: &str| split_meta_command(&chars(input), &view);
+
+        assert_eq!(
+            split("%!sort -r").expect("Split"),
+            (Some(0..3), "!".to_string(), "sort -r".to_string())
+        );
+        assert_eq!(
+            split("w out.txt").expect("Split"),
+            (None, "w".to_string(), "out.txt".to_string())
+        );
+        assert_eq!(
+            split("s/a/b").expect("Split"),
+            (None, "s".to_string(), "a/b".to_string())
+        );
+
+        // A lone line number goes to that line
+        assert_eq!(
+            split("2").expect("Split"),
+            (None, "2".to_string(), String::new())
+        );
+
+        assert!(split("1,2w").is_err());
+        assert!(split("5!sort").is_err());
+    }
+
+    #[test]
+    fn filter_a_range_as_one_action() {
+        let mut buffer = buffer();
+        let mut undo_tree = UndoTree::default();
+
+        filter_lines(&mut buffer, 1..4, "sort", &mut undo_tree).expect("Filtered");
+        assert_eq!(buffer.rope.to_string(), "one\nfour\nthree\ntwo\nfive");
+        assert_eq!(buffer.get_row(), 1);
+
+        // The last line keeps its lack of a trailing newline
+        filter_lines(&mut buffer, 3..5, "sort -r", &mut undo_tree).expect("Filtered");
+        assert_eq!(buffer.rope.to_string(), "one\nfour\nthree\ntwo\nfive");
+        filter_lines(&mut buffer, 3..5, "sort", &mut undo_tree).expect("Filtered");
+        assert_eq!(buffer.rope.to_string(), "one\nfour\nthree\nfive\ntwo");
+
+        undo_tree.undo(&mut buffer);
+        undo_tree.undo(&mut buffer);
+        undo_tree.undo(&mut buffer);
+        assert_eq!(buffer.rope.to_string(), "one\ntwo\nthree\nfour\nfive");
+
+        assert!(filter_lines(&mut buffer, 0..1, "exit 1", &mut undo_tree).is_err());
+        assert_eq!(buffer.rope.to_string(), "one\ntwo\nthree\nfour\nfive");
+    }
 }
@@ -1,7 +1,7 @@
-use std::{fmt::Display, io::stdout};
+use std::fmt::Display;
 
 use clap::ValueEnum;
-use crossterm::{cursor::SetCursorStyle, queue};
+use crossterm::cursor::SetCursorStyle;
 
 #[derive(Clone, Debug, ValueEnum)]
 #[clap(rename_all = "kebab-case")]
@@ -14,22 +14,24 @@ pub enum Mode {
 }
 
 impl Mode {
-    pub fn insert(&mut self) {
+    pub const fn insert(&mut self) {
         *self = Self::Insert;
-        queue!(stdout(), SetCursorStyle::BlinkingBar)
-            .expect("Crossterm blinking bar command failed");
     }
 
-    pub fn normal(&mut self) {
+    pub const fn normal(&mut self) {
         *self = Self::Normal;
-        queue!(stdout(), SetCursorStyle::SteadyBlock)
-            .expect("Crossterm steady block command failed");
     }
 
-    pub fn search(&mut self) {
+    pub const fn search(&mut self) {
         *self = Self::Search;
-        queue!(stdout(), SetCursorStyle::SteadyBlock)
-            .expect("Crossterm steady block command failed");
+    }
+
+    /// The shape of the cursor while in this mode, which is set whenever the view is rendered
+    pub const fn cursor_style(&self) -> SetCursorStyle {
+        match self {
+            Self::Insert => SetCursorStyle::BlinkingBar,
+            Self::Normal | Self::Meta | Self::Search | Self::Visual => SetCursorStyle::SteadyBlock,
+        }
     }
 }
 
@@ -1,12 +1,14 @@
-use crate::{
-    buffer::Buffer,
-    utility::{is_symbol, on_next_input},
-};
-use crossterm::event::KeyCode;
+use crate::{buffer::Buffer, utility::is_symbol};
+
+enum MotionFn {
+    Plain(fn(buffer: &mut Buffer)),
+    /// Takes the character typed after the motion's name, eg. the one `f` finds
+    Argument(fn(buffer: &mut Buffer, argument: char)),
+}
 
 pub struct Motion {
     pub name: char,
-    command: fn(buffer: &mut Buffer),
+    command: MotionFn,
     pub inclusive: bool,
 }
 
@@ -14,7 +16,7 @@ impl Motion {
     pub const fn exclusive(name: char, command: fn(buffer: &mut Buffer)) -> Self {
         Self {
             name,
-            command,
+            command: MotionFn::Plain(command),
             inclusive: false,
         }
     }
@@ -22,21 +24,45 @@ impl Motion {
     pub const fn inclusive(name: char, command: fn(buffer: &mut Buffer)) -> Self {
         Self {
             name,
-            command,
+            command: MotionFn::Plain(command),
+            inclusive: true,
+        }
+    }
+
+    /// An inclusive motion which takes the character typed after it as an argument
+    pub const fn with_argument(
+        name: char,
+        command: fn(buffer: &mut Buffer, argument: char),
+    ) -> Self {
+        Self {
+            name,
+            command: MotionFn::Argument(command),
             inclusive: true,
         }
     }
 
+    pub const fn takes_argument(&self) -> bool {
+        matches!(self.command, MotionFn::Argument(_))
+    }
+
+    fn run(&self, buffer: &mut Buffer, argument: Option<char>) {
+        match (&self.command, argument) {
+            (MotionFn::Plain(command), _) => command(buffer),
+            (MotionFn::Argument(command), Some(argument)) => command(buffer, argument),
+            (MotionFn::Argument(_), None) => {}
+        }
+    }
+
     // Called when the motion should be applied directly
-    pub fn apply(&self, buffer: &mut Buffer) {
-        (self.command)(buffer);
+    pub fn apply(&self, buffer: &mut Buffer, argument: Option<char>) {
+        self.run(buffer, argument);
     }
 
     // Called when the motion is chained to an operator
     // Doesn't apply the motion to the buffer but returns where the motion would have gone
-    pub fn evaluate(&self, buffer: &Buffer) -> usize {
+    pub fn evaluate(&self, buffer: &Buffer, argument: Option<char>) -> usize {
         let mut fake_buffer = buffer.clone();
-        (self.command)(&mut fake_buffer);
+        self.run(&mut fake_buffer, argument);
 
         fake_buffer.cursor
     }
@@ -162,36 +188,25 @@ impl Buffer {
 
 // Find Single Characters
 impl Buffer {
-    fn find_generic(&mut self, key: KeyCode, traverse: impl Fn(&Self, char) -> Option<usize>) {
-        if let KeyCode::Char(target) = key
-            && let Some(position) = traverse(self, target)
-        {
+    fn find_generic(&mut self, target: char, traverse: impl Fn(&Self, char) -> Option<usize>) {
+        if let Some(position) = traverse(self, target) {
             self.set_cursor(position);
         }
     }
 
-    pub fn find(buffer: &mut Self) {
-        let find_forward =
-            |key: KeyCode, buffer: &mut Self| buffer.find_generic(key, Self::find_next);
-
-        on_next_input(buffer, find_forward).expect("Failed to get character to find");
+    pub fn find(buffer: &mut Self, target: char) {
+        buffer.find_generic(target, Self::find_next);
     }
 
-    pub fn find_until(buffer: &mut Self) {
-        let find_until = |key: KeyCode, buffer: &mut Self| {
-            buffer.find_generic(key, Self::find_next);
-            if buffer.cursor != 0 {
-                buffer.cursor -= 1;
-            }
-        };
-
-        on_next_input(buffer, find_until).expect("Failed to get character to find");
+    pub fn find_until(buffer: &mut Self, target: char) {
+        buffer.find_generic(target, Self::find_next);
+        if buffer.cursor != 0 {
+            buffer.cursor -= 1;
+        }
     }
 
-    pub fn find_back(buffer: &mut Self) {
-        let find_back = |key: KeyCode, buffer: &mut Self| buffer.find_generic(key, Self::find_prev);
-
-        on_next_input(buffer, find_back).expect("Failed to get character to find");
+    pub fn find_back(buffer: &mut Self, target: char) {
+        buffer.find_generic(target, Self::find_prev);
     }
 }
 
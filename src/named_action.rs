@@ -0,0 +1,93 @@
+use crate::{
+    finder::Finder,
+    format::format_buffer,
+    global_state::GlobalState,
+    make::make,
+    meta_command::{show_in_split, write, write_all},
+    popup::Popup,
+    view::View,
+};
+use anyhow::Result;
+use std::ops::ControlFlow;
+
+/// Something keys can be mapped to by name, rather than by the keys (or meta-command) it's bound
+/// to, eg. `"<leader>w" = { action = "write" }`
+pub struct NamedAction<'a> {
+    pub name: &'a str,
+    action: fn(global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>>,
+}
+
+impl<'a> NamedAction<'a> {
+    pub const fn new(
+        name: &'a str,
+        action: fn(global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>>,
+    ) -> Self {
+        NamedAction { name, action }
+    }
+
+    /// # Returns
+    /// `ControlFlow::Break` if the action quits the editor
+    pub fn execute(&self, global_state: &mut GlobalState, view: &mut View) -> ControlFlow<()> {
+        (self.action)(global_state, view).unwrap_or_else(|err| {
+            global_state.messages.error(format!("{err:#}"));
+            ControlFlow::Continue(())
+        })
+    }
+}
+
+pub fn write_buffer(global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    write(global_state, view)?;
+    Ok(ControlFlow::Continue(()))
+}
+
+pub fn write_all_buffers(
+    global_state: &mut GlobalState,
+    view: &mut View,
+) -> Result<ControlFlow<()>> {
+    write_all(global_state, view)?;
+    Ok(ControlFlow::Continue(()))
+}
+
+pub fn quit(_global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    view.check_hidden_written()?;
+    Ok(ControlFlow::Break(()))
+}
+
+pub fn format(global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    format_buffer(global_state, view)?;
+    Ok(ControlFlow::Continue(()))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub fn find_file(global_state: &mut GlobalState, _view: &mut View) -> Result<ControlFlow<()>> {
+    global_state.finder = Some(Finder::new(""));
+    Ok(ControlFlow::Continue(()))
+}
+
+pub fn run_make(global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    make(global_state, view, "")?;
+    Ok(ControlFlow::Continue(()))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub fn show_messages(global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    let messages = global_state.messages.history();
+    show_in_split(view, &messages, &mut global_state.undo_tree);
+    Ok(ControlFlow::Continue(()))
+}
+
+pub fn show_hunk(_global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    view.popup = Some(Popup::new(&view.get_view_box().hunk_preview()?));
+    Ok(ControlFlow::Continue(()))
+}
+
+pub fn stage_hunk(_global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    view.get_view_box().stage_hunk()?;
+    Ok(ControlFlow::Continue(()))
+}
+
+pub fn reset_hunk(global_state: &mut GlobalState, view: &mut View) -> Result<ControlFlow<()>> {
+    view.get_view_box()
+        .reset_hunk(&mut global_state.undo_tree)?;
+    Ok(ControlFlow::Continue(()))
+}
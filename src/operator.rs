@@ -3,9 +3,12 @@ use crate::{
     mode::Mode,
     motion::Motion,
     register::RegisterHandler,
-    text_object::{TextObject, TextObjectType},
+    status_bar::StatusBar,
+    syntax_motion::SyntaxMotion,
+    text_object::TOBounds,
     undo::{Action, UndoTree},
 };
+use tree_sitter::Tree;
 
 pub struct Operator {
     pub name: char,
@@ -15,6 +18,7 @@ pub struct Operator {
         register_handler: &mut RegisterHandler,
         mode: &mut Mode,
         undo_tree: &mut UndoTree,
+        status_bar: &mut StatusBar,
     ),
 }
 
@@ -27,20 +31,24 @@ impl Operator {
             register_handler: &mut RegisterHandler,
             mode: &mut Mode,
             undo_tree: &mut UndoTree,
+            status_bar: &mut StatusBar,
         ),
     ) -> Self {
         Self { name, command }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn execute_motion(
         &self,
         motion: &Motion,
+        argument: Option<char>,
         buffer: &mut Buffer,
         register_handler: &mut RegisterHandler,
         mode: &mut Mode,
         undo_tree: &mut UndoTree,
+        status_bar: &mut StatusBar,
     ) {
-        let mut end = motion.evaluate(buffer);
+        let mut end = motion.evaluate(buffer, argument);
 
         // NOTE
         // So I'm pretty sure the delete behavior thing isn't fixable
@@ -66,27 +74,62 @@ impl Operator {
             end = usize::max(end, 1) - 1;
         }
 
-        (self.command)(end, buffer, register_handler, mode, undo_tree);
+        (self.command)(end, buffer, register_handler, mode, undo_tree, status_bar);
     }
 
-    pub fn execute_text_object(
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_syntax_motion(
         &self,
-        text_object: &TextObject,
-        text_object_type: &TextObjectType,
+        motion: &SyntaxMotion,
+        tree: &Tree,
         buffer: &mut Buffer,
         register_handler: &mut RegisterHandler,
         mode: &mut Mode,
         undo_tree: &mut UndoTree,
+        status_bar: &mut StatusBar,
     ) {
-        let Some((beginning, end)) = (match text_object_type {
-            TextObjectType::Inside => text_object.inside(buffer),
-            TextObjectType::Around => text_object.around(buffer),
-        }) else {
+        let mut end = motion.evaluate(buffer, tree);
+
+        // NOTE
+        // See the note in `execute_motion`
+        if !motion.inclusive && end > buffer.cursor {
+            end = usize::max(end, 1) - 1;
+        }
+
+        (self.command)(end, buffer, register_handler, mode, undo_tree, status_bar);
+    }
+
+    /// Applies the operator over the given (inclusive) bounds, doing nothing if there are none
+    pub fn execute_bounds(
+        &self,
+        bounds: TOBounds,
+        buffer: &mut Buffer,
+        register_handler: &mut RegisterHandler,
+        mode: &mut Mode,
+        undo_tree: &mut UndoTree,
+        status_bar: &mut StatusBar,
+    ) {
+        let Some((beginning, mut end)) = bounds else {
             return;
         };
-
         buffer.cursor = beginning;
-        (self.command)(end, buffer, register_handler, mode, undo_tree);
+
+        // NOTE
+        // Linewise bounds (eg. the lines inside a block whose braces are on lines of their own)
+        // end with the newline before the next line, which changing keeps so that what's typed
+        // goes on a line of its own instead of being joined onto the next one
+        if self.name == 't'
+            && buffer.rope.get_char(end) == Some('\n')
+            && buffer.get_start_of_char_line(beginning) == beginning
+        {
+            if end == beginning {
+                mode.insert();
+                return;
+            }
+            end -= 1;
+        }
+
+        (self.command)(end, buffer, register_handler, mode, undo_tree, status_bar);
     }
 
     pub fn entire_line(
@@ -95,12 +138,20 @@ impl Operator {
         register_handler: &mut RegisterHandler,
         mode: &mut Mode,
         undo_tree: &mut UndoTree,
+        status_bar: &mut StatusBar,
     ) {
         buffer.start_of_line();
         let end_of_line = buffer.get_end_of_line();
         let reg_before = register_handler.get_reg().to_string();
 
-        (self.command)(end_of_line, buffer, register_handler, mode, undo_tree);
+        (self.command)(
+            end_of_line,
+            buffer,
+            register_handler,
+            mode,
+            undo_tree,
+            status_bar,
+        );
         if reg_before != register_handler.get_reg()
             && register_handler.get_reg().chars().last().unwrap_or('\n') != '\n'
         {
@@ -199,7 +250,7 @@ const fn reset_position(
     buffer.cursor = start;
 }
 
-fn insert(
+const fn insert(
     _start: usize,
     _register_handler: &mut RegisterHandler,
     _buffer: &mut Buffer,
@@ -225,6 +276,7 @@ pub fn delete(
     register_handler: &mut RegisterHandler,
     mode: &mut Mode,
     undo_tree: &mut UndoTree,
+    _status_bar: &mut StatusBar,
 ) {
     iterate_range(
         mode,
@@ -256,6 +308,7 @@ pub fn yank(
     register_handler: &mut RegisterHandler,
     mode: &mut Mode,
     _undo_tree: &mut UndoTree,
+    _status_bar: &mut StatusBar,
 ) {
     if end == buffer.rope.len_chars() && end == buffer.cursor {
         return;
@@ -281,6 +334,7 @@ pub fn change(
     register_handler: &mut RegisterHandler,
     mode: &mut Mode,
     undo_tree: &mut UndoTree,
+    _status_bar: &mut StatusBar,
 ) {
     iterate_range(
         mode,
@@ -303,3 +357,35 @@ pub fn change(
 
     buffer.clamp_cursor();
 }
+
+/// Enters Meta mode with the lines from the cursor to `end` as the range, ready to be filtered
+/// through a shell command (eg. `:.,.+2!`)
+pub fn filter(
+    end: usize,
+    buffer: &mut Buffer,
+    _register_handler: &mut RegisterHandler,
+    mode: &mut Mode,
+    _undo_tree: &mut UndoTree,
+    status_bar: &mut StatusBar,
+) {
+    let cursor_line = buffer.rope.char_to_line(buffer.cursor);
+    let end_line = buffer
+        .rope
+        .char_to_line(usize::min(end, buffer.rope.len_chars()));
+    let (start, lines) = (
+        usize::min(cursor_line, end_line),
+        cursor_line.abs_diff(end_line),
+    );
+
+    buffer.set_cursor(buffer.rope.line_to_char(start));
+
+    let range = if lines == 0 {
+        ":.!".to_string()
+    } else {
+        format!(":.,.+{lines}!")
+    };
+
+    *mode = Mode::Meta;
+    status_bar.clear();
+    range.chars().for_each(|c| status_bar.push(c));
+}
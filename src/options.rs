@@ -0,0 +1,459 @@
+use crate::{config, message::Messages, view::View};
+use anyhow::{Context, Result, bail};
+use ropey::Rope;
+use std::{collections::HashMap, path::Path};
+
+/// How many lines are looked at to guess how a file is indented
+const DETECT_INDENT_LINES: usize = 1000;
+
+/// The options a view box uses, which can be changed with `:set`
+// NOTE
+// These are the on/off options `:set` knows about, not state the bools would be better off as an
+// enum for
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// How many columns a tab character takes up
+    pub tabstop: usize,
+    /// How many columns an indent is, or `0` to use `tabstop`
+    pub shiftwidth: usize,
+    /// Whether indents are inserted as spaces rather than tab characters
+    pub expandtab: bool,
+    /// Whether line numbers are shown in the gutter
+    pub number: bool,
+    /// Whether the gutter shows how far each line is from the cursor's, rather than (or with
+    /// `number`, as well as) the line numbers
+    pub relativenumber: bool,
+    /// Whether the sign column is always shown, rather than only while there are signs in it
+    pub signcolumn: bool,
+    /// How many lines are kept between the cursor and the top or bottom of the view box
+    pub scrolloff: usize,
+    /// Whether `expandtab` and `shiftwidth` are changed to match how a file is indented when it's
+    /// loaded
+    pub detectindent: bool,
+    /// Whether lines too long for the view box continue onto the rows below, rather than the
+    /// view box scrolling sideways
+    pub wrap: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tabstop: 4,
+            shiftwidth: 0,
+            expandtab: true,
+            number: true,
+            relativenumber: false,
+            signcolumn: false,
+            scrolloff: 0,
+            detectindent: true,
+            wrap: false,
+        }
+    }
+}
+
+impl Options {
+    pub const fn indent(&self) -> Indent {
+        let width = if self.shiftwidth == 0 {
+            self.tabstop
+        } else {
+            self.shiftwidth
+        };
+
+        Indent {
+            width,
+            tabs: !self.expandtab,
+        }
+    }
+}
+
+/// Changes `expandtab` and `shiftwidth` to match how the text is indented, if any of it is
+///
+/// Lines starting with tabs and with spaces are counted, and the file is indented by whichever
+/// there are more of. The width of a space indent is how many spaces the indent most often grows
+/// by from one line to the next.
+pub fn detect_indent(options: &mut Options, rope: &Rope) {
+    let (mut tabs, mut spaces) = (0, 0);
+    // How many times the indent grew by each number of spaces
+    let mut growths: HashMap<usize, usize> = HashMap::new();
+    let mut last_indent = 0;
+
+    for line in rope.lines().take(DETECT_INDENT_LINES) {
+        // Blank lines don't say anything about the indent
+        if line.chars().all(char::is_whitespace) {
+            continue;
+        }
+
+        if line.char(0) == '\t' {
+            tabs += 1;
+            continue;
+        }
+
+        let indent = line.chars().take_while(|c| *c == ' ').count();
+        if indent > 0 {
+            spaces += 1;
+        }
+        // Single spaces are mostly the ` *` of block comments
+        if indent > last_indent + 1 {
+            *growths.entry(indent - last_indent).or_default() += 1;
+        }
+        last_indent = indent;
+    }
+
+    if tabs > spaces {
+        options.expandtab = false;
+        options.shiftwidth = 0;
+    } else if let Some((width, _)) = growths
+        .into_iter()
+        .max_by_key(|(width, count)| (*count, std::cmp::Reverse(*width)))
+    {
+        options.expandtab = true;
+        options.shiftwidth = width;
+    }
+}
+
+/// How indents are inserted into a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Indent {
+    pub width: usize,
+    pub tabs: bool,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Options::default().indent()
+    }
+}
+
+impl Indent {
+    /// The text of a single indent
+    // NOTE
+    // With tabs, an indent is always one tab, whatever its width is
+    pub fn text(self) -> String {
+        if self.tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.width)
+        }
+    }
+}
+
+enum Field {
+    Bool(fn(&mut Options) -> &mut bool),
+    Number {
+        field: fn(&mut Options) -> &mut usize,
+        min: usize,
+    },
+}
+
+pub struct OptionSpec {
+    name: &'static str,
+    short: &'static str,
+    field: Field,
+}
+
+impl OptionSpec {
+    const fn bool(
+        name: &'static str,
+        short: &'static str,
+        field: fn(&mut Options) -> &mut bool,
+    ) -> Self {
+        Self {
+            name,
+            short,
+            field: Field::Bool(field),
+        }
+    }
+
+    const fn number(
+        name: &'static str,
+        short: &'static str,
+        min: usize,
+        field: fn(&mut Options) -> &mut usize,
+    ) -> Self {
+        Self {
+            name,
+            short,
+            field: Field::Number { field, min },
+        }
+    }
+
+    fn find(name: &str) -> Result<&'static Self> {
+        OPTIONS
+            .iter()
+            .find(|spec| spec.name == name || spec.short == name)
+            .with_context(|| format!("Unknown option: {name}"))
+    }
+
+    /// The option's value in `options`, as `:set` shows it
+    fn show(&self, mut options: Options) -> String {
+        match self.field {
+            Field::Bool(field) if *field(&mut options) => self.name.to_string(),
+            Field::Bool(_) => format!("no{}", self.name),
+            Field::Number { field, .. } => format!("{}={}", self.name, field(&mut options)),
+        }
+    }
+}
+
+pub static OPTIONS: [OptionSpec; 9] = [
+    OptionSpec::number("tabstop", "ts", 1, |options| &mut options.tabstop),
+    OptionSpec::number("shiftwidth", "sw", 0, |options| &mut options.shiftwidth),
+    OptionSpec::bool("expandtab", "et", |options| &mut options.expandtab),
+    OptionSpec::bool("number", "nu", |options| &mut options.number),
+    OptionSpec::bool("relativenumber", "rnu", |options| {
+        &mut options.relativenumber
+    }),
+    OptionSpec::bool("signcolumn", "scl", |options| &mut options.signcolumn),
+    OptionSpec::number("scrolloff", "so", 0, |options| &mut options.scrolloff),
+    OptionSpec::bool("detectindent", "di", |options| &mut options.detectindent),
+    OptionSpec::bool("wrap", "wrap", |options| &mut options.wrap),
+];
+
+#[derive(Clone, Copy)]
+enum Value {
+    Bool(bool),
+    Number(usize),
+}
+
+/// A value given to an option
+#[derive(Clone, Copy)]
+struct Setting {
+    spec: &'static OptionSpec,
+    value: Value,
+}
+
+impl Setting {
+    fn new(spec: &'static OptionSpec, value: Value) -> Result<Self> {
+        match (&spec.field, value) {
+            (Field::Number { min, .. }, Value::Number(n)) if n < *min => {
+                bail!("{} can't be less than {min}", spec.name);
+            }
+            (Field::Bool(_), Value::Bool(_)) | (Field::Number { .. }, Value::Number(_)) => {}
+            (Field::Bool(_), Value::Number(_)) => bail!("{} is either on or off", spec.name),
+            (Field::Number { .. }, Value::Bool(_)) => bail!("{} takes a number", spec.name),
+        }
+
+        Ok(Self { spec, value })
+    }
+
+    fn from_toml(name: &str, value: &toml::Value) -> Result<Self> {
+        let spec = OptionSpec::find(name)?;
+        let value = match value {
+            toml::Value::Boolean(b) => Value::Bool(*b),
+            toml::Value::Integer(n) => Value::Number(
+                usize::try_from(*n).with_context(|| format!("{name} can't be negative"))?,
+            ),
+            _ => bail!("{name} has to be a boolean or a number"),
+        };
+
+        Self::new(spec, value)
+    }
+
+    fn apply(self, options: &mut Options) {
+        match (&self.spec.field, self.value) {
+            (Field::Bool(field), Value::Bool(b)) => *field(options) = b,
+            (Field::Number { field, .. }, Value::Number(n)) => *field(options) = n,
+            // Checked when the setting is made
+            _ => unreachable!("Mismatched option value"),
+        }
+    }
+}
+
+/// The options from `$XDG_CONFIG_HOME/orinfar/options.toml`
+#[derive(Default)]
+pub struct OptionsConfig {
+    /// The options every view box starts with, which `:set` also changes
+    pub global: Options,
+    /// Settings applied on top of the global options for files with the extension (or name, for
+    /// files without one)
+    filetypes: HashMap<String, Vec<Setting>>,
+}
+
+impl OptionsConfig {
+    /// Reads the options config, if there is one
+    ///
+    /// # Errors
+    /// - If the config file can't be read or is malformed, or sets an option which doesn't exist
+    pub fn load() -> Result<Self> {
+        let mut config = Self::default();
+
+        let table: toml::Table = config::load("options.toml")?;
+        let context = "Invalid options config";
+
+        for (name, value) in &table {
+            if name != "filetype" {
+                Setting::from_toml(name, value)
+                    .context(context)?
+                    .apply(&mut config.global);
+                continue;
+            }
+
+            let Some(filetypes) = value.as_table() else {
+                bail!("{context}: filetype has to be a table");
+            };
+            for (filetype, settings) in filetypes {
+                let Some(settings) = settings.as_table() else {
+                    bail!("{context}: filetype.{filetype} has to be a table");
+                };
+
+                let settings = settings
+                    .iter()
+                    .map(|(name, value)| Setting::from_toml(name, value))
+                    .collect::<Result<Vec<Setting>>>()
+                    .context(context)?;
+                config.filetypes.insert(filetype.clone(), settings);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The options a view box showing the file at `path` starts with
+    pub fn for_path(&self, path: Option<&Path>) -> Options {
+        let mut options = self.global;
+
+        let filetype = path.and_then(|path| path.extension().or_else(|| path.file_name()));
+        if let Some(settings) = filetype
+            .and_then(|filetype| filetype.to_str())
+            .and_then(|filetype| self.filetypes.get(filetype))
+        {
+            for setting in settings {
+                setting.apply(&mut options);
+            }
+        }
+
+        options
+    }
+}
+
+/// The `:set` meta-command, which takes any number of
+/// - `option` to turn it on, or show it if it's a number
+/// - `nooption` to turn it off
+/// - `option!` or `invoption` to toggle it
+/// - `option=n` to set it
+/// - `option?` to show it
+///
+/// Options are changed in the current view box, and for the view boxes opened afterwards.
+/// Without any arguments, every option is shown.
+///
+/// # Errors
+/// - If an option doesn't exist or is given the wrong kind of value
+pub fn set(view: &mut View, messages: &mut Messages, arg: &str) -> Result<()> {
+    let mut options = *view.get_view_box().options();
+    let mut shown = if arg.trim().is_empty() {
+        OPTIONS.iter().map(|spec| spec.show(options)).collect()
+    } else {
+        Vec::new()
+    };
+
+    for word in arg.split_whitespace() {
+        let Some(setting) = parse_set_arg(word, options)? else {
+            let name = word.trim_end_matches('?');
+            shown.push(OptionSpec::find(name)?.show(options));
+            continue;
+        };
+
+        setting.apply(&mut options);
+        setting.apply(&mut view.options.global);
+    }
+
+    view.get_view_box().set_options(options);
+    if !shown.is_empty() {
+        messages.info(shown.join(" "));
+    }
+
+    Ok(())
+}
+
+/// # Returns
+/// The setting an argument to `:set` makes, or `None` if it's asking what the option is
+fn parse_set_arg(word: &str, options: Options) -> Result<Option<Setting>> {
+    if word.ends_with('?') {
+        return Ok(None);
+    }
+
+    if let Some((name, value)) = word.split_once('=') {
+        let spec = OptionSpec::find(name)?;
+        let n = value
+            .parse::<usize>()
+            .with_context(|| format!("{name} takes a number, not {value}"))?;
+
+        return Setting::new(spec, Value::Number(n)).map(Some);
+    }
+
+    let toggled = word.strip_suffix('!').or_else(|| word.strip_prefix("inv"));
+    if let Some(name) = toggled {
+        let spec = OptionSpec::find(name)?;
+        let Field::Bool(field) = spec.field else {
+            bail!("{name} takes a number");
+        };
+        let mut options = options;
+        let value = !*field(&mut options);
+
+        return Setting::new(spec, Value::Bool(value)).map(Some);
+    }
+
+    if let Ok(spec) = OptionSpec::find(word) {
+        return match spec.field {
+            Field::Bool(_) => Setting::new(spec, Value::Bool(true)).map(Some),
+            // Like Vi, naming a number option shows it
+            Field::Number { .. } => Ok(None),
+        };
+    }
+
+    match word.strip_prefix("no") {
+        Some(name) => Setting::new(OptionSpec::find(name)?, Value::Bool(false)).map(Some),
+        None => bail!("Unknown option: {word}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected(text: &str) -> Options {
+        let mut options = Options::default();
+        detect_indent(&mut options, &Rope::from_str(text));
+        options
+    }
+
+    #[test]
+    fn detect_tabs() {
+        let options = detected("fn a() {\n\tif b {\n\t\tc();\n\t}\n}\n");
+
+        assert!(!options.expandtab);
+        assert_eq!(options.shiftwidth, 0);
+    }
+
+    #[test]
+    fn detect_two_spaces() {
+        let options = detected("a:\n  b:\n    c: 1\n  d: 2\ne:\n  f: 3\n");
+
+        assert!(options.expandtab);
+        assert_eq!(options.shiftwidth, 2);
+    }
+
+    #[test]
+    fn detect_four_spaces() {
+        let options =
+            detected("fn a() {\n    if b {\n        c();\n    }\n}\n\nfn d() {\n    e();\n}\n");
+
+        assert!(options.expandtab);
+        assert_eq!(options.shiftwidth, 4);
+    }
+
+    #[test]
+    fn detect_spaces_around_block_comments() {
+        // The ` *` lines grow the indent by one space, which isn't an indent
+        let options =
+            detected("/**\n * a\n * b\n */\nfn c() {\n    /**\n     * d\n     */\n    e();\n}\n");
+
+        assert!(options.expandtab);
+        assert_eq!(options.shiftwidth, 4);
+    }
+
+    #[test]
+    fn keep_options_without_indents() {
+        assert_eq!(detected("a\nb\n\nc\n"), Options::default());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::theme::{HLGroup, Theme};
+use anyhow::Result;
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::{Print, SetBackgroundColor, SetForegroundColor},
+};
+use std::io::Write;
+
+const MAX_WIDTH: usize = 80;
+const MAX_HEIGHT: usize = 15;
+
+/// A box of text drawn over the view boxes next to the cursor, until the next keypress
+pub struct Popup {
+    lines: Vec<String>,
+    /// The highlighted line of a menu
+    selected: Option<usize>,
+}
+
+impl Popup {
+    /// Code fences are left out, since the text isn't rendered as Markdown
+    pub fn new(text: &str) -> Self {
+        let mut lines: Vec<String> = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("```"))
+            .map(|line| line.trim_end().to_string())
+            .collect();
+
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+        while lines.first().is_some_and(String::is_empty) {
+            lines.remove(0);
+        }
+
+        Self {
+            lines,
+            selected: None,
+        }
+    }
+
+    /// A popup where one of the lines is highlighted, scrolled so that it's visible
+    pub const fn menu(lines: Vec<String>, selected: usize) -> Self {
+        Self {
+            lines,
+            selected: Some(selected),
+        }
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Draws the popup below the cursor, or above it if there isn't room below
+    ///
+    /// # Arguments
+    /// - `cursor`: The position of the cursor on the screen
+    /// - `screen`: The width and height of the area the popup can be drawn in
+    pub fn render(
+        &self,
+        cursor: (u16, u16),
+        screen: (u16, u16),
+        theme: &Theme,
+        stdout: &mut impl Write,
+    ) -> Result<()> {
+        let (col, row) = (usize::from(cursor.0), usize::from(cursor.1));
+        let (screen_width, screen_height) = (usize::from(screen.0), usize::from(screen.1));
+
+        // One column of padding on each side
+        let width = self
+            .lines
+            .iter()
+            .map(|line| line.chars().count() + 2)
+            .max()
+            .unwrap_or(0)
+            .min(MAX_WIDTH)
+            .min(screen_width);
+
+        let below = screen_height.saturating_sub(row + 1);
+        let above = row;
+        let (top, height) = if below >= usize::min(self.lines.len(), MAX_HEIGHT) || below >= above {
+            (row + 1, self.lines.len().min(MAX_HEIGHT).min(below))
+        } else {
+            let height = self.lines.len().min(MAX_HEIGHT).min(above);
+            (row - height, height)
+        };
+        let left = usize::min(col, screen_width - width);
+
+        let scroll = self
+            .selected
+            .map_or(0, |selected| (selected + 1).saturating_sub(height));
+
+        for (i, line) in self.lines.iter().enumerate().skip(scroll).take(height) {
+            let text: String = line.chars().take(width.saturating_sub(2)).collect();
+            let text = format!(" {text:<inner$} ", inner = width.saturating_sub(2));
+
+            let group = if self.selected == Some(i) {
+                HLGroup::Selection
+            } else {
+                HLGroup::Popup
+            };
+            let style = theme.style(group);
+
+            let (Ok(x), Ok(y)) = (u16::try_from(left), u16::try_from(top + i - scroll)) else {
+                continue;
+            };
+            queue!(
+                stdout,
+                MoveTo(x, y),
+                SetForegroundColor(style.fg),
+                SetBackgroundColor(style.bg),
+                Print(text)
+            )?;
+        }
+
+        Ok(())
+    }
+}
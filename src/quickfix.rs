@@ -0,0 +1,272 @@
+use crate::{message::Messages, view::View};
+use anyhow::{Context, Result, bail};
+use ropey::Rope;
+use std::path::{Path, PathBuf};
+
+/// A position in a file, with a message about what's there (eg. a search result or an error)
+pub struct Entry {
+    pub path: PathBuf,
+    /// Zero-indexed
+    pub line: usize,
+    /// Zero-indexed, in chars
+    pub col: usize,
+    pub text: String,
+}
+
+/// A list of positions across the project, which is shown in its own view box and can be stepped
+/// through from any view box
+#[derive(Default)]
+pub struct Quickfix {
+    entries: Vec<Entry>,
+    current: usize,
+    /// The view box entries are opened in, which is the one the list was made from
+    target: usize,
+}
+
+impl Quickfix {
+    /// Replaces the list with `entries`, showing it in a view box below the current one (or the
+    /// view box already showing the list), then jumps to the first entry
+    ///
+    /// # Returns
+    /// A message about the first entry
+    ///
+    /// # Errors
+    /// - If there are no entries, or the first entry's file can't be opened
+    pub fn set(&mut self, entries: Vec<Entry>, view: &mut View) -> Result<String> {
+        if entries.is_empty() {
+            bail!("No entries");
+        }
+
+        self.entries = entries;
+        self.target = view.current_view_box();
+        if view.get_view_box().quickfix {
+            self.target = first_file_box(view).unwrap_or(self.target);
+        }
+
+        let contents = self
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}:{}:{}: {}",
+                    entry.path.to_string_lossy(),
+                    entry.line + 1,
+                    entry.col + 1,
+                    entry.text
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let list = match list_box(view) {
+            Some(list) => list,
+            None => view
+                .split_view_box_horizontal(self.target)
+                .context("No room to show the list")?,
+        };
+
+        // NOTE
+        // The list isn't an edit, so it isn't recorded in the undo tree
+        let view_box = view.get_view_box_at(list);
+        view_box.set_path(None);
+        view_box.explorer = None;
+        view_box.quickfix = true;
+        view_box.top = 0;
+        view_box.left = 0;
+
+        let buffer = &mut view_box.buffer;
+        buffer.rope = Rope::from(contents);
+        buffer.lines_for_updating = (0..buffer.len()).map(|_| true).collect();
+        buffer.cursor = 0;
+        buffer.has_changed = true;
+
+        self.jump(0, view)
+    }
+
+    /// Jumps to the next entry
+    ///
+    /// # Errors
+    /// - If there are no more entries, or the entry's file can't be opened
+    pub fn next(&mut self, view: &mut View) -> Result<String> {
+        if self.current + 1 >= self.entries.len() {
+            bail!("No more entries");
+        }
+        self.jump(self.current + 1, view)
+    }
+
+    /// Jumps to the previous entry
+    ///
+    /// # Errors
+    /// - If there are no earlier entries, or the entry's file can't be opened
+    pub fn prev(&mut self, view: &mut View) -> Result<String> {
+        if self.current == 0 || self.entries.is_empty() {
+            bail!("No earlier entries");
+        }
+        self.jump(self.current - 1, view)
+    }
+
+    /// Jumps to the entry on the cursor's line in the list's view box
+    ///
+    /// # Errors
+    /// - If the entry's file can't be opened
+    pub fn open_at_cursor(&mut self, view: &mut View) -> Result<String> {
+        let row = view.get_buffer().get_row();
+        if row >= self.entries.len() {
+            return Ok(String::new());
+        }
+        self.jump(row, view)
+    }
+
+    /// Opens the entry's file in the target view box (if it isn't already showing it), puts the
+    /// cursor on the entry, and moves the list's cursor to its line
+    fn jump(&mut self, idx: usize, view: &mut View) -> Result<String> {
+        self.current = idx;
+        let entry = &self.entries[idx];
+
+        if let Some(list) = list_box(view) {
+            let buffer = &mut view.get_view_box_at(list).buffer;
+            buffer.cursor = 0;
+            buffer.set_row(idx);
+            buffer.update_list_set(.., true);
+            buffer.has_changed = true;
+        }
+
+        let target = if view
+            .view_boxes()
+            .nth(self.target)
+            .is_some_and(|view_box| !view_box.quickfix)
+        {
+            Some(self.target)
+        } else {
+            first_file_box(view)
+        };
+        let target = match target {
+            Some(target) => target,
+            None => view
+                .split_view_box_horizontal(view.current_view_box())
+                .context("No room to open the entry")?,
+        };
+        view.set_current_view_box(target);
+        self.target = target;
+
+        if !shows_file(view, &entry.path) {
+            view.open(entry.path.clone())?;
+        }
+
+        let buffer = view.get_buffer_mut();
+        let line = entry.line.min(buffer.len().saturating_sub(1));
+        let line_len = buffer.rope.line(line).len_chars();
+        let col = entry.col.min(line_len.saturating_sub(1));
+        buffer.set_cursor(buffer.rope.line_to_char(line) + col);
+
+        Ok(format!(
+            "({} of {}) {}",
+            idx + 1,
+            self.entries.len(),
+            entry.text.trim()
+        ))
+    }
+}
+
+/// The index of the view box showing the list
+fn list_box(view: &View) -> Option<usize> {
+    view.view_boxes().position(|view_box| view_box.quickfix)
+}
+
+/// The index of the first view box which isn't showing the list
+fn first_file_box(view: &View) -> Option<usize> {
+    view.view_boxes().position(|view_box| !view_box.quickfix)
+}
+
+fn shows_file(view: &View, path: &Path) -> bool {
+    let absolute = |path: &Path| std::path::absolute(path).ok();
+    view.get_path()
+        .is_some_and(|current| absolute(current) == absolute(path))
+}
+
+/// A command which steps through the quickfix list. The entry it lands on is shown in the
+/// status bar.
+pub struct QuickfixCommand<'a> {
+    pub name: &'a str,
+    command: fn(quickfix: &mut Quickfix, view: &mut View) -> Result<String>,
+}
+
+impl<'a> QuickfixCommand<'a> {
+    pub const fn new(
+        name: &'a str,
+        command: fn(quickfix: &mut Quickfix, view: &mut View) -> Result<String>,
+    ) -> Self {
+        QuickfixCommand { name, command }
+    }
+
+    pub fn execute(&self, quickfix: &mut Quickfix, view: &mut View, messages: &mut Messages) {
+        match (self.command)(quickfix, view) {
+            Ok(message) => messages.info(message),
+            Err(err) => messages.warn(format!("{err:#}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OptionsConfig;
+    use std::fs;
+
+    #[test]
+    fn next_and_prev_stop_at_the_ends() {
+        let dir = std::env::temp_dir().join(format!("orinfar-quickfix-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("Created");
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, "one\ntwo\n").expect("Written");
+        fs::write(&b, "three\n").expect("Written");
+        let entry = |path: &Path, line, col, text: &str| Entry {
+            path: path.to_path_buf(),
+            line,
+            col,
+            text: text.to_string(),
+        };
+
+        let mut view = View::new(80, 24, OptionsConfig::default());
+        let mut quickfix = Quickfix::default();
+        let entries = vec![
+            entry(&a, 1, 1, "two"),
+            entry(&b, 0, 0, "three"),
+            entry(&a, 0, 2, "one"),
+        ];
+        assert_eq!(
+            quickfix.set(entries, &mut view).expect("Set"),
+            "(1 of 3) two"
+        );
+        assert_eq!(view.get_path(), Some(&a));
+        assert_eq!(view.get_buffer().cursor, 5);
+        assert!(quickfix.prev(&mut view).is_err());
+
+        assert_eq!(quickfix.next(&mut view).expect("Next"), "(2 of 3) three");
+        assert_eq!(view.get_path(), Some(&b));
+        assert_eq!(quickfix.next(&mut view).expect("Next"), "(3 of 3) one");
+        assert_eq!(view.get_path(), Some(&a));
+        assert_eq!(view.get_buffer().cursor, 2);
+
+        // Stepping past the end leaves the cursor where it was
+        assert!(quickfix.next(&mut view).is_err());
+        assert_eq!(view.get_buffer().cursor, 2);
+        assert_eq!(quickfix.prev(&mut view).expect("Prev"), "(2 of 3) three");
+
+        // The list follows along
+        let list = list_box(&view).expect("Listed");
+        assert_eq!(view.get_view_box_at(list).buffer.get_row(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn empty_lists_are_refused() {
+        let mut view = View::new(80, 24, OptionsConfig::default());
+        let mut quickfix = Quickfix::default();
+
+        assert!(quickfix.set(Vec::new(), &mut view).is_err());
+        assert!(quickfix.next(&mut view).is_err());
+        assert!(quickfix.prev(&mut view).is_err());
+    }
+}
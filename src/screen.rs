@@ -0,0 +1,224 @@
+use std::{fmt::Display, io, ops::RangeBounds};
+use unicode_width::UnicodeWidthChar;
+
+/// An in-memory stand-in for the terminal, which the editor can be rendered to instead of
+/// stdout. It understands the escape sequences the editor writes (moving the cursor and
+/// clearing), and ignores the rest (colors and cursor styles), so only the text is kept.
+///
+/// Text written past the end of a row is dropped rather than wrapped.
+pub struct Screen {
+    /// The text in each cell, which is empty for the cell covered by the right half of a wide
+    /// character, and has any combining characters after the character they combine with
+    cells: Vec<Vec<String>>,
+    /// The column and row text is written at next, zero-indexed
+    pub cursor: (u16, u16),
+    /// The end of the last write, if it stopped partway through an escape sequence or character
+    pending: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            cells: vec![vec![" ".to_string(); usize::from(width)]; usize::from(height)],
+            cursor: (0, 0),
+            pending: Vec::new(),
+        }
+    }
+
+    fn width(&self) -> u16 {
+        self.cells
+            .first()
+            .map_or(0, |row| u16::try_from(row.len()).unwrap_or(u16::MAX))
+    }
+
+    fn height(&self) -> u16 {
+        u16::try_from(self.cells.len()).unwrap_or(u16::MAX)
+    }
+
+    fn put(&mut self, c: char) {
+        let (x, y) = (usize::from(self.cursor.0), usize::from(self.cursor.1));
+        let Some(row) = self.cells.get_mut(y) else {
+            return;
+        };
+
+        match c.width().unwrap_or(1) {
+            0 => {
+                if let Some(cell) = x.checked_sub(1).and_then(|x| row.get_mut(x)) {
+                    cell.push(c);
+                }
+            }
+            width => {
+                if let Some(cell) = row.get_mut(x) {
+                    *cell = c.to_string();
+                }
+                row.iter_mut()
+                    .skip(x + 1)
+                    .take(width - 1)
+                    .for_each(String::clear);
+
+                let width = u16::try_from(width).unwrap_or(1);
+                self.cursor.0 = self.cursor.0.saturating_add(width);
+            }
+        }
+    }
+
+    /// Clears the cells in `cols` of `row`
+    fn clear_row(&mut self, row: u16, cols: impl RangeBounds<usize>) {
+        if let Some(row) = self.cells.get_mut(usize::from(row)) {
+            let cols = (cols.start_bound().cloned(), cols.end_bound().cloned());
+            row.iter_mut()
+                .enumerate()
+                .filter(|(i, _)| cols.contains(i))
+                .for_each(|(_, cell)| *cell = " ".to_string());
+        }
+    }
+
+    /// Runs a control sequence, written as `ESC [ params final`
+    fn control(&mut self, params: &str, last: u8) {
+        // NOTE
+        // Private sequences (eg. showing the cursor) start with `?`, and have nothing to do with
+        // the text
+        if params.starts_with('?') {
+            return;
+        }
+
+        let mut args = params
+            .split(';')
+            .map(|arg| arg.trim().parse::<u16>().ok().filter(|n| *n > 0));
+        let mut arg = || args.next().flatten();
+        let (x, y) = self.cursor;
+
+        match last {
+            b'H' | b'f' => {
+                let row = arg().unwrap_or(1) - 1;
+                let col = arg().unwrap_or(1) - 1;
+                self.cursor = (col, row);
+            }
+            b'G' => self.cursor.0 = arg().unwrap_or(1) - 1,
+            b'd' => self.cursor.1 = arg().unwrap_or(1) - 1,
+            b'A' => self.cursor.1 = y.saturating_sub(arg().unwrap_or(1)),
+            b'B' => self.cursor.1 = y.saturating_add(arg().unwrap_or(1)),
+            b'C' => self.cursor.0 = x.saturating_add(arg().unwrap_or(1)),
+            b'D' => self.cursor.0 = x.saturating_sub(arg().unwrap_or(1)),
+            b'K' => match arg() {
+                None => self.clear_row(y, usize::from(x)..),
+                Some(1) => self.clear_row(y, ..=usize::from(x)),
+                Some(_) => self.clear_row(y, ..),
+            },
+            b'J' => {
+                let rows = match arg() {
+                    None => {
+                        self.clear_row(y, usize::from(x)..);
+                        y + 1..self.height()
+                    }
+                    Some(1) => {
+                        self.clear_row(y, ..=usize::from(x));
+                        0..y
+                    }
+                    Some(_) => 0..self.height(),
+                };
+                rows.for_each(|row| self.clear_row(row, ..));
+            }
+            _ => {}
+        }
+
+        self.cursor.1 = self.cursor.1.min(self.height().saturating_sub(1));
+        self.cursor.0 = self.cursor.0.min(self.width());
+    }
+
+    /// Handles as much of the pending output as is complete
+    fn process(&mut self) {
+        let bytes = std::mem::take(&mut self.pending);
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                0x1b => {
+                    let Some((params, last, len)) = escape_sequence(&bytes[i..]) else {
+                        break;
+                    };
+                    if let Some(last) = last {
+                        self.control(&params, last);
+                    }
+                    i += len;
+                }
+                b'\r' => {
+                    self.cursor.0 = 0;
+                    i += 1;
+                }
+                b'\n' => {
+                    self.cursor = (0, (self.cursor.1 + 1).min(self.height().saturating_sub(1)));
+                    i += 1;
+                }
+                first => {
+                    let len = match first {
+                        0xf0.. => 4,
+                        0xe0.. => 3,
+                        0xc0.. => 2,
+                        _ => 1,
+                    };
+                    let Some(encoded) = bytes.get(i..i + len) else {
+                        break;
+                    };
+                    let c = std::str::from_utf8(encoded)
+                        .ok()
+                        .and_then(|text| text.chars().next())
+                        .unwrap_or(char::REPLACEMENT_CHARACTER);
+                    if !c.is_control() {
+                        self.put(c);
+                    }
+                    i += len;
+                }
+            }
+        }
+
+        self.pending = bytes[i..].to_vec();
+    }
+}
+
+/// Reads the escape sequence at the start of `bytes`
+///
+/// # Returns
+/// - The parameters and final byte of the sequence if it's a control sequence (`ESC [ ...`),
+///   and the number of bytes it takes up
+/// - `None` if the sequence hasn't been written in full yet
+fn escape_sequence(bytes: &[u8]) -> Option<(String, Option<u8>, usize)> {
+    match bytes.get(1)? {
+        b'[' => {
+            // NOTE
+            // Parameters and intermediate bytes (eg. the space in `ESC [ 2 SP q`) come before the
+            // final byte, which is the first in `@` to `~`
+            let end = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))? + 2;
+            let params = String::from_utf8_lossy(&bytes[2..end])
+                .trim_end_matches(|c: char| (' '..='/').contains(&c))
+                .to_string();
+            Some((params, Some(bytes[end]), end + 1))
+        }
+        _ => Some((String::new(), None, 2)),
+    }
+}
+
+impl io::Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.process();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The rows of text on the screen, without trailing spaces
+impl Display for Screen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows: Vec<String> = self
+            .cells
+            .iter()
+            .map(|row| row.concat().trim_end().to_string())
+            .collect();
+
+        f.write_str(&rows.join("\n"))
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::{Context, Result, bail};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// What a command wrote, and whether it succeeded
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+    pub success: bool,
+}
+
+impl Output {
+    /// # Errors
+    /// - If the command failed, in which case the first line it wrote to stderr is the error
+    pub fn stdout_or_error(self, name: &str) -> Result<String> {
+        if !self.success {
+            let error = self
+                .stderr
+                .lines()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or("no output");
+            bail!("{name} failed: {}", error.trim());
+        }
+
+        Ok(self.stdout)
+    }
+}
+
+/// Runs `command`, writing `input` to its stdin
+///
+/// # Errors
+/// - If the command can't be started
+pub fn run(command: &[String], input: &str) -> Result<Output> {
+    let Some((program, args)) = command.split_first() else {
+        bail!("No command to run");
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run {program}"))?;
+
+    // NOTE
+    // Written from another thread so that a command filling up its stdout before it's read all
+    // of its stdin can't deadlock
+    let mut stdin = child.stdin.take().context("Command has no stdin")?;
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child.wait_with_output()?;
+    // The command doesn't have to read all of its input
+    let _ = writer.join();
+
+    Ok(Output {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        success: output.status.success(),
+    })
+}
+
+/// Runs `command` with the user's shell (`$SHELL`, or failing that `sh`)
+///
+/// # Errors
+/// - If the shell can't be started
+pub fn run_shell(command: &str, input: &str) -> Result<Output> {
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+    run(&[shell, "-c".to_string(), command.to_string()], input)
+}
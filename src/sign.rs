@@ -0,0 +1,96 @@
+use crate::theme::HLGroup;
+use std::collections::{BTreeMap, HashMap};
+
+/// What placed a sign. Each source replaces all of its signs at once, and where two sources have a
+/// sign on the same line, the one declared first is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SignSource {
+    Diagnostic,
+    Git,
+}
+
+/// A character drawn in the sign column, next to a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sign {
+    pub text: char,
+    pub group: HLGroup,
+}
+
+impl Sign {
+    pub const fn new(text: char, group: HLGroup) -> Self {
+        Self { text, group }
+    }
+}
+
+/// The signs in a view box's sign column, by the line they're next to
+#[derive(Debug, Default)]
+pub struct SignColumn {
+    signs: BTreeMap<SignSource, HashMap<usize, Sign>>,
+}
+
+impl SignColumn {
+    /// Replaces the signs placed by `source`
+    ///
+    /// # Returns
+    /// Whether the signs changed
+    pub fn set(&mut self, source: SignSource, signs: HashMap<usize, Sign>) -> bool {
+        if signs.is_empty() {
+            return self.signs.remove(&source).is_some();
+        }
+
+        self.signs.insert(source, signs.clone()) != Some(signs)
+    }
+
+    /// The sign shown next to the line, if there is one
+    pub fn at(&self, line_num: usize) -> Option<Sign> {
+        self.signs
+            .values()
+            .find_map(|signs| signs.get(&line_num))
+            .copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ERROR: Sign = Sign::new('E', HLGroup::DiagnosticError);
+    const ADDED: Sign = Sign::new('+', HLGroup::GitAdded);
+
+    #[test]
+    fn diagnostics_are_shown_over_git() {
+        let mut signs = SignColumn::default();
+        // The order they're placed in doesn't matter
+        signs.set(SignSource::Git, HashMap::from([(1, ADDED), (2, ADDED)]));
+        signs.set(
+            SignSource::Diagnostic,
+            HashMap::from([(2, ERROR), (3, ERROR)]),
+        );
+
+        assert_eq!(signs.at(0), None);
+        assert_eq!(signs.at(1), Some(ADDED));
+        assert_eq!(signs.at(2), Some(ERROR));
+        assert_eq!(signs.at(3), Some(ERROR));
+
+        // Once the diagnostic goes away, git's sign shows through again
+        signs.set(SignSource::Diagnostic, HashMap::new());
+        assert_eq!(signs.at(2), Some(ADDED));
+    }
+
+    #[test]
+    fn setting_reports_changes() {
+        let mut signs = SignColumn::default();
+
+        assert!(signs.set(SignSource::Git, HashMap::from([(1, ADDED)])));
+        assert!(!signs.set(SignSource::Git, HashMap::from([(1, ADDED)])));
+        assert!(!signs.is_empty());
+
+        assert!(signs.set(SignSource::Git, HashMap::new()));
+        assert!(!signs.set(SignSource::Git, HashMap::new()));
+        assert!(signs.is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{
+    buffer::Buffer,
+    syntax_text_object::{FUNCTION_KINDS, byte_to_char, node_at_cursor},
+    utility::traverse_tree,
+};
+use tree_sitter::{Node, Tree};
+
+/// A motion which moves the cursor based on the parse tree of the buffer.
+/// These are all multi-character, since they're all prefixed by `]` or `[`.
+pub struct SyntaxMotion<'a> {
+    pub name: &'a str,
+    command: fn(buffer: &mut Buffer, tree: &Tree),
+    pub inclusive: bool,
+}
+
+impl<'a> SyntaxMotion<'a> {
+    pub const fn exclusive(name: &'a str, command: fn(buffer: &mut Buffer, tree: &Tree)) -> Self {
+        SyntaxMotion {
+            name,
+            command,
+            inclusive: false,
+        }
+    }
+
+    // Called when the motion should be applied directly
+    pub fn apply(&self, buffer: &mut Buffer, tree: &Tree) {
+        (self.command)(buffer, tree);
+    }
+
+    // Called when the motion is chained to an operator
+    // Doesn't apply the motion to the buffer but returns where the motion would have gone
+    pub fn evaluate(&self, buffer: &Buffer, tree: &Tree) -> usize {
+        let mut fake_buffer = buffer.clone();
+        (self.command)(&mut fake_buffer, tree);
+
+        fake_buffer.cursor
+    }
+}
+
+/// The largest node starting where the smallest named node containing the cursor starts.
+/// This way, when the cursor is at the beginning of a statement, the statement is chosen rather
+/// than the first identifier in it.
+fn outermost_node_at_cursor<'t>(buffer: &Buffer, tree: &'t Tree) -> Option<Node<'t>> {
+    let mut node = node_at_cursor(buffer, tree)?;
+
+    while let Some(parent) = node.parent()
+        && parent.parent().is_some()
+        && parent.start_byte() == node.start_byte()
+    {
+        node = parent;
+    }
+
+    Some(node)
+}
+
+/// Returns the starting positions (in chars) of every node with one of the given `kinds`, in
+/// order
+fn node_starts(buffer: &Buffer, tree: &Tree, kinds: &[&str]) -> Vec<usize> {
+    let node_action = |cursor: &tree_sitter::TreeCursor, starts: &mut Vec<usize>| {
+        let node = cursor.node();
+        if kinds.contains(&node.kind())
+            && let Some(start) = byte_to_char(buffer, node.start_byte())
+        {
+            starts.push(start);
+        }
+    };
+
+    traverse_tree(tree, node_action, |_| {}, |_| {})
+}
+
+// Functions
+impl Buffer {
+    pub fn next_function(buffer: &mut Self, tree: &Tree) {
+        let starts = node_starts(buffer, tree, FUNCTION_KINDS);
+
+        if let Some(start) = starts.into_iter().find(|start| *start > buffer.cursor) {
+            buffer.set_cursor(start);
+        }
+    }
+
+    pub fn prev_function(buffer: &mut Self, tree: &Tree) {
+        let starts = node_starts(buffer, tree, FUNCTION_KINDS);
+
+        if let Some(start) = starts
+            .into_iter()
+            .rev()
+            .find(|start| *start < buffer.cursor)
+        {
+            buffer.set_cursor(start);
+        }
+    }
+}
+
+// Siblings
+impl Buffer {
+    /// Moves to the start of the next sibling of the node under the cursor.
+    /// If the node is the last of its siblings, its parent's next sibling is used instead.
+    pub fn next_sibling(buffer: &mut Self, tree: &Tree) {
+        let Some(mut node) = outermost_node_at_cursor(buffer, tree) else {
+            return;
+        };
+
+        loop {
+            if let Some(sibling) = node.next_named_sibling() {
+                if let Some(start) = byte_to_char(buffer, sibling.start_byte()) {
+                    buffer.set_cursor(start);
+                }
+                return;
+            }
+
+            node = unwrap_or_return!(node.parent());
+        }
+    }
+
+    /// Moves to the start of the node under the cursor, or to the start of its previous sibling if
+    /// the cursor is already there.
+    /// If the node is the first of its siblings, its parent's previous sibling is used instead.
+    pub fn prev_sibling(buffer: &mut Self, tree: &Tree) {
+        let Some(mut node) = outermost_node_at_cursor(buffer, tree) else {
+            return;
+        };
+
+        if let Some(start) = byte_to_char(buffer, node.start_byte())
+            && start < buffer.cursor
+        {
+            buffer.set_cursor(start);
+            return;
+        }
+
+        loop {
+            if let Some(sibling) = node.prev_named_sibling() {
+                if let Some(start) = byte_to_char(buffer, sibling.start_byte()) {
+                    buffer.set_cursor(start);
+                }
+                return;
+            }
+
+            node = unwrap_or_return!(node.parent());
+        }
+    }
+}
+
+// Parents and children
+impl Buffer {
+    /// Moves out to the start of the parent of the node under the cursor, expanding the node the
+    /// cursor is on
+    pub fn parent_node(buffer: &mut Self, tree: &Tree) {
+        let Some(node) = outermost_node_at_cursor(buffer, tree) else {
+            return;
+        };
+        let Some(parent) = node.parent().filter(|parent| parent.parent().is_some()) else {
+            return;
+        };
+        let Some(start) = byte_to_char(buffer, parent.start_byte()) else {
+            return;
+        };
+
+        let from = buffer.cursor;
+        buffer.set_cursor(start);
+        buffer.node_history.push((from, buffer.cursor));
+    }
+
+    /// Moves back in to where the cursor was before the last move out to a parent node, shrinking
+    /// the node the cursor is on. If the cursor has moved since then, it moves in to the first
+    /// child of the node under the cursor which starts after it.
+    pub fn child_node(buffer: &mut Self, tree: &Tree) {
+        if let Some((from, to)) = buffer.node_history.last().copied() {
+            if to == buffer.cursor {
+                buffer.node_history.pop();
+                buffer.set_cursor(from);
+                return;
+            }
+            buffer.node_history.clear();
+        }
+
+        let Some(node) = outermost_node_at_cursor(buffer, tree) else {
+            return;
+        };
+        let mut cursor = node.walk();
+        let start = node
+            .named_children(&mut cursor)
+            .filter_map(|child| byte_to_char(buffer, child.start_byte()))
+            .find(|start| *start > buffer.cursor);
+
+        if let Some(start) = start {
+            buffer.set_cursor(start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buffer::Buffer;
+    use ropey::Rope;
+    use tree_sitter::{Parser, Tree};
+
+    fn parse_c(text: &str) -> (Buffer, Tree) {
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_c::LANGUAGE.into())
+            .expect("C grammar loads");
+        let tree = parser.parse(text, None).expect("C parses");
+
+        let mut buffer = Buffer::new();
+        buffer.rope = Rope::from(text);
+        (buffer, tree)
+    }
+
+    const SOURCE: &str = "int main() {\n    if (x) {\n        return f(a, b);\n    }\n}\n";
+
+    #[test]
+    fn moves_out_to_parents_and_back_in() {
+        let (mut buffer, tree) = parse_c(SOURCE);
+        let b = SOURCE.find("b)").expect("b is in the source");
+        buffer.set_cursor(b);
+
+        // b -> (a, b) -> f(a, b) -> return -> { ... } of the if
+        Buffer::parent_node(&mut buffer, &tree);
+        assert_eq!(buffer.cursor, SOURCE.find("(a").expect("Arguments exist"));
+        Buffer::parent_node(&mut buffer, &tree);
+        assert_eq!(buffer.cursor, SOURCE.find("f(").expect("Call exists"));
+        Buffer::parent_node(&mut buffer, &tree);
+        assert_eq!(buffer.cursor, SOURCE.find("return").expect("Return exists"));
+
+        Buffer::child_node(&mut buffer, &tree);
+        assert_eq!(buffer.cursor, SOURCE.find("f(").expect("Call exists"));
+        Buffer::child_node(&mut buffer, &tree);
+        Buffer::child_node(&mut buffer, &tree);
+        assert_eq!(buffer.cursor, b);
+        assert!(buffer.node_history.is_empty());
+    }
+
+    #[test]
+    fn moves_in_to_first_child_after_moving_elsewhere() {
+        let (mut buffer, tree) = parse_c(SOURCE);
+        buffer.set_cursor(SOURCE.find("return").expect("Return exists"));
+        Buffer::parent_node(&mut buffer, &tree);
+
+        // Moving the cursor forgets where the parent was moved to from
+        let call = SOURCE.find("f(").expect("Call exists");
+        buffer.set_cursor(call);
+        Buffer::child_node(&mut buffer, &tree);
+        assert_eq!(buffer.cursor, SOURCE.find("(a").expect("Arguments exist"));
+        assert!(buffer.node_history.is_empty());
+    }
+}
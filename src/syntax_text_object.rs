@@ -0,0 +1,229 @@
+use crate::{
+    buffer::Buffer,
+    text_object::{TOBounds, TextObjectType},
+};
+use tree_sitter::{Node, Tree};
+
+// NOTE
+// These are C's node kinds, since Markdown (the only other language we have a grammar for) has
+// no functions, structures or arguments. They'll need to be extended when we add languages.
+pub const FUNCTION_KINDS: &[&str] = &["function_definition"];
+const STRUCTURE_KINDS: &[&str] = &["struct_specifier", "union_specifier", "enum_specifier"];
+const ARGUMENT_LIST_KINDS: &[&str] = &["parameter_list", "argument_list"];
+const COMMENT_KINDS: &[&str] = &["comment"];
+const BLOCK_KINDS: &[&str] = &[
+    "compound_statement",
+    "field_declaration_list",
+    "enumerator_list",
+    "initializer_list",
+];
+
+/// A text object whose bounds come from the parse tree of the buffer rather than from the
+/// characters around the cursor.
+///
+/// Like a `TextObject`, a `level` of `n` selects the `n`th enclosing match, so `2daf` deletes the
+/// function surrounding the function the cursor is in.
+pub struct SyntaxTextObject<'a> {
+    pub name: &'a str,
+    command: fn(buffer: &Buffer, tree: &Tree, to_type: &TextObjectType, level: usize) -> TOBounds,
+}
+
+impl<'a> SyntaxTextObject<'a> {
+    pub const fn new(
+        name: &'a str,
+        command: fn(
+            buffer: &Buffer,
+            tree: &Tree,
+            to_type: &TextObjectType,
+            level: usize,
+        ) -> TOBounds,
+    ) -> Self {
+        SyntaxTextObject { name, command }
+    }
+
+    pub fn bounds(
+        &self,
+        buffer: &Buffer,
+        tree: &Tree,
+        to_type: &TextObjectType,
+        level: usize,
+    ) -> TOBounds {
+        (self.command)(buffer, tree, to_type, level)
+    }
+}
+
+/// The smallest named node containing the cursor
+pub fn node_at_cursor<'t>(buffer: &Buffer, tree: &'t Tree) -> Option<Node<'t>> {
+    let byte = buffer.rope.try_char_to_byte(buffer.cursor).ok()?;
+    tree.root_node().named_descendant_for_byte_range(byte, byte)
+}
+
+/// Walks up the tree from the cursor, returning the `level`th node (one-indexed) that satisfies
+/// `predicate`
+fn enclosing<'t>(
+    buffer: &Buffer,
+    tree: &'t Tree,
+    level: usize,
+    predicate: impl Fn(&Node) -> bool,
+) -> Option<Node<'t>> {
+    let mut node = node_at_cursor(buffer, tree)?;
+    let mut found = 0;
+
+    loop {
+        if predicate(&node) {
+            found += 1;
+            if found >= level {
+                return Some(node);
+            }
+        }
+
+        node = node.parent()?;
+    }
+}
+
+fn is_kind(kinds: &[&str]) -> impl Fn(&Node) -> bool {
+    |node: &Node| kinds.contains(&node.kind())
+}
+
+/// Converts a tree-sitter byte offset into a char index into the buffer.
+/// Returns `None` if the tree is out of date and points past the end of the buffer.
+pub fn byte_to_char(buffer: &Buffer, byte: usize) -> Option<usize> {
+    buffer.rope.try_byte_to_char(byte).ok()
+}
+
+/// The inclusive bounds of `node`
+pub fn node_bounds(buffer: &Buffer, node: &Node) -> TOBounds {
+    let start = byte_to_char(buffer, node.start_byte())?;
+    let end = byte_to_char(buffer, node.end_byte())?;
+
+    if end <= start {
+        return None;
+    }
+
+    Some((start, end - 1))
+}
+
+/// The bounds of `node` without its first and last characters (usually its delimiters)
+fn inner_bounds(buffer: &Buffer, node: &Node) -> TOBounds {
+    let (start, end) = node_bounds(buffer, node)?;
+    if end - start < 2 {
+        return None;
+    }
+
+    Some((start + 1, end - 1))
+}
+
+/// The bounds of the body of `node` (eg. the contents of the braces of a function), falling back
+/// to the entire node for nodes without bodies
+fn body_bounds(buffer: &Buffer, node: &Node) -> TOBounds {
+    node.child_by_field_name("body").map_or_else(
+        || node_bounds(buffer, node),
+        |body| inner_bounds(buffer, &body),
+    )
+}
+
+pub fn function(buffer: &Buffer, tree: &Tree, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let node = enclosing(buffer, tree, level, is_kind(FUNCTION_KINDS))?;
+
+    match to_type {
+        TextObjectType::Inside => body_bounds(buffer, &node),
+        TextObjectType::Around => node_bounds(buffer, &node),
+    }
+}
+
+pub fn structure(buffer: &Buffer, tree: &Tree, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let node = enclosing(buffer, tree, level, is_kind(STRUCTURE_KINDS))?;
+
+    match to_type {
+        TextObjectType::Inside => body_bounds(buffer, &node),
+        TextObjectType::Around => node_bounds(buffer, &node),
+    }
+}
+
+pub fn block(buffer: &Buffer, tree: &Tree, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let node = enclosing(buffer, tree, level, is_kind(BLOCK_KINDS))?;
+
+    match to_type {
+        TextObjectType::Inside => inner_bounds(buffer, &node),
+        TextObjectType::Around => node_bounds(buffer, &node),
+    }
+}
+
+/// Around an argument includes the comma and whitespace separating it from the next argument, or
+/// from the previous one if it's the last argument in the list
+pub fn argument(buffer: &Buffer, tree: &Tree, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let is_argument = |node: &Node| {
+        node.is_named()
+            && node
+                .parent()
+                .is_some_and(|parent| ARGUMENT_LIST_KINDS.contains(&parent.kind()))
+    };
+    let node = enclosing(buffer, tree, level, is_argument)?;
+    let (start, end) = node_bounds(buffer, &node)?;
+
+    if matches!(to_type, TextObjectType::Inside) {
+        return Some((start, end));
+    }
+
+    let comma_after = node.next_sibling().filter(|sibling| sibling.kind() == ",");
+    let comma_before = node.prev_sibling().filter(|sibling| sibling.kind() == ",");
+
+    if comma_after.is_some()
+        && let Some(next) = node.next_named_sibling()
+    {
+        let next_start = byte_to_char(buffer, next.start_byte())?;
+        Some((start, next_start - 1))
+    } else if comma_before.is_some()
+        && let Some(prev) = node.prev_named_sibling()
+    {
+        let prev_end = byte_to_char(buffer, prev.end_byte())?;
+        Some((prev_end, end))
+    } else {
+        Some((start, end))
+    }
+}
+
+/// Inside a comment excludes the comment markers and the whitespace padding them
+pub fn comment(buffer: &Buffer, tree: &Tree, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let node = enclosing(buffer, tree, level, is_kind(COMMENT_KINDS))?;
+    let (mut start, mut end) = node_bounds(buffer, &node)?;
+
+    if matches!(to_type, TextObjectType::Around) {
+        return Some((start, end));
+    }
+
+    let text = buffer.rope.slice(start..=end).to_string();
+    if text.starts_with("//") || text.starts_with("/*") {
+        start += 2;
+    }
+    if text.ends_with("*/") && end >= start + 2 {
+        end -= 2;
+    }
+
+    while start < end && buffer.rope.char(start).is_whitespace() {
+        start += 1;
+    }
+    while end > start && buffer.rope.char(end).is_whitespace() {
+        end -= 1;
+    }
+
+    Some((start, end))
+}
+
+/// Around a node is the smallest named node containing the cursor, with each level selecting its
+/// parent (ie. expanding the selection). Inside a node is the span of its named children.
+pub fn node(buffer: &Buffer, tree: &Tree, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let node = enclosing(buffer, tree, level, |node: &Node| node.is_named())?;
+
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.named_children(&mut cursor).collect();
+
+    match (to_type, children.first(), children.last()) {
+        (TextObjectType::Inside, Some(first), Some(last)) => {
+            let (start, _) = node_bounds(buffer, first)?;
+            let (_, end) = node_bounds(buffer, last)?;
+            Some((start, end))
+        }
+        _ => node_bounds(buffer, &node),
+    }
+}
@@ -9,7 +9,7 @@ pub type TOBounds = Option<(usize, usize)>;
 
 pub struct TextObject<'a> {
     pub name: &'a str,
-    command: fn(buffer: &Buffer) -> TOBounds,
+    command: fn(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds,
 }
 
 // TODO
@@ -25,28 +25,55 @@ pub struct TextObject<'a> {
 // This is cool, but I think the best course of action is the boring one:
 // 1. Move more (if not all) motion logic into the `Buffer` struct
 // 2. Utilize the shared logic in both the sets of motion and text object functions
-
-// TODO
-// Currently, there isn't a counting mechanism to match the corresponding bracket, which is sad
 impl<'a> TextObject<'a> {
-    pub const fn new(name: &'a str, command: fn(buffer: &Buffer) -> TOBounds) -> Self {
+    pub const fn new(
+        name: &'a str,
+        command: fn(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds,
+    ) -> Self {
         TextObject { name, command }
     }
 
     /// Returns the range that the text object occupies
-    pub fn around(&self, buffer: &Buffer) -> TOBounds {
-        (self.command)(buffer)
+    ///
+    /// # Arguments
+    /// - `level`: For text objects that nest, which enclosing match to select, starting from `1`
+    ///   for the innermost one. Otherwise, how many text objects to select.
+    pub fn bounds(&self, buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+        (self.command)(buffer, to_type, level)
     }
+}
 
-    pub fn inside(&self, buffer: &Buffer) -> TOBounds {
-        let (i, j) = (self.command)(buffer)?;
+/// Converts the bounds of a pair of delimiters into the bounds of the text object.
+///
+/// Inside is the range between the delimiters. If the opening delimiter ends its line and the
+/// closing delimiter is only preceded by whitespace on its line, only the lines in between are
+/// selected, like in Vi.
+fn delimited(buffer: &Buffer, to_type: &TextObjectType, bounds: TOBounds) -> TOBounds {
+    let (i, j) = bounds?;
 
-        if i == j {
-            return Some((i, j));
-        }
+    if matches!(to_type, TextObjectType::Around) || i == j {
+        return Some((i, j));
+    }
+    // Empty pairs (eg. `()`) have nothing inside of them
+    if j == i + 1 {
+        return None;
+    }
 
-        Some((i + 1, j - 1))
+    let start_of_close_line = buffer.get_start_of_char_line(j);
+    let only_whitespace_before_close = buffer
+        .rope
+        .slice(start_of_close_line..j)
+        .chars()
+        .all(char::is_whitespace);
+
+    if buffer.rope.char(i + 1) == '\n'
+        && start_of_close_line > i + 2
+        && only_whitespace_before_close
+    {
+        return Some((i + 2, start_of_close_line - 1));
     }
+
+    Some((i + 1, j - 1))
 }
 
 /// Returns the positions of the matching characters `start` and `end`
@@ -57,9 +84,9 @@ impl<'a> TextObject<'a> {
 /// 3. Pair that you are currently on the closing character of
 /// 4. Pair with the opening in front of you
 ///
-/// ## TODO
-/// - Prioritize the pair you are inside of,
-///   separate from the one that starts behind you
+/// ## NOTE
+/// This is only used for quotations, which can't nest and don't span lines, so brackets use
+/// `find_enclosing_pair` instead
 pub fn find_matching(buffer: &Buffer, start: char, end: char) -> TOBounds {
     if buffer.get_curr_char() == start {
         let second = buffer.find_next_on_line(end)?;
@@ -81,26 +108,871 @@ pub fn find_matching(buffer: &Buffer, start: char, end: char) -> TOBounds {
     }
 }
 
-pub fn parentheses(buffer: &Buffer) -> TOBounds {
-    find_matching(buffer, '(', ')')
+/// Splits `chars` into regions, where every character in the same string or comment shares a
+/// region, and every other character is in region `0`.
+///
+/// This is a rough C-like lexer (`"strings"`, `'c'`, `// comments`, and `/* comments */`) rather
+/// than anything language specific, so it errs on the side of not treating things as strings:
+/// - Strings end at the end of their line
+/// - `'` only starts a string if it isn't preceded by an alphanumeric character, so apostrophes in
+///   prose (eg. "don't") are ignored
+fn literal_regions(chars: &[char]) -> Vec<usize> {
+    enum State {
+        Code,
+        String(char),
+        LineComment,
+        BlockComment,
+    }
+
+    let mut regions = vec![0; chars.len()];
+    let mut state = State::Code;
+    let mut region = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let prev = if i == 0 { None } else { Some(chars[i - 1]) };
+
+        match state {
+            State::Code => {
+                let new_state = match (c, next) {
+                    ('/', Some('/')) => Some(State::LineComment),
+                    ('/', Some('*')) => Some(State::BlockComment),
+                    ('\'', _) if prev.is_some_and(char::is_alphanumeric) => None,
+                    ('"' | '\'', _) => Some(State::String(c)),
+                    _ => None,
+                };
+
+                if let Some(new_state) = new_state {
+                    region += 1;
+                    regions[i] = region;
+                    state = new_state;
+
+                    // Skip the `*` so that `/*/` isn't a complete comment
+                    if matches!(state, State::BlockComment) {
+                        regions[i + 1] = region;
+                        i += 1;
+                    }
+                }
+            }
+            State::String(quote) => {
+                regions[i] = region;
+
+                if c == '\\' && next.is_some_and(|next| next != '\n') {
+                    regions[i + 1] = region;
+                    i += 1;
+                } else if c == quote || c == '\n' {
+                    state = State::Code;
+                }
+            }
+            State::LineComment => {
+                if c == '\n' {
+                    state = State::Code;
+                } else {
+                    regions[i] = region;
+                }
+            }
+            State::BlockComment => {
+                regions[i] = region;
+
+                if c == '*' && next == Some('/') {
+                    regions[i + 1] = region;
+                    i += 1;
+                    state = State::Code;
+                }
+            }
+        }
+
+        i += 1;
+    }
+
+    regions
 }
 
-pub fn curly_braces(buffer: &Buffer) -> TOBounds {
-    find_matching(buffer, '{', '}')
+/// Returns the positions of the `level`th pair of `open` and `close` enclosing the cursor, counting
+/// nested pairs and working across lines. Delimiters in strings or comments are skipped, unless
+/// the cursor is in the same string or comment (and it contains a matching pair).
+///
+/// ## Matching Priority:
+/// 1. Pair that you are currently on the opening character of
+/// 2. Innermost pair surrounding the cursor (including the pair you are on the closing character of)
+/// 3. Pair with the opening in front of you on the current line (only for the innermost level)
+pub fn find_enclosing_pair(buffer: &Buffer, open: char, close: char, level: usize) -> TOBounds {
+    let chars: Vec<char> = buffer.rope.chars().collect();
+    let regions = literal_regions(&chars);
+    let cursor_region = regions.get(buffer.cursor).copied().unwrap_or(0);
+
+    find_enclosing_pair_in_region(
+        buffer,
+        &chars,
+        &regions,
+        cursor_region,
+        (open, close),
+        level,
+    )
+    .or_else(|| {
+        if cursor_region == 0 {
+            return None;
+        }
+        find_enclosing_pair_in_region(buffer, &chars, &regions, 0, (open, close), level)
+    })
 }
 
-pub fn square_braces(buffer: &Buffer) -> TOBounds {
-    find_matching(buffer, '[', ']')
+/// Does the work of `find_enclosing_pair`, only considering delimiters in `region`
+fn find_enclosing_pair_in_region(
+    buffer: &Buffer,
+    chars: &[char],
+    regions: &[usize],
+    region: usize,
+    (open, close): (char, char),
+    level: usize,
+) -> TOBounds {
+    let is_delimiter = |i: usize, delimiter: char| chars[i] == delimiter && regions[i] == region;
+
+    // Scans backwards from (but not including) `from` for an `open` without a matching `close`
+    let unmatched_open = |from: usize| -> Option<usize> {
+        let mut depth = 0;
+        for i in (0..from).rev() {
+            if is_delimiter(i, close) {
+                depth += 1;
+            } else if is_delimiter(i, open) {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+        }
+
+        None
+    };
+
+    // Scans forwards from (but not including) `from` for the `close` matching the `open` at `from`
+    let matching_close = |from: usize| -> Option<usize> {
+        let mut depth = 0;
+        for i in from + 1..chars.len() {
+            if is_delimiter(i, open) {
+                depth += 1;
+            } else if is_delimiter(i, close) {
+                if depth == 0 {
+                    return Some(i);
+                }
+                depth -= 1;
+            }
+        }
+
+        None
+    };
+
+    let on_open = buffer.cursor < chars.len() && is_delimiter(buffer.cursor, open);
+    let mut first = if on_open {
+        Some(buffer.cursor)
+    } else {
+        unmatched_open(buffer.cursor)
+    };
+
+    for _ in 1..level {
+        first = unmatched_open(first?);
+    }
+
+    let first = match first {
+        Some(first) => first,
+        None if level <= 1 => {
+            let end_of_line = buffer.get_end_of_line();
+            (buffer.cursor..end_of_line).find(|i| is_delimiter(*i, open))?
+        }
+        None => return None,
+    };
+    let second = matching_close(first)?;
+
+    Some((first, second))
+}
+
+pub fn parentheses(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    delimited(
+        buffer,
+        to_type,
+        find_enclosing_pair(buffer, '(', ')', level),
+    )
+}
+
+pub fn curly_braces(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    delimited(
+        buffer,
+        to_type,
+        find_enclosing_pair(buffer, '{', '}', level),
+    )
+}
+
+pub fn square_braces(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    delimited(
+        buffer,
+        to_type,
+        find_enclosing_pair(buffer, '[', ']', level),
+    )
+}
+
+// NOTE
+// Quotations can't nest, so there are no outer levels to select
+pub fn quotations(buffer: &Buffer, to_type: &TextObjectType, _level: usize) -> TOBounds {
+    delimited(buffer, to_type, find_matching(buffer, '"', '"'))
 }
 
-pub fn quotations(buffer: &Buffer) -> TOBounds {
-    find_matching(buffer, '\"', '\"')
+pub fn single_quotations(buffer: &Buffer, to_type: &TextObjectType, _level: usize) -> TOBounds {
+    delimited(buffer, to_type, find_matching(buffer, '\'', '\''))
 }
 
-pub fn single_quotations(buffer: &Buffer) -> TOBounds {
-    find_matching(buffer, '\'', '\'')
+pub fn grav(buffer: &Buffer, to_type: &TextObjectType, _level: usize) -> TOBounds {
+    delimited(buffer, to_type, find_matching(buffer, '`', '`'))
 }
 
-pub fn grav(buffer: &Buffer) -> TOBounds {
-    find_matching(buffer, '`', '`')
+// Words
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Symbol,
+}
+
+/// A word is a run of characters of the same class (see "word" in the manual).
+/// For WORDs, every non-whitespace character is in the same class.
+fn char_class(c: char, big: bool) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if big || c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Symbol
+    }
+}
+
+/// Returns the bounds of `count` words (or WORDs if `big`) starting with the one under the cursor.
+/// Words never span lines.
+///
+/// Around also selects the whitespace after each word, or the whitespace before the first word if
+/// there's none after the last one. If the cursor is on whitespace, around instead selects the
+/// whitespace along with the word after it.
+fn word_generic(buffer: &Buffer, to_type: &TextObjectType, count: usize, big: bool) -> TOBounds {
+    let line_start = buffer.get_start_of_line();
+    let line_end = line_start
+        + buffer
+            .get_curr_line()
+            .chars()
+            .take_while(|c| *c != '\n')
+            .count();
+
+    if buffer.cursor >= line_end {
+        return None;
+    }
+
+    let class = |i: usize| char_class(buffer.rope.char(i), big);
+    // The (exclusive) end of the run of characters with the same class as `i`
+    let run_end = |i: usize| {
+        (i..line_end)
+            .find(|j| class(*j) != class(i))
+            .unwrap_or(line_end)
+    };
+    // The (inclusive) start of the run of characters with the same class as `i`
+    let run_start = |i: usize| {
+        (line_start..=i)
+            .rev()
+            .find(|j| class(*j) != class(i))
+            .map_or(line_start, |j| j + 1)
+    };
+
+    let on_whitespace = class(buffer.cursor) == CharClass::Whitespace;
+    let mut start = run_start(buffer.cursor);
+    let mut end = start;
+    let mut trailing_whitespace = false;
+
+    for _ in 0..count {
+        if end >= line_end {
+            break;
+        }
+
+        match to_type {
+            TextObjectType::Inside => end = run_end(end),
+            TextObjectType::Around => {
+                if on_whitespace && class(end) == CharClass::Whitespace {
+                    end = run_end(end);
+                }
+                if end < line_end {
+                    end = run_end(end);
+                }
+
+                trailing_whitespace =
+                    !on_whitespace && end < line_end && class(end) == CharClass::Whitespace;
+                if trailing_whitespace {
+                    end = run_end(end);
+                }
+            }
+        }
+    }
+
+    // NOTE
+    // Indentation is never taken, since then deleting the last word on a line would unindent it
+    if matches!(to_type, TextObjectType::Around)
+        && !on_whitespace
+        && !trailing_whitespace
+        && start > line_start
+        && class(start - 1) == CharClass::Whitespace
+        && run_start(start - 1) > line_start
+    {
+        start = run_start(start - 1);
+    }
+
+    Some((start, end - 1))
+}
+
+pub fn word(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    word_generic(buffer, to_type, level, false)
+}
+
+pub fn big_word(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    word_generic(buffer, to_type, level, true)
+}
+
+// Sentences
+/// Returns the end of the sentence if `i` is a sentence's closing punctuation (`.`, `!`, or `?`),
+/// including any closing brackets or quotes after it.
+/// It's only the end of a sentence if it's followed by whitespace or the end of the buffer.
+fn sentence_end_at(buffer: &Buffer, i: usize) -> Option<usize> {
+    if !".!?".contains(buffer.rope.char(i)) {
+        return None;
+    }
+
+    let len = buffer.rope.len_chars();
+    let mut end = i;
+    while end + 1 < len && ")]\"'".contains(buffer.rope.char(end + 1)) {
+        end += 1;
+    }
+
+    if end + 1 == len || buffer.rope.char(end + 1).is_whitespace() {
+        Some(end)
+    } else {
+        None
+    }
+}
+
+/// Whether `i` is the last character before an empty line, which ends both paragraphs and sentences
+fn ends_paragraph(buffer: &Buffer, i: usize) -> bool {
+    buffer.rope.get_char(i + 1) == Some('\n') && buffer.rope.get_char(i + 2) == Some('\n')
+}
+
+/// Returns the (inclusive) bounds of every sentence in the buffer, in order
+fn sentences(buffer: &Buffer) -> Vec<(usize, usize)> {
+    let len = buffer.rope.len_chars();
+    let mut sentences = Vec::new();
+    let mut i = 0;
+
+    while i < len {
+        if buffer.rope.char(i).is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let end = loop {
+            if let Some(end) = sentence_end_at(buffer, i) {
+                break end;
+            }
+            if i + 1 >= len || ends_paragraph(buffer, i) {
+                break i;
+            }
+            i += 1;
+        };
+
+        let mut trimmed_end = end;
+        while trimmed_end > start && buffer.rope.char(trimmed_end).is_whitespace() {
+            trimmed_end -= 1;
+        }
+
+        sentences.push((start, trimmed_end));
+        i = end + 1;
+    }
+
+    sentences
+}
+
+/// Selects `level` sentences, starting with the one under the cursor (or the next one if the cursor
+/// is between sentences).
+///
+/// Around also selects the whitespace after the last sentence, or the whitespace before the first
+/// sentence on its line if there's none after. Neither crosses an empty line.
+pub fn sentence(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let sentences = sentences(buffer);
+    let first = sentences
+        .iter()
+        .position(|(_, end)| *end >= buffer.cursor)?;
+    let last = usize::min(first + usize::max(level, 1), sentences.len()) - 1;
+
+    let (mut start, _) = sentences[first];
+    let (_, mut end) = sentences[last];
+
+    if matches!(to_type, TextObjectType::Inside) {
+        return Some((start, end));
+    }
+
+    // NOTE
+    // The trailing newline of the buffer isn't taken, since there's nothing to join it with
+    let len = buffer.rope.len_chars();
+    let last_selectable = if buffer.rope.char(len - 1) == '\n' {
+        len.saturating_sub(2)
+    } else {
+        len - 1
+    };
+
+    let anchor = end;
+    while end < last_selectable
+        && !ends_paragraph(buffer, end)
+        && buffer.rope.char(end + 1).is_whitespace()
+    {
+        end += 1;
+    }
+
+    if end == anchor {
+        while start > 0
+            && buffer.rope.char(start - 1) != '\n'
+            && buffer.rope.char(start - 1).is_whitespace()
+        {
+            start -= 1;
+        }
+    }
+
+    Some((start, end))
+}
+
+// Paragraphs
+fn is_empty_row(buffer: &Buffer, row: usize) -> bool {
+    buffer.rope.line(row).chars().all(|c| c == '\n')
+}
+
+/// The first and last rows of the block of lines containing `row`, where a block is either a
+/// paragraph or a run of empty lines
+fn block_rows(buffer: &Buffer, row: usize) -> (usize, usize) {
+    // NOTE
+    // Scanning the rows directly rather than moving a cursor keeps the last line right when
+    // there's no trailing newline
+    let empty = is_empty_row(buffer, row);
+    let first = (0..row)
+        .rev()
+        .find(|row| is_empty_row(buffer, *row) != empty)
+        .map_or(0, |row| row + 1);
+    let last = (row..buffer.len())
+        .find(|row| is_empty_row(buffer, *row) != empty)
+        .map_or(buffer.len() - 1, |row| row - 1);
+
+    (first, last)
+}
+
+/// Selects `level` paragraphs linewise, starting with the one under the cursor.
+/// Runs of empty lines count as paragraphs for inside.
+///
+/// Around also selects the empty lines after each paragraph, or the empty lines before the first
+/// paragraph if there are none after the last one. If the cursor is on an empty line, around
+/// instead selects the empty lines along with the paragraph after them.
+pub fn paragraph(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let len = buffer.rope.len_chars();
+    if len == 0 {
+        return None;
+    }
+
+    // NOTE
+    // The line after a trailing newline isn't a real line, so it can't be selected
+    let last_row = if buffer.rope.char(len - 1) == '\n' {
+        buffer.len() - 2
+    } else {
+        buffer.len() - 1
+    };
+
+    let blocks_per_level = match to_type {
+        TextObjectType::Inside => 1,
+        TextObjectType::Around => 2,
+    };
+    let wanted_blocks = usize::max(level, 1) * blocks_per_level;
+
+    let row = usize::min(buffer.get_row(), last_row);
+    let (mut first, mut last) = block_rows(buffer, row);
+    let mut blocks = 1;
+
+    while blocks < wanted_blocks && last < last_row {
+        last = block_rows(buffer, last + 1).1;
+        blocks += 1;
+    }
+
+    if matches!(to_type, TextObjectType::Around)
+        && blocks < wanted_blocks
+        && !is_empty_row(buffer, first)
+        && first > 0
+    {
+        first = block_rows(buffer, first - 1).0;
+    }
+
+    let last = usize::min(last, last_row);
+    Some((
+        buffer.get_start_of_n_line(first),
+        buffer.get_end_of_n_line(last),
+    ))
+}
+
+// Tags
+struct Tag {
+    name: String,
+    start: usize,
+    end: usize,
+    closing: bool,
+}
+
+/// Finds every opening and closing XML/HTML tag in the buffer, skipping comments, declarations
+/// (eg. `<!DOCTYPE html>`), processing instructions, and self-closing tags
+fn tags(buffer: &Buffer) -> Vec<Tag> {
+    let chars: Vec<char> = buffer.rope.chars().collect();
+    let mut tags = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] != '<' {
+            i += 1;
+            continue;
+        }
+
+        if chars[i + 1..].starts_with(&['!', '-', '-']) {
+            let Some(comment_end) =
+                (i + 4..chars.len()).find(|j| chars[*j..].starts_with(&['-', '-', '>']))
+            else {
+                break;
+            };
+
+            i = comment_end + 3;
+            continue;
+        }
+
+        let closing = chars.get(i + 1) == Some(&'/');
+        let name_start = i + 1 + usize::from(closing);
+        let name: String = chars[usize::min(name_start, chars.len())..]
+            .iter()
+            .take_while(|c| c.is_alphanumeric() || "-_:.".contains(**c))
+            .collect();
+
+        // NOTE
+        // Things like `a < b` aren't tags
+        if !name.starts_with(char::is_alphabetic) {
+            i += 1;
+            continue;
+        }
+
+        let Some(end) = (name_start..chars.len()).find(|j| chars[*j] == '>') else {
+            break;
+        };
+
+        if chars[end - 1] != '/' {
+            tags.push(Tag {
+                name,
+                start: i,
+                end,
+                closing,
+            });
+        }
+
+        i = end + 1;
+    }
+
+    tags
+}
+
+/// Selects the `level`th pair of tags enclosing the cursor, starting from `1` for the innermost one.
+/// Unclosed tags (eg. `<br>`) are ignored.
+pub fn tag(buffer: &Buffer, to_type: &TextObjectType, level: usize) -> TOBounds {
+    let mut open_tags: Vec<Tag> = Vec::new();
+    let mut enclosing = Vec::new();
+
+    for tag in tags(buffer) {
+        if !tag.closing {
+            open_tags.push(tag);
+            continue;
+        }
+
+        let Some(idx) = open_tags
+            .iter()
+            .rposition(|open| open.name.eq_ignore_ascii_case(&tag.name))
+        else {
+            continue;
+        };
+        // NOTE
+        // Anything opened after the matching tag was never closed, so it's dropped
+        let Some(open) = open_tags.drain(idx..).next() else {
+            continue;
+        };
+
+        if open.start <= buffer.cursor && buffer.cursor <= tag.end {
+            enclosing.push((open, tag));
+        }
+    }
+
+    // Inner pairs are always closed before outer pairs, so they come first
+    let (open, close) = enclosing.get(usize::max(level, 1) - 1)?;
+
+    match to_type {
+        TextObjectType::Around => Some((open.start, close.end)),
+        TextObjectType::Inside if close.start == open.end + 1 => None,
+        TextObjectType::Inside => Some((open.end + 1, close.start - 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    /// A buffer of `text` with the cursor where the `|` is (which is removed)
+    fn buffer_at(text: &str) -> Buffer {
+        let cursor = text.find('|').expect("Text has a cursor");
+        let mut buffer = Buffer::new();
+        buffer.rope = Rope::from(text.replacen('|', "", 1));
+        buffer.set_cursor(text[..cursor].chars().count());
+        buffer
+    }
+
+    /// The text a text object selects
+    fn selected(buffer: &Buffer, bounds: TOBounds) -> Option<String> {
+        let (start, end) = bounds?;
+        Some(buffer.rope.slice(start..=end).to_string())
+    }
+
+    fn regions(text: &str) -> Vec<usize> {
+        literal_regions(&text.chars().collect::<Vec<char>>())
+    }
+
+    #[test]
+    fn quotes_in_comments_dont_start_strings() {
+        let text = "a // don't \"quote\nb /* \" */ c";
+        let regions = regions(text);
+        let region_of = |pat: &str| regions[text.find(pat).expect("Pattern is in the text")];
+
+        assert_eq!(region_of("a"), 0);
+        assert_ne!(region_of("don't"), 0);
+        assert_eq!(region_of("don't"), region_of("\"quote"));
+        assert_eq!(
+            region_of("b"),
+            0,
+            "Line comments end at the end of the line"
+        );
+        assert_ne!(region_of("\" */"), 0);
+        assert_eq!(region_of(" c"), 0);
+    }
+
+    #[test]
+    fn escaped_quotes_dont_end_strings() {
+        let text = r#"x = "a \" b"; y"#;
+        let regions = regions(text);
+        let string = regions[text.find('a').expect("a is in the text")];
+
+        assert_ne!(string, 0);
+        assert_eq!(regions[text.find(" b").expect("b is in the text")], string);
+        assert_eq!(regions[text.find("; y").expect("y is in the text")], 0);
+    }
+
+    #[test]
+    fn apostrophes_in_words_dont_start_strings() {
+        assert!(regions("it's (a) pair").iter().all(|region| *region == 0));
+    }
+
+    #[test]
+    fn delimiters_in_strings_and_comments_are_skipped() {
+        let buffer = buffer_at("f(a, \")\", |b) // (\n");
+        assert_eq!(
+            selected(&buffer, parentheses(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("a, \")\", b")
+        );
+
+        let buffer = buffer_at("g(/* ) */ |x)");
+        assert_eq!(
+            selected(&buffer, parentheses(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("(/* ) */ x)")
+        );
+    }
+
+    #[test]
+    fn escaped_delimiters_stay_in_their_string() {
+        // The cursor is in the string, so the pair in the string is used
+        let buffer = buffer_at(r#"s("a \" (|b) c")"#);
+        assert_eq!(
+            selected(&buffer, parentheses(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("(b)")
+        );
+
+        // Without a pair in the string, the pair around it is used instead
+        let buffer = buffer_at(r#"s("a \" |b c")"#);
+        assert_eq!(
+            selected(&buffer, parentheses(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some(r#""a \" b c""#)
+        );
+    }
+
+    #[test]
+    fn nested_pairs_select_by_level() {
+        let buffer = buffer_at("f(g(|a), b)");
+        assert_eq!(
+            selected(&buffer, parentheses(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("a")
+        );
+        assert_eq!(
+            selected(&buffer, parentheses(&buffer, &TextObjectType::Inside, 2)).as_deref(),
+            Some("g(a), b")
+        );
+        assert_eq!(parentheses(&buffer, &TextObjectType::Inside, 3), None);
+
+        // Pairs closed before the cursor don't count
+        let buffer = buffer_at("f(g(a), |b)");
+        assert_eq!(
+            selected(&buffer, parentheses(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("(g(a), b)")
+        );
+    }
+
+    #[test]
+    fn pairs_span_lines() {
+        let buffer = buffer_at("if (x) {\n    |y;\n}\n");
+        assert_eq!(
+            selected(&buffer, curly_braces(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("    y;\n")
+        );
+        assert_eq!(
+            selected(&buffer, curly_braces(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("{\n    y;\n}")
+        );
+    }
+
+    #[test]
+    fn sentences_at_the_end_of_the_buffer() {
+        let buffer = buffer_at("One. Two |words");
+        assert_eq!(
+            selected(&buffer, sentence(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("Two words")
+        );
+        // There's no whitespace after the last sentence, so the whitespace before it is taken
+        assert_eq!(
+            selected(&buffer, sentence(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some(" Two words")
+        );
+
+        // The buffer's trailing newline is never taken
+        let buffer = buffer_at("One. |Two.\n");
+        assert_eq!(
+            selected(&buffer, sentence(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some(" Two.")
+        );
+    }
+
+    #[test]
+    fn sentences_end_at_punctuation_and_empty_lines() {
+        let buffer = buffer_at("(He |said \"hi.\") Then left!\n\nNext");
+        assert_eq!(
+            selected(&buffer, sentence(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("(He said \"hi.\")")
+        );
+        assert_eq!(
+            selected(&buffer, sentence(&buffer, &TextObjectType::Around, 2)).as_deref(),
+            Some("(He said \"hi.\") Then left!")
+        );
+
+        let buffer = buffer_at("No stop\n\n|Next one");
+        assert_eq!(
+            selected(&buffer, sentence(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("Next one")
+        );
+    }
+
+    #[test]
+    fn paragraphs_and_runs_of_empty_lines() {
+        let text = "a\nb\n\n\n\nc\n";
+
+        let buffer = buffer_at(&text.replacen('b', "|b", 1));
+        assert_eq!(
+            selected(&buffer, paragraph(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("a\nb\n")
+        );
+        assert_eq!(
+            selected(&buffer, paragraph(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("a\nb\n\n\n\n")
+        );
+
+        // On an empty line, inside is the run of empty lines and around adds the next paragraph
+        let buffer = buffer_at("a\nb\n\n|\n\nc\n");
+        assert_eq!(
+            selected(&buffer, paragraph(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("\n\n\n")
+        );
+        assert_eq!(
+            selected(&buffer, paragraph(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("\n\n\nc\n")
+        );
+    }
+
+    #[test]
+    fn paragraphs_at_the_end_of_the_buffer() {
+        // Without empty lines after the last paragraph, around takes the ones before it
+        let buffer = buffer_at("a\n\n\n|c\nd\n");
+        assert_eq!(
+            selected(&buffer, paragraph(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("\n\nc\nd\n")
+        );
+
+        let buffer = buffer_at("a\n\n|c");
+        assert_eq!(
+            selected(&buffer, paragraph(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("c")
+        );
+        assert_eq!(
+            selected(&buffer, paragraph(&buffer, &TextObjectType::Inside, 5)).as_deref(),
+            Some("c")
+        );
+    }
+
+    #[test]
+    fn tags_skip_self_closing_and_unclosed_tags() {
+        let buffer = buffer_at("<div><br/><img src=x>te|xt</div>");
+        assert_eq!(
+            selected(&buffer, tag(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("<br/><img src=x>text")
+        );
+        assert_eq!(
+            selected(&buffer, tag(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("<div><br/><img src=x>text</div>")
+        );
+    }
+
+    #[test]
+    fn tags_nest_and_ignore_comments() {
+        let buffer = buffer_at("<a><B><!-- </b> --><i>x|</i></b></a>");
+        assert_eq!(
+            selected(&buffer, tag(&buffer, &TextObjectType::Inside, 1)).as_deref(),
+            Some("x")
+        );
+        assert_eq!(
+            selected(&buffer, tag(&buffer, &TextObjectType::Around, 2)).as_deref(),
+            Some("<B><!-- </b> --><i>x</i></b>")
+        );
+        assert_eq!(
+            selected(&buffer, tag(&buffer, &TextObjectType::Inside, 3)).as_deref(),
+            Some("<B><!-- </b> --><i>x</i></b>")
+        );
+        assert_eq!(tag(&buffer, &TextObjectType::Inside, 4), None);
+    }
+
+    #[test]
+    fn mismatched_tags_pair_with_the_nearest_open_tag() {
+        // `<i>` is never closed, so it's dropped when `</b>` closes `<b>`
+        let buffer = buffer_at("<b><i>x|</b></i>");
+        assert_eq!(
+            selected(&buffer, tag(&buffer, &TextObjectType::Around, 1)).as_deref(),
+            Some("<b><i>x</b>")
+        );
+
+        let buffer = buffer_at("<b>|</b>");
+        assert_eq!(tag(&buffer, &TextObjectType::Inside, 1), None);
+
+        let buffer = buffer_at("a |< b > c");
+        assert_eq!(tag(&buffer, &TextObjectType::Around, 1), None);
+    }
 }
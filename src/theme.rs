@@ -0,0 +1,510 @@
+use crate::config;
+use anyhow::{Result, bail};
+use crossterm::style::Color;
+use serde::Deserialize;
+use std::{collections::HashMap, env};
+
+/// A semantic highlight group.
+/// Languages and UI elements only ever refer to these, and the current `Theme` decides what
+/// color each one is actually drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HLGroup {
+    // Code
+    Text,
+    Keyword,
+    String,
+    Comment,
+    Function,
+    Type,
+    CustomType,
+    Field,
+    Constant,
+    Operator,
+    Preproc,
+    Delimiter,
+
+    // Markdown
+    Heading1,
+    Heading2,
+    Heading3,
+    Heading4,
+    Heading5,
+    Heading6,
+    LinkText,
+    LinkDestination,
+    ListMarker,
+
+    // Interface
+    Gutter,
+    EmptyLine,
+    StatusBar,
+    Selection,
+    SearchMatch,
+    WarningMessage,
+    ErrorMessage,
+    DiagnosticError,
+    DiagnosticWarning,
+    DiagnosticInfo,
+    DiagnosticHint,
+    GitAdded,
+    GitChanged,
+    GitDeleted,
+    Popup,
+}
+
+/// The names used to refer to each `HLGroup` in theme files
+const GROUP_NAMES: [(&str, HLGroup); 36] = [
+    ("text", HLGroup::Text),
+    ("keyword", HLGroup::Keyword),
+    ("string", HLGroup::String),
+    ("comment", HLGroup::Comment),
+    ("function", HLGroup::Function),
+    ("type", HLGroup::Type),
+    ("custom_type", HLGroup::CustomType),
+    ("field", HLGroup::Field),
+    ("constant", HLGroup::Constant),
+    ("operator", HLGroup::Operator),
+    ("preproc", HLGroup::Preproc),
+    ("delimiter", HLGroup::Delimiter),
+    ("heading1", HLGroup::Heading1),
+    ("heading2", HLGroup::Heading2),
+    ("heading3", HLGroup::Heading3),
+    ("heading4", HLGroup::Heading4),
+    ("heading5", HLGroup::Heading5),
+    ("heading6", HLGroup::Heading6),
+    ("link_text", HLGroup::LinkText),
+    ("link_destination", HLGroup::LinkDestination),
+    ("list_marker", HLGroup::ListMarker),
+    ("gutter", HLGroup::Gutter),
+    ("empty_line", HLGroup::EmptyLine),
+    ("status_bar", HLGroup::StatusBar),
+    ("selection", HLGroup::Selection),
+    ("search_match", HLGroup::SearchMatch),
+    ("warning_message", HLGroup::WarningMessage),
+    ("error_message", HLGroup::ErrorMessage),
+    ("diagnostic_error", HLGroup::DiagnosticError),
+    ("diagnostic_warning", HLGroup::DiagnosticWarning),
+    ("diagnostic_info", HLGroup::DiagnosticInfo),
+    ("diagnostic_hint", HLGroup::DiagnosticHint),
+    ("git_added", HLGroup::GitAdded),
+    ("git_changed", HLGroup::GitChanged),
+    ("git_deleted", HLGroup::GitDeleted),
+    ("popup", HLGroup::Popup),
+];
+
+impl HLGroup {
+    pub fn from_name(name: &str) -> Option<Self> {
+        GROUP_NAMES
+            .iter()
+            .find(|(group_name, _)| *group_name == name)
+            .map(|(_, group)| *group)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Style {
+    pub const fn fg(fg: Color) -> Self {
+        Self {
+            fg,
+            bg: Color::Reset,
+        }
+    }
+
+    pub const fn new(fg: Color, bg: Color) -> Self {
+        Self { fg, bg }
+    }
+}
+
+/// How many colors the terminal we're drawing to can display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorSupport {
+    /// Guesses the color support of the terminal from `$COLORTERM` and `$TERM`, the same way most
+    /// terminal programs do
+    pub fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// Converts `color` into the closest color this terminal can actually display
+    pub fn downgrade(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(rgb_to_ansi256(r, g, b)),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => nearest_ansi16(r, g, b),
+            (Self::Ansi16, Color::AnsiValue(n)) => {
+                let (r, g, b) = ansi256_to_rgb(n);
+                nearest_ansi16(r, g, b)
+            }
+            (_, color) => color,
+        }
+    }
+}
+
+/// Maps every `HLGroup` onto a `Style`
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    styles: HashMap<HLGroup, Style>,
+}
+
+impl Theme {
+    pub fn style(&self, group: HLGroup) -> Style {
+        self.styles
+            .get(&group)
+            .or_else(|| self.styles.get(&HLGroup::Text))
+            .copied()
+            .unwrap_or(Style::fg(Color::Reset))
+    }
+
+    pub fn fg(&self, group: HLGroup) -> Color {
+        self.style(group).fg
+    }
+
+    /// Loads the theme called `name`, first looking in `$XDG_CONFIG_HOME/orinfar/themes/`, then
+    /// falling back to the built-in themes
+    ///
+    /// # Errors
+    /// - If no theme called `name` exists
+    /// - If the theme file can't be read or is malformed
+    pub fn load(name: &str) -> Result<Self> {
+        let support = ColorSupport::detect();
+
+        let theme = match config::find(&format!("themes/{name}.toml"))? {
+            Some(file) => Self::from_file(name, file)?,
+            None => Self::builtin(name)?,
+        };
+
+        Ok(theme.downgraded(support))
+    }
+
+    /// # Errors
+    /// - If it refers to a highlight group, color, or parent theme that doesn't exist
+    fn from_file(name: &str, file: ThemeFile) -> Result<Self> {
+        let mut theme = Self::builtin(file.inherits.as_deref().unwrap_or(DEFAULT_THEME))?;
+        theme.name = name.to_string();
+
+        for (group_name, spec) in file.groups {
+            let Some(group) = HLGroup::from_name(&group_name) else {
+                bail!("Unknown highlight group in theme {name}: {group_name}");
+            };
+
+            let style = match spec {
+                StyleSpec::Fg(fg) => Style::fg(parse_color(&fg)?),
+                StyleSpec::Full { fg, bg } => {
+                    let current = theme.style(group);
+                    Style::new(
+                        fg.as_deref().map_or(Ok(current.fg), parse_color)?,
+                        bg.as_deref().map_or(Ok(current.bg), parse_color)?,
+                    )
+                }
+            };
+
+            theme.styles.insert(group, style);
+        }
+
+        Ok(theme)
+    }
+
+    /// # Errors
+    /// - If there's no built-in theme called `name`
+    pub fn builtin(name: &str) -> Result<Self> {
+        let styles: &[(HLGroup, Style)] = match name {
+            "everforest" => &EVERFOREST,
+            "base16" => &BASE16,
+            "monochrome" => &MONOCHROME,
+            _ => bail!("Unknown theme: {name}"),
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            styles: styles.iter().copied().collect(),
+        })
+    }
+
+    fn downgraded(mut self, support: ColorSupport) -> Self {
+        self.styles.values_mut().for_each(|style| {
+            style.fg = support.downgrade(style.fg);
+            style.bg = support.downgrade(style.bg);
+        });
+
+        self
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::builtin(DEFAULT_THEME)
+            .expect("The default theme should always exist")
+            .downgraded(ColorSupport::detect())
+    }
+}
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    inherits: Option<String>,
+    #[serde(default)]
+    groups: HashMap<String, StyleSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StyleSpec {
+    Fg(String),
+    Full {
+        fg: Option<String>,
+        bg: Option<String>,
+    },
+}
+
+/// Parses a color in any of the following forms:
+/// - A name, as accepted by crossterm (eg. `dark_grey`)
+/// - A hex code (eg. `#e69875`)
+/// - An ansi 256 color code (eg. `208`)
+fn parse_color(str: &str) -> Result<Color> {
+    let str = str.trim();
+
+    if let Some(hex) = str.strip_prefix('#') {
+        // Checking the bytes are ASCII first means the channels can be sliced out by byte
+        if hex.len() != 6 || !hex.is_ascii() {
+            bail!("Invalid hex color: {str}");
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+        if let (Ok(r), Ok(g), Ok(b)) = (channel(0), channel(2), channel(4)) {
+            return Ok(Color::Rgb { r, g, b });
+        }
+    } else if let Ok(n) = str.parse::<u8>() {
+        return Ok(Color::AnsiValue(n));
+    } else if let Ok(color) = Color::try_from(str) {
+        return Ok(color);
+    }
+
+    bail!("Invalid color: {str}")
+}
+
+/// The standard xterm values for the 16 basic colors
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+fn nearest_ansi16(r: u8, g: u8, b: u8) -> Color {
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        let dr = i32::from(r) - i32::from(r2);
+        let dg = i32::from(g) - i32::from(g2);
+        let db = i32::from(b) - i32::from(b2);
+        dr * dr + dg * dg + db * db
+    };
+
+    ANSI16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+/// Maps a channel onto the 6 levels of the 256 color cube
+const fn cube_level(v: u8) -> u8 {
+    if v < 48 {
+        0
+    } else if v < 115 {
+        1
+    } else {
+        (v - 35) / 40
+    }
+}
+
+const fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // Greys get the dedicated grayscale ramp since it's much finer than the cube
+    if r == g && g == b {
+        return if r < 8 {
+            16
+        } else if r > 248 {
+            231
+        } else {
+            232 + (r - 8) / 10
+        };
+    }
+
+    16 + 36 * cube_level(r) + 6 * cube_level(g) + cube_level(b)
+}
+
+fn ansi256_to_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..16 => ANSI16[n as usize].1,
+        16..232 => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            (level(n / 36), level((n / 6) % 6), level(n % 6))
+        }
+        232.. => {
+            let v = 8 + (n - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+pub const DEFAULT_THEME: &str = "everforest";
+
+// NOTE
+// My special orange since my colorscheme (everforest) isn't actually base16 compliant
+const ORANGE: Color = Color::Rgb {
+    r: 230,
+    g: 152,
+    b: 117,
+};
+
+const EVERFOREST: [(HLGroup, Style); 36] = [
+    (HLGroup::Text, Style::fg(Color::Grey)),
+    (HLGroup::Keyword, Style::fg(Color::Red)),
+    (HLGroup::String, Style::fg(Color::Green)),
+    (HLGroup::Comment, Style::fg(Color::DarkGrey)),
+    (HLGroup::Function, Style::fg(Color::Green)),
+    (HLGroup::Type, Style::fg(Color::Yellow)),
+    (HLGroup::CustomType, Style::fg(Color::DarkMagenta)),
+    (HLGroup::Field, Style::fg(Color::Blue)),
+    (HLGroup::Constant, Style::fg(Color::Magenta)),
+    (HLGroup::Operator, Style::fg(ORANGE)),
+    (HLGroup::Preproc, Style::fg(Color::DarkRed)),
+    (HLGroup::Delimiter, Style::fg(Color::DarkGrey)),
+    (HLGroup::Heading1, Style::fg(Color::Red)),
+    (HLGroup::Heading2, Style::fg(ORANGE)),
+    (HLGroup::Heading3, Style::fg(Color::Yellow)),
+    (HLGroup::Heading4, Style::fg(Color::Green)),
+    (HLGroup::Heading5, Style::fg(Color::Blue)),
+    (HLGroup::Heading6, Style::fg(Color::Magenta)),
+    (HLGroup::LinkText, Style::fg(Color::Green)),
+    (HLGroup::LinkDestination, Style::fg(Color::Blue)),
+    (HLGroup::ListMarker, Style::fg(Color::Blue)),
+    (HLGroup::Gutter, Style::fg(Color::Grey)),
+    (HLGroup::EmptyLine, Style::fg(Color::DarkGrey)),
+    (HLGroup::StatusBar, Style::fg(Color::White)),
+    (HLGroup::Selection, Style::new(Color::Black, Color::Grey)),
+    (
+        HLGroup::SearchMatch,
+        Style::new(Color::Black, Color::Yellow),
+    ),
+    (HLGroup::WarningMessage, Style::fg(ORANGE)),
+    (HLGroup::ErrorMessage, Style::fg(Color::Red)),
+    (HLGroup::DiagnosticError, Style::fg(Color::Red)),
+    (HLGroup::DiagnosticWarning, Style::fg(ORANGE)),
+    (HLGroup::DiagnosticInfo, Style::fg(Color::Blue)),
+    (HLGroup::DiagnosticHint, Style::fg(Color::Green)),
+    (HLGroup::GitAdded, Style::fg(Color::Green)),
+    (HLGroup::GitChanged, Style::fg(Color::Blue)),
+    (HLGroup::GitDeleted, Style::fg(Color::Red)),
+    (HLGroup::Popup, Style::new(Color::White, Color::DarkGrey)),
+];
+
+/// Everforest, but only using the 16 colors every terminal theme defines
+const BASE16: [(HLGroup, Style); 36] = [
+    (HLGroup::Text, Style::fg(Color::Reset)),
+    (HLGroup::Keyword, Style::fg(Color::Red)),
+    (HLGroup::String, Style::fg(Color::Green)),
+    (HLGroup::Comment, Style::fg(Color::DarkGrey)),
+    (HLGroup::Function, Style::fg(Color::Blue)),
+    (HLGroup::Type, Style::fg(Color::Yellow)),
+    (HLGroup::CustomType, Style::fg(Color::DarkYellow)),
+    (HLGroup::Field, Style::fg(Color::Cyan)),
+    (HLGroup::Constant, Style::fg(Color::Magenta)),
+    (HLGroup::Operator, Style::fg(Color::DarkCyan)),
+    (HLGroup::Preproc, Style::fg(Color::DarkMagenta)),
+    (HLGroup::Delimiter, Style::fg(Color::DarkGrey)),
+    (HLGroup::Heading1, Style::fg(Color::Red)),
+    (HLGroup::Heading2, Style::fg(Color::DarkYellow)),
+    (HLGroup::Heading3, Style::fg(Color::Yellow)),
+    (HLGroup::Heading4, Style::fg(Color::Green)),
+    (HLGroup::Heading5, Style::fg(Color::Blue)),
+    (HLGroup::Heading6, Style::fg(Color::Magenta)),
+    (HLGroup::LinkText, Style::fg(Color::Green)),
+    (HLGroup::LinkDestination, Style::fg(Color::Blue)),
+    (HLGroup::ListMarker, Style::fg(Color::Blue)),
+    (HLGroup::Gutter, Style::fg(Color::DarkGrey)),
+    (HLGroup::EmptyLine, Style::fg(Color::DarkGrey)),
+    (HLGroup::StatusBar, Style::fg(Color::Reset)),
+    (HLGroup::Selection, Style::new(Color::Black, Color::White)),
+    (
+        HLGroup::SearchMatch,
+        Style::new(Color::Black, Color::Yellow),
+    ),
+    (HLGroup::WarningMessage, Style::fg(Color::Yellow)),
+    (HLGroup::ErrorMessage, Style::fg(Color::Red)),
+    (HLGroup::DiagnosticError, Style::fg(Color::Red)),
+    (HLGroup::DiagnosticWarning, Style::fg(Color::Yellow)),
+    (HLGroup::DiagnosticInfo, Style::fg(Color::Blue)),
+    (HLGroup::DiagnosticHint, Style::fg(Color::Cyan)),
+    (HLGroup::GitAdded, Style::fg(Color::Green)),
+    (HLGroup::GitChanged, Style::fg(Color::Blue)),
+    (HLGroup::GitDeleted, Style::fg(Color::Red)),
+    (HLGroup::Popup, Style::new(Color::Reset, Color::DarkGrey)),
+];
+
+/// No colors, except to tell code apart from comments and the interface
+const MONOCHROME: [(HLGroup, Style); 8] = [
+    (HLGroup::Text, Style::fg(Color::Reset)),
+    (HLGroup::Comment, Style::fg(Color::DarkGrey)),
+    (HLGroup::Gutter, Style::fg(Color::DarkGrey)),
+    (HLGroup::EmptyLine, Style::fg(Color::DarkGrey)),
+    (HLGroup::StatusBar, Style::fg(Color::Reset)),
+    (HLGroup::Selection, Style::new(Color::Black, Color::White)),
+    (HLGroup::SearchMatch, Style::new(Color::Black, Color::White)),
+    (HLGroup::Popup, Style::new(Color::Black, Color::White)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::parse_color;
+    use crossterm::style::Color;
+
+    #[test]
+    fn parses_hex_ansi_and_named_colors() {
+        assert_eq!(
+            parse_color("#e69875").ok(),
+            Some(Color::Rgb {
+                r: 0xe6,
+                g: 0x98,
+                b: 0x75
+            })
+        );
+        assert_eq!(parse_color(" 208 ").ok(), Some(Color::AnsiValue(208)));
+        assert_eq!(parse_color("dark_grey").ok(), Some(Color::DarkGrey));
+    }
+
+    #[test]
+    fn rejects_malformed_hex_colors() {
+        for color in ["#ééé", "#aééa", "#12345", "#1234567", "#gggggg", "#"] {
+            assert!(parse_color(color).is_err(), "{color} should be invalid");
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::buffer::Buffer;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub enum Action {
@@ -15,6 +16,11 @@ pub enum Action {
         original: String,
         new: String,
     },
+    /// Actions which are undone together, in reverse order
+    Group(Vec<Self>),
+    /// Actions in the buffers of other files, which can only be undone by the `View`.
+    /// See `UndoTree::take_workspace_undo`.
+    Workspace(Vec<(PathBuf, Self)>),
 }
 
 impl Action {
@@ -53,6 +59,7 @@ impl Action {
 #[derive(Debug, Clone)]
 pub struct UndoTree {
     actions: Vec<Action>,
+    workspace_undo: Vec<(PathBuf, Action)>,
 }
 
 impl Default for UndoTree {
@@ -65,6 +72,7 @@ impl UndoTree {
     pub const fn new() -> Self {
         Self {
             actions: Vec::new(),
+            workspace_undo: Vec::new(),
         }
     }
 
@@ -73,10 +81,15 @@ impl UndoTree {
             return;
         };
 
+        self.undo_action(action, buffer);
+    }
+
+    /// Reverts `action` in `buffer`, without touching the history
+    pub fn undo_action(&mut self, action: Action, buffer: &mut Buffer) {
         match action {
             Action::Insert { text, position } => {
                 buffer.cursor = position;
-                (0..text.len()).for_each(|_| buffer.delete_curr_char());
+                (0..text.chars().count()).for_each(|_| buffer.delete_curr_char());
             }
             Action::Delete { text, position } => {
                 buffer.cursor = position;
@@ -89,12 +102,27 @@ impl UndoTree {
             } => {
                 buffer.replace_text(&original, &new, &positions, self, true);
             }
+            Action::Group(actions) => {
+                actions
+                    .into_iter()
+                    .rev()
+                    .for_each(|action| self.undo_action(action, buffer));
+            }
+            Action::Workspace(actions) => {
+                self.workspace_undo = actions;
+            }
         }
 
         buffer.update_list_set(.., true);
         buffer.has_changed = true;
     }
 
+    /// Takes the actions of a `Action::Workspace` which was just undone, which still need to be
+    /// undone in the buffers of their files
+    pub fn take_workspace_undo(&mut self) -> Vec<(PathBuf, Action)> {
+        std::mem::take(&mut self.workspace_undo)
+    }
+
     pub fn new_action_merge(&mut self, mut action: Action) {
         // The point of this is to squash keystrokes
         match &action {
@@ -146,6 +174,7 @@ impl UndoTree {
                     action = Action::replace(positions, original, new);
                 }
             }
+            Action::Group(_) | Action::Workspace(_) => {}
         }
 
         self.actions.push(action);
@@ -1,6 +1,4 @@
-use crate::{buffer::Buffer, log, logn};
-use anyhow::Result;
-use crossterm::event::{Event, KeyCode, read};
+use crate::{log, logn};
 use tree_sitter::{Tree, TreeCursor};
 
 macro_rules! unwrap_or_return {
@@ -57,19 +55,6 @@ pub fn is_symbol(c: char) -> bool {
     "$`\'\":;~()\\+-=$#^[&]*<@%!{|}>/?.,".contains(c)
 }
 
-/// # Errors
-/// - I/O error if `crossterm::events::read()` fails
-pub fn on_next_input(buffer: &mut Buffer, closure: fn(KeyCode, &mut Buffer)) -> Result<()> {
-    loop {
-        if let Event::Key(event) = read()? {
-            closure(event.code, buffer);
-            break;
-        }
-    }
-
-    Ok(())
-}
-
 /// Returns `\0` if the string is empty
 pub fn last_char(str: &str) -> char {
     str.chars().last().unwrap_or('\0')
@@ -1,50 +1,78 @@
 use crate::{
-    buffer::Buffer, global_state::GlobalState, mode::Mode, status_bar::StatusBar,
-    view_box::ViewBox, view_node::ViewNode,
+    buffer::Buffer,
+    global_state::GlobalState,
+    mode::Mode,
+    options::OptionsConfig,
+    popup::Popup,
+    status_bar::StatusBar,
+    theme::HLGroup,
+    undo::{Action, UndoTree},
+    view_box::ViewBox,
+    view_node::ViewNode,
 };
 use anyhow::Result;
 use crossterm::{
     cursor::{MoveTo, MoveToColumn, MoveToRow, SetCursorStyle, Show},
     execute, queue,
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
     terminal::{
         Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode,
         enable_raw_mode,
     },
 };
 use std::{
+    collections::HashMap,
     io::{Write, stdout},
     path::PathBuf,
 };
 
 /// Represents the entire view of the editor in the terminal
+#[allow(clippy::struct_field_names)]
 pub struct View {
     view_box_structure: ViewNode,
     current_view_box: usize,
+    /// Shown until the next keypress
+    pub popup: Option<Popup>,
     width: u16,
     height: u16,
+    pub options: OptionsConfig,
+    /// Buffers of files which were changed without being shown in a view box (eg. by a rename),
+    /// by absolute path. They're shown instead of what's on disk when their file is opened, and
+    /// written with `:wall`.
+    pub hidden_buffers: HashMap<PathBuf, Buffer>,
 }
 
 impl View {
-    pub fn new(cols: u16, rows: u16) -> Self {
-        Self {
+    pub fn new(cols: u16, rows: u16, options: OptionsConfig) -> Self {
+        let mut view = Self {
             view_box_structure: ViewNode::Leaf(ViewBox::new(cols, rows - 1, 0, 0)),
-            cursor: 0,
+            current_view_box: 0,
+            popup: None,
             width: cols, // Don't subtract one because each viewbox handles line nums separately
             height: rows - 1,
-        }
+            options,
+            hidden_buffers: HashMap::new(),
+        };
+
+        let options = view.options.global;
+        view.get_view_box().set_options(options);
+        view
     }
 
     pub fn get_buffer_mut(&mut self) -> &mut Buffer {
-        &mut self.view_box_structure[self.cursor].buffer
+        &mut self.view_box_structure[self.current_view_box].buffer
     }
 
     pub fn get_buffer(&self) -> &Buffer {
-        &self.view_box_structure[self.cursor].buffer
+        &self.view_box_structure[self.current_view_box].buffer
     }
 
     pub fn get_view_box(&mut self) -> &mut ViewBox {
-        &mut self.view_box_structure[self.cursor]
+        &mut self.view_box_structure[self.current_view_box]
+    }
+
+    pub fn view_box(&self) -> &ViewBox {
+        &self.view_box_structure[self.current_view_box]
     }
 
     pub fn normal_unattached_status(chained: &[char], count: u32, register: char) -> String {
@@ -89,7 +117,7 @@ impl View {
         };
         let chained_str = chained.iter().collect::<String>();
 
-        let git_hash = self.get_git_hash().unwrap_or("");
+        let git_status = self.get_git_status().unwrap_or_default();
 
         let status_bar_width: usize = info_str.len()
             + path.len()
@@ -100,7 +128,7 @@ impl View {
             + reg_str.len()
             + count_str.len()
             + chained_str.len()
-            + git_hash.len();
+            + git_status.len();
 
         if status_bar_width > self.width as usize {
             // TODO Maybe add more breakpoints???
@@ -122,12 +150,12 @@ impl View {
                     - reg_str.len()
                     - count_str.len()
                     - chained_str.len()
-                    - git_hash.len())
+                    - git_status.len())
             .map(|_| ' ')
             .collect::<String>();
 
         Ok(format!(
-            "{info_str}\"{path}\" {file_size}b {reg_str}{count_str} {chained_str}{middle_buffer}{git_hash}",
+            "{info_str}\"{path}\" {file_size}b {reg_str}{count_str} {chained_str}{middle_buffer}{git_status}",
         ))
     }
 
@@ -153,7 +181,12 @@ impl View {
         Ok(status_message)
     }
 
-    pub fn render(&self, global_state: &GlobalState, adjusted: bool) -> Result<()> {
+    pub fn render(
+        &self,
+        global_state: &GlobalState,
+        adjusted: bool,
+        stdout: &mut impl Write,
+    ) -> Result<()> {
         let register = global_state.register_handler.get_curr_reg();
 
         let mut errors = self
@@ -161,26 +194,76 @@ impl View {
             .iter()
             .enumerate()
             .filter_map(|(i, view_box)| {
-                let adjusted = adjusted && i == self.cursor;
-                view_box.render(adjusted).err()
+                let adjusted = adjusted && i == self.current_view_box;
+                view_box.render(adjusted, &global_state.theme, stdout).err()
             });
         if let Some(err) = errors.next() {
             return Err(err);
         }
 
-        let mut stdout = stdout().lock();
+        if let Some(popup) = &self.popup {
+            let cursor = self.view_box_structure[self.current_view_box].cursor_position();
+            popup.render(
+                cursor,
+                (self.width, self.height),
+                &global_state.theme,
+                stdout,
+            )?;
+        }
 
-        let status_message = self.status_message(
-            &global_state.status_bar,
-            &global_state.mode,
-            &global_state.chained,
-            global_state.count,
-            register,
-        )?;
+        let finder_cursor = match &global_state.finder {
+            Some(finder) => Some(finder.render(
+                (self.width, self.height),
+                &self.options,
+                &global_state.theme,
+                stdout,
+            )?),
+            None => None,
+        };
+
+        // NOTE
+        // Messages are cut off at the width of the terminal, otherwise they'd wrap and scroll the
+        // screen
+        let fit_to_width = |text: &str| -> String {
+            text.lines()
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .take(self.width as usize)
+                .collect()
+        };
+
+        // The diagnostic on the cursor's line is shown in place of the normal status, but not
+        // while a command is being typed
+        let diagnostic = self.view_box_structure[self.current_view_box].cursor_diagnostic();
+        let show_diagnostic =
+            matches!(global_state.mode, Mode::Normal) && global_state.chained.is_empty();
+
+        let (status_message, group) = match (global_state.messages.current(), diagnostic) {
+            (Some(message), _) if !matches!(global_state.mode, Mode::Meta | Mode::Search) => {
+                (fit_to_width(&message.text), message.severity.group())
+            }
+            (None, Some(diagnostic)) if show_diagnostic => (
+                fit_to_width(&diagnostic.message),
+                diagnostic.severity.group(),
+            ),
+            _ => (
+                self.status_message(
+                    &global_state.status_bar,
+                    &global_state.mode,
+                    &global_state.chained,
+                    global_state.count,
+                    register,
+                )?,
+                HLGroup::StatusBar,
+            ),
+        };
 
+        let style = global_state.theme.style(group);
         queue!(
             stdout,
-            SetForegroundColor(Color::White),
+            SetForegroundColor(style.fg),
+            SetBackgroundColor(style.bg),
             MoveTo(0, self.height + 1),
             Clear(ClearType::CurrentLine),
             Print(status_message)
@@ -190,10 +273,24 @@ impl View {
         let (new_col, new_row) = if matches!(global_state.mode, Mode::Meta | Mode::Search) {
             (global_state.status_bar.idx(), self.height + 1)
         } else {
-            let view_box = &self.view_box_structure[self.cursor];
+            let view_box = &self.view_box_structure[self.current_view_box];
             view_box.cursor_position()
         };
-        queue!(stdout, MoveToColumn(new_col), MoveToRow(new_row), Show)?;
+        // A command waiting on a character (eg. `r`) is shown with an underscore
+        let cursor_style = if global_state.awaiting_argument {
+            SetCursorStyle::SteadyUnderScore
+        } else {
+            global_state.mode.cursor_style()
+        };
+        // The finder's query is typed in place of the buffer
+        let (new_col, new_row) = finder_cursor.unwrap_or((new_col, new_row));
+        queue!(
+            stdout,
+            MoveToColumn(new_col),
+            MoveToRow(new_row),
+            cursor_style,
+            Show
+        )?;
 
         stdout.flush()?;
         Ok(())
@@ -202,6 +299,71 @@ impl View {
 
 /// `ViewBox` Manipulation Methods
 impl View {
+    pub fn view_boxes(&self) -> impl Iterator<Item = &ViewBox> {
+        self.view_box_structure.iter()
+    }
+
+    pub fn view_boxes_mut(&mut self) -> impl Iterator<Item = &mut ViewBox> {
+        self.view_box_structure.iter_mut()
+    }
+
+    /// Forces every view box to be fully redrawn on the next render
+    pub fn redraw_all(&mut self) {
+        self.view_box_structure.iter_mut().for_each(|view_box| {
+            view_box.buffer.update_list_set(.., true);
+            view_box.buffer.has_changed = true;
+        });
+    }
+
+    /// Undoes the actions of an `Action::Workspace` in the buffers which made them
+    pub fn undo_workspace(&mut self, actions: Vec<(PathBuf, Action)>, undo_tree: &mut UndoTree) {
+        // NOTE
+        // Every view box showing a file made its own action, in the order the view boxes are in,
+        // so the nth action for a file belongs to the nth view box showing it
+        let mut undone: HashMap<PathBuf, usize> = HashMap::new();
+
+        for (path, action) in actions {
+            let nth = undone.entry(path.clone()).or_default();
+            let view_box = self
+                .view_boxes_mut()
+                .filter(|view_box| {
+                    view_box
+                        .path()
+                        .and_then(|box_path| std::path::absolute(box_path).ok())
+                        .is_some_and(|box_path| box_path == path)
+                })
+                .nth(*nth);
+            *nth += 1;
+
+            if let Some(view_box) = view_box {
+                undo_tree.undo_action(action, &mut view_box.buffer);
+                let _ = view_box.parse();
+            } else if let Ok(buffer) = self.hidden_buffer(&path) {
+                // NOTE
+                // The file might have been written by `:wall` since, in which case undoing reads
+                // it back into a hidden buffer
+                undo_tree.undo_action(action, buffer);
+            }
+        }
+    }
+
+    pub const fn current_view_box(&self) -> usize {
+        self.current_view_box
+    }
+
+    /// Makes the view box at `idx` the one being edited
+    pub fn set_current_view_box(&mut self, idx: usize) {
+        self.current_view_box = usize::min(idx, self.view_box_count() - 1);
+    }
+
+    pub fn view_box_count(&self) -> usize {
+        self.view_box_structure.len()
+    }
+
+    pub fn get_view_box_at(&mut self, idx: usize) -> &mut ViewBox {
+        &mut self.view_box_structure[idx]
+    }
+
     pub fn position_of_box<P>(&self, predicate: P) -> Option<usize>
     where
         P: FnMut(&ViewBox) -> bool,
@@ -210,7 +372,7 @@ impl View {
     }
 
     /// # Returns
-    /// The position (in `self.boxes`) of one `view_box` down, if it exists
+    /// The position (in `self.view_box_structure`) of one `view_box` down, if it exists
     pub fn position_view_box_down(&mut self) -> Option<usize> {
         let view_box = self.get_view_box();
 
@@ -249,54 +411,41 @@ impl View {
         self.position_of_box(predicate)
     }
 
+    /// Closes the current view box, unless it's the only one, giving its space to the view
+    /// boxes it was split from
     pub fn delete_curr_view_box(&mut self) {
-        let mut down = self.position_view_box_down();
-        let mut up = self.position_view_box_up();
-
-        let view_box = self.view_box_structure.remove(self.cursor);
-        if let Some(ref mut down) = down
-            && *down > self.cursor
-        {
-            *down -= 1;
-        }
-        if let Some(ref mut up) = up
-            && *up > self.cursor
-        {
-            *up -= 1;
-        }
-
-        self.cursor = usize::max(self.cursor, 1) - 1;
-
-        match (down, up) {
-            (_, Some(up_i)) => {
-                let up_box = &mut self.view_box_structure[up_i];
-                up_box.height += view_box.height;
-                self.cursor = up_i;
-            }
-            (Some(down_i), None) => {
-                let down_box = &mut self.view_box_structure[down_i];
-                down_box.y = view_box.y;
-                down_box.height += view_box.height;
-                self.cursor = down_i;
-            }
-            (None, None) => {}
-        }
+        let Some(view_box) = self.view_box_structure.remove(self.current_view_box) else {
+            return;
+        };
 
-        let view_box = self.get_view_box();
-        view_box.buffer.has_changed = true;
+        // The cursor moves to whichever view box took over the closed one's top left corner
+        let (x, y) = (view_box.x, view_box.y);
+        self.current_view_box = self
+            .position_of_box(|view_box| {
+                (view_box.x..view_box.x + view_box.width.max(1)).contains(&x)
+                    && (view_box.y..view_box.y + view_box.height).contains(&y)
+            })
+            .unwrap_or_default();
+
+        self.redraw_all();
     }
 
-    pub fn split_view_box_vertical(&mut self, idx: usize) {
+    /// Splits the view box at `idx` into a top and bottom half
+    ///
+    /// # Returns
+    /// The position of the new (bottom) view box, or `None` if the view box is too short to split
+    pub fn split_view_box_vertical(&mut self, idx: usize) -> Option<usize> {
         let view_box = &mut self.view_box_structure[idx];
 
         let half_height = view_box.height / 2;
         let half_y = half_height + view_box.y;
 
         if half_height == 1 {
-            return;
+            return None;
         }
 
         let mut new_view_box = ViewBox::new(view_box.width, half_height, view_box.x, half_y);
+        new_view_box.set_options(*view_box.options());
 
         let original_height = view_box.height;
 
@@ -305,10 +454,14 @@ impl View {
             new_view_box.height += 1;
         }
 
-        self.view_box_structure.push(new_view_box);
+        self.insert_view_box(idx, new_view_box, false)
     }
 
-    pub fn split_view_box_horizontal(&mut self, idx: usize) {
+    /// Splits the view box at `idx` into a left and right half
+    ///
+    /// # Returns
+    /// The position of the new (right) view box, or `None` if the view box is too narrow to split
+    pub fn split_view_box_horizontal(&mut self, idx: usize) -> Option<usize> {
         let view_box = &mut self.view_box_structure[idx];
 
         let half_width = view_box.width / 2;
@@ -316,10 +469,11 @@ impl View {
 
         let left_padding = view_box.left_padding();
         if (half_width as usize) < left_padding {
-            return;
+            return None;
         }
 
         let mut new_view_box = ViewBox::new(half_width, view_box.height, half_x, view_box.y);
+        new_view_box.set_options(*view_box.options());
 
         let original_width = view_box.width;
 
@@ -328,7 +482,22 @@ impl View {
             new_view_box.width += 1;
         }
 
-        self.view_box_structure.push(new_view_box);
+        self.insert_view_box(idx, new_view_box, true)
+    }
+
+    /// Adds `new_view_box` after the view box at `idx`, keeping the current view box the same
+    fn insert_view_box(
+        &mut self,
+        idx: usize,
+        new_view_box: ViewBox,
+        beside: bool,
+    ) -> Option<usize> {
+        let new_idx = self.view_box_structure.split(idx, new_view_box, beside)?;
+        if self.current_view_box >= new_idx {
+            self.current_view_box += 1;
+        }
+
+        Some(new_idx)
     }
 }
 
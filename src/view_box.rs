@@ -1,30 +1,57 @@
 use crate::{
     buffer::Buffer,
-    file_io::try_get_git_hash,
+    columns::{byte_columns, char_at_col, columns, slice, width, wrap_starts},
+    diff::Hunk,
+    explorer::Explorer,
+    git::GitFile,
     highlight::{HLBlock, HLEnd},
     language::OrinLanguage,
+    lsp::{Diagnostic, DiagnosticSeverity},
+    options::Options,
+    sign::{Sign, SignColumn, SignSource},
+    theme::{HLGroup, Theme},
+    undo::UndoTree,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crossterm::{
     cursor::{Hide, MoveDown, MoveTo, MoveToColumn},
     queue,
-    style::{Color, Print, SetBackgroundColor, SetForegroundColor},
+    style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor},
 };
 use ropey::RopeSlice;
-use std::{
-    io::{StdoutLock, stdout},
-    path::PathBuf,
-};
+use std::{collections::HashMap, io::Write, path::PathBuf};
 use tree_sitter::{Parser, Tree};
 
+/// Shown in the gutter of the rows a wrapped line continues onto
+const WRAP_MARKER: char = '↪';
+
+/// A row of the screen, showing part of a line of the buffer
+#[derive(Debug, Clone, Copy)]
+struct Row {
+    line_num: usize,
+    // The display column of the line the row starts at
+    left: usize,
+    // Whether the row continues a wrapped line, rather than starting it
+    continued: bool,
+}
+
 pub struct ViewBox {
     // Components inherant to the view box
     pub buffer: Buffer,
     path: Option<PathBuf>,
-    pub git_hash: Option<String>,
+    // Set when the file is in a git repository
+    git: Option<GitFile>,
+    // Set when the view box is browsing a directory rather than editing a file
+    pub explorer: Option<Explorer>,
+    // Set when the view box is showing the quickfix list
+    pub quickfix: bool,
 
     pub parser: Option<(Parser, OrinLanguage)>,
     pub parse_tree: Option<Tree>,
+    // Sorted by where they start
+    diagnostics: Vec<Diagnostic>,
+    signs: SignColumn,
+    options: Options,
 
     // The x and y corrdinates of the upper right hand corner of where the buffer will be displayed
     pub x: u16,
@@ -36,8 +63,12 @@ pub struct ViewBox {
     pub height: u16,
     // The leftmost row of the buffer being displayed (zero-indexed)
     pub left: usize,
+    // How many rows of the top line are scrolled past, when it's wrapped onto more rows than fit
+    top_segment: usize,
     // The width in rows of the entire view box
     pub width: u16,
+    // The row relative line numbers were last counted from
+    numbered_row: usize,
 }
 
 impl ViewBox {
@@ -50,39 +81,54 @@ impl ViewBox {
         Self {
             buffer: Buffer::new(),
             path: None,
-            git_hash: None,
+            git: None,
+            explorer: None,
+            quickfix: false,
             parser: None,
             parse_tree: None,
+            diagnostics: Vec::new(),
+            signs: SignColumn::default(),
+            options: Options::default(),
 
             x,
             y,
             top: 0,
             height: rows,
             left: 0,
-            // Reserve one for line numbers
-            // TODO
-            // Have option to not have line nums
+            top_segment: 0,
             width: cols - 1,
+            numbered_row: 0,
         }
     }
 
     pub fn adjust(&mut self) -> bool {
-        let col = self.buffer.get_col();
+        let col = self.cursor_col();
         let row = self.buffer.get_row();
         let mut adjusted = false;
 
-        if self.top > row {
-            self.top = row;
+        // The scroll offset can't be more than half the view box, or it couldn't be kept on both
+        // sides of the cursor
+        let scrolloff = usize::min(
+            self.options.scrolloff,
+            (self.height as usize).saturating_sub(1) / 2,
+        );
+        // Past the end of the file there aren't any lines to keep below the cursor
+        let bottom = usize::min(row + scrolloff, self.buffer.len() - 1);
+
+        if self.top + scrolloff > row {
+            self.top = row.saturating_sub(scrolloff);
             adjusted = true;
-        } else if self.top + self.height as usize <= row {
-            self.top = row - self.height as usize + 1;
+        } else if self.top + self.height as usize <= bottom {
+            self.top = bottom - self.height as usize + 1;
             adjusted = true;
         }
 
         // This doesn't take into account the gutter
         let left_padding = self.left_padding();
 
-        if self.left > col {
+        if self.options.wrap {
+            adjusted |= self.adjust_wrapped(bottom, left_padding);
+        } else if self.left > col {
             self.left = col;
             adjusted = true;
         } else if self.left + (self.width as usize) < col + left_padding {
@@ -90,6 +136,12 @@ impl ViewBox {
             adjusted = true;
         }
 
+        // Every relative line number changes when the cursor moves to another row
+        if self.options.relativenumber && self.numbered_row != row {
+            self.numbered_row = row;
+            adjusted = true;
+        }
+
         if adjusted {
             self.buffer.update_list_set(.., true);
         }
@@ -97,25 +149,61 @@ impl ViewBox {
         adjusted
     }
 
-    fn write_buffer(&self, stdout: &mut StdoutLock, left_padding: usize) -> Result<()> {
-        let lines = self
-            .buffer
-            .rope
-            .lines()
-            .zip(self.buffer.lines_for_updating.iter())
-            .enumerate()
-            .skip(self.top)
-            .take(self.height.into());
+    /// Scrolls down until every row from the top to `bottom` fits in the view box, when lines are
+    /// wrapped and so can take up more than one row
+    ///
+    /// # Returns
+    /// Whether the view box moved
+    fn adjust_wrapped(&mut self, bottom: usize, left_padding: usize) -> bool {
+        let text_width = self.text_width(left_padding);
+        let row = self.buffer.get_row();
+        let (top, top_segment) = (self.top, self.top_segment);
+        let mut adjusted = self.left != 0;
+        self.left = 0;
+
+        // NOTE
+        // Only the rows up to the cursor's have to fit for the lines after the cursor
+        let cursor_rows = self.cursor_segment(text_width) + 1;
+        let mut rows: usize = (self.top..row)
+            .chain(row + 1..=bottom)
+            .map(|line_num| self.line_rows(line_num, text_width))
+            .sum::<usize>()
+            + cursor_rows;
+
+        while rows > self.height as usize && self.top < row {
+            rows -= self.line_rows(self.top, text_width);
+            self.top += 1;
+            adjusted = true;
+        }
+
+        // A line taller than the view box is shown from its start, until the cursor moves further
+        // down it than fits
+        self.top_segment = if self.top == row {
+            let kept = if self.top == top { top_segment } else { 0 };
+            usize::max(
+                usize::min(kept, cursor_rows - 1),
+                cursor_rows.saturating_sub(self.height as usize),
+            )
+        } else {
+            0
+        };
+
+        adjusted || self.top_segment != top_segment
+    }
+
+    fn write_buffer(
+        &self,
+        stdout: &mut impl Write,
+        left_padding: usize,
+        theme: &Theme,
+    ) -> Result<()> {
+        let rows = self.rows(left_padding);
 
         queue!(stdout, Hide, MoveTo(self.x, self.y))?;
         let mut padding_buffer = String::with_capacity(left_padding);
 
         let clear_str: String = (0..=self.width).map(|_| ' ').collect();
 
-        // Taking the length to avoid having to clone all of lines
-        // Since its used in the statement below
-        let maybe_len_lines = u16::try_from(lines.len()).ok();
-
         if let Some(_tree) = self.parse_tree.as_ref()
             && let Some((_, language)) = self.parser.as_ref()
             && let Some(path) = self.path.as_ref()
@@ -124,31 +212,31 @@ impl ViewBox {
             && language.extensions.contains(&ex.to_string())
         {
             self.print_line_hl(
-                lines,
-                // Expensive
-                self.highlight(),
+                &rows,
                 stdout,
                 &mut padding_buffer,
                 left_padding,
                 &clear_str,
+                theme,
             );
         } else {
             self.print_lines_colorless(
-                lines,
+                &rows,
                 stdout,
                 &mut padding_buffer,
                 left_padding,
                 &clear_str,
+                theme,
             );
         }
 
         // This is for clearing trailing lines that we missed
-        if let Some(len_lines) = maybe_len_lines
-            && len_lines < self.height
+        if let Ok(len_rows) = u16::try_from(rows.len())
+            && len_rows < self.height
         {
-            queue!(stdout, MoveTo(self.x, self.y + len_lines))?;
+            queue!(stdout, MoveTo(self.x, self.y + len_rows))?;
 
-            (len_lines..self.height).for_each(|_| {
+            (len_rows..self.height).for_each(|_| {
                 queue!(stdout, Print(&clear_str), MoveDown(1), MoveToColumn(self.x))
                     .expect("Crossterm clearing trailing lines failed");
             });
@@ -157,63 +245,138 @@ impl ViewBox {
         Ok(())
     }
 
+    /// The rows shown in the view box, from the top
+    fn rows(&self, left_padding: usize) -> Vec<Row> {
+        let text_width = self.text_width(left_padding);
+        let mut rows = Vec::with_capacity(self.height.into());
+
+        for line_num in self.top..self.buffer.len() {
+            if rows.len() >= self.height.into() {
+                break;
+            }
+
+            if !self.options.wrap {
+                rows.push(Row {
+                    line_num,
+                    left: self.left,
+                    continued: false,
+                });
+                continue;
+            }
+
+            let skipped = if line_num == self.top {
+                self.top_segment
+            } else {
+                0
+            };
+            let starts = self.row_starts(line_num, text_width);
+            starts
+                .iter()
+                .enumerate()
+                .skip(skipped)
+                .for_each(|(i, &left)| {
+                    rows.push(Row {
+                        line_num,
+                        left,
+                        continued: i > 0,
+                    });
+                });
+        }
+        rows.truncate(self.height.into());
+
+        rows
+    }
+
+    /// Whether the row has to be drawn again
+    fn should_update(&self, row: Row) -> bool {
+        // NOTE
+        // A changed line can take up a different number of rows when lines are wrapped, which
+        // moves every row after it
+        self.options.wrap
+            || self
+                .buffer
+                .lines_for_updating
+                .get(row.line_num)
+                .copied()
+                .unwrap_or(true)
+    }
+
     /// At this point, the highlight groups should have been cropped to fit within the line
-    fn print_line_hl<'b>(
+    fn print_line_hl(
         &self,
-        lines: impl Iterator<Item = (usize, (RopeSlice<'b>, &'b bool))>,
-        hl_lines: Vec<Vec<HLBlock>>,
-        stdout: &mut StdoutLock,
+        rows: &[Row],
+        stdout: &mut impl Write,
 
         padding_buffer: &mut String,
         left_padding: usize,
         clear_str: &str,
+        theme: &Theme,
     ) {
-        let hl_lines = hl_lines.into_iter().skip(self.top).take(self.height.into());
-        let lines = lines
-            .zip(hl_lines)
-            .map(|((line_num, (line, should_update)), hl_blocks)| {
-                (line_num, line, hl_blocks, should_update)
-            });
+        // Expensive
+        let hl_lines = self.highlight(theme);
 
-        lines.for_each(|(line_num, line, hl_blocks, should_update)| {
-            if !should_update {
+        for (i, row) in rows.iter().enumerate() {
+            if !self.should_update(*row) {
                 queue!(stdout, MoveDown(1)).expect("Crossterm MoveDown command failed");
-                return;
+                continue;
             }
 
             Self::clear_line(clear_str, stdout);
-            self.print_padding(padding_buffer, left_padding, line_num, stdout);
+            self.print_padding(padding_buffer, left_padding, *row, stdout, theme);
 
-            let line_len = Self::calculate_total_line_len(line);
+            let line = Self::line_text(self.buffer.rope.line(row.line_num));
+            let columns = byte_columns(&line, self.options.tabstop);
+            let line_len = columns.last().copied().unwrap_or(0);
             if line_len == 0 {
                 queue!(stdout, MoveToColumn(self.x), MoveDown(1))
                     .expect("Crossterm padding buffer print failed");
-                return;
+                continue;
             }
 
             // NOTE
             // We don't need to slice the string, we can just choose the hl blocks we  want to print
-            let line = line.to_string();
-
-            let last_col = self.last_col(left_padding, line_len);
-            let hl_blocks = self.crop_hl_blocks(&hl_blocks, last_col, line_len);
+            let last_col = self.last_col(row.left, left_padding, line_len);
+            let hl_blocks = hl_lines.get(row.line_num).map_or(&[][..], Vec::as_slice);
+            let hl_blocks = Self::hl_blocks_at_columns(hl_blocks, &columns);
+            let hl_blocks = Self::crop_hl_blocks(&hl_blocks, row.left, last_col, line_len);
 
             if hl_blocks.is_empty() {
-                return;
+                queue!(stdout, MoveToColumn(self.x), MoveDown(1))
+                    .expect("Crossterm MoveDown command failed");
+                continue;
             }
 
             self.print_hl_blocks(&hl_blocks, &line, stdout);
-        });
+            self.print_diagnostics(*row, i, left_padding, last_col, stdout, theme);
+        }
+    }
+
+    /// Moves hl blocks from the bytes of the line they start and end at to the display columns
+    /// those bytes are shown at
+    fn hl_blocks_at_columns(hl_blocks: &[HLBlock], columns: &[usize]) -> Vec<HLBlock> {
+        let col = |idx: usize| columns[usize::min(idx, columns.len() - 1)];
+
+        hl_blocks
+            .iter()
+            .map(|hl| HLBlock {
+                start: col(hl.start),
+                end: match hl.end {
+                    HLEnd::Bounded(end) => HLEnd::Bounded(col(end)),
+                    HLEnd::EndOfLine => HLEnd::EndOfLine,
+                },
+                ..hl.clone()
+            })
+            .collect()
     }
 
-    // Returns a new list of hl blocks that only print the line from `[self.left, last_col)`
+    // Returns a new list of hl blocks that only print the line from `[left, last_col)`
     //
     // - Eliminates hl blocks that don't overlap with the line at all
     // - Crops hl blocks (on both ends) that go past the ends of the line
     // - Leaves hl blocks that are fully within the line in tact
     fn crop_hl_blocks(
-        &self,
         hl_blocks: &[HLBlock],
+        left: usize,
         last_col: usize,
         line_len: usize,
     ) -> Vec<HLBlock> {
@@ -222,8 +385,8 @@ impl ViewBox {
             .filter(|hl| {
                 hl.start <= last_col
                     && match hl.end {
-                        HLEnd::Bounded(end) => end >= self.left,
-                        HLEnd::EndOfLine => line_len >= self.left,
+                        HLEnd::Bounded(end) => end >= left,
+                        HLEnd::EndOfLine => line_len >= left,
                     }
             })
             .map(std::clone::Clone::clone)
@@ -235,7 +398,7 @@ impl ViewBox {
         }
 
         // Crop first block
-        hl_blocks[0].start = usize::max(self.left, hl_blocks[0].start);
+        hl_blocks[0].start = usize::max(left, hl_blocks[0].start);
 
         // Crop last block
         if let Some(block) = hl_blocks.last_mut() {
@@ -251,9 +414,9 @@ impl ViewBox {
     /// Prints a line highlighted based on `hl_blocks`.
     /// The line has already been sliced to the correct size
     /// The hl blocks have already been cropped
-    fn print_hl_blocks(&self, hl_blocks: &[HLBlock], line: &str, stdout: &mut StdoutLock) {
+    fn print_hl_blocks(&self, hl_blocks: &[HLBlock], line: &str, stdout: &mut impl Write) {
         for hl in hl_blocks {
-            let text = hl.slice_text(line);
+            let text = hl.slice_text(line, self.options.tabstop);
             queue!(
                 stdout,
                 SetForegroundColor(hl.fg_color),
@@ -266,48 +429,53 @@ impl ViewBox {
         queue!(stdout, MoveToColumn(self.x), MoveDown(1)).expect("Crossterm reset command failed");
     }
 
-    fn print_lines_colorless<'b>(
+    fn print_lines_colorless(
         &self,
-        lines: impl Iterator<Item = (usize, (RopeSlice<'b>, &'b bool))>,
-        stdout: &mut StdoutLock,
+        rows: &[Row],
+        stdout: &mut impl Write,
 
         padding_buffer: &mut String,
         left_padding: usize,
         clear_str: &str,
+        theme: &Theme,
     ) {
         log!("\n");
-        lines.for_each(|(line_num, (line, should_update))| {
-            log!("{:?}", line);
-            if !should_update {
+        for (i, row) in rows.iter().enumerate() {
+            if !self.should_update(*row) {
                 queue!(stdout, MoveDown(1)).expect("Crossterm MoveDown command failed");
-                return;
+                continue;
             }
 
             Self::clear_line(clear_str, stdout);
-            self.print_padding(padding_buffer, left_padding, line_num, stdout);
+            self.print_padding(padding_buffer, left_padding, *row, stdout, theme);
 
-            let line_len = Self::calculate_total_line_len(line);
+            let line = Self::line_text(self.buffer.rope.line(row.line_num));
+            log!("{:?}", line);
+            let line_len = width(line.chars(), self.options.tabstop);
             if line_len == 0 {
                 queue!(stdout, MoveToColumn(self.x), MoveDown(1))
                     .expect("Crossterm padding buffer print failed");
-                return;
+                continue;
             }
 
-            let characters_to_print = self.last_col(left_padding, line_len);
-            let line = self.slice_line(line, characters_to_print);
+            let characters_to_print = self.last_col(row.left, left_padding, line_len);
+            let line = self.slice_line(&line, row.left, characters_to_print);
 
+            let style = theme.style(HLGroup::Text);
             queue!(
                 stdout,
-                SetForegroundColor(Color::Grey),
+                SetForegroundColor(style.fg),
+                SetBackgroundColor(style.bg),
                 Print(&line),
                 MoveToColumn(self.x),
                 MoveDown(1)
             )
             .expect("Crossterm print line command failed");
-        });
+            self.print_diagnostics(*row, i, left_padding, characters_to_print, stdout, theme);
+        }
     }
 
-    fn clear_line(clear_str: &str, stdout: &mut StdoutLock) {
+    fn clear_line(clear_str: &str, stdout: &mut impl Write) {
         queue!(stdout, Print(&clear_str)).expect("Could not print clear string");
     }
 
@@ -315,27 +483,137 @@ impl ViewBox {
         &self,
         padding_buffer: &mut String,
         left_padding: usize,
-        line_num: usize,
-        stdout: &mut StdoutLock,
+        row: Row,
+        stdout: &mut impl Write,
+        theme: &Theme,
     ) {
-        let line_num = line_num.to_string();
+        queue!(stdout, MoveToColumn(self.x)).expect("Crossterm padding buffer print failed");
+
+        let mut left_padding = left_padding;
+        if self.has_sign_column() {
+            let sign = self.signs.at(row.line_num).filter(|_| !row.continued);
+            let sign = sign.unwrap_or(Sign::new(' ', HLGroup::Gutter));
+
+            let style = theme.style(sign.group);
+            queue!(
+                stdout,
+                SetForegroundColor(style.fg),
+                SetBackgroundColor(style.bg),
+                Print(sign.text),
+            )
+            .expect("Crossterm sign print failed");
+            left_padding -= 1;
+        }
+
+        if !self.has_number_column() {
+            return;
+        }
+
+        // The rows a wrapped line continues onto are marked instead of numbered
+        let line_num = if row.continued {
+            WRAP_MARKER.to_string()
+        } else {
+            self.line_number(row.line_num).to_string()
+        };
         // `-1` for the last space character that gets pushed
-        for _ in 0..left_padding - line_num.len() - 1 {
+        for _ in 0..left_padding - line_num.chars().count() - 1 {
             padding_buffer.push(' ');
         }
         padding_buffer.push_str(&line_num);
         padding_buffer.push(' ');
 
+        let style = theme.style(HLGroup::Gutter);
         queue!(
             stdout,
-            SetForegroundColor(Color::Grey),
-            MoveToColumn(self.x),
+            SetForegroundColor(style.fg),
+            SetBackgroundColor(style.bg),
             Print(padding_buffer.clone()),
         )
         .expect("Crossterm padding buffer print failed");
         padding_buffer.clear();
     }
 
+    /// Underlines the parts of the row covered by diagnostics in the color of their severity,
+    /// drawing over the already printed row, which is the `screen_row`th in the view box.
+    /// Leaves the cursor at the start of the next row, like printing the line does.
+    fn print_diagnostics(
+        &self,
+        row: Row,
+        screen_row: usize,
+        left_padding: usize,
+        last_col: usize,
+        stdout: &mut impl Write,
+        theme: &Theme,
+    ) {
+        let rope = &self.buffer.rope;
+        let line_start = rope.line_to_char(row.line_num);
+        let line_end = line_start + Self::calculate_total_line_len(rope.line(row.line_num));
+
+        let mut diagnostics: Vec<&Diagnostic> = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.on_line(line_start, line_end))
+            .collect();
+        if diagnostics.is_empty() {
+            return;
+        }
+        // The most severe are drawn last so they're on top
+        diagnostics.sort_by_key(|diagnostic| std::cmp::Reverse(diagnostic.severity));
+
+        let screen_row = self.y + u16::try_from(screen_row).unwrap_or(0);
+        let text_bg = theme.style(HLGroup::Text).bg;
+
+        let line = Self::line_text(rope.line(row.line_num));
+        let columns = columns(&line, self.options.tabstop);
+        let line_len = columns.last().copied().unwrap_or(0);
+        // Past the end of the line, every character is drawn as a space
+        let col = |idx: usize| {
+            columns
+                .get(idx)
+                .copied()
+                .unwrap_or(line_len + idx + 1 - columns.len())
+        };
+
+        for diagnostic in diagnostics {
+            let start = usize::max(diagnostic.start, line_start) - line_start;
+            let end = usize::min(diagnostic.end, line_end) - line_start;
+            // Empty diagnostics, and ones at the end of the line, still get a character
+            let end = usize::max(end, start + 1);
+            let (start, end) = (col(start), col(end));
+
+            // The character after the end of a line that fits on the screen is drawn as a space
+            let last_col = last_col + usize::from(last_col == line_len);
+            let (start, end) = (usize::max(start, row.left), usize::min(end, last_col));
+            if start >= end {
+                continue;
+            }
+
+            let Ok(col) = u16::try_from(start - row.left + left_padding) else {
+                continue;
+            };
+            if col >= self.width {
+                continue;
+            }
+
+            let text = slice(&line, start, end, self.options.tabstop);
+            let padding = end - start - width(text.chars(), self.options.tabstop);
+            let text = format!("{text}{}", " ".repeat(padding));
+
+            queue!(
+                stdout,
+                MoveTo(self.x + col, screen_row),
+                SetAttribute(Attribute::Underlined),
+                SetForegroundColor(theme.fg(diagnostic.severity.group())),
+                SetBackgroundColor(text_bg),
+                Print(text),
+                SetAttribute(Attribute::NoUnderline),
+            )
+            .expect("Crossterm print diagnostic failed");
+        }
+
+        queue!(stdout, MoveTo(self.x, screen_row + 1)).expect("Crossterm reset command failed");
+    }
+
     /// Calculates the number of characters in the line after removing the newline character
     fn calculate_total_line_len(line: RopeSlice) -> usize {
         let mut total_line_len = line.len_chars();
@@ -349,43 +627,104 @@ impl ViewBox {
         total_line_len
     }
 
-    /// Returns the last column in the line that's being rendered to the screen
-    const fn last_col(&self, left_padding: usize, line_len: usize) -> usize {
-        let max_len_of_line = self.width as usize - left_padding;
-        if max_len_of_line > line_len {
-            line_len
-        } else {
-            self.left + max_len_of_line
-        }
+    /// Returns the last column in the line that's being rendered to the screen, for a row starting
+    /// at display column `left`
+    fn last_col(&self, left: usize, left_padding: usize, line_len: usize) -> usize {
+        usize::min(line_len, left + self.text_width(left_padding))
+    }
+
+    /// How many columns of text fit in a row, next to the gutter
+    fn text_width(&self, left_padding: usize) -> usize {
+        usize::max((self.width as usize).saturating_sub(left_padding), 1)
+    }
+
+    /// How many rows the line takes up when it's wrapped
+    fn line_rows(&self, line_num: usize, text_width: usize) -> usize {
+        self.row_starts(line_num, text_width).len()
     }
 
-    fn slice_line(&self, line: RopeSlice, last_col: usize) -> String {
-        // What was happening here was when we cropped the line
-        // we also cropped the newline character at the
-        // end, meaning that we never moved down to the next row!
-        if self.left >= last_col {
+    /// The display column each row of the line starts at when it's wrapped
+    fn row_starts(&self, line_num: usize, text_width: usize) -> Vec<usize> {
+        let line = Self::line_text(self.buffer.rope.line(line_num));
+        wrap_starts(&line, text_width, self.options.tabstop)
+    }
+
+    fn slice_line(&self, line: &str, left: usize, last_col: usize) -> String {
+        if left >= last_col {
             String::new()
         } else {
-            line.slice(self.left..last_col)
-                .to_string()
-                .trim_matches('\n')
-                .to_string()
+            slice(line, left, last_col, self.options.tabstop)
         }
     }
 
-    pub fn render(&self, adjusted: bool) -> Result<()> {
-        let mut stdout = stdout().lock();
+    /// The line without its newline
+    fn line_text(line: RopeSlice) -> String {
+        let line = line.to_string();
+        line.trim_end_matches('\n').to_string()
+    }
+
+    /// The display column the cursor is shown at, which is past any tabs and wide characters
+    /// before it on its line
+    fn cursor_col(&self) -> usize {
+        let col = self.buffer.get_col();
+        width(
+            self.buffer.get_curr_line().chars().take(col),
+            self.options.tabstop,
+        )
+    }
+
+    pub fn render(&self, adjusted: bool, theme: &Theme, stdout: &mut impl Write) -> Result<()> {
         let left_padding = self.left_padding();
 
         if self.buffer.has_changed || adjusted {
-            self.write_buffer(&mut stdout, left_padding)?;
+            self.write_buffer(stdout, left_padding, theme)?;
         }
 
         Ok(())
     }
 
     pub fn left_padding(&self) -> usize {
-        (self.top + self.height as usize).to_string().len() + 1
+        let sign_column = usize::from(self.has_sign_column());
+        // NOTE
+        // Relative numbers are never more than the number of lines either, and sizing the column
+        // for the whole buffer keeps it from changing width while scrolling
+        let number_column = if self.has_number_column() {
+            self.buffer.len().to_string().len() + 1
+        } else {
+            0
+        };
+
+        number_column + sign_column
+    }
+
+    /// The sign column is shown while there are signs, or always with `signcolumn`
+    fn has_sign_column(&self) -> bool {
+        self.options.signcolumn || !self.signs.is_empty()
+    }
+
+    const fn has_number_column(&self) -> bool {
+        self.options.number || self.options.relativenumber
+    }
+
+    /// The number shown in the gutter next to the line
+    ///
+    /// With `relativenumber` it's how far the line is from the cursor's, except for the cursor's
+    /// own line when `number` is also on
+    fn line_number(&self, line_num: usize) -> usize {
+        let row = self.buffer.get_row();
+        if !self.options.relativenumber || (self.options.number && line_num == row) {
+            line_num
+        } else {
+            line_num.abs_diff(row)
+        }
+    }
+
+    /// Replaces the signs placed by `source`, redrawing the view box if they changed
+    pub fn set_signs(&mut self, source: SignSource, signs: HashMap<usize, Sign>) {
+        if self.signs.set(source, signs) {
+            self.buffer.update_list_set(.., true);
+            self.buffer.has_changed = true;
+        }
     }
 
     pub const fn _get_lower_right(&self) -> (u16, u16) {
@@ -405,7 +744,7 @@ impl ViewBox {
     /// Given that the cursor is in the given view box
     pub fn cursor_position(&self) -> (u16, u16) {
         let left_padding = self.left_padding();
-        let buffer_col = self.buffer.get_col();
+        let buffer_col = self.cursor_col();
         let buffer_row = self.buffer.get_row();
 
         // NOTE
@@ -416,16 +755,47 @@ impl ViewBox {
         // Of course, the difference between the buffer row and the top row
         // can't be greater than the size of the screen, which for all screens
         // I know about, should be fewer rows tall than `u16::MAX`
-        let absolute_row = self.y + u16::try_from(buffer_row - self.top).unwrap_or(0);
-        let absolute_col = self.x
-            + u16::min(
-                u16::try_from(buffer_col - self.left + left_padding).unwrap_or(0),
-                self.width,
-            );
+        let (row, col) = if self.options.wrap {
+            let text_width = self.text_width(left_padding);
+            let segment = self.cursor_segment(text_width);
+            let rows_above: usize = (self.top..buffer_row)
+                .map(|line_num| self.line_rows(line_num, text_width))
+                .sum();
+
+            (
+                usize::min(
+                    rows_above + segment - self.top_segment,
+                    (self.height as usize).saturating_sub(1),
+                ),
+                buffer_col - self.row_starts(buffer_row, text_width)[segment],
+            )
+        } else {
+            (buffer_row - self.top, buffer_col - self.left)
+        };
+
+        let absolute_row = self.y + u16::try_from(row).unwrap_or(0);
+        let absolute_col =
+            self.x + u16::min(u16::try_from(col + left_padding).unwrap_or(0), self.width);
         (absolute_col, absolute_row)
     }
 
+    /// Which of the rows of its wrapped line the cursor is on
+    fn cursor_segment(&self, text_width: usize) -> usize {
+        // NOTE
+        // The cursor can be just after the end of a line which exactly fills its rows, which is
+        // drawn at the end of the last row instead of on a row of its own
+        let col = self.cursor_col();
+        self.row_starts(self.buffer.get_row(), text_width)
+            .iter()
+            .rposition(|&start| start <= col)
+            .unwrap_or(0)
+    }
+
     pub fn set_path(&mut self, path: Option<PathBuf>) {
+        // A buffer left without a language isn't parsed as the previous file's
+        self.parser = None;
+        self.parse_tree = None;
+
         if let Some(path) = &path
             && let Some(ext) = path.extension()
             && let Some(ext) = ext.to_str()
@@ -439,11 +809,325 @@ impl ViewBox {
             self.parser = Some((parser, language));
         }
 
-        self.git_hash = try_get_git_hash(path.as_ref());
+        self.git = path.as_deref().and_then(GitFile::open);
+        self.set_signs(SignSource::Git, HashMap::new());
         self.path = path;
     }
 
     pub const fn path(&self) -> Option<&PathBuf> {
         self.path.as_ref()
     }
+
+    pub const fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Replaces the view box's options, redrawing it in case they change how it looks
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+        self.buffer.indent = options.indent();
+
+        self.buffer.update_list_set(.., true);
+        self.buffer.has_changed = true;
+    }
+}
+
+/// Display lines
+impl ViewBox {
+    /// Moves the cursor down a row of the screen, which is within the same line when it's wrapped
+    pub fn next_display_row(&mut self) {
+        if !self.options.wrap {
+            self.buffer.next_row();
+            return;
+        }
+
+        let text_width = self.text_width(self.left_padding());
+        let row = self.buffer.get_row();
+        let starts = self.row_starts(row, text_width);
+        let segment = self.cursor_segment(text_width);
+        let col = self.cursor_col() - starts[segment];
+
+        if segment + 1 < starts.len() {
+            self.set_display_col(row_col(&starts, segment + 1, col));
+        } else if !self.buffer.is_last_row() {
+            self.buffer.set_row(row + 1);
+            let starts = self.row_starts(row + 1, text_width);
+            self.set_display_col(row_col(&starts, 0, col));
+        }
+    }
+
+    /// Moves the cursor up a row of the screen, which is within the same line when it's wrapped
+    pub fn prev_display_row(&mut self) {
+        if !self.options.wrap {
+            self.buffer.prev_row();
+            return;
+        }
+
+        let text_width = self.text_width(self.left_padding());
+        let row = self.buffer.get_row();
+        let starts = self.row_starts(row, text_width);
+        let segment = self.cursor_segment(text_width);
+        let col = self.cursor_col() - starts[segment];
+
+        if segment > 0 {
+            self.set_display_col(row_col(&starts, segment - 1, col));
+        } else if row > 0 {
+            self.buffer.set_row(row - 1);
+            let starts = self.row_starts(row - 1, text_width);
+            self.set_display_col(row_col(&starts, starts.len() - 1, col));
+        }
+    }
+
+    /// Moves the cursor to the character shown at display column `col` of its line, or the last
+    /// one if the line is shorter
+    fn set_display_col(&mut self, col: usize) {
+        let line = Self::line_text(self.buffer.get_curr_line());
+        let idx = char_at_col(&line, col, self.options.tabstop);
+
+        self.buffer.set_col(idx);
+        self.buffer.cursor = self.buffer.grapheme_at(self.buffer.cursor).start;
+    }
+}
+
+/// The display column `col` columns into row `segment` of a wrapped line starting at `starts`, or
+/// the last column of the row if it's narrower (rows which end before a wide character are
+/// narrower than the view box)
+fn row_col(starts: &[usize], segment: usize, col: usize) -> usize {
+    let col = starts[segment] + col;
+    starts
+        .get(segment + 1)
+        .map_or(col, |&end| usize::min(col, end - 1))
+}
+
+/// Git
+impl ViewBox {
+    /// Compares the buffer with the version of its file staged in git, if it's changed since
+    /// they were last compared
+    pub fn diff_git(&mut self) {
+        if !self.buffer.has_changed {
+            return;
+        }
+        let Some(git) = &mut self.git else {
+            return;
+        };
+
+        git.diff(&self.buffer.rope);
+        let signs = git.signs();
+        self.set_signs(SignSource::Git, signs);
+    }
+
+    /// Reads the staged version of the file and the repository's status again, in case they've
+    /// changed since the file was opened
+    ///
+    /// # Errors
+    /// - If the index or the staged version can't be read
+    pub fn reload_git(&mut self) -> Result<()> {
+        if let Some(git) = &mut self.git {
+            git.reload()?;
+            self.buffer.has_changed = true;
+            self.diff_git();
+        }
+
+        Ok(())
+    }
+
+    /// The repository's branch, commit and whether it's dirty, for the status bar
+    pub fn git_status(&self) -> Option<String> {
+        self.git.as_ref()?.status().map(ToString::to_string)
+    }
+
+    fn git(&self) -> Result<&GitFile> {
+        self.git.as_ref().context("Not in a git repository")
+    }
+
+    /// The hunk of changes on the cursor's line
+    fn cursor_hunk(&self) -> Result<Hunk> {
+        self.git()?
+            .hunk_at(self.buffer.get_row())
+            .cloned()
+            .context("No changes on this line")
+    }
+
+    /// Moves the cursor to the start of the next hunk of changes
+    pub fn next_hunk(&mut self) {
+        if let Some(git) = &self.git
+            && let Some(line_num) = git.next_hunk(self.buffer.get_row())
+        {
+            self.buffer
+                .set_cursor(self.buffer.rope.line_to_char(line_num));
+        }
+    }
+
+    /// Moves the cursor to the start of the previous hunk of changes
+    pub fn prev_hunk(&mut self) {
+        if let Some(git) = &self.git
+            && let Some(line_num) = git.prev_hunk(self.buffer.get_row())
+        {
+            self.buffer
+                .set_cursor(self.buffer.rope.line_to_char(line_num));
+        }
+    }
+
+    /// The hunk of changes on the cursor's line, as a diff
+    ///
+    /// # Errors
+    /// - If the file isn't in a git repository, or the line hasn't changed
+    pub fn hunk_preview(&self) -> Result<String> {
+        let hunk = self.cursor_hunk()?;
+        Ok(self.git()?.preview(&hunk, &self.buffer.rope))
+    }
+
+    /// Stages the hunk of changes on the cursor's line
+    ///
+    /// # Errors
+    /// - If the line hasn't changed, or the hunk can't be staged
+    pub fn stage_hunk(&mut self) -> Result<()> {
+        let hunk = self.cursor_hunk()?;
+        let git = self.git.as_mut().context("Not in a git repository")?;
+
+        git.stage(&hunk, &self.buffer.rope)?;
+        let signs = git.signs();
+        self.set_signs(SignSource::Git, signs);
+
+        Ok(())
+    }
+
+    /// Replaces the hunk of changes on the cursor's line with the staged version of those lines
+    ///
+    /// # Errors
+    /// - If the file isn't in a git repository, or the line hasn't changed
+    pub fn reset_hunk(&mut self, undo_tree: &mut UndoTree) -> Result<()> {
+        let hunk = self.cursor_hunk()?;
+        let staged = self.git()?.staged_text(&hunk);
+
+        let rope = &self.buffer.rope;
+        let start = rope.line_to_char(hunk.new.start);
+        let end = rope.line_to_char(hunk.new.end);
+        let action = self.buffer.replace_range(start..end, &staged);
+        self.buffer.set_cursor(start);
+        undo_tree.new_action(action);
+
+        Ok(())
+    }
+}
+
+/// Diagnostics
+impl ViewBox {
+    /// Replaces the diagnostics shown in the view box, redrawing it if they changed
+    pub fn set_diagnostics(&mut self, mut diagnostics: Vec<Diagnostic>) {
+        diagnostics.sort_by_key(|diagnostic| diagnostic.start);
+        if diagnostics == self.diagnostics {
+            return;
+        }
+
+        self.diagnostics = diagnostics;
+        self.buffer.update_list_set(.., true);
+        self.buffer.has_changed = true;
+
+        // Each line is signed with the most severe diagnostic starting on it
+        let rope = &self.buffer.rope;
+        let mut severities: HashMap<usize, DiagnosticSeverity> = HashMap::new();
+        for diagnostic in &self.diagnostics {
+            let line_num = rope.char_to_line(usize::min(diagnostic.start, rope.len_chars()));
+            let severity = severities.entry(line_num).or_insert(diagnostic.severity);
+            *severity = DiagnosticSeverity::min(*severity, diagnostic.severity);
+        }
+
+        let signs = severities
+            .into_iter()
+            .map(|(line_num, severity)| (line_num, Sign::new(severity.sign(), severity.group())))
+            .collect();
+        self.set_signs(SignSource::Diagnostic, signs);
+    }
+
+    /// The diagnostic to show in the status bar: the one under the cursor, or failing that the
+    /// most severe one on the cursor's line
+    pub fn cursor_diagnostic(&self) -> Option<&Diagnostic> {
+        let rope = &self.buffer.rope;
+        let cursor = self.buffer.cursor;
+        let row = self.buffer.get_row();
+        let line_start = rope.line_to_char(row);
+        let line_end = line_start + Self::calculate_total_line_len(rope.line(row));
+
+        let on_line = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.on_line(line_start, line_end));
+
+        on_line
+            .clone()
+            .filter(|diagnostic| diagnostic.start <= cursor && cursor < diagnostic.end)
+            .min_by_key(|diagnostic| diagnostic.severity)
+            .or_else(|| on_line.min_by_key(|diagnostic| diagnostic.severity))
+    }
+
+    /// Moves the cursor to the start of the next diagnostic after it, wrapping around to the
+    /// first one after the last
+    pub fn next_diagnostic(&mut self) {
+        let cursor = self.buffer.cursor;
+        if let Some(diagnostic) = self
+            .diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.start > cursor)
+            .or_else(|| self.diagnostics.first())
+        {
+            self.buffer.cursor = diagnostic.start;
+            self.buffer.clamp_cursor();
+        }
+    }
+
+    /// Moves the cursor to the start of the last diagnostic before it, wrapping around to the
+    /// last one before the first
+    pub fn prev_diagnostic(&mut self) {
+        let cursor = self.buffer.cursor;
+        if let Some(diagnostic) = self
+            .diagnostics
+            .iter()
+            .rev()
+            .find(|diagnostic| diagnostic.start < cursor)
+            .or_else(|| self.diagnostics.last())
+        {
+            self.buffer.cursor = diagnostic.start;
+            self.buffer.clamp_cursor();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::DiagnosticSeverity;
+    use ropey::Rope;
+
+    #[test]
+    fn jump_between_diagnostics_wrapping_around() {
+        let mut view_box = ViewBox::new(30, 6, 0, 0);
+        view_box.buffer.rope = Rope::from("a b\nc d\ne f\n");
+        let diagnostic = |start, message: &str| Diagnostic {
+            start,
+            end: start + 1,
+            severity: DiagnosticSeverity::Warning,
+            message: message.to_string(),
+        };
+        // They're sorted by where they start
+        view_box.set_diagnostics(vec![diagnostic(6, "second"), diagnostic(2, "first")]);
+
+        view_box.next_diagnostic();
+        assert_eq!(view_box.buffer.cursor, 2);
+        view_box.next_diagnostic();
+        assert_eq!(view_box.buffer.cursor, 6);
+        view_box.next_diagnostic();
+        assert_eq!(view_box.buffer.cursor, 2);
+
+        view_box.prev_diagnostic();
+        assert_eq!(view_box.buffer.cursor, 6);
+        view_box.prev_diagnostic();
+        assert_eq!(view_box.buffer.cursor, 2);
+
+        // Without any diagnostics, the cursor stays where it is
+        view_box.set_diagnostics(Vec::new());
+        view_box.next_diagnostic();
+        view_box.prev_diagnostic();
+        assert_eq!(view_box.buffer.cursor, 2);
+    }
 }
@@ -33,30 +33,51 @@ pub fn center_viewbox_on_cursor(view: &mut View) {
 
 pub fn move_down_one_view_box(view: &mut View) {
     if let Some(i) = view.position_view_box_down() {
-        view.cursor = i;
+        view.set_current_view_box(i);
     }
 }
 pub fn move_up_one_view_box(view: &mut View) {
     if let Some(i) = view.position_view_box_up() {
-        view.cursor = i;
+        view.set_current_view_box(i);
     }
 }
 
 pub fn move_left_one_view_box(view: &mut View) {
     if let Some(i) = view.position_view_box_left() {
-        view.cursor = i;
+        view.set_current_view_box(i);
     }
 }
 pub fn move_right_one_view_box(view: &mut View) {
     if let Some(i) = view.position_view_box_right() {
-        view.cursor = i;
+        view.set_current_view_box(i);
     }
 }
 
 pub fn split_curr_view_box_vertical(view: &mut View) {
-    view.split_view_box_vertical(view.cursor);
+    view.split_view_box_vertical(view.current_view_box());
 }
 
 pub fn split_curr_view_box_horizontal(view: &mut View) {
-    view.split_view_box_horizontal(view.cursor);
+    view.split_view_box_horizontal(view.current_view_box());
+}
+
+pub fn next_diagnostic(view: &mut View) {
+    view.get_view_box().next_diagnostic();
+}
+pub fn prev_diagnostic(view: &mut View) {
+    view.get_view_box().prev_diagnostic();
+}
+
+pub fn next_hunk(view: &mut View) {
+    view.get_view_box().next_hunk();
+}
+pub fn prev_hunk(view: &mut View) {
+    view.get_view_box().prev_hunk();
+}
+
+pub fn next_display_row(view: &mut View) {
+    view.get_view_box().next_display_row();
+}
+pub fn prev_display_row(view: &mut View) {
+    view.get_view_box().prev_display_row();
 }
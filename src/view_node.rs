@@ -1,13 +1,200 @@
 use crate::view_box::ViewBox;
+use std::ops::{Index, IndexMut};
 
+// NOTE
+// There are only ever as many nodes as there are splits, so the size of leaves doesn't matter
+#[allow(clippy::large_enum_variant)]
 pub enum ViewNode {
     Leaf(ViewBox),
-    SplitVertical {
-        left: Box<ViewNode>,
-        right: Box<ViewNode>,
-    },
-    SplitHorizontal {
-        top: Box<ViewNode>,
-        bottom: Box<ViewNode>,
-    },
+    SplitVertical { left: Box<Self>, right: Box<Self> },
+    SplitHorizontal { top: Box<Self>, bottom: Box<Self> },
+}
+
+/// View boxes are numbered in the order they appear in the tree, from left to right and top to
+/// bottom within each split
+impl ViewNode {
+    pub fn iter(&self) -> impl Iterator<Item = &ViewBox> {
+        let mut leaves = Vec::new();
+        self.leaves(&mut leaves);
+        leaves.into_iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut ViewBox> {
+        let mut leaves = Vec::new();
+        self.leaves_mut(&mut leaves);
+        leaves.into_iter()
+    }
+
+    /// How many view boxes there are
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Leaf(_) => 1,
+            Self::SplitVertical { left, right } => left.len() + right.len(),
+            Self::SplitHorizontal { top, bottom } => top.len() + bottom.len(),
+        }
+    }
+
+    /// Splits the view box at `idx` in two, with `new_view_box` to the right of it if `beside`,
+    /// or below it otherwise
+    ///
+    /// # Returns
+    /// The new view box's index, or `None` if there's no view box at `idx`
+    pub fn split(&mut self, idx: usize, new_view_box: ViewBox, beside: bool) -> Option<usize> {
+        let node = self.node_mut(idx)?;
+        node.map(|old| {
+            let (old, new) = (Box::new(old), Box::new(Self::Leaf(new_view_box)));
+            if beside {
+                Self::SplitVertical {
+                    left: old,
+                    right: new,
+                }
+            } else {
+                Self::SplitHorizontal {
+                    top: old,
+                    bottom: new,
+                }
+            }
+        });
+
+        Some(idx + 1)
+    }
+
+    /// Removes the view box at `idx`, growing the view boxes it was split from to fill its space
+    ///
+    /// # Returns
+    /// The removed view box, or `None` if it's the only one or there's no view box at `idx`
+    pub fn remove(&mut self, idx: usize) -> Option<ViewBox> {
+        let (first, second, beside) = match self {
+            Self::Leaf(_) => return None,
+            Self::SplitVertical { left, right } => (left, right, true),
+            Self::SplitHorizontal { top, bottom } => (top, bottom, false),
+        };
+
+        let first_len = first.len();
+        let (target, target_idx) = if idx < first_len {
+            (first, idx)
+        } else {
+            (second, idx - first_len)
+        };
+        if !matches!(**target, Self::Leaf(_)) {
+            return target.remove(target_idx);
+        }
+        if target_idx != 0 {
+            return None;
+        }
+
+        let removes_first = idx < first_len;
+        let mut removed = None;
+        self.map(|node| {
+            let (first, second) = match node {
+                Self::SplitVertical { left, right } => (left, right),
+                Self::SplitHorizontal { top, bottom } => (top, bottom),
+                Self::Leaf(_) => unreachable!("Only splits have view boxes to remove"),
+            };
+            let (target, mut sibling) = if removes_first {
+                (first, second)
+            } else {
+                (second, first)
+            };
+
+            if let Self::Leaf(view_box) = *target {
+                sibling.fill(&view_box, beside);
+                removed = Some(view_box);
+            }
+            *sibling
+        });
+
+        removed
+    }
+
+    /// Grows the view boxes on the edge next to `removed` to take up its space, which was beside
+    /// them if `beside`, or above or below them otherwise
+    fn fill(&mut self, removed: &ViewBox, beside: bool) {
+        for view_box in self.iter_mut() {
+            if beside {
+                if view_box.x == removed.x + removed.width {
+                    view_box.x = removed.x;
+                    view_box.width += removed.width;
+                } else if view_box.x + view_box.width == removed.x {
+                    view_box.width += removed.width;
+                }
+            } else if view_box.y == removed.y + removed.height {
+                view_box.y = removed.y;
+                view_box.height += removed.height;
+            } else if view_box.y + view_box.height == removed.y {
+                view_box.height += removed.height;
+            }
+        }
+    }
+
+    /// The leaf holding the view box at `idx`
+    fn node_mut(&mut self, idx: usize) -> Option<&mut Self> {
+        let (first, second) = match self {
+            Self::Leaf(_) => return (idx == 0).then_some(self),
+            Self::SplitVertical { left, right } => (left, right),
+            Self::SplitHorizontal { top, bottom } => (top, bottom),
+        };
+
+        let first_len = first.len();
+        if idx < first_len {
+            first.node_mut(idx)
+        } else {
+            second.node_mut(idx - first_len)
+        }
+    }
+
+    /// Replaces the node with what `f` makes from it
+    fn map(&mut self, f: impl FnOnce(Self) -> Self) {
+        // NOTE
+        // A node can't be moved out from behind a reference without leaving something in its
+        // place, so an empty view box stands in until `f` is done
+        let node = std::mem::replace(self, Self::Leaf(ViewBox::new(1, 0, 0, 0)));
+        *self = f(node);
+    }
+
+    fn leaves<'a>(&'a self, leaves: &mut Vec<&'a ViewBox>) {
+        match self {
+            Self::Leaf(view_box) => leaves.push(view_box),
+            Self::SplitVertical { left, right } => {
+                left.leaves(leaves);
+                right.leaves(leaves);
+            }
+            Self::SplitHorizontal { top, bottom } => {
+                top.leaves(leaves);
+                bottom.leaves(leaves);
+            }
+        }
+    }
+
+    fn leaves_mut<'a>(&'a mut self, leaves: &mut Vec<&'a mut ViewBox>) {
+        match self {
+            Self::Leaf(view_box) => leaves.push(view_box),
+            Self::SplitVertical { left, right } => {
+                left.leaves_mut(leaves);
+                right.leaves_mut(leaves);
+            }
+            Self::SplitHorizontal { top, bottom } => {
+                top.leaves_mut(leaves);
+                bottom.leaves_mut(leaves);
+            }
+        }
+    }
+}
+
+impl Index<usize> for ViewNode {
+    type Output = ViewBox;
+
+    fn index(&self, idx: usize) -> &ViewBox {
+        self.iter()
+            .nth(idx)
+            .unwrap_or_else(|| panic!("No view box at {idx}"))
+    }
+}
+
+impl IndexMut<usize> for ViewNode {
+    fn index_mut(&mut self, idx: usize) -> &mut ViewBox {
+        self.iter_mut()
+            .nth(idx)
+            .unwrap_or_else(|| panic!("No view box at {idx}"))
+    }
 }
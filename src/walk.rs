@@ -0,0 +1,305 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Walking stops after this many files, so that starting in a huge directory (eg. `$HOME`)
+/// doesn't hang the editor
+const MAX_FILES: usize = 100_000;
+
+/// A pattern from a `.gitignore` file
+struct Rule {
+    /// The directory of the `.gitignore` the rule is from, relative to the root of the walk
+    base: PathBuf,
+    pattern: Vec<char>,
+    /// `!pattern`, which re-includes paths an earlier rule ignored
+    negated: bool,
+    /// `pattern/`, which only matches directories
+    dir_only: bool,
+    /// Patterns with a `/` (other than a trailing one) match paths relative to `base`, others
+    /// match names at any depth
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str, base: &Path) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = line
+            .strip_prefix('!')
+            .map_or((false, line), |line| (true, line));
+        let (dir_only, line) = line
+            .strip_suffix('/')
+            .map_or((false, line), |line| (true, line));
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+
+        Some(Self {
+            base: base.to_path_buf(),
+            pattern: line.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+
+        let text: Vec<char> = if self.anchored {
+            relative.to_string_lossy().chars().collect()
+        } else {
+            relative
+                .file_name()
+                .map(|name| name.to_string_lossy().chars().collect())
+                .unwrap_or_default()
+        };
+
+        glob_match(&self.pattern, &text)
+    }
+}
+
+/// Matches `text` against a gitignore glob. `*` and `?` don't match `/`, `**/` matches any number
+/// of directories, and a trailing `**` matches everything.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*'] => true,
+        ['*', '*', '/', rest @ ..] => {
+            glob_match(rest, text)
+                || text
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '/')
+                    .any(|(i, _)| glob_match(rest, &text[i + 1..]))
+        }
+        ['*', rest @ ..] => {
+            let segment = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=segment).any(|i| glob_match(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => text
+            .split_first()
+            .is_some_and(|(c, text)| *c != '/' && glob_match(rest, text)),
+        ['[', rest @ ..] => {
+            let Some(end) = rest.iter().skip(1).position(|c| *c == ']').map(|i| i + 1) else {
+                return text.first() == Some(&'[') && glob_match(rest, &text[1..]);
+            };
+            let (class, rest) = (&rest[..end], &rest[end + 1..]);
+
+            text.split_first().is_some_and(|(c, text)| {
+                *c != '/' && class_matches(class, *c) && glob_match(rest, text)
+            })
+        }
+        ['\\', c, rest @ ..] | [c, rest @ ..] => text
+            .split_first()
+            .is_some_and(|(first, text)| first == c && glob_match(rest, text)),
+    }
+}
+
+/// Whether `c` is in a `[...]` character class, given what's between the brackets
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class {
+        ['!' | '^', class @ ..] => (true, class),
+        class => (false, class),
+    };
+
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    matched != negated
+}
+
+fn read_rules(path: &Path, base: &Path) -> Vec<Rule> {
+    fs::read_to_string(path)
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| Rule::parse(line, base))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The last rule matching a path decides whether it's ignored
+fn is_ignored(rules: &[Rule], path: &Path, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+/// Every file under `root`, relative to it and sorted by path, leaving out `.git` and anything
+/// ignored by a `.gitignore` (or `.git/info/exclude`)
+///
+/// Ignored directories aren't walked into, and neither are symlinks to directories, which could
+/// loop back on themselves.
+pub fn walk(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut rules = read_rules(&root.join(".git/info/exclude"), Path::new(""));
+
+    visit(root, Path::new(""), &mut rules, &mut files);
+
+    files
+}
+
+fn visit(root: &Path, dir: &Path, rules: &mut Vec<Rule>, files: &mut Vec<PathBuf>) {
+    let inherited = rules.len();
+    rules.extend(read_rules(&root.join(dir).join(".gitignore"), dir));
+
+    let mut entries = fs::read_dir(root.join(dir))
+        .map(|entries| entries.filter_map(Result::ok).collect::<Vec<_>>())
+        .unwrap_or_default();
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    for entry in entries {
+        if files.len() >= MAX_FILES {
+            break;
+        }
+
+        let name = entry.file_name();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if name == ".git" {
+            continue;
+        }
+
+        let path = dir.join(&name);
+        let is_dir = file_type.is_dir();
+        if is_ignored(rules, &path, is_dir) {
+            continue;
+        }
+
+        if is_dir {
+            visit(root, &path, rules, files);
+        } else if !file_type.is_symlink() || root.join(&path).is_file() {
+            files.push(path);
+        }
+    }
+
+    rules.truncate(inherited);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn globs() {
+        let cases = [
+            ("*.rs", "main.rs", true),
+            ("*.rs", "main.rs.bak", false),
+            ("*", "a/b", false),
+            ("a?c", "abc", true),
+            ("a?c", "a/c", false),
+            ("**/foo", "foo", true),
+            ("**/foo", "a/b/foo", true),
+            ("**/foo", "a/foobar", false),
+            ("a/**/b", "a/b", true),
+            ("a/**/b", "a/x/y/b", true),
+            ("a/**", "a/x/y", true),
+            ("\\*", "*", true),
+            ("\\*", "a", false),
+            ("[ab]c", "bc", true),
+            ("[ab]c", "cc", false),
+            ("[a", "[a", true),
+        ];
+
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                glob_match(&chars(pattern), &chars(text)),
+                expected,
+                "{pattern} against {text}"
+            );
+        }
+    }
+
+    #[test]
+    fn character_classes() {
+        let cases = [
+            ("abc", 'b', true),
+            ("abc", 'd', false),
+            ("a-z", 'm', true),
+            ("a-z", 'M', false),
+            ("a-cx-z", 'y', true),
+            ("!a-z", 'm', false),
+            ("!a-z", '0', true),
+            ("^0-9", 'a', true),
+            ("-a", '-', true),
+            ("a-", '-', true),
+        ];
+
+        for (class, c, expected) in cases {
+            assert_eq!(
+                class_matches(&chars(class), c),
+                expected,
+                "[{class}] against {c}"
+            );
+        }
+    }
+
+    #[test]
+    fn ignored_paths() {
+        let rules = |lines: &[&str], base: &str| -> Vec<Rule> {
+            lines
+                .iter()
+                .filter_map(|line| Rule::parse(line, Path::new(base)))
+                .collect()
+        };
+
+        let cases = [
+            // Unanchored patterns match names at any depth
+            (rules(&["*.o"], ""), "a/b/c.o", false, true),
+            (rules(&["*.o"], ""), "c.oo", false, false),
+            // A leading or middle `/` anchors the pattern to the `.gitignore`'s directory
+            (rules(&["/build"], ""), "build", true, true),
+            (rules(&["/build"], ""), "src/build", true, false),
+            (rules(&["doc/out"], ""), "doc/out", true, true),
+            (rules(&["doc/out"], ""), "src/doc/out", true, false),
+            (rules(&["**/out"], ""), "src/doc/out", true, true),
+            // A trailing `/` only matches directories
+            (rules(&["target/"], ""), "target", true, true),
+            (rules(&["target/"], ""), "target", false, false),
+            // Later rules win, so negations only re-include what came before them
+            (rules(&["*.log", "!keep.log"], ""), "keep.log", false, false),
+            (rules(&["*.log", "!keep.log"], ""), "other.log", false, true),
+            (rules(&["!keep.log", "*.log"], ""), "keep.log", false, true),
+            // Rules only apply under the directory of their `.gitignore`
+            (rules(&["/out"], "sub"), "sub/out", true, true),
+            (rules(&["/out"], "sub"), "out", true, false),
+            (rules(&["out"], "sub"), "other/out", true, false),
+            // Comments and blank lines aren't rules
+            (rules(&["# out", ""], ""), "# out", false, false),
+        ];
+
+        for (rules, path, is_dir, expected) in cases {
+            assert_eq!(
+                is_ignored(&rules, Path::new(path), is_dir),
+                expected,
+                "{path}"
+            );
+        }
+    }
+}